@@ -0,0 +1,11 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc not found");
+        std::env::set_var("PROTOC", protoc);
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_protos(&["proto/usage.proto"], &["proto"])
+            .expect("failed to compile proto/usage.proto");
+    }
+}
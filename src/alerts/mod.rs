@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::budget::Budget;
+use crate::providers::billing_period_start;
+use crate::MonitorError;
+
+pub mod pagerduty;
+pub mod slack;
+pub mod webhook;
+
+use pagerduty::PagerDutyNotifier;
+use slack::SlackNotifier;
+use webhook::WebhookNotifier;
+
+const DEFAULT_THRESHOLDS: &[f64] = &[0.8, 0.9, 1.0];
+const DEFAULT_COOLDOWN_SECS: u64 = 86400;
+
+/// A budget whose spend reached one of its alert thresholds.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetAlert {
+    pub budget: String,
+    /// Fraction of the limit that was crossed, e.g. `0.9`.
+    pub threshold: f64,
+    pub utilization: f64,
+    pub spend_usd: f64,
+    pub limit_usd: f64,
+    /// Limit left before the budget is exhausted, zero once it is exceeded.
+    pub remaining_usd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    pub period_start: chrono::DateTime<chrono::Utc>,
+    pub message: String,
+}
+
+/// Destination of budget alerts.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Sends the alert for `budget`, whose settings may route it, e.g. to a Slack channel.
+    async fn notify(&self, budget: &Budget, alert: &BudgetAlert) -> Result<(), MonitorError>;
+
+    /// Called when spend is under a threshold, e.g. after the billing period rolls over, for
+    /// destinations that track open incidents. After a restart it is called once for every
+    /// threshold under the spend, since alerts sent before the restart are not known, so it
+    /// must accept thresholds that never alerted.
+    async fn resolve(&self, _budget: &Budget, _threshold: f64) -> Result<(), MonitorError> {
+        Ok(())
+    }
+}
+
+/// Sends an alert when a budget's spend crosses one of its thresholds. Each threshold of a
+/// budget alerts at most once per cooldown while the spend stays above it; when several are
+/// crossed at once only the highest is sent.
+pub struct AlertEvaluator {
+    thresholds: Vec<f64>,
+    cooldown: Duration,
+    notifiers: Vec<Box<dyn Notifier>>,
+    /// When each `(budget, threshold)` last alerted, keyed by the threshold's bits.
+    fired: Mutex<HashMap<(String, u64), Instant>>,
+    /// `(budget, threshold)` pairs resolved since the start, which are not resolved again
+    /// until they alert.
+    resolved: Mutex<HashSet<(String, u64)>>,
+}
+
+impl AlertEvaluator {
+    /// Enabled when `ALERT_WEBHOOK_URLS` is set, PagerDuty is configured, or Slack is
+    /// configured globally or by a budget. Budgets without their own `alert_thresholds` use
+    /// `ALERT_THRESHOLDS` (default `0.8,0.9,1.0`).
+    pub fn from_env(budgets: &[Budget]) -> Result<Option<Self>, MonitorError> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = std::env::var("ALERT_WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| -> Box<dyn Notifier> { Box::new(WebhookNotifier::new(url.to_string())) })
+            .collect();
+        if let Some(slack) = SlackNotifier::from_env(budgets) {
+            notifiers.push(Box::new(slack));
+        }
+        if let Some(pagerduty) = PagerDutyNotifier::from_env()? {
+            notifiers.push(Box::new(pagerduty));
+        }
+        if notifiers.is_empty() {
+            return Ok(None);
+        }
+        let thresholds = match std::env::var("ALERT_THRESHOLDS") {
+            Ok(thresholds) if !thresholds.is_empty() => parse_thresholds(&thresholds)
+                .map_err(|e| anyhow::anyhow!("invalid ALERT_THRESHOLDS: {}", e))?,
+            _ => DEFAULT_THRESHOLDS.to_vec(),
+        };
+        let cooldown_secs = match std::env::var("ALERT_COOLDOWN_SECS") {
+            Ok(secs) => secs
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid ALERT_COOLDOWN_SECS {:?}", secs))?,
+            Err(_) => DEFAULT_COOLDOWN_SECS,
+        };
+        Ok(Some(Self {
+            thresholds,
+            cooldown: Duration::from_secs(cooldown_secs),
+            notifiers,
+            fired: Mutex::new(HashMap::new()),
+            resolved: Mutex::new(HashSet::new()),
+        }))
+    }
+
+    /// Checks every budget's spend against its thresholds, notifies about new crossings and
+    /// resolves thresholds the spend is back under.
+    pub async fn evaluate(&self, spend: &[(&Budget, f64)]) {
+        for (budget, spend) in spend {
+            let utilization = spend / budget.limit_usd;
+            let (due, resolved) = self.crossed(budget, utilization);
+            for (threshold, alerted) in resolved {
+                if alerted {
+                    info!(
+                        budget = %budget.name,
+                        threshold,
+                        utilization,
+                        "budget threshold resolved"
+                    );
+                }
+                for notifier in &self.notifiers {
+                    if let Err(e) = notifier.resolve(budget, threshold).await {
+                        warn!(
+                            notifier = notifier.name(),
+                            budget = %budget.name,
+                            error = %e,
+                            "failed to resolve budget alert"
+                        );
+                    }
+                }
+            }
+            let Some(threshold) = due else {
+                continue;
+            };
+            let alert = BudgetAlert {
+                budget: budget.name.clone(),
+                threshold,
+                utilization,
+                spend_usd: *spend,
+                limit_usd: budget.limit_usd,
+                remaining_usd: (budget.limit_usd - spend).max(0.0),
+                provider: budget.provider.clone(),
+                model: budget.model.clone(),
+                labels: budget.labels.clone(),
+                // budgets across providers follow the default anchor day
+                period_start: billing_period_start(budget.provider.as_deref().unwrap_or_default()),
+                message: format!(
+                    "LLM budget {} is at {:.0}% (${:.2} of ${:.2})",
+                    budget.name,
+                    utilization * 100.0,
+                    spend,
+                    budget.limit_usd
+                ),
+            };
+            info!(budget = %alert.budget, threshold, utilization, "budget threshold crossed");
+            for notifier in &self.notifiers {
+                if let Err(e) = notifier.notify(budget, &alert).await {
+                    warn!(
+                        notifier = notifier.name(),
+                        budget = %alert.budget,
+                        error = %e,
+                        "failed to send budget alert"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Highest threshold that is due an alert, recording it and every lower crossed threshold
+    /// as fired, and the thresholds the spend is under that were not resolved yet, which are
+    /// re-armed, each with whether it alerted since the start.
+    fn crossed(&self, budget: &Budget, utilization: f64) -> (Option<f64>, Vec<(f64, bool)>) {
+        let thresholds = budget.alert_thresholds.as_ref().unwrap_or(&self.thresholds);
+        let mut fired = self.fired.lock().unwrap();
+        let mut settled = self.resolved.lock().unwrap();
+        let now = Instant::now();
+        let mut due = None;
+        let mut resolved = Vec::new();
+        for &threshold in thresholds {
+            let key = (budget.name.clone(), threshold.to_bits());
+            if utilization < threshold {
+                let alerted = fired.remove(&key).is_some();
+                if settled.insert(key) {
+                    resolved.push((threshold, alerted));
+                }
+                continue;
+            }
+            settled.remove(&key);
+            let cooling_down = fired
+                .get(&key)
+                .is_some_and(|last| now.duration_since(*last) < self.cooldown);
+            if !cooling_down {
+                fired.insert(key, now);
+                due = Some(due.map_or(threshold, |due: f64| due.max(threshold)));
+            }
+        }
+        (due, resolved)
+    }
+}
+
+/// Parses a comma separated list of fractions such as `0.8,0.9,1.0`.
+fn parse_thresholds(value: &str) -> Result<Vec<f64>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .map(|threshold| match threshold.parse::<f64>() {
+            Ok(threshold) if threshold.is_finite() && threshold > 0.0 => Ok(threshold),
+            _ => Err(format!("{:?} is not a positive number", threshold)),
+        })
+        .collect()
+}
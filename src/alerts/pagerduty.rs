@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{BudgetAlert, Notifier};
+use crate::budget::Budget;
+use crate::MonitorError;
+
+const DEFAULT_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const DEFAULT_SEVERITIES: &str = "1.0=critical";
+const SEVERITY_LEVELS: &[&str] = &["critical", "error", "warning", "info"];
+
+/// Opens a PagerDuty incident through the Events API v2 when a budget crosses a threshold
+/// that has a severity, and resolves it once spend is back under the threshold. Each budget
+/// and threshold is its own incident, deduplicated by a key derived from the two alone, so an
+/// incident opened before a restart is still resolved after it.
+pub struct PagerDutyNotifier {
+    client: reqwest::Client,
+    events_url: String,
+    routing_key: String,
+    /// Severity of each threshold that pages; other thresholds are ignored.
+    severities: Vec<(f64, String)>,
+}
+
+impl PagerDutyNotifier {
+    /// Enabled when `PAGERDUTY_ROUTING_KEY` is set. `PAGERDUTY_SEVERITIES` maps thresholds to
+    /// severities, e.g. `0.9=warning,1.0=critical`; by default only the full budget pages.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let routing_key = match std::env::var("PAGERDUTY_ROUTING_KEY") {
+            Ok(key) if !key.is_empty() => key,
+            _ => return Ok(None),
+        };
+        let severities = std::env::var("PAGERDUTY_SEVERITIES")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| DEFAULT_SEVERITIES.to_string());
+        let severities = parse_severities(&severities)
+            .map_err(|e| anyhow::anyhow!("invalid PAGERDUTY_SEVERITIES: {}", e))?;
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            events_url: std::env::var("PAGERDUTY_EVENTS_URL")
+                .ok()
+                .filter(|url| !url.is_empty())
+                .unwrap_or_else(|| DEFAULT_EVENTS_URL.to_string()),
+            routing_key,
+            severities,
+        }))
+    }
+
+    fn severity(&self, threshold: f64) -> Option<&str> {
+        self.severities
+            .iter()
+            .find(|(mapped, _)| *mapped == threshold)
+            .map(|(_, severity)| severity.as_str())
+    }
+
+    /// Same for every alert of a budget's threshold, whichever instance or run sent it.
+    fn dedup_key(budget: &Budget, threshold: f64) -> String {
+        format!("llm-cost-exporter/{}/{}", budget.name, threshold)
+    }
+
+    async fn send(&self, event: Value) -> Result<(), MonitorError> {
+        self.client
+            .post(&self.events_url)
+            .timeout(Duration::from_secs(10))
+            .json(&event)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    fn name(&self) -> &'static str {
+        "pagerduty"
+    }
+
+    async fn notify(&self, budget: &Budget, alert: &BudgetAlert) -> Result<(), MonitorError> {
+        let Some(severity) = self.severity(alert.threshold) else {
+            return Ok(());
+        };
+        self.send(json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": Self::dedup_key(budget, alert.threshold),
+            "client": "llm-cost-exporter",
+            "payload": {
+                "summary": alert.message,
+                "source": "llm-cost-exporter",
+                "severity": severity,
+                "component": alert.provider.as_deref().unwrap_or("all"),
+                "group": alert.budget,
+                "class": "budget_threshold",
+                "custom_details": alert,
+            },
+        }))
+        .await
+    }
+
+    async fn resolve(&self, budget: &Budget, threshold: f64) -> Result<(), MonitorError> {
+        if self.severity(threshold).is_none() {
+            return Ok(());
+        }
+        self.send(json!({
+            "routing_key": self.routing_key,
+            "event_action": "resolve",
+            "dedup_key": Self::dedup_key(budget, threshold),
+        }))
+        .await
+    }
+}
+
+/// Parses `threshold=severity` pairs such as `0.9=warning,1.0=critical`.
+fn parse_severities(value: &str) -> Result<Vec<(f64, String)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (threshold, severity) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected threshold=severity, got {:?}", pair))?;
+            let threshold = threshold
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|threshold| threshold.is_finite() && *threshold > 0.0)
+                .ok_or_else(|| format!("{:?} is not a positive number", threshold))?;
+            let severity = severity.trim();
+            if !SEVERITY_LEVELS.contains(&severity) {
+                return Err(format!(
+                    "unknown severity {:?}, expected one of {}",
+                    severity,
+                    SEVERITY_LEVELS.join(", ")
+                ));
+            }
+            Ok((threshold, severity.to_string()))
+        })
+        .collect()
+}
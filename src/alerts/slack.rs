@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::{BudgetAlert, Notifier};
+use crate::budget::Budget;
+use crate::providers::reporting_timezone;
+use crate::MonitorError;
+
+const DEFAULT_API_BASE: &str = "https://slack.com/api";
+
+/// Posts alerts to Slack, through an incoming webhook (`SLACK_WEBHOOK_URL`) or as a bot
+/// (`SLACK_BOT_TOKEN` and `SLACK_CHANNEL`). A budget's `slack_channel` or `slack_webhook_url`
+/// sends its alerts elsewhere; without a destination of either kind a budget's alerts skip
+/// Slack.
+pub struct SlackNotifier {
+    client: reqwest::Client,
+    api_base: String,
+    webhook_url: Option<String>,
+    bot_token: Option<String>,
+    channel: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostMessageResponse {
+    ok: bool,
+    error: Option<String>,
+}
+
+impl SlackNotifier {
+    /// Enabled when a webhook URL or bot token is set, or a budget has its own webhook URL.
+    pub fn from_env(budgets: &[Budget]) -> Option<Self> {
+        let setting = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let webhook_url = setting("SLACK_WEBHOOK_URL");
+        let bot_token = setting("SLACK_BOT_TOKEN");
+        let budget_webhooks = budgets
+            .iter()
+            .any(|budget| budget.slack_webhook_url.is_some());
+        if webhook_url.is_none() && bot_token.is_none() && !budget_webhooks {
+            return None;
+        }
+        Some(Self {
+            client: reqwest::Client::new(),
+            api_base: setting("SLACK_API_BASE").unwrap_or_else(|| DEFAULT_API_BASE.to_string()),
+            webhook_url,
+            bot_token,
+            channel: setting("SLACK_CHANNEL"),
+        })
+    }
+
+    /// Block Kit message with a plain text fallback for notifications.
+    fn message(alert: &BudgetAlert) -> Value {
+        let icon = if alert.utilization >= 1.0 {
+            ":rotating_light:"
+        } else {
+            ":warning:"
+        };
+        let mut fields = vec![
+            format!("*Spend*\n${:.2}", alert.spend_usd),
+            format!("*Budget*\n${:.2}", alert.limit_usd),
+            format!("*Remaining*\n${:.2}", alert.remaining_usd),
+            format!("*Threshold*\n{:.0}%", alert.threshold * 100.0),
+        ];
+        if let Some(provider) = &alert.provider {
+            fields.push(format!("*Provider*\n{}", provider));
+        }
+        if let Some(model) = &alert.model {
+            fields.push(format!("*Model*\n{}", model));
+        }
+        for (label, value) in &alert.labels {
+            fields.push(format!("*{}*\n{}", label, value));
+        }
+        json!({
+            "text": alert.message,
+            "blocks": [
+                {
+                    "type": "section",
+                    "text": {
+                        "type": "mrkdwn",
+                        "text": format!("{} *{}*", icon, alert.message),
+                    },
+                },
+                {
+                    "type": "section",
+                    // Slack allows at most 10 fields per section
+                    "fields": fields
+                        .into_iter()
+                        .take(10)
+                        .map(|text| json!({"type": "mrkdwn", "text": text}))
+                        .collect::<Vec<_>>(),
+                },
+                {
+                    "type": "context",
+                    "elements": [{
+                        "type": "mrkdwn",
+                        "text": format!(
+                            "Billing period since {}",
+                            alert
+                                .period_start
+                                .with_timezone(&reporting_timezone())
+                                .format("%Y-%m-%d")
+                        ),
+                    }],
+                },
+            ],
+        })
+    }
+
+    async fn post_webhook(&self, url: &str, message: &Value) -> Result<(), MonitorError> {
+        self.client
+            .post(url)
+            .timeout(Duration::from_secs(10))
+            .json(message)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn post_message(
+        &self,
+        token: &str,
+        channel: &str,
+        message: &Value,
+    ) -> Result<(), MonitorError> {
+        let mut message = message.clone();
+        message["channel"] = json!(channel);
+        let response: PostMessageResponse = self
+            .client
+            .post(format!("{}/chat.postMessage", self.api_base))
+            .timeout(Duration::from_secs(10))
+            .bearer_auth(token)
+            .json(&message)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if !response.ok {
+            return Err(anyhow::anyhow!(
+                "Slack chat.postMessage failed: {}",
+                response.error.unwrap_or_default()
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn notify(&self, budget: &Budget, alert: &BudgetAlert) -> Result<(), MonitorError> {
+        let message = Self::message(alert);
+        // the budget's own destination wins over the global one
+        match (
+            &self.bot_token,
+            &budget.slack_channel,
+            &budget.slack_webhook_url,
+        ) {
+            (Some(token), Some(channel), _) => self.post_message(token, channel, &message).await,
+            (_, _, Some(url)) => self.post_webhook(url, &message).await,
+            _ => match (&self.bot_token, &self.channel, &self.webhook_url) {
+                (Some(token), Some(channel), _) => {
+                    self.post_message(token, channel, &message).await
+                }
+                (_, _, Some(url)) => self.post_webhook(url, &message).await,
+                _ => Ok(()),
+            },
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::{BudgetAlert, Notifier};
+use crate::budget::Budget;
+use crate::MonitorError;
+
+/// POSTs every alert as JSON to a URL.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn notify(&self, _budget: &Budget, alert: &BudgetAlert) -> Result<(), MonitorError> {
+        self.client
+            .post(&self.url)
+            .timeout(Duration::from_secs(10))
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use crate::metrics::LLMMetrics;
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+const DEFAULT_WINDOW: usize = 288;
+const DEFAULT_THRESHOLD: f64 = 5.0;
+const DEFAULT_MIN_HOURLY_USD: f64 = 1.0;
+/// Rates needed in the window before a model is scored.
+const MIN_SAMPLES: usize = 12;
+/// Scales the median absolute deviation to the standard deviation of normally distributed rates.
+const MAD_SCALE: f64 = 1.4826;
+
+#[derive(Debug)]
+struct SeriesState {
+    last_poll: DateTime<Utc>,
+    last_total: f64,
+    /// Spend in USD per hour between recent polls, oldest first.
+    rates: VecDeque<f64>,
+}
+
+/// Scores the spend rate of every model against its recent history with a robust z-score,
+/// `(rate - median) / (1.4826 * MAD)`, and counts an anomaly when the score reaches the threshold.
+pub struct AnomalyDetector {
+    metrics: Arc<LLMMetrics>,
+    /// Polls of history kept per model.
+    window: usize,
+    threshold: f64,
+    /// Lower bound of the deviation in USD per hour, so models with steady or no spend don't
+    /// flag cents.
+    min_hourly_usd: f64,
+    series: Mutex<HashMap<(&'static str, String), SeriesState>>,
+}
+
+impl AnomalyDetector {
+    /// Reads `ANOMALY_WINDOW`, `ANOMALY_THRESHOLD` and `ANOMALY_MIN_HOURLY_USD`.
+    pub fn from_env(metrics: Arc<LLMMetrics>) -> Result<Self, MonitorError> {
+        let window = match std::env::var("ANOMALY_WINDOW") {
+            Ok(window) => window
+                .parse::<usize>()
+                .ok()
+                .filter(|window| *window >= MIN_SAMPLES)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "invalid ANOMALY_WINDOW {:?}, expected at least {} polls",
+                        window,
+                        MIN_SAMPLES
+                    )
+                })?,
+            Err(_) => DEFAULT_WINDOW,
+        };
+        Ok(Self {
+            metrics,
+            window,
+            threshold: positive_env("ANOMALY_THRESHOLD", DEFAULT_THRESHOLD)?,
+            min_hourly_usd: positive_env("ANOMALY_MIN_HOURLY_USD", DEFAULT_MIN_HOURLY_USD)?,
+            series: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Adds a poll's month-to-date cost of a model and returns its anomaly score, once the model
+    /// has enough history.
+    fn observe(
+        &self,
+        provider: &'static str,
+        model: &str,
+        total: f64,
+        now: DateTime<Utc>,
+    ) -> Option<f64> {
+        let mut series = self.series.lock().unwrap();
+        let state = match series.get_mut(&(provider, model.to_string())) {
+            // a lower total is a new billing period, which starts a new history
+            Some(state) if total >= state.last_total => state,
+            _ => {
+                series.insert(
+                    (provider, model.to_string()),
+                    SeriesState {
+                        last_poll: now,
+                        last_total: total,
+                        rates: VecDeque::new(),
+                    },
+                );
+                return None;
+            }
+        };
+        let hours = (now - state.last_poll).num_seconds().max(1) as f64 / 3600.0;
+        let rate = (total - state.last_total) / hours;
+        state.last_poll = now;
+        state.last_total = total;
+
+        let score = (state.rates.len() >= MIN_SAMPLES).then(|| {
+            let center = median(state.rates.iter().copied());
+            let mad = median(state.rates.iter().map(|r| (r - center).abs()));
+            (rate - center) / (MAD_SCALE * mad).max(self.min_hourly_usd)
+        });
+        if state.rates.len() == self.window {
+            state.rates.pop_front();
+        }
+        state.rates.push_back(rate);
+        score
+    }
+}
+
+fn positive_env(name: &str, default: f64) -> Result<f64, MonitorError> {
+    match std::env::var(name) {
+        Ok(value) => Ok(value
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .ok_or_else(|| anyhow::anyhow!("invalid {} {:?}", name, value))?),
+        Err(_) => Ok(default),
+    }
+}
+
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let mut values: Vec<f64> = values.collect();
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[async_trait]
+impl UsageSink for AnomalyDetector {
+    fn name(&self) -> &'static str {
+        "anomaly"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let now = Utc::now();
+        for provider_usage in usage {
+            let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+            for model_usage in &provider_usage.usage {
+                *totals.entry(model_usage.model.as_str()).or_default() += model_usage.cost_usd;
+            }
+            for (model, total) in totals {
+                let Some(score) = self.observe(provider_usage.provider, model, total, now) else {
+                    continue;
+                };
+                self.metrics
+                    .set_cost_anomaly_score(provider_usage.provider, model, score);
+                if score >= self.threshold {
+                    warn!(
+                        provider = provider_usage.provider,
+                        model, score, "abnormal spend rate"
+                    );
+                    self.metrics
+                        .record_cost_anomaly(provider_usage.provider, model);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use warp::filters::BoxedFilter;
+use warp::{Filter, Reply};
+
+use crate::auth::{self, AuthConfig};
+use crate::providers::LLMUsage;
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+/// Latest usage reported by one provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderSnapshot {
+    pub provider: &'static str,
+    pub last_updated: DateTime<Utc>,
+    pub models: Vec<LLMUsage>,
+}
+
+/// Keeps the result of the last successful poll of every provider for the JSON API.
+#[derive(Debug, Clone, Default)]
+pub struct UsageStore {
+    providers: Arc<RwLock<BTreeMap<&'static str, ProviderSnapshot>>>,
+}
+
+impl UsageStore {
+    pub fn snapshot(&self, provider: Option<&str>) -> Vec<ProviderSnapshot> {
+        self.providers
+            .read()
+            .unwrap()
+            .values()
+            .filter(|snapshot| provider.is_none_or(|provider| snapshot.provider == provider))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl UsageSink for UsageStore {
+    fn name(&self) -> &'static str {
+        "api"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let now = Utc::now();
+        let mut providers = self.providers.write().unwrap();
+        for provider_usage in usage {
+            providers.insert(
+                provider_usage.provider,
+                ProviderSnapshot {
+                    provider: provider_usage.provider,
+                    last_updated: now,
+                    models: provider_usage.usage.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageQuery {
+    provider: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UsageResponse {
+    providers: Vec<ProviderSnapshot>,
+}
+
+/// `GET /api/v1/usage[?provider=<name>]`, behind the same authentication as the metrics path.
+pub fn routes(
+    store: UsageStore,
+    auth: Option<AuthConfig>,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("api" / "v1" / "usage")
+        .and(warp::get())
+        .and(auth::require(auth))
+        .and(warp::query::<UsageQuery>())
+        .map(move |query: UsageQuery| {
+            warp::reply::json(&UsageResponse {
+                providers: store.snapshot(query.provider.as_deref()),
+            })
+            .into_response()
+        })
+        .boxed()
+}
@@ -0,0 +1,113 @@
+use base64::Engine;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use warp::filters::BoxedFilter;
+use warp::http::{header, StatusCode};
+use warp::{Filter, Rejection, Reply};
+
+use crate::MonitorError;
+
+/// Credentials a scrape must present on the metrics route, or that the exporter presents
+/// when pushing metrics.
+#[derive(Debug, Clone)]
+pub enum AuthConfig {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+impl AuthConfig {
+    /// Reads `<PREFIX>_USERNAME` and `<PREFIX>_PASSWORD`, or `<PREFIX>_BEARER_TOKEN`.
+    pub fn from_env(prefix: &str) -> Result<Option<Self>, MonitorError> {
+        let var = |suffix: &str| {
+            std::env::var(format!("{}_{}", prefix, suffix))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        match (var("USERNAME"), var("PASSWORD"), var("BEARER_TOKEN")) {
+            (None, None, None) => Ok(None),
+            (Some(username), Some(password), None) => {
+                Ok(Some(AuthConfig::Basic { username, password }))
+            }
+            (None, None, Some(token)) => Ok(Some(AuthConfig::Bearer { token })),
+            _ => Err(anyhow::anyhow!(
+                "set either {prefix}_USERNAME and {prefix}_PASSWORD or {prefix}_BEARER_TOKEN"
+            )
+            .into()),
+        }
+    }
+
+    /// Adds the credentials to an outgoing request.
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            AuthConfig::Basic { username, password } => {
+                request.basic_auth(username, Some(password))
+            }
+            AuthConfig::Bearer { token } => request.bearer_auth(token),
+        }
+    }
+
+    /// The exact `Authorization` header value a client has to send.
+    fn expected_header(&self) -> String {
+        match self {
+            AuthConfig::Basic { username, password } => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password))
+            ),
+            AuthConfig::Bearer { token } => format!("Bearer {}", token),
+        }
+    }
+
+    /// Whether a provided `Authorization` header matches, compared in constant time.
+    pub fn verify(&self, provided: Option<&str>) -> bool {
+        let provided = provided.unwrap_or_default();
+        bool::from(provided.as_bytes().ct_eq(self.expected_header().as_bytes()))
+    }
+
+    fn challenge(&self) -> &'static str {
+        match self {
+            AuthConfig::Basic { .. } => "Basic realm=\"llm-cost-exporter\"",
+            AuthConfig::Bearer { .. } => "Bearer",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Unauthorized(&'static str);
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Rejects requests whose `Authorization` header does not match.
+/// Passes everything through when no auth is configured.
+pub fn require(auth: Option<AuthConfig>) -> BoxedFilter<()> {
+    let Some(auth) = auth else {
+        return warp::any().boxed();
+    };
+    let challenge = auth.challenge();
+    let auth = Arc::new(auth);
+    warp::header::optional::<String>(header::AUTHORIZATION.as_str())
+        .and_then(move |provided: Option<String>| {
+            let auth = auth.clone();
+            async move {
+                if auth.verify(provided.as_deref()) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(Unauthorized(challenge)))
+                }
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+/// Turns an auth rejection into a 401 with the matching `WWW-Authenticate` challenge.
+pub async fn handle_rejection(rejection: Rejection) -> Result<impl Reply, Rejection> {
+    match rejection.find::<Unauthorized>() {
+        Some(Unauthorized(challenge)) => Ok(warp::reply::with_header(
+            warp::reply::with_status("Unauthorized", StatusCode::UNAUTHORIZED),
+            header::WWW_AUTHENTICATE,
+            *challenge,
+        )),
+        None => Err(rejection),
+    }
+}
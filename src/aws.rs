@@ -0,0 +1,460 @@
+use std::time::SystemTime;
+
+use aws_config::SdkConfig;
+use aws_credential_types::provider::{self, ProvideCredentials, SharedCredentialsProvider};
+use aws_credential_types::Credentials;
+use aws_sigv4::http_request::{sign, SignableBody, SignableRequest, SigningSettings};
+use aws_sigv4::sign::v4;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::providers::timed;
+use crate::MonitorError;
+
+/// Refresh temporary credentials this long before they expire.
+const CREDENTIAL_REFRESH_MARGIN: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// Caches credentials from an inner provider (e.g. STS assume-role) and fetches new ones
+/// shortly before the cached ones expire, instead of calling STS on every request.
+#[derive(Debug)]
+pub struct RefreshingCredentials<P> {
+    inner: P,
+    cached: Mutex<Option<Credentials>>,
+}
+
+impl<P: ProvideCredentials> RefreshingCredentials<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            cached: Mutex::new(None),
+        }
+    }
+
+    async fn credentials(&self) -> provider::Result {
+        let mut cached = self.cached.lock().await;
+        if let Some(credentials) = cached.as_ref() {
+            let fresh = credentials
+                .expiry()
+                .is_none_or(|expiry| expiry > SystemTime::now() + CREDENTIAL_REFRESH_MARGIN);
+            if fresh {
+                return Ok(credentials.clone());
+            }
+        }
+        let credentials = self.inner.provide_credentials().await?;
+        *cached = Some(credentials.clone());
+        Ok(credentials)
+    }
+}
+
+impl<P: ProvideCredentials> ProvideCredentials for RefreshingCredentials<P> {
+    fn provide_credentials<'a>(&'a self) -> provider::future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        provider::future::ProvideCredentials::new(self.credentials())
+    }
+}
+
+/// AWS error codes of calls rejected for exceeding a rate limit or request quota.
+const THROTTLING_CODES: &[&str] = &[
+    "ThrottlingException",
+    "Throttling",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+    "LimitExceededException",
+];
+
+/// Error code of a failed JSON RPC call, from the `x-amzn-ErrorType` header or the `__type` of
+/// the body, without the namespace (`com.amazonaws.ce#LimitExceededException`) or the URL
+/// (`ThrottlingException:http://...`) some services add.
+fn error_code(header: Option<&str>, body: &str) -> Option<String> {
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        #[serde(rename = "__type")]
+        error_type: Option<String>,
+    }
+    let code = match header {
+        Some(code) => code.to_string(),
+        None => serde_json::from_str::<ErrorBody>(body).ok()?.error_type?,
+    };
+    let code = code.split(':').next().unwrap_or_default();
+    Some(code.rsplit('#').next().unwrap_or_default().to_string())
+}
+
+/// Minimal SigV4-signed client for AWS services speaking the JSON RPC protocols, used for
+/// the APIs that have no SDK crate in our dependency tree.
+pub struct AwsJsonClient {
+    signing_name: &'static str,
+    target_prefix: &'static str,
+    content_type: &'static str,
+    region: String,
+    endpoint: String,
+    credentials: SharedCredentialsProvider,
+    client: reqwest::Client,
+}
+
+impl AwsJsonClient {
+    fn new(
+        config: &SdkConfig,
+        signing_name: &'static str,
+        host_prefix: &str,
+        target_prefix: &'static str,
+        content_type: &'static str,
+    ) -> Result<Self, MonitorError> {
+        let region = config
+            .region()
+            .map(|region| region.to_string())
+            .ok_or_else(|| anyhow::anyhow!("AWS region is not configured"))?;
+        let credentials = config
+            .credentials_provider()
+            .ok_or_else(|| anyhow::anyhow!("no AWS credentials provider configured"))?;
+        Ok(Self {
+            signing_name,
+            target_prefix,
+            content_type,
+            endpoint: format!("https://{}.{}.amazonaws.com/", host_prefix, region),
+            region,
+            credentials,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    pub fn cloudwatch(config: &SdkConfig) -> Result<Self, MonitorError> {
+        Self::new(
+            config,
+            "monitoring",
+            "monitoring",
+            "GraniteServiceVersion20100801",
+            "application/x-amz-json-1.0",
+        )
+    }
+
+    /// Cost Explorer is a global service served from us-east-1 only.
+    pub fn cost_explorer(config: &SdkConfig) -> Result<Self, MonitorError> {
+        let mut client = Self::new(
+            config,
+            "ce",
+            "ce",
+            "AWSInsightsIndexService",
+            "application/x-amz-json-1.1",
+        )?;
+        client.region = "us-east-1".to_string();
+        client.endpoint = "https://ce.us-east-1.amazonaws.com/".to_string();
+        Ok(client)
+    }
+
+    pub fn logs(config: &SdkConfig) -> Result<Self, MonitorError> {
+        Self::new(
+            config,
+            "logs",
+            "logs",
+            "Logs_20140328",
+            "application/x-amz-json-1.1",
+        )
+    }
+
+    pub fn sagemaker(config: &SdkConfig) -> Result<Self, MonitorError> {
+        Self::new(
+            config,
+            "sagemaker",
+            "api.sagemaker",
+            "SageMaker",
+            "application/x-amz-json-1.1",
+        )
+    }
+
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    pub async fn call<Req, Resp>(&self, action: &str, request: &Req) -> Result<Resp, MonitorError>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(request).map_err(|_| MonitorError::InvalidResponse)?;
+        let target = format!("{}.{}", self.target_prefix, action);
+        let credentials = self
+            .credentials
+            .provide_credentials()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to resolve AWS credentials: {}", e))?;
+
+        let identity = credentials.into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region)
+            .name(self.signing_name)
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()
+            .map_err(|e| anyhow::anyhow!("failed to build signing params: {}", e))?
+            .into();
+        let headers = [
+            ("content-type", self.content_type),
+            ("x-amz-target", target.as_str()),
+        ];
+        let signable = SignableRequest::new(
+            "POST",
+            self.endpoint.as_str(),
+            headers.iter().copied(),
+            SignableBody::Bytes(&body),
+        )
+        .map_err(|e| anyhow::anyhow!("failed to sign AWS request: {}", e))?;
+        let (instructions, _signature) = sign(signable, &params)
+            .map_err(|e| anyhow::anyhow!("failed to sign AWS request: {}", e))?
+            .into_parts();
+
+        let mut builder = self.client.post(&self.endpoint).body(body);
+        for (name, value) in headers.iter().copied().chain(instructions.headers()) {
+            builder = builder.header(name, value);
+        }
+
+        let response = timed(action, builder.send()).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let header_code = response
+                .headers()
+                .get("x-amzn-errortype")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let message = response.text().await.unwrap_or_default();
+            if error_code(header_code.as_deref(), &message)
+                .is_some_and(|code| THROTTLING_CODES.contains(&code.as_str()))
+            {
+                return Err(MonitorError::RateLimited { retry_after: None });
+            }
+            return Err(MonitorError::StatusError(
+                status,
+                format!("{}: {}", target, message),
+            ));
+        }
+        // some actions (PutMetricData) answer with an empty body
+        let body = response.bytes().await?;
+        let body: &[u8] = if body.is_empty() { b"{}" } else { &body };
+        serde_json::from_slice(body).map_err(|_| MonitorError::InvalidResponse)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Dimension {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetMetricDataRequest<'a> {
+    metric_data_queries: Vec<MetricDataQuery<'a>>,
+    start_time: i64,
+    end_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetricDataQuery<'a> {
+    id: String,
+    metric_stat: MetricStat<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetricStat<'a> {
+    metric: Metric<'a>,
+    period: i64,
+    stat: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Metric<'a> {
+    namespace: &'a str,
+    metric_name: &'a str,
+    dimensions: &'a [Dimension],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetMetricDataResponse {
+    #[serde(default)]
+    metric_data_results: Vec<MetricDataResult>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetricDataResult {
+    id: String,
+    #[serde(default)]
+    values: Vec<f64>,
+}
+
+impl AwsJsonClient {
+    /// Sums each CloudWatch metric over `[start, now)`, returning totals in the order given.
+    pub async fn metric_sums(
+        &self,
+        namespace: &str,
+        metrics: &[(&str, &[Dimension])],
+        start: DateTime<Utc>,
+    ) -> Result<Vec<f64>, MonitorError> {
+        let end = Utc::now();
+        // CloudWatch rejects more than 1440 datapoints per series, so size the period to fit.
+        let period = ((end - start).num_seconds() / 1440 / 60 + 1) * 60;
+        let mut totals = vec![0.0; metrics.len()];
+        let mut next_token = None;
+
+        loop {
+            let request = GetMetricDataRequest {
+                metric_data_queries: metrics
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (metric_name, dimensions))| MetricDataQuery {
+                        id: format!("m{}", index),
+                        metric_stat: MetricStat {
+                            metric: Metric {
+                                namespace,
+                                metric_name,
+                                dimensions,
+                            },
+                            period: period.max(60),
+                            stat: "Sum",
+                        },
+                    })
+                    .collect(),
+                start_time: start.timestamp(),
+                end_time: end.timestamp(),
+                next_token,
+            };
+            let response: GetMetricDataResponse = self.call("GetMetricData", &request).await?;
+
+            for result in response.metric_data_results {
+                let index: usize = result
+                    .id
+                    .trim_start_matches('m')
+                    .parse()
+                    .map_err(|_| MonitorError::InvalidResponse)?;
+                if let Some(total) = totals.get_mut(index) {
+                    *total += result.values.iter().sum::<f64>();
+                }
+            }
+
+            match response.next_token {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+
+        Ok(totals)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListMetricsRequest<'a> {
+    namespace: &'a str,
+    metric_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListMetricsResponse {
+    #[serde(default)]
+    metrics: Vec<ListedMetric>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedMetric {
+    #[serde(default)]
+    dimensions: Vec<ListedDimension>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListedDimension {
+    name: String,
+    value: String,
+}
+
+impl AwsJsonClient {
+    /// Lists the distinct values CloudWatch has recorded for one dimension of a metric.
+    pub async fn dimension_values(
+        &self,
+        namespace: &str,
+        metric_name: &str,
+        dimension: &str,
+    ) -> Result<Vec<String>, MonitorError> {
+        let mut values = Vec::new();
+        let mut next_token = None;
+        loop {
+            let response: ListMetricsResponse = self
+                .call(
+                    "ListMetrics",
+                    &ListMetricsRequest {
+                        namespace,
+                        metric_name,
+                        next_token,
+                    },
+                )
+                .await?;
+            for metric in response.metrics {
+                // Only series keyed by this dimension alone, so totals aren't double counted.
+                if let [only] = metric.dimensions.as_slice() {
+                    if only.name == dimension && !values.contains(&only.value) {
+                        values.push(only.value.clone());
+                    }
+                }
+            }
+            match response.next_token {
+                Some(token) => next_token = Some(token),
+                None => return Ok(values),
+            }
+        }
+    }
+}
+
+/// CloudWatch accepts at most this many data points per PutMetricData call.
+const PUT_METRIC_DATA_BATCH: usize = 1000;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MetricDatum {
+    pub metric_name: String,
+    pub dimensions: Vec<Dimension>,
+    pub value: f64,
+    pub unit: &'static str,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PutMetricDataRequest<'a> {
+    namespace: &'a str,
+    metric_data: &'a [MetricDatum],
+}
+
+impl AwsJsonClient {
+    /// Publishes data points, split into batches within the PutMetricData limit.
+    pub async fn put_metric_data(
+        &self,
+        namespace: &str,
+        data: &[MetricDatum],
+    ) -> Result<(), MonitorError> {
+        for batch in data.chunks(PUT_METRIC_DATA_BATCH) {
+            let request = PutMetricDataRequest {
+                namespace,
+                metric_data: batch,
+            };
+            let _: serde::de::IgnoredAny = self.call("PutMetricData", &request).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use yaml_rust::Yaml;
+
+use crate::alerts::AlertEvaluator;
+use crate::metrics::LLMMetrics;
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+/// A monthly spending limit on the usage matching its selectors: every provider when none is
+/// set, one provider, one model, or the series carrying given labels (e.g. a team's project).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Budget {
+    pub name: String,
+    pub limit_usd: f64,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Values the [`USAGE_LABELS`] of matching usage must have.
+    pub labels: BTreeMap<String, String>,
+    /// Fractions of the limit that alert, instead of `ALERT_THRESHOLDS`.
+    pub alert_thresholds: Option<Vec<f64>>,
+    /// Slack channel for this budget's alerts when posting as a bot.
+    pub slack_channel: Option<String>,
+    /// Slack incoming webhook for this budget's alerts.
+    pub slack_webhook_url: Option<String>,
+}
+
+impl Budget {
+    /// Reads the `budgets` list of the config file.
+    pub fn list_from_yaml(node: &Yaml) -> Result<Vec<Self>, String> {
+        let entries = node.as_vec().ok_or("budgets must be a list")?;
+        let mut budgets: Vec<Self> = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            let budget =
+                Self::from_yaml(entry).map_err(|e| format!("budgets[{}]: {}", index, e))?;
+            if budgets.iter().any(|other| other.name == budget.name) {
+                return Err(format!("duplicate budget name {:?}", budget.name));
+            }
+            budgets.push(budget);
+        }
+        Ok(budgets)
+    }
+
+    fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let name = node["name"].as_str().ok_or("name is required")?.to_string();
+        let limit_usd = match &node["limit_usd"] {
+            Yaml::Real(_) => node["limit_usd"].as_f64(),
+            Yaml::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+        .filter(|limit| limit.is_finite() && *limit > 0.0)
+        .ok_or("limit_usd must be a positive number")?;
+        let mut labels = BTreeMap::new();
+        if let Some(entries) = node["labels"].as_hash() {
+            for (label, value) in entries {
+                let label = label.as_str().ok_or("label names must be strings")?;
+                if !USAGE_LABELS.contains(&label) {
+                    return Err(format!(
+                        "unknown label {:?}, expected one of {}",
+                        label,
+                        USAGE_LABELS.join(", ")
+                    ));
+                }
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("labels.{} must be a string", label))?;
+                labels.insert(label.to_string(), value.to_string());
+            }
+        }
+        let alert_thresholds = match &node["alert_thresholds"] {
+            Yaml::BadValue | Yaml::Null => None,
+            Yaml::Array(thresholds) => Some(
+                thresholds
+                    .iter()
+                    .map(|threshold| match threshold {
+                        Yaml::Real(_) => threshold.as_f64(),
+                        Yaml::Integer(value) => Some(*value as f64),
+                        _ => None,
+                    })
+                    .map(|threshold| {
+                        threshold
+                            .filter(|threshold| threshold.is_finite() && *threshold > 0.0)
+                            .ok_or("alert_thresholds must be positive numbers")
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err("alert_thresholds must be a list".to_string()),
+        };
+        Ok(Self {
+            name,
+            limit_usd,
+            provider: node["provider"].as_str().map(str::to_string),
+            model: node["model"].as_str().map(str::to_string),
+            labels,
+            alert_thresholds,
+            slack_channel: node["slack_channel"].as_str().map(str::to_string),
+            slack_webhook_url: node["slack_webhook_url"].as_str().map(str::to_string),
+        })
+    }
+
+    fn matches(&self, provider: &str, usage: &LLMUsage) -> bool {
+        self.provider.as_deref().is_none_or(|p| p == provider)
+            && self.model.as_deref().is_none_or(|m| m == usage.model)
+            && self.labels.iter().all(|(label, value)| {
+                usage.labels.get(label.as_str()).map(String::as_str) == Some(value.as_str())
+            })
+    }
+}
+
+/// Month-to-date spend of every budget, from the last successful poll of each provider, with
+/// alerts on threshold crossings when an evaluator is configured. Clones share the budgets and
+/// usage, so one can be kept to change the budgets after the tracker is added as a sink.
+#[derive(Clone)]
+pub struct BudgetTracker {
+    budgets: Arc<RwLock<Vec<Budget>>>,
+    metrics: Arc<LLMMetrics>,
+    alerts: Option<Arc<AlertEvaluator>>,
+    usage: Arc<Mutex<BTreeMap<&'static str, Vec<LLMUsage>>>>,
+}
+
+impl BudgetTracker {
+    pub fn new(
+        budgets: Vec<Budget>,
+        metrics: Arc<LLMMetrics>,
+        alerts: Option<AlertEvaluator>,
+    ) -> Self {
+        for budget in &budgets {
+            metrics.set_budget(&budget.name, budget.limit_usd, 0.0);
+        }
+        Self {
+            budgets: Arc::new(RwLock::new(budgets)),
+            metrics,
+            alerts: alerts.map(Arc::new),
+            usage: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Replaces the budgets, e.g. when the config is reloaded, and updates their metrics with
+    /// the spend of the last polls. Alerts are evaluated with the next poll.
+    pub fn set_budgets(&self, budgets: Vec<Budget>) {
+        for old in self.budgets.read().unwrap().iter() {
+            if !budgets.iter().any(|budget| budget.name == old.name) {
+                self.metrics.remove_budget(&old.name);
+            }
+        }
+        *self.budgets.write().unwrap() = budgets;
+        for (budget, spend) in self.spend() {
+            self.metrics
+                .set_budget(&budget.name, budget.limit_usd, spend);
+        }
+    }
+
+    /// Drops the usage of providers that are no longer polled, e.g. after a config reload, so
+    /// it doesn't count towards the budgets anymore.
+    pub fn retain_providers(&self, providers: &[&str]) {
+        self.usage
+            .lock()
+            .unwrap()
+            .retain(|provider, _| providers.contains(provider));
+    }
+
+    /// Spend of every budget, in configuration order.
+    pub fn spend(&self) -> Vec<(Budget, f64)> {
+        let usage = self.usage.lock().unwrap();
+        self.budgets
+            .read()
+            .unwrap()
+            .iter()
+            .map(|budget| {
+                let spend = usage
+                    .iter()
+                    .flat_map(|(provider, models)| models.iter().map(move |m| (*provider, m)))
+                    .filter(|(provider, model_usage)| budget.matches(provider, model_usage))
+                    // folded from 0.0 because an empty f64 sum is -0.0
+                    .fold(0.0, |spend, (_, model_usage)| spend + model_usage.cost_usd);
+                (budget.clone(), spend)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl UsageSink for BudgetTracker {
+    fn name(&self) -> &'static str {
+        "budgets"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        {
+            let mut stored = self.usage.lock().unwrap();
+            for provider_usage in usage {
+                stored.insert(provider_usage.provider, provider_usage.usage.clone());
+            }
+        }
+        let spend = self.spend();
+        for (budget, spend) in &spend {
+            self.metrics
+                .set_budget(&budget.name, budget.limit_usd, *spend);
+        }
+        if let Some(alerts) = &self.alerts {
+            let spend: Vec<(&Budget, f64)> = spend
+                .iter()
+                .map(|(budget, spend)| (budget, *spend))
+                .collect();
+            alerts.evaluate(&spend).await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use prometheus::IntCounterVec;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::labels::LabelPolicy;
+use crate::providers::{parse_map, LLMUsage};
+use crate::MonitorError;
+
+/// Distinct values a label keeps by default before new ones are exported as `other`.
+pub const DEFAULT_LABEL_VALUE_LIMIT: usize = 1000;
+
+/// Value exported in place of label values beyond the limit.
+pub const OVERFLOW_VALUE: &str = "other";
+
+/// How many distinct values each label may take, so per-user or per-model labels can't grow
+/// the number of series without bound. Zero means unlimited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelLimits {
+    default: usize,
+    per_label: HashMap<String, usize>,
+}
+
+impl Default for LabelLimits {
+    fn default() -> Self {
+        Self::new(DEFAULT_LABEL_VALUE_LIMIT)
+    }
+}
+
+impl LabelLimits {
+    pub fn new(default: usize) -> Self {
+        Self {
+            default,
+            per_label: HashMap::new(),
+        }
+    }
+
+    /// Sets the limit of one label, e.g. a lower one for `user`.
+    pub fn label(mut self, label: &str, limit: usize) -> Self {
+        self.per_label.insert(label.to_string(), limit);
+        self
+    }
+
+    /// Reads the default limit from `LABEL_VALUE_LIMIT` and per-label limits from
+    /// `LABEL_VALUE_LIMITS`, e.g. `user=200,model=500`.
+    pub fn from_env() -> Result<Self, MonitorError> {
+        let parse = |name: &str, value: &str| {
+            value.parse::<usize>().map_err(|_| {
+                MonitorError::from(anyhow::anyhow!("invalid {} limit {:?}", name, value))
+            })
+        };
+        let mut limits = match std::env::var("LABEL_VALUE_LIMIT") {
+            Ok(limit) if !limit.is_empty() => Self::new(parse("LABEL_VALUE_LIMIT", &limit)?),
+            _ => Self::default(),
+        };
+        let per_label = parse_map(&std::env::var("LABEL_VALUE_LIMITS").unwrap_or_default());
+        for (label, limit) in per_label {
+            let limit = parse(&label, &limit)?;
+            limits = limits.label(&label, limit);
+        }
+        Ok(limits)
+    }
+
+    fn limit(&self, label: &str) -> usize {
+        self.per_label.get(label).copied().unwrap_or(self.default)
+    }
+}
+
+/// Applies the [`LabelPolicy`], then tracks the distinct values of every label and replaces
+/// values beyond the label's limit with `other`. The first values seen keep their own series
+/// for the life of the process; values the policy removes don't count towards the limit.
+pub(crate) struct CardinalityGuard {
+    policy: LabelPolicy,
+    limits: LabelLimits,
+    seen: Mutex<HashMap<String, HashSet<String>>>,
+    dropped: IntCounterVec,
+}
+
+impl CardinalityGuard {
+    /// Counts the values replaced in `dropped`, by label.
+    pub(crate) fn new(dropped: IntCounterVec) -> Self {
+        Self {
+            policy: LabelPolicy::default(),
+            limits: LabelLimits::default(),
+            seen: Mutex::new(HashMap::new()),
+            dropped,
+        }
+    }
+
+    pub(crate) fn set_limits(&mut self, limits: LabelLimits) {
+        self.limits = limits;
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: LabelPolicy) {
+        self.policy = policy;
+    }
+
+    pub(crate) fn exports(&self, label: &str) -> bool {
+        self.policy.exports(label)
+    }
+
+    /// The value to export for `label`.
+    pub(crate) fn value<'a>(&self, label: &str, value: &'a str) -> Cow<'a, str> {
+        if !self.policy.allows(label, value) {
+            return Cow::Borrowed("");
+        }
+        let limit = self.limits.limit(label);
+        if value.is_empty() || limit == 0 {
+            return Cow::Borrowed(value);
+        }
+        let mut seen = self.seen.lock().unwrap();
+        let values = seen.entry(label.to_string()).or_default();
+        if values.contains(value) {
+            return Cow::Borrowed(value);
+        }
+        if values.len() < limit {
+            values.insert(value.to_string());
+            return Cow::Borrowed(value);
+        }
+        self.dropped.with_label_values(&[label]).inc();
+        Cow::Borrowed(OVERFLOW_VALUE)
+    }
+
+    /// Filters and limits the `model` and usage labels of a provider's usage, adding up the
+    /// usage that ends up with the same labels so no series overwrites another.
+    pub(crate) fn limit_usage(&self, usage: &[LLMUsage]) -> Vec<LLMUsage> {
+        let mut limited: Vec<LLMUsage> = Vec::with_capacity(usage.len());
+        for model_usage in usage {
+            let mut model_usage = model_usage.clone();
+            model_usage.model = self.value("model", &model_usage.model).into_owned();
+            for (label, value) in model_usage.labels.iter_mut() {
+                *value = self.value(label, value).into_owned();
+            }
+            match limited.iter_mut().find(|other| {
+                other.model == model_usage.model && other.labels == model_usage.labels
+            }) {
+                Some(other) => other.add(&model_usage),
+                None => limited.push(model_usage),
+            }
+        }
+        limited
+    }
+}
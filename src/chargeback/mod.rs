@@ -0,0 +1,325 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::history::UsageHistory;
+use crate::leader::Leadership;
+use crate::output;
+use crate::providers::{
+    billing_period_end, billing_period_start, billing_period_start_at, reporting_timezone,
+};
+use crate::report::{self, ReportGroup, ReportRow};
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+pub mod email;
+pub mod s3;
+pub mod webhook;
+
+use email::EmailDelivery;
+use s3::S3Delivery;
+use webhook::WebhookDelivery;
+
+const DEFAULT_TOP_MODELS: usize = 3;
+/// How long after a billing period ends its report is generated, so that a poll running at
+/// the end of the period is recorded first.
+const GENERATION_DELAY: Duration = Duration::from_secs(300);
+
+/// One of a team's most expensive models.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelCharge {
+    pub provider: String,
+    pub model: String,
+    pub cost_usd: f64,
+}
+
+/// What a team is charged for a billing period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TeamCharge {
+    /// Empty for the usage no team claims.
+    pub team: String,
+    pub cost_usd: f64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub requests: u64,
+    /// The team's most expensive models, most expensive first.
+    pub top_models: Vec<ModelCharge>,
+}
+
+/// Usage and cost of a billing period broken down by team, most expensive team first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChargebackReport {
+    pub period_start: NaiveDate,
+    /// Last day of the period, included.
+    pub period_end: NaiveDate,
+    pub total_usd: f64,
+    pub teams: Vec<TeamCharge>,
+}
+
+impl ChargebackReport {
+    pub const HEADER: [&'static str; 6] = [
+        "team",
+        "cost_usd",
+        "prompt_tokens",
+        "completion_tokens",
+        "requests",
+        "top_models",
+    ];
+
+    /// Sums report rows grouped by provider, model and team into one charge per team.
+    pub fn new(
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+        rows: Vec<ReportRow>,
+        top_models: usize,
+    ) -> Self {
+        let mut teams: BTreeMap<String, TeamCharge> = BTreeMap::new();
+        for row in rows {
+            let charge = teams.entry(row.team.clone()).or_insert_with(|| TeamCharge {
+                team: row.team.clone(),
+                ..TeamCharge::default()
+            });
+            charge.cost_usd += row.cost_usd;
+            charge.prompt_tokens += row.prompt_tokens;
+            charge.completion_tokens += row.completion_tokens;
+            charge.requests += row.requests;
+            charge.top_models.push(ModelCharge {
+                provider: row.provider,
+                model: row.model,
+                cost_usd: row.cost_usd,
+            });
+        }
+        let mut teams: Vec<TeamCharge> = teams
+            .into_values()
+            .map(|mut charge| {
+                charge
+                    .top_models
+                    .sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+                charge.top_models.truncate(top_models);
+                charge
+            })
+            .collect();
+        teams.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+        // folded from 0.0 because an empty f64 sum is -0.0
+        let total_usd = teams
+            .iter()
+            .fold(0.0, |total, charge| total + charge.cost_usd);
+        Self {
+            period_start,
+            period_end,
+            total_usd,
+            teams,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "LLM chargeback {} to {}",
+            self.period_start, self.period_end
+        )
+    }
+
+    /// One row per team, usage no team claims as `unassigned`, and the top models in one
+    /// cell, e.g. `openai/gpt-4o $12.50; anthropic/claude-sonnet-4 $3.10`.
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.teams
+            .iter()
+            .map(|charge| {
+                let top_models: Vec<String> = charge
+                    .top_models
+                    .iter()
+                    .map(|model| {
+                        format!("{}/{} ${:.2}", model.provider, model.model, model.cost_usd)
+                    })
+                    .collect();
+                vec![
+                    match charge.team.as_str() {
+                        "" => "unassigned".to_string(),
+                        team => team.to_string(),
+                    },
+                    format!("{:.4}", charge.cost_usd),
+                    charge.prompt_tokens.to_string(),
+                    charge.completion_tokens.to_string(),
+                    charge.requests.to_string(),
+                    top_models.join("; "),
+                ]
+            })
+            .collect()
+    }
+
+    pub fn csv(&self) -> String {
+        output::csv(&Self::HEADER, &self.rows())
+    }
+
+    /// A summary for reading, e.g. the body of an email.
+    pub fn text(&self) -> String {
+        format!(
+            "{}\n\nTotal: ${:.2}\n\n{}",
+            self.title(),
+            self.total_usd,
+            output::table(&Self::HEADER, &self.rows())
+        )
+    }
+}
+
+/// Destination of chargeback reports.
+#[async_trait]
+pub trait ReportDelivery: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn deliver(&self, report: &ChargebackReport) -> Result<(), MonitorError>;
+}
+
+/// First and last day of the billing period starting at `start`, in the reporting timezone.
+/// Reports follow the default anchor day, like budgets across providers.
+fn period_days(start: DateTime<Utc>) -> (NaiveDate, NaiveDate) {
+    let date = |time: DateTime<Utc>| time.with_timezone(&reporting_timezone()).date_naive();
+    let end = date(billing_period_end("", start));
+    (date(start), end.pred_opt().unwrap_or(end))
+}
+
+/// First and last day of the last billing period that ended.
+pub fn last_period() -> (NaiveDate, NaiveDate) {
+    period_days(billing_period_start_at(
+        "",
+        billing_period_start("") - TimeDelta::days(1),
+    ))
+}
+
+/// Generates a per-team chargeback report from the usage history at the end of each billing
+/// period and delivers it to every configured destination.
+pub struct ChargebackReporter {
+    history: Arc<dyn UsageHistory>,
+    deliveries: Vec<Box<dyn ReportDelivery>>,
+    top_models: usize,
+}
+
+impl ChargebackReporter {
+    /// Enabled when `CHARGEBACK_WEBHOOK_URLS`, `CHARGEBACK_EMAIL_TO` or `CHARGEBACK_S3_URL` is
+    /// set, which requires the usage history. `CHARGEBACK_TOP_MODELS` (default 3) is how many
+    /// models are listed per team.
+    pub async fn from_env(
+        history: Option<Arc<dyn UsageHistory>>,
+    ) -> Result<Option<Self>, MonitorError> {
+        let urls = std::env::var("CHARGEBACK_WEBHOOK_URLS").unwrap_or_default();
+        let mut deliveries: Vec<Box<dyn ReportDelivery>> = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| -> Box<dyn ReportDelivery> {
+                Box::new(WebhookDelivery::new(url.to_string()))
+            })
+            .collect();
+        if let Some(email) = EmailDelivery::from_env()? {
+            deliveries.push(Box::new(email));
+        }
+        if let Some(s3) = S3Delivery::from_env().await? {
+            deliveries.push(Box::new(s3));
+        }
+        if deliveries.is_empty() {
+            return Ok(None);
+        }
+        let history = history.ok_or_else(|| {
+            anyhow::anyhow!(
+                "chargeback reports need usage history, set HISTORY_SQLITE_PATH or HISTORY_POSTGRES_URL"
+            )
+        })?;
+        let top_models = match std::env::var("CHARGEBACK_TOP_MODELS") {
+            Ok(top) if !top.is_empty() => top
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid CHARGEBACK_TOP_MODELS {:?}", top))?,
+            _ => DEFAULT_TOP_MODELS,
+        };
+        Ok(Some(Self {
+            history,
+            deliveries,
+            top_models,
+        }))
+    }
+
+    /// Reports the usage from `first` to `last` day inclusive.
+    pub async fn generate(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<ChargebackReport, MonitorError> {
+        let rows = report::usage(
+            self.history.as_ref(),
+            first,
+            last,
+            &[ReportGroup::Provider, ReportGroup::Model, ReportGroup::Team],
+        )
+        .await?;
+        Ok(ChargebackReport::new(first, last, rows, self.top_models))
+    }
+
+    /// Sends the report to every destination, and fails if any of them failed.
+    pub async fn deliver(&self, report: &ChargebackReport) -> Result<(), MonitorError> {
+        let mut failed = Vec::new();
+        for delivery in &self.deliveries {
+            match delivery.deliver(report).await {
+                Ok(()) => info!(
+                    delivery = delivery.name(),
+                    period_start = %report.period_start,
+                    "chargeback report delivered"
+                ),
+                Err(e) => {
+                    warn!(
+                        delivery = delivery.name(),
+                        period_start = %report.period_start,
+                        error = %e,
+                        "failed to deliver chargeback report"
+                    );
+                    failed.push(delivery.name());
+                }
+            }
+        }
+        if !failed.is_empty() {
+            return Err(anyhow::anyhow!(
+                "chargeback report delivery failed for {}",
+                failed.join(", ")
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Reports each billing period shortly after it ends, until shutdown. Only the leader
+    /// reports; a period that ends while the exporter is down isn't reported, which the
+    /// `chargeback` command makes up for.
+    pub async fn run(self, leadership: Leadership, shutdown: Shutdown) {
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        loop {
+            let period_start = billing_period_start("");
+            let wait = (billing_period_end("", period_start) - Utc::now())
+                .to_std()
+                .unwrap_or_default()
+                + GENERATION_DELAY;
+            tokio::select! {
+                _ = time::sleep(wait) => {}
+                _ = &mut stop => break,
+            }
+            if !leadership.is_leader() {
+                continue;
+            }
+            let (first, last) = period_days(period_start);
+            match self.generate(first, last).await {
+                Ok(report) => {
+                    // failures were logged per destination
+                    let _ = self.deliver(&report).await;
+                }
+                Err(e) => warn!(
+                    period_start = %first,
+                    error = %e,
+                    "failed to generate chargeback report"
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use aws_sdk_s3::primitives::ByteStream;
+
+use super::{ChargebackReport, ReportDelivery};
+use crate::MonitorError;
+
+/// Writes every report to S3 as `chargeback-<period start>.json` and `.csv` under a prefix.
+pub struct S3Delivery {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl S3Delivery {
+    /// Enabled when `CHARGEBACK_S3_URL` is set, e.g. `s3://finance/llm-chargeback/`, using the
+    /// default AWS configuration.
+    pub async fn from_env() -> Result<Option<Self>, MonitorError> {
+        let url = match std::env::var("CHARGEBACK_S3_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let location = url
+            .strip_prefix("s3://")
+            .filter(|location| !location.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "CHARGEBACK_S3_URL must be s3://<bucket>/<prefix>, got {:?}",
+                    url
+                )
+            })?;
+        let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+        let prefix = match prefix {
+            "" => String::new(),
+            prefix => format!("{}/", prefix.trim_end_matches('/')),
+        };
+        let aws_config = aws_config::load_from_env().await;
+        Ok(Some(Self {
+            client: aws_sdk_s3::Client::new(&aws_config),
+            bucket: bucket.to_string(),
+            prefix,
+        }))
+    }
+
+    async fn put(
+        &self,
+        key: String,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<(), MonitorError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to write s3://{}/{}: {}", self.bucket, key, e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReportDelivery for S3Delivery {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn deliver(&self, report: &ChargebackReport) -> Result<(), MonitorError> {
+        let name = format!("{}chargeback-{}", self.prefix, report.period_start);
+        let json = serde_json::to_vec_pretty(report).map_err(anyhow::Error::from)?;
+        self.put(format!("{}.json", name), "application/json", json)
+            .await?;
+        self.put(
+            format!("{}.csv", name),
+            "text/csv",
+            report.csv().into_bytes(),
+        )
+        .await
+    }
+}
@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::{ChargebackReport, ReportDelivery};
+use crate::MonitorError;
+
+/// POSTs every report as JSON to a URL.
+pub struct WebhookDelivery {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookDelivery {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl ReportDelivery for WebhookDelivery {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn deliver(&self, report: &ChargebackReport) -> Result<(), MonitorError> {
+        self.client
+            .post(&self.url)
+            .timeout(Duration::from_secs(30))
+            .json(report)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
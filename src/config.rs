@@ -0,0 +1,393 @@
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::auth::AuthConfig;
+use crate::budget::Budget;
+use crate::cost_centers::CostCenterRules;
+use crate::labels::LabelPolicy;
+use crate::logging::LogFormat;
+use crate::metrics::METRIC_LABELS;
+use crate::output::OutputFormat;
+use crate::providers::{ProviderEntry, USAGE_LABELS};
+use crate::report::ReportGroup;
+use crate::teams::TeamMapping;
+use crate::tls::TlsConfig;
+use crate::MonitorError;
+
+/// Command line flags. Each flag can also be set from its environment variable, and anything
+/// left unset falls back to the config file and then to the built-in default.
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "Prometheus exporter for LLM provider usage and cost")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Same as the once command.
+    #[arg(long)]
+    pub once: bool,
+    /// YAML config file.
+    #[arg(long, env = "CONFIG_FILE", global = true)]
+    pub config: Option<String>,
+    /// Address to bind the metrics server to, e.g. 127.0.0.1 for localhost only.
+    #[arg(long, env = "LISTEN_ADDRESS", global = true)]
+    pub listen_address: Option<IpAddr>,
+    /// Port of the metrics server.
+    #[arg(long, env = "LISTEN_PORT", global = true)]
+    pub port: Option<u16>,
+    /// Path the metrics are served on.
+    #[arg(long, env = "METRICS_PATH", global = true)]
+    pub metrics_path: Option<String>,
+    /// PEM certificate chain; serves HTTPS together with --tls-key-file.
+    #[arg(long, env = "TLS_CERT_FILE", global = true)]
+    pub tls_cert_file: Option<String>,
+    /// PEM private key of the certificate.
+    #[arg(long, env = "TLS_KEY_FILE", global = true)]
+    pub tls_key_file: Option<String>,
+    /// Providers that do not have to report before /readyz succeeds.
+    #[arg(long, env = "OPTIONAL_PROVIDERS", value_delimiter = ',', global = true)]
+    pub optional_providers: Vec<String>,
+    /// YAML or JSON file with model prices that add to or override the built-in ones.
+    #[arg(long, env = "PRICING_FILE", global = true)]
+    pub pricing_file: Option<String>,
+    /// Constant label added to every series, as name=value, e.g. env=prod.
+    #[arg(
+        long = "extra-label",
+        env = "EXTRA_LABELS",
+        value_delimiter = ',',
+        global = true
+    )]
+    pub extra_labels: Vec<String>,
+    /// Log level or filter, e.g. debug or llm_cost_exporter=debug,warp=warn. Defaults to info.
+    #[arg(long, env = "RUST_LOG", global = true)]
+    pub log_level: Option<String>,
+    /// Log output format.
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t, global = true)]
+    pub log_format: LogFormat,
+}
+
+/// What the binary does, by default `serve`. Every command reads the same flags, environment
+/// and config file.
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Runs the exporter: polls the providers and serves the metrics until stopped.
+    Serve,
+    /// Checks the config file, pricing and provider credentials, and exits non-zero with every
+    /// problem found, e.g. in CI before a deploy.
+    Validate,
+    /// Polls every provider once, prints the usage and exits, non-zero when a provider failed,
+    /// e.g. from cron or to check credentials.
+    Once {
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+        /// Also pushes the metrics to the Pushgateway of PUSHGATEWAY_URL.
+        #[arg(long)]
+        push: bool,
+    },
+    /// Prints the usage and cost of a date range from the usage history, by provider, model
+    /// and team, e.g. for monthly chargeback.
+    Report {
+        /// First day of the range, by default the first day of last month.
+        #[arg(long)]
+        start: Option<NaiveDate>,
+        /// Last day of the range, included; by default the last day of last month.
+        #[arg(long)]
+        end: Option<NaiveDate>,
+        /// Breakdowns of the rows; usage is summed over the others.
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_values_t = [
+                ReportGroup::Provider,
+                ReportGroup::Model,
+                ReportGroup::Team,
+                ReportGroup::CostCenter,
+            ]
+        )]
+        group_by: Vec<ReportGroup>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+        format: OutputFormat,
+    },
+    /// Generates the per-team chargeback report of a date range from the usage history, by
+    /// default of the last billing period that ended, prints it and delivers it to the
+    /// configured destinations, e.g. for a period that ended while the exporter was down.
+    Chargeback {
+        /// First day of the range.
+        #[arg(long, requires = "end")]
+        start: Option<NaiveDate>,
+        /// Last day of the range, included.
+        #[arg(long, requires = "start")]
+        end: Option<NaiveDate>,
+    },
+    /// Prints the model prices in use: the built-in ones with the pricing file applied. Token
+    /// prices are USD per 1M tokens, request fees USD per 1K requests.
+    PrintPricing {
+        /// Only the prices of this provider.
+        #[arg(long)]
+        provider: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+}
+
+/// Settings of the HTTP server exposing the metrics.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub listen_address: IpAddr,
+    pub port: u16,
+    pub metrics_path: String,
+    pub tls: Option<TlsConfig>,
+    pub auth: Option<AuthConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: IpAddr::from([0, 0, 0, 0]),
+            port: 8000,
+            metrics_path: "/metrics".to_string(),
+            tls: None,
+            auth: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.listen_address, self.port)
+    }
+}
+
+/// Parsed config file. Only sections the exporter knows about are read.
+#[derive(Debug, Default)]
+pub struct FileConfig {
+    root: Option<Yaml>,
+}
+
+impl FileConfig {
+    pub fn load(path: &str) -> Result<Self, MonitorError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read config file {}: {}", path, e))?;
+        let mut documents = YamlLoader::load_from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path, e))?;
+        Ok(Self {
+            root: (!documents.is_empty()).then(|| documents.remove(0)),
+        })
+    }
+
+    /// Looks up a nested key such as `["server", "port"]`.
+    pub fn get(&self, path: &[&str]) -> Option<&Yaml> {
+        let mut node = self.root.as_ref()?;
+        for key in path {
+            node = &node[*key];
+        }
+        (!node.is_badvalue() && !node.is_null()).then_some(node)
+    }
+
+    fn get_str(&self, path: &[&str]) -> Option<String> {
+        self.get(path).and_then(|node| match node {
+            Yaml::String(value) => Some(value.clone()),
+            Yaml::Integer(value) => Some(value.to_string()),
+            _ => None,
+        })
+    }
+}
+
+/// Everything the exporter reads at startup besides the per-provider environment variables.
+#[derive(Debug)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub optional_providers: Vec<String>,
+    pub pricing_file: Option<String>,
+    pub budgets: Vec<Budget>,
+    /// Providers to monitor; `None` enables every provider configured in the environment.
+    pub providers: Option<Vec<ProviderEntry>>,
+    pub teams: TeamMapping,
+    pub cost_centers: CostCenterRules,
+    pub labels: LabelPolicy,
+    /// Constant labels of every series, to tell exporter instances apart.
+    pub extra_labels: HashMap<String, String>,
+}
+
+impl Config {
+    /// Resolves the configuration with precedence CLI flag, environment, config file, default.
+    pub fn load(cli: Cli) -> Result<Self, MonitorError> {
+        let file = match &cli.config {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+
+        let defaults = ServerConfig::default();
+        let listen_address = match cli.listen_address {
+            Some(address) => address,
+            None => match file.get_str(&["server", "listen_address"]) {
+                Some(address) => address
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid server.listen_address {:?}", address))?,
+                None => defaults.listen_address,
+            },
+        };
+        let port = match cli.port {
+            Some(port) => port,
+            None => match file.get(&["server", "port"]).and_then(Yaml::as_i64) {
+                Some(port) => u16::try_from(port)
+                    .map_err(|_| anyhow::anyhow!("invalid server.port {}", port))?,
+                None => defaults.port,
+            },
+        };
+        let metrics_path = cli
+            .metrics_path
+            .or_else(|| file.get_str(&["server", "metrics_path"]))
+            .unwrap_or(defaults.metrics_path);
+        if !metrics_path.starts_with('/') || metrics_path.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "metrics path must start with / and not be empty, got {:?}",
+                metrics_path
+            )
+            .into());
+        }
+
+        let cert_file = cli
+            .tls_cert_file
+            .or_else(|| file.get_str(&["server", "tls", "cert_file"]));
+        let key_file = cli
+            .tls_key_file
+            .or_else(|| file.get_str(&["server", "tls", "key_file"]));
+        let tls = match (cert_file, key_file) {
+            (Some(cert_file), Some(key_file)) => Some(TlsConfig {
+                cert_file,
+                key_file,
+            }),
+            (None, None) => None,
+            _ => return Err(anyhow::anyhow!("TLS needs both a certificate and a key file").into()),
+        };
+
+        let optional_providers = if cli.optional_providers.is_empty() {
+            file.get(&["optional_providers"])
+                .and_then(Yaml::as_vec)
+                .map(|providers| {
+                    providers
+                        .iter()
+                        .filter_map(Yaml::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            cli.optional_providers
+        };
+
+        let pricing_file = cli.pricing_file.or_else(|| file.get_str(&["pricing_file"]));
+
+        let budgets = match file.get(&["budgets"]) {
+            Some(budgets) => Budget::list_from_yaml(budgets)
+                .map_err(|e| anyhow::anyhow!("invalid budgets: {}", e))?,
+            None => Vec::new(),
+        };
+        let providers = file
+            .get(&["providers"])
+            .map(ProviderEntry::list_from_yaml)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("invalid providers: {}", e))?;
+        let teams = match file.get(&["teams"]) {
+            Some(teams) => TeamMapping::from_yaml(teams)
+                .map_err(|e| anyhow::anyhow!("invalid teams: {}", e))?,
+            None => TeamMapping::default(),
+        };
+        let cost_centers = match file.get(&["cost_centers"]) {
+            Some(rules) => CostCenterRules::from_yaml(rules)
+                .map_err(|e| anyhow::anyhow!("invalid cost_centers: {}", e))?,
+            None => CostCenterRules::default(),
+        };
+        let labels = match file.get(&["labels"]) {
+            Some(labels) => LabelPolicy::from_yaml(labels)
+                .map_err(|e| anyhow::anyhow!("invalid labels: {}", e))?,
+            None => LabelPolicy::default(),
+        };
+
+        let extra_labels = if cli.extra_labels.is_empty() {
+            file.get(&["extra_labels"])
+                .and_then(Yaml::as_hash)
+                .into_iter()
+                .flatten()
+                .map(|(name, value)| match (name.as_str(), value) {
+                    (Some(name), Yaml::String(value)) => Ok((name.to_string(), value.clone())),
+                    (Some(name), Yaml::Integer(value)) => Ok((name.to_string(), value.to_string())),
+                    _ => Err(anyhow::anyhow!("extra_labels must map names to values")),
+                })
+                .collect::<Result<HashMap<_, _>, _>>()?
+        } else {
+            cli.extra_labels
+                .iter()
+                .map(|label| {
+                    label
+                        .split_once('=')
+                        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("invalid extra label {:?}, expected name=value", label)
+                        })
+                })
+                .collect::<Result<HashMap<_, _>, _>>()?
+        };
+        for name in extra_labels.keys() {
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !name.starts_with("__");
+            if !valid {
+                return Err(anyhow::anyhow!("invalid extra label name {:?}", name).into());
+            }
+            if METRIC_LABELS.contains(&name.as_str()) || USAGE_LABELS.contains(&name.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "extra label {:?} is already a label of the exporter's metrics",
+                    name
+                )
+                .into());
+            }
+        }
+
+        // Credentials come from the environment or the config file only, never from flags,
+        // so they do not show up in the process list.
+        let setting = |env: &str, path: &[&str]| {
+            std::env::var(env)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .or_else(|| file.get_str(path))
+        };
+        let username = setting("METRICS_AUTH_USERNAME", &["server", "auth", "username"]);
+        let password = setting("METRICS_AUTH_PASSWORD", &["server", "auth", "password"]);
+        let token = setting("METRICS_AUTH_TOKEN", &["server", "auth", "bearer_token"]);
+        let auth = match (username, password, token) {
+            (None, None, None) => None,
+            (Some(username), Some(password), None) => {
+                Some(AuthConfig::Basic { username, password })
+            }
+            (None, None, Some(token)) => Some(AuthConfig::Bearer { token }),
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "configure either a metrics auth username and password or a bearer token"
+                )
+                .into())
+            }
+        };
+
+        Ok(Self {
+            server: ServerConfig {
+                listen_address,
+                port,
+                metrics_path,
+                tls,
+                auth,
+            },
+            optional_providers,
+            pricing_file,
+            budgets,
+            providers,
+            teams,
+            cost_centers,
+            labels,
+            extra_labels,
+        })
+    }
+}
@@ -0,0 +1,247 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use yaml_rust::Yaml;
+
+use crate::providers::{ImageCount, LLMUsage};
+
+/// Conditions of a rule, each a pattern the whole value has to match. Conditions left out
+/// match anything.
+#[derive(Debug, Clone, Default)]
+pub struct RuleMatch {
+    pub provider: Option<Regex>,
+    pub model: Option<Regex>,
+    pub team: Option<Regex>,
+    /// The `project_id` or `project_name` label.
+    pub project: Option<Regex>,
+    /// A request header, by lowercase name, and its value. Only calls tracked in process
+    /// have headers, so polled usage never matches a rule with one.
+    pub header: Option<(String, Regex)>,
+}
+
+/// Charges the usage it matches to one cost center or splits it across several.
+#[derive(Debug, Clone)]
+pub struct CostCenterRule {
+    pub matches: RuleMatch,
+    /// Cost centers and the fraction of the usage each is charged, adding up to 1.
+    pub shares: Vec<(String, f64)>,
+}
+
+/// Rules attaching a `cost_center` label to polled usage and tracked calls; the first rule
+/// matching a series applies. Usage no rule matches keeps an empty `cost_center` label.
+#[derive(Debug, Clone, Default)]
+pub struct CostCenterRules {
+    rules: Vec<CostCenterRule>,
+}
+
+/// A pattern matching whole values.
+fn pattern(node: &Yaml, field: &str) -> Result<Option<Regex>, String> {
+    node.as_str()
+        .map(|pattern| {
+            Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("invalid {} pattern: {}", field, e))
+        })
+        .transpose()
+}
+
+impl CostCenterRules {
+    pub fn new(rules: Vec<CostCenterRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Reads the `cost_centers` list of the config file.
+    pub fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let entries = node.as_vec().ok_or("cost_centers must be a list")?;
+        let rules = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                Self::rule_from_yaml(entry).map_err(|e| format!("cost_centers[{}]: {}", index, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    fn rule_from_yaml(node: &Yaml) -> Result<CostCenterRule, String> {
+        let conditions = &node["match"];
+        let header = match conditions["header"].as_hash() {
+            None => None,
+            Some(headers) => {
+                let mut headers = headers.iter();
+                let (Some((name, value)), None) = (headers.next(), headers.next()) else {
+                    return Err("match.header must map one header to a pattern".to_string());
+                };
+                let name = name.as_str().ok_or("match.header must be keyed by name")?;
+                let value = pattern(value, "header")?.ok_or("header patterns must be strings")?;
+                Some((name.to_lowercase(), value))
+            }
+        };
+        let matches = RuleMatch {
+            provider: pattern(&conditions["provider"], "provider")?,
+            model: pattern(&conditions["model"], "model")?,
+            team: pattern(&conditions["team"], "team")?,
+            project: pattern(&conditions["project"], "project")?,
+            header,
+        };
+
+        let shares = match (node["cost_center"].as_str(), node["split"].as_hash()) {
+            (Some(center), None) => vec![(center.to_string(), 1.0)],
+            (None, Some(split)) => {
+                let mut shares = Vec::with_capacity(split.len());
+                let mut total = 0.0;
+                for (center, percent) in split {
+                    let center = center
+                        .as_str()
+                        .ok_or("split must be keyed by cost center")?;
+                    let percent = match percent {
+                        Yaml::Real(_) => percent.as_f64(),
+                        Yaml::Integer(value) => Some(*value as f64),
+                        _ => None,
+                    }
+                    .filter(|percent| percent.is_finite() && *percent > 0.0)
+                    .ok_or_else(|| format!("split.{} must be a positive percentage", center))?;
+                    shares.push((center.to_string(), percent / 100.0));
+                    total += percent;
+                }
+                if (total - 100.0).abs() > 1e-6 {
+                    return Err(format!("split percentages add up to {}, not 100", total));
+                }
+                shares
+            }
+            _ => return Err("needs either cost_center or split".to_string()),
+        };
+        Ok(CostCenterRule { matches, shares })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Lowercase names of the headers rules match on, which tracked calls have to keep.
+    pub fn headers(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.matches.header.as_ref())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Cost centers and shares of the first rule matching usage with these labels, empty when
+    /// none does.
+    fn shares(
+        &self,
+        provider: &str,
+        model: &str,
+        labels: &BTreeMap<&str, String>,
+        headers: &BTreeMap<String, String>,
+    ) -> &[(String, f64)] {
+        let is = |pattern: &Option<Regex>, value: &str| {
+            pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(value))
+        };
+        let label = |name| labels.get(name).map_or("", String::as_str);
+        self.rules
+            .iter()
+            .find(|rule| {
+                let matches = &rule.matches;
+                is(&matches.provider, provider)
+                    && is(&matches.model, model)
+                    && is(&matches.team, label("team"))
+                    && matches.project.as_ref().is_none_or(|project| {
+                        project.is_match(label("project_id"))
+                            || project.is_match(label("project_name"))
+                    })
+                    && matches.header.as_ref().is_none_or(|(name, value)| {
+                        headers
+                            .get(name)
+                            .is_some_and(|header| value.is_match(header))
+                    })
+            })
+            .map_or(&[], |rule| rule.shares.as_slice())
+    }
+
+    /// Labels polled usage with its cost center, splitting the usage of a series shared by
+    /// several cost centers into one series per center.
+    pub fn apply(&self, provider: &str, usage: Vec<LLMUsage>) -> Vec<LLMUsage> {
+        if self.rules.is_empty() {
+            return usage;
+        }
+        let headers = BTreeMap::new();
+        usage
+            .into_iter()
+            .flat_map(|usage| {
+                let shares = self.shares(provider, &usage.model, &usage.labels, &headers);
+                split(usage, shares)
+            })
+            .collect()
+    }
+
+    /// Cost centers a tracked call is charged to and their shares, empty when no rule matches.
+    pub fn of_call(
+        &self,
+        provider: &str,
+        model: &str,
+        team: &str,
+        headers: &BTreeMap<String, String>,
+    ) -> Vec<(String, f64)> {
+        let labels = BTreeMap::from([("team", team.to_string())]);
+        self.shares(provider, model, &labels, headers).to_vec()
+    }
+}
+
+/// The part of `total` at `index` of `shares`: the difference of the rounded down running
+/// totals up to and including it, so every part grows with its share and the parts add up
+/// to the total.
+fn portion(total: u64, shares: &[(String, f64)], index: usize) -> u64 {
+    let upto = |end: usize| match end {
+        end if end == shares.len() => total,
+        end => {
+            let share: f64 = shares[..end].iter().map(|(_, share)| share).sum();
+            ((total as f64 * share).floor() as u64).min(total)
+        }
+    };
+    upto(index + 1).saturating_sub(upto(index))
+}
+
+/// The usage charged to each cost center.
+fn split(usage: LLMUsage, shares: &[(String, f64)]) -> Vec<LLMUsage> {
+    match shares {
+        [] => vec![usage],
+        [(center, _)] => {
+            let mut usage = usage;
+            usage.labels.insert("cost_center", center.clone());
+            vec![usage]
+        }
+        _ => shares
+            .iter()
+            .enumerate()
+            .map(|(index, (center, share))| {
+                let count = |total| portion(total, shares, index);
+                let mut labels = usage.labels.clone();
+                labels.insert("cost_center", center.clone());
+                LLMUsage {
+                    model: usage.model.clone(),
+                    cost_usd: usage.cost_usd * share,
+                    request_cost_usd: usage.request_cost_usd * share,
+                    prompt_tokens: count(usage.prompt_tokens),
+                    cached_prompt_tokens: count(usage.cached_prompt_tokens),
+                    cache_write_tokens: count(usage.cache_write_tokens),
+                    completion_tokens: count(usage.completion_tokens),
+                    embedding_tokens: count(usage.embedding_tokens),
+                    request_count: count(usage.request_count),
+                    images_generated: usage
+                        .images_generated
+                        .iter()
+                        .map(|images| ImageCount {
+                            count: count(images.count),
+                            ..images.clone()
+                        })
+                        .collect(),
+                    transcription_minutes: usage.transcription_minutes * share,
+                    speech_characters: count(usage.speech_characters),
+                    labels,
+                    request_tier: usage.request_tier.clone(),
+                }
+            })
+            .collect(),
+    }
+}
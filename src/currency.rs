@@ -0,0 +1,132 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time;
+
+use crate::metrics::LLMMetrics;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+const DEFAULT_RATE_URL: &str = "https://api.frankfurter.app/latest?from=USD&to={currency}";
+
+/// Conversion from USD into the reporting currency, shared between the metrics and the
+/// task that refreshes it.
+#[derive(Debug, Clone)]
+pub struct ExchangeRate {
+    currency: String,
+    /// Units of `currency` per USD, unknown until the first fetch succeeds.
+    rate: Arc<RwLock<Option<f64>>>,
+}
+
+impl ExchangeRate {
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn get(&self) -> Option<f64> {
+        *self.rate.read().unwrap()
+    }
+
+    fn set(&self, rate: f64) {
+        *self.rate.write().unwrap() = Some(rate);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RatesResponse {
+    rates: HashMap<String, f64>,
+}
+
+/// Periodically fetches the USD exchange rate from an API answering in the
+/// `{"rates": {"EUR": 0.92}}` shape of Frankfurter and exchangerate.host.
+pub struct RateFetcher {
+    client: reqwest::Client,
+    url: String,
+    interval: Duration,
+    rate: ExchangeRate,
+}
+
+/// Reads `REPORTING_CURRENCY` and either the fixed `EXCHANGE_RATE` or the API to fetch the
+/// rate from. Nothing is configured when the reporting currency is unset or USD.
+pub fn from_env() -> Result<Option<(ExchangeRate, Option<RateFetcher>)>, MonitorError> {
+    let currency = match std::env::var("REPORTING_CURRENCY") {
+        Ok(currency) if !currency.is_empty() => currency.to_uppercase(),
+        _ => return Ok(None),
+    };
+    if currency == "USD" {
+        return Ok(None);
+    }
+    let rate = ExchangeRate {
+        currency: currency.clone(),
+        rate: Arc::new(RwLock::new(None)),
+    };
+    if let Ok(fixed) = std::env::var("EXCHANGE_RATE") {
+        if !fixed.is_empty() {
+            let fixed: f64 = fixed
+                .parse()
+                .ok()
+                .filter(|rate: &f64| rate.is_finite() && *rate > 0.0)
+                .ok_or_else(|| anyhow::anyhow!("invalid EXCHANGE_RATE {:?}", fixed))?;
+            rate.set(fixed);
+            return Ok(Some((rate, None)));
+        }
+    }
+    let url = std::env::var("EXCHANGE_RATE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_RATE_URL.to_string())
+        .replace("{currency}", &currency);
+    let interval_secs = std::env::var("EXCHANGE_RATE_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3600);
+    let fetcher = RateFetcher {
+        client: reqwest::Client::new(),
+        url,
+        interval: Duration::from_secs(interval_secs),
+        rate: rate.clone(),
+    };
+    Ok(Some((rate, Some(fetcher))))
+}
+
+impl RateFetcher {
+    /// Fetches once and stores the rate.
+    pub async fn update(&self, metrics: &LLMMetrics) -> Result<(), MonitorError> {
+        let response: RatesResponse = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let rate = response
+            .rates
+            .get(&self.rate.currency)
+            .copied()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| anyhow::anyhow!("no valid {} rate in response", self.rate.currency))?;
+        self.rate.set(rate);
+        metrics.set_exchange_rate(&self.rate.currency, rate);
+        Ok(())
+    }
+
+    /// Refreshes on every interval until shutdown, keeping the last rate on failure.
+    pub async fn run(self, metrics: Arc<LLMMetrics>, shutdown: Shutdown) {
+        let mut interval = time::interval(self.interval);
+        // the first tick completes immediately and main already fetched once
+        interval.tick().await;
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut stop => break,
+            }
+            if let Err(e) = self.update(&metrics).await {
+                tracing::warn!(url = %self.url, error = %e, "exchange rate update failed");
+            }
+        }
+    }
+}
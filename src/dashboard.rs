@@ -0,0 +1,481 @@
+use serde_json::{json, Value};
+use std::sync::Arc;
+use warp::filters::BoxedFilter;
+use warp::{Filter, Reply};
+
+use crate::auth::{self, AuthConfig};
+use crate::exporter::Monitors;
+use crate::metrics::{LLMMetrics, MetricsMode};
+
+/// Usage labels worth breaking cost down by, each getting a panel when the label policy
+/// exports it.
+const BREAKDOWN_LABELS: &[(&str, &str)] = &[
+    ("team", "Team"),
+    ("cost_center", "Cost center"),
+    ("project_name", "Project"),
+    ("service_tier", "Service tier"),
+];
+
+const WIDTH: u32 = 24;
+
+/// Places panels left to right in rows of the 24 columns of a Grafana grid.
+#[derive(Default)]
+struct Layout {
+    panels: Vec<Value>,
+    x: u32,
+    y: u32,
+    row_height: u32,
+}
+
+impl Layout {
+    fn add(&mut self, width: u32, height: u32, mut panel: Value) {
+        if self.x + width > WIDTH {
+            self.y += self.row_height;
+            self.x = 0;
+            self.row_height = 0;
+        }
+        panel["id"] = json!(self.panels.len() + 1);
+        panel["gridPos"] = json!({"x": self.x, "y": self.y, "w": width, "h": height});
+        panel["datasource"] = datasource();
+        self.panels.push(panel);
+        self.x += width;
+        self.row_height = self.row_height.max(height);
+    }
+
+    /// Starts a titled row below the panels so far.
+    fn row(&mut self, title: &str) {
+        if self.x > 0 {
+            self.y += self.row_height;
+        }
+        self.panels.push(json!({
+            "id": self.panels.len() + 1,
+            "type": "row",
+            "title": title,
+            "collapsed": false,
+            "gridPos": {"x": 0, "y": self.y, "w": WIDTH, "h": 1},
+            "panels": [],
+        }));
+        self.x = 0;
+        self.y += 1;
+        self.row_height = 0;
+    }
+}
+
+fn datasource() -> Value {
+    json!({"type": "prometheus", "uid": "${datasource}"})
+}
+
+fn target(expr: String, legend: &str) -> Value {
+    json!({
+        "datasource": datasource(),
+        "expr": expr,
+        "legendFormat": legend,
+        "refId": "A",
+    })
+}
+
+/// A label value quoted for a PromQL matcher.
+fn quoted(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+fn stat(title: &str, expr: String, unit: &str) -> Value {
+    json!({
+        "type": "stat",
+        "title": title,
+        "targets": [target(expr, "")],
+        "fieldConfig": {"defaults": {"unit": unit}, "overrides": []},
+        "options": {"reduceOptions": {"calcs": ["lastNotNull"]}, "colorMode": "value"},
+    })
+}
+
+fn timeseries(title: &str, expr: String, legend: &str, unit: &str) -> Value {
+    json!({
+        "type": "timeseries",
+        "title": title,
+        "targets": [target(expr, legend)],
+        "fieldConfig": {"defaults": {"unit": unit}, "overrides": []},
+        "options": {"legend": {"displayMode": "table", "placement": "right", "calcs": ["lastNotNull"]}},
+    })
+}
+
+fn bar_gauge(title: &str, expr: String, legend: &str, unit: &str) -> Value {
+    json!({
+        "type": "bargauge",
+        "title": title,
+        "targets": [target(expr, legend)],
+        "fieldConfig": {"defaults": {"unit": unit}, "overrides": []},
+        "options": {
+            "orientation": "horizontal",
+            "displayMode": "gradient",
+            "reduceOptions": {"calcs": ["lastNotNull"]},
+        },
+    })
+}
+
+fn variable(name: &str, label: &str, query: String) -> Value {
+    json!({
+        "type": "query",
+        "name": name,
+        "label": label,
+        "datasource": datasource(),
+        "query": {"query": query, "refId": name},
+        "definition": query,
+        "refresh": 2,
+        "multi": true,
+        "includeAll": true,
+        // `.*` rather than the values found, so series with the label empty are included
+        "allValue": ".*",
+        "current": {"text": "All", "value": "$__all"},
+        "sort": 1,
+    })
+}
+
+/// Queries of the usage metrics as exported in one [`MetricsMode`]: month-to-date gauges as
+/// they are, counters as their increase over the dashboard's time range.
+struct Queries {
+    mode: MetricsMode,
+    selector: String,
+}
+
+impl Queries {
+    /// The queries of one provider's usage.
+    fn of_provider(&self, provider: &str) -> Self {
+        Self {
+            mode: self.mode,
+            selector: self.selector.replace(
+                "provider=~\"$provider\"",
+                &format!("provider={}", quoted(provider)),
+            ),
+        }
+    }
+
+    /// The name a usage metric is exported under.
+    fn name(&self, name: &str) -> String {
+        match self.mode {
+            MetricsMode::Gauge => name.to_string(),
+            MetricsMode::Counter => format!("{}_total", name),
+        }
+    }
+
+    fn metric(&self, name: &str) -> String {
+        format!("{}{{{}}}", self.name(name), self.selector)
+    }
+
+    /// The total of a usage metric, summed by `by`.
+    fn total(&self, name: &str, by: &str) -> String {
+        let series = match self.mode {
+            MetricsMode::Gauge => self.metric(name),
+            MetricsMode::Counter => format!("increase({}[$__range])", self.metric(name)),
+        };
+        match by {
+            "" => format!("sum({})", series),
+            by => format!("sum by ({}) ({})", by, series),
+        }
+    }
+
+    /// The same total over time: month-to-date for gauges, per hour for counters.
+    fn over_time(&self, name: &str, by: &str) -> String {
+        match self.mode {
+            MetricsMode::Gauge => format!("sum by ({}) ({})", by, self.metric(name)),
+            MetricsMode::Counter => {
+                format!("sum by ({}) (increase({}[1h]))", by, self.metric(name))
+            }
+        }
+    }
+
+    /// What totals cover, for panel titles.
+    fn period(&self) -> &'static str {
+        match self.mode {
+            MetricsMode::Gauge => "month to date",
+            MetricsMode::Counter => "in range",
+        }
+    }
+
+    fn over_time_title(&self, title: &str) -> String {
+        match self.mode {
+            MetricsMode::Gauge => format!("{} month to date", title),
+            MetricsMode::Counter => format!("{} per hour", title),
+        }
+    }
+}
+
+/// A Grafana dashboard of the exporter's metrics, with a cost panel per provider, a
+/// utilization gauge per budget and a cost breakdown per exported usage label.
+pub fn overview(providers: &[&str], budgets: &[String], metrics: &LLMMetrics) -> Value {
+    let mut providers = providers.to_vec();
+    providers.sort();
+    providers.dedup();
+    let labels: Vec<(&str, &str)> = BREAKDOWN_LABELS
+        .iter()
+        .copied()
+        .filter(|(label, _)| metrics.exports_label(label))
+        .collect();
+    let mut selector = vec![
+        "provider=~\"$provider\"".to_string(),
+        "model=~\"$model\"".to_string(),
+    ];
+    selector.extend(
+        labels
+            .iter()
+            .map(|(label, _)| format!("{}=~\"${}\"", label, label)),
+    );
+    let queries = Queries {
+        mode: metrics.mode(),
+        selector: selector.join(", "),
+    };
+    let period = queries.period();
+
+    let mut layout = Layout::default();
+    layout.row("Overview");
+    layout.add(
+        6,
+        4,
+        stat(
+            &format!("Cost {}", period),
+            queries.total("llm_cost_usd", ""),
+            "currencyUSD",
+        ),
+    );
+    layout.add(
+        6,
+        4,
+        stat(
+            &format!("Requests {}", period),
+            queries.total("llm_requests", ""),
+            "short",
+        ),
+    );
+    layout.add(
+        6,
+        4,
+        stat(
+            &format!("Tokens {}", period),
+            queries.total("llm_tokens", ""),
+            "short",
+        ),
+    );
+    layout.add(
+        6,
+        4,
+        stat(
+            "Forecast month end",
+            "sum(llm_cost_forecast_month_end_usd{provider=~\"$provider\"})".to_string(),
+            "currencyUSD",
+        ),
+    );
+    for provider in &providers {
+        layout.add(
+            4,
+            4,
+            stat(
+                provider,
+                queries.of_provider(provider).total("llm_cost_usd", ""),
+                "currencyUSD",
+            ),
+        );
+    }
+
+    layout.row("Cost");
+    layout.add(
+        12,
+        8,
+        timeseries(
+            &queries.over_time_title("Cost by provider"),
+            queries.over_time("llm_cost_usd", "provider"),
+            "{{provider}}",
+            "currencyUSD",
+        ),
+    );
+    layout.add(
+        12,
+        8,
+        timeseries(
+            &queries.over_time_title("Cost by model"),
+            format!(
+                "topk(10, {})",
+                queries.over_time("llm_cost_usd", "provider, model")
+            ),
+            "{{provider}}/{{model}}",
+            "currencyUSD",
+        ),
+    );
+    layout.add(
+        12,
+        8,
+        bar_gauge(
+            &format!("Top models {}", period),
+            format!(
+                "topk(10, {})",
+                queries.total("llm_cost_usd", "provider, model")
+            ),
+            "{{provider}}/{{model}}",
+            "currencyUSD",
+        ),
+    );
+    layout.add(
+        12,
+        8,
+        timeseries(
+            &queries.over_time_title("Tokens by type"),
+            queries.over_time("llm_tokens", "type"),
+            "{{type}}",
+            "short",
+        ),
+    );
+
+    if !budgets.is_empty() {
+        layout.row("Budgets");
+        for budget in budgets {
+            let mut gauge = json!({
+                "type": "gauge",
+                "title": budget,
+                "targets": [target(
+                    format!("llm_budget_utilization_ratio{{budget={}}}", quoted(budget)),
+                    "",
+                )],
+                "fieldConfig": {
+                    "defaults": {
+                        "unit": "percentunit",
+                        "min": 0,
+                        "max": 1,
+                        "thresholds": {
+                            "mode": "absolute",
+                            "steps": [
+                                {"color": "green", "value": null},
+                                {"color": "orange", "value": 0.8},
+                                {"color": "red", "value": 1},
+                            ],
+                        },
+                    },
+                    "overrides": [],
+                },
+            });
+            gauge["options"] = json!({"reduceOptions": {"calcs": ["lastNotNull"]}});
+            layout.add(6, 6, gauge);
+        }
+        layout.add(
+            24,
+            8,
+            timeseries(
+                "Budget spend",
+                "llm_budget_spend_usd".to_string(),
+                "{{budget}}",
+                "currencyUSD",
+            ),
+        );
+    }
+
+    if !labels.is_empty() {
+        layout.row("Breakdown");
+        for (label, title) in &labels {
+            layout.add(
+                8,
+                8,
+                bar_gauge(
+                    &format!("Cost by {} {}", title.to_lowercase(), period),
+                    queries.total("llm_cost_usd", label),
+                    &format!("{{{{{}}}}}", label),
+                    "currencyUSD",
+                ),
+            );
+        }
+    }
+
+    layout.row("Exporter");
+    layout.add(
+        12,
+        6,
+        timeseries(
+            "Provider errors",
+            "sum by (provider, error_type) (increase(llm_provider_errors_total{provider=~\"$provider\"}[$__rate_interval]))"
+                .to_string(),
+            "{{provider}} {{error_type}}",
+            "short",
+        ),
+    );
+    layout.add(
+        12,
+        6,
+        timeseries(
+            "Time since last successful poll",
+            "time() - llm_provider_last_success_timestamp_seconds{provider=~\"$provider\"}"
+                .to_string(),
+            "{{provider}}",
+            "s",
+        ),
+    );
+
+    // values of the models and labels of the selected providers
+    let label_values = |label: &str| {
+        format!(
+            "label_values({}{{provider=~\"$provider\"}}, {})",
+            queries.name("llm_cost_usd"),
+            label
+        )
+    };
+    let mut variables = vec![
+        json!({
+            "type": "datasource",
+            "name": "datasource",
+            "label": "Data source",
+            "query": "prometheus",
+            "current": {},
+        }),
+        json!({
+            "type": "custom",
+            "name": "provider",
+            "label": "Provider",
+            "query": providers.join(","),
+            "options": providers
+                .iter()
+                .map(|provider| json!({"text": provider, "value": provider, "selected": false}))
+                .collect::<Vec<_>>(),
+            "multi": true,
+            "includeAll": true,
+            "allValue": ".*",
+            "current": {"text": "All", "value": "$__all"},
+        }),
+        variable("model", "Model", label_values("model")),
+    ];
+    variables.extend(
+        labels
+            .iter()
+            .map(|(label, title)| variable(label, title, label_values(label))),
+    );
+
+    json!({
+        "uid": "llm-cost-overview",
+        "title": "LLM cost overview",
+        "tags": ["llm", "cost"],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "editable": true,
+        "refresh": "5m",
+        "time": {"from": "now-7d", "to": "now"},
+        "templating": {"list": variables},
+        "panels": layout.panels,
+    })
+}
+
+/// `GET /dashboards/overview.json`, the [`overview`] dashboard of the providers polled and
+/// budgets exported at the time of the request, behind the same authentication as the
+/// metrics path.
+pub fn routes(
+    monitors: Monitors,
+    metrics: Arc<LLMMetrics>,
+    auth: Option<AuthConfig>,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("dashboards" / "overview.json")
+        .and(warp::get())
+        .and(auth::require(auth))
+        .map(move || {
+            let dashboard = overview(&monitors.providers(), &metrics.budgets(), &metrics);
+            warp::reply::json(&dashboard).into_response()
+        })
+        .boxed()
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tracing::warn;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use crate::auth::{self, AuthConfig};
+use crate::pricing::Pricing;
+use crate::providers::{is_embedding_model, LLMUsage};
+use crate::tracker::request_tokens;
+use crate::MonitorError;
+
+const DEFAULT_ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Messages API fields the token counting endpoint accepts; the rest of a request, such as
+/// `max_tokens` or `stream`, is rejected there.
+const COUNT_TOKENS_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "system",
+    "tools",
+    "tool_choice",
+    "thinking",
+    "mcp_servers",
+];
+
+/// Request fields capping the generated tokens: Messages and legacy Chat Completions
+/// `max_tokens`, Chat Completions `max_completion_tokens` and Responses `max_output_tokens`.
+const MAX_OUTPUT_FIELDS: &[&str] = &["max_tokens", "max_completion_tokens", "max_output_tokens"];
+
+/// Cost of a request before it is sent: its input tokens, and the output it may generate at
+/// most.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostEstimate {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    /// The request's output token limit, if it sets one.
+    pub max_output_tokens: Option<u64>,
+    pub input_cost_usd: f64,
+    /// Input cost plus the output limit at the output rate; the input cost alone without a
+    /// limit.
+    pub max_cost_usd: f64,
+    /// Whether the input tokens were estimated locally rather than counted by the provider.
+    pub estimated: bool,
+    /// Whether the pricing catalog has a price for the model; costs are zero otherwise.
+    pub priced: bool,
+}
+
+/// Estimates the cost of an LLM request before it is sent, so expensive jobs can be checked
+/// against a budget first. Anthropic requests are counted by Anthropic's token counting
+/// endpoint when an API key is configured; everything else is estimated locally the way the
+/// tracker estimates responses without usage, with the tiktoken encoding of OpenAI models and
+/// an approximation of it for other models.
+pub struct CostEstimator {
+    client: reqwest::Client,
+    pricing: Pricing,
+    anthropic_key: Option<String>,
+    anthropic_base_url: String,
+}
+
+impl CostEstimator {
+    pub fn new(pricing: Pricing) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            pricing,
+            anthropic_key: None,
+            anthropic_base_url: DEFAULT_ANTHROPIC_BASE_URL.to_string(),
+        }
+    }
+
+    /// Counts Anthropic requests with Anthropic's token counting endpoint, which takes a
+    /// regular API key rather than the admin key of the usage API.
+    pub fn anthropic_key(mut self, key: String) -> Self {
+        self.anthropic_key = Some(key);
+        self
+    }
+
+    /// Reads the key from `ESTIMATE_ANTHROPIC_API_KEY` and the base URL from
+    /// `ANTHROPIC_API_BASE`.
+    pub fn from_env(pricing: Pricing) -> Self {
+        let mut estimator = Self::new(pricing);
+        if let Ok(base_url) = std::env::var("ANTHROPIC_API_BASE") {
+            estimator.anthropic_base_url = base_url;
+        }
+        match std::env::var("ESTIMATE_ANTHROPIC_API_KEY") {
+            Ok(key) if !key.is_empty() => estimator.anthropic_key(key),
+            _ => estimator,
+        }
+    }
+
+    /// Estimates a request body of the provider's API, e.g. a Messages API request for
+    /// `anthropic` or a Chat Completions request for `openai`.
+    pub async fn estimate(
+        &self,
+        provider: &str,
+        request: &Value,
+    ) -> Result<CostEstimate, MonitorError> {
+        let model = request
+            .get("model")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow::anyhow!("request has no model"))?;
+        let (input_tokens, estimated) = match &self.anthropic_key {
+            Some(key) if provider == "anthropic" => (self.count_tokens(key, request).await?, false),
+            _ => (request_tokens(model, request), true),
+        };
+        let max_output_tokens = MAX_OUTPUT_FIELDS
+            .iter()
+            .find_map(|field| request.get(*field)?.as_u64());
+
+        let mut usage = LLMUsage {
+            model: model.to_string(),
+            ..Default::default()
+        };
+        if is_embedding_model(model) {
+            usage.embedding_tokens = input_tokens;
+        } else {
+            usage.prompt_tokens = input_tokens;
+        }
+        let input_cost_usd = self.pricing.token_cost(provider, &usage);
+        usage.completion_tokens = max_output_tokens.unwrap_or_default();
+        Ok(CostEstimate {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens,
+            max_output_tokens,
+            input_cost_usd,
+            max_cost_usd: self.pricing.token_cost(provider, &usage),
+            estimated,
+            priced: self.pricing.price(provider, model).is_some(),
+        })
+    }
+
+    /// Input tokens of a Messages API request, counted by Anthropic.
+    async fn count_tokens(&self, key: &str, request: &Value) -> Result<u64, MonitorError> {
+        let body: Map<String, Value> = request
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(field, _)| COUNT_TOKENS_FIELDS.contains(&field.as_str()))
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/messages/count_tokens",
+                self.anthropic_base_url
+            ))
+            .header("x-api-key", key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(MonitorError::StatusError(status, response.text().await?));
+        }
+        let counted: Value = response.json().await?;
+        counted
+            .get("input_tokens")
+            .and_then(Value::as_u64)
+            .ok_or(MonitorError::InvalidResponse)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EstimateQuery {
+    provider: Option<String>,
+}
+
+fn error_reply(status: StatusCode, message: String) -> warp::reply::Response {
+    warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({ "error": message })),
+        status,
+    )
+    .into_response()
+}
+
+/// `POST /api/v1/estimate[?provider=<name>]` with the request body as it would be sent to the
+/// provider, behind the same authentication as the metrics path. Without `provider`, Claude
+/// models are estimated as `anthropic` and everything else as `openai`.
+pub fn routes(
+    estimator: Arc<CostEstimator>,
+    auth: Option<AuthConfig>,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("api" / "v1" / "estimate")
+        .and(warp::post())
+        .and(auth::require(auth))
+        .and(warp::query::<EstimateQuery>())
+        .and(warp::body::json())
+        .then(move |query: EstimateQuery, request: Value| {
+            let estimator = estimator.clone();
+            async move {
+                let model = request.get("model").and_then(Value::as_str);
+                let provider = query.provider.unwrap_or_else(|| {
+                    if model.is_some_and(|model| model.starts_with("claude")) {
+                        "anthropic".to_string()
+                    } else {
+                        "openai".to_string()
+                    }
+                });
+                if model.is_none() {
+                    return error_reply(StatusCode::BAD_REQUEST, "request has no model".into());
+                }
+                match estimator.estimate(&provider, &request).await {
+                    Ok(estimate) => warp::reply::json(&estimate).into_response(),
+                    Err(e) => {
+                        warn!(error = %e, %provider, "cost estimate failed");
+                        error_reply(StatusCode::BAD_GATEWAY, e.to_string())
+                    }
+                }
+            }
+        })
+        .boxed()
+}
@@ -0,0 +1,147 @@
+use prometheus::core::{Collector, MetricVec, MetricVecBuilder};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::providers::parse_map;
+use crate::MonitorError;
+
+/// How many poll cycles a series may go without being updated before it is removed, by the
+/// metric's exported name. Zero keeps series forever, the default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SeriesTtls {
+    default: u64,
+    per_metric: HashMap<String, u64>,
+}
+
+impl SeriesTtls {
+    pub fn new(default: u64) -> Self {
+        Self {
+            default,
+            per_metric: HashMap::new(),
+        }
+    }
+
+    /// Sets the TTL of one metric, e.g. a shorter one for `llm_ratelimit_remaining`.
+    pub fn metric(mut self, metric: &str, polls: u64) -> Self {
+        self.per_metric.insert(metric.to_string(), polls);
+        self
+    }
+
+    /// Reads the default TTL from `SERIES_TTL_POLLS` and TTLs per metric from `SERIES_TTLS`,
+    /// e.g. `llm_cost_usd=12,llm_ratelimit_remaining=3`, or `llm_cost_usd_total=12` for the
+    /// usage counters of [`crate::MetricsMode::Counter`].
+    pub fn from_env() -> Result<Self, MonitorError> {
+        let parse = |name: &str, value: &str| {
+            value.parse::<u64>().map_err(|_| {
+                MonitorError::from(anyhow::anyhow!("invalid {} series TTL {:?}", name, value))
+            })
+        };
+        let mut ttls = match std::env::var("SERIES_TTL_POLLS") {
+            Ok(ttl) if !ttl.is_empty() => Self::new(parse("SERIES_TTL_POLLS", &ttl)?),
+            _ => Self::default(),
+        };
+        for (metric, ttl) in parse_map(&std::env::var("SERIES_TTLS").unwrap_or_default()) {
+            let ttl = parse(&metric, &ttl)?;
+            ttls = ttls.metric(&metric, ttl);
+        }
+        Ok(ttls)
+    }
+
+    fn ttl(&self, metric: &str) -> u64 {
+        self.per_metric.get(metric).copied().unwrap_or(self.default)
+    }
+}
+
+/// A metric whose series can be removed by label values.
+trait RemoveSeries: Send + Sync {
+    fn remove(&self, labels: &[&str]);
+}
+
+impl<P: MetricVecBuilder> RemoveSeries for MetricVec<P> {
+    fn remove(&self, labels: &[&str]) {
+        let _ = self.remove_label_values(labels);
+    }
+}
+
+/// Exported name of a metric.
+pub(crate) fn metric_name<P: MetricVecBuilder>(metric: &MetricVec<P>) -> String {
+    metric
+        .desc()
+        .first()
+        .map(|desc| desc.fq_name.clone())
+        .unwrap_or_default()
+}
+
+/// Exported name and label values of a series.
+pub(crate) type SeriesKey = (String, Vec<String>);
+
+/// Remembers the poll cycle each series of the tracked metrics was last updated in, and
+/// removes the series that weren't updated for their metric's TTL, so models that are no
+/// longer used and providers that were removed don't linger at their last value.
+#[derive(Default)]
+pub(crate) struct SeriesExpiry {
+    ttls: SeriesTtls,
+    metrics: HashMap<String, Box<dyn RemoveSeries>>,
+    /// The current poll cycle and the cycle each series was last updated in, by metric and
+    /// label values.
+    updated: Mutex<(u64, HashMap<SeriesKey, u64>)>,
+}
+
+impl SeriesExpiry {
+    /// Lets the series of `metric` expire.
+    pub(crate) fn track<P: MetricVecBuilder + 'static>(&mut self, metric: &MetricVec<P>) {
+        self.metrics
+            .insert(metric_name(metric), Box::new(metric.clone()));
+    }
+
+    pub(crate) fn set_ttls(&mut self, ttls: SeriesTtls) {
+        for metric in ttls.per_metric.keys() {
+            if !self.metrics.contains_key(metric) {
+                warn!(
+                    metric,
+                    "series TTL set for a metric whose series don't expire"
+                );
+            }
+        }
+        self.ttls = ttls;
+    }
+
+    /// Marks a series as updated in the current poll cycle.
+    pub(crate) fn touch<P: MetricVecBuilder>(&self, metric: &MetricVec<P>, labels: &[&str]) {
+        if self.ttls.default == 0 && self.ttls.per_metric.values().all(|ttl| *ttl == 0) {
+            return;
+        }
+        let name = metric_name(metric);
+        if self.ttls.ttl(&name) == 0 {
+            return;
+        }
+        let key = (name, labels.iter().map(|label| label.to_string()).collect());
+        let mut updated = self.updated.lock().unwrap();
+        let cycle = updated.0;
+        updated.1.insert(key, cycle);
+    }
+
+    /// Ends a poll cycle, removing the series that have now gone their TTL without an update.
+    /// Returns the removed series.
+    pub(crate) fn expire(&self) -> Vec<SeriesKey> {
+        let mut removed = Vec::new();
+        let mut updated = self.updated.lock().unwrap();
+        let (cycle, series) = &mut *updated;
+        series.retain(|(metric, labels), last_updated| {
+            // cycles that ended without an update, counting this one
+            if *cycle - *last_updated < self.ttls.ttl(metric) {
+                return true;
+            }
+            if let Some(vec) = self.metrics.get(metric) {
+                let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                vec.remove(&labels);
+            }
+            debug!(metric, ?labels, "removed stale series");
+            removed.push((metric.clone(), labels.clone()));
+            false
+        });
+        *cycle += 1;
+        removed
+    }
+}
@@ -0,0 +1,913 @@
+use futures_util::future::{join_all, BoxFuture, Shared};
+use futures_util::FutureExt;
+use prometheus::{Encoder, Registry, TextEncoder};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::time;
+use tracing::{debug, error, info, warn, Instrument};
+use warp::filters::BoxedFilter;
+use warp::{Filter, Reply};
+
+use crate::api::{self, UsageStore};
+use crate::auth;
+use crate::cardinality::LabelLimits;
+use crate::config::ServerConfig;
+use crate::cost_centers::CostCenterRules;
+use crate::currency::ExchangeRate;
+use crate::dashboard;
+use crate::estimate::{self, CostEstimator};
+use crate::expiry::SeriesTtls;
+use crate::health::{self, Readiness};
+use crate::labels::LabelPolicy;
+use crate::leader::Leadership;
+use crate::metrics::{LLMMetrics, MetricsMode};
+use crate::openmetrics::{self, OpenMetricsEncoder};
+use crate::providers::{parse_map, ApiTimer, LLMMonitor, LLMUsage};
+use crate::reload::{self, ReloadHandle};
+use crate::retry::{RetryPolicy, Throttle};
+use crate::shutdown::Shutdown;
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::teams::TeamMapping;
+use crate::tls;
+use crate::MonitorError;
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(300);
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// When the providers are polled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PollMode {
+    /// In the background, every poll interval.
+    #[default]
+    Interval,
+    /// When `/metrics` is scraped, so a rarely scraped exporter doesn't spend API quota in
+    /// between.
+    Scrape,
+    /// When `/metrics` is scraped with `?refresh=true`; other scrapes get the last values.
+    Refresh,
+}
+
+impl PollMode {
+    pub fn from_env() -> Result<Self, MonitorError> {
+        match std::env::var("POLL_MODE").as_deref() {
+            Ok("interval") | Ok("") | Err(_) => Ok(Self::Interval),
+            Ok("scrape") => Ok(Self::Scrape),
+            Ok("refresh") => Ok(Self::Refresh),
+            Ok(other) => Err(anyhow::anyhow!(
+                "invalid POLL_MODE {:?}, expected interval, scrape or refresh",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+/// How long each provider's poll may take before it is abandoned and counted as failed, so a
+/// provider that hangs doesn't hold up the end of the poll cycle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollTimeouts {
+    default: Duration,
+    per_provider: HashMap<String, Duration>,
+}
+
+impl Default for PollTimeouts {
+    fn default() -> Self {
+        Self::new(DEFAULT_POLL_TIMEOUT)
+    }
+}
+
+impl PollTimeouts {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default,
+            per_provider: HashMap::new(),
+        }
+    }
+
+    /// Sets the timeout of one provider, e.g. a longer one for `bedrock` with many accounts.
+    pub fn provider(mut self, provider: &str, timeout: Duration) -> Self {
+        self.per_provider.insert(provider.to_string(), timeout);
+        self
+    }
+
+    /// Reads the default timeout from `POLL_TIMEOUT_SECS` and per-provider timeouts from
+    /// `POLL_TIMEOUTS`, e.g. `bedrock=300,openai=60`.
+    pub fn from_env() -> Result<Self, MonitorError> {
+        let parse = |name: &str, value: &str| {
+            value.parse::<u64>().map(Duration::from_secs).map_err(|_| {
+                MonitorError::from(anyhow::anyhow!("invalid {} poll timeout {:?}", name, value))
+            })
+        };
+        let mut timeouts = match std::env::var("POLL_TIMEOUT_SECS") {
+            Ok(timeout) if !timeout.is_empty() => Self::new(parse("POLL_TIMEOUT_SECS", &timeout)?),
+            _ => Self::default(),
+        };
+        let per_provider = parse_map(&std::env::var("POLL_TIMEOUTS").unwrap_or_default());
+        for (provider, timeout) in per_provider {
+            let timeout = parse(&provider, &timeout)?;
+            timeouts = timeouts.provider(&provider, timeout);
+        }
+        Ok(timeouts)
+    }
+
+    fn timeout(&self, provider: &str) -> Duration {
+        self.per_provider
+            .get(provider)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// The providers an [`Exporter`] polls. Clones share them, so they can be swapped while the
+/// exporter runs, e.g. when the config is reloaded.
+#[derive(Clone)]
+pub struct Monitors {
+    monitors: Arc<RwLock<Vec<Arc<dyn LLMMonitor>>>>,
+    readiness: Arc<Readiness>,
+    metrics: Arc<LLMMetrics>,
+}
+
+impl Monitors {
+    /// The `provider` label of every monitor.
+    pub fn providers(&self) -> Vec<&'static str> {
+        self.current()
+            .iter()
+            .map(|monitor| monitor.provider())
+            .collect()
+    }
+
+    /// Polls these monitors instead from the next poll on. A poll in progress finishes with
+    /// the old ones. Series of removed providers are kept until they expire.
+    pub fn replace(&self, monitors: Vec<Box<dyn LLMMonitor>>) {
+        let monitors: Vec<Arc<dyn LLMMonitor>> = monitors.into_iter().map(Arc::from).collect();
+        self.metrics.set_monitors_active(monitors.len());
+        let providers: Vec<&str> = monitors.iter().map(|monitor| monitor.provider()).collect();
+        self.readiness.retain(&providers);
+        *self.monitors.write().unwrap() = monitors;
+    }
+
+    fn current(&self) -> Vec<Arc<dyn LLMMonitor>> {
+        self.monitors.read().unwrap().clone()
+    }
+
+    /// The monitors by provider, suffixed with `/1`, `/2`, ... for further monitors of the
+    /// same provider, so checkpoints find their way back to the monitor that saved them.
+    fn by_key(&self) -> Vec<(String, Arc<dyn LLMMonitor>)> {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        self.current()
+            .into_iter()
+            .map(|monitor| {
+                let index = seen.entry(monitor.provider()).or_default();
+                let key = match *index {
+                    0 => monitor.provider().to_string(),
+                    index => format!("{}/{}", monitor.provider(), index),
+                };
+                *index += 1;
+                (key, monitor)
+            })
+            .collect()
+    }
+
+    /// Checkpoints of the monitors that keep one.
+    pub async fn checkpoints(&self) -> BTreeMap<String, serde_json::Value> {
+        let mut checkpoints = BTreeMap::new();
+        for (key, monitor) in self.by_key() {
+            if let Some(checkpoint) = monitor.checkpoint().await {
+                checkpoints.insert(key, checkpoint);
+            }
+        }
+        checkpoints
+    }
+
+    /// Resumes the monitors from the checkpoints of a previous run.
+    pub async fn restore(
+        &self,
+        mut checkpoints: BTreeMap<String, serde_json::Value>,
+    ) -> Result<(), MonitorError> {
+        for (key, monitor) in self.by_key() {
+            if let Some(checkpoint) = checkpoints.remove(&key) {
+                monitor.restore(checkpoint).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configures an [`Exporter`]. Everything is optional: by default the exporter registers its
+/// metrics in a registry of its own, exports gauges, polls every 5 minutes and serves nothing,
+/// leaving it to the embedding service to expose the registry.
+pub struct ExporterBuilder {
+    monitors: Vec<Box<dyn LLMMonitor>>,
+    sinks: Vec<Box<dyn UsageSink>>,
+    registry: Option<Registry>,
+    mode: MetricsMode,
+    exchange_rate: Option<ExchangeRate>,
+    poll_interval: Duration,
+    server: Option<ServerConfig>,
+    estimator: Option<CostEstimator>,
+    optional_providers: Vec<String>,
+    teams: TeamMapping,
+    cost_centers: CostCenterRules,
+    label_limits: LabelLimits,
+    label_policy: LabelPolicy,
+    series_ttls: SeriesTtls,
+    leadership: Leadership,
+    retry: RetryPolicy,
+    poll_timeouts: PollTimeouts,
+    poll_mode: PollMode,
+    min_refresh_interval: Duration,
+    refresh_timeout: Duration,
+    reload: Option<ReloadHandle>,
+}
+
+impl Default for ExporterBuilder {
+    fn default() -> Self {
+        Self {
+            monitors: Vec::new(),
+            sinks: Vec::new(),
+            registry: None,
+            mode: MetricsMode::Gauge,
+            exchange_rate: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            server: None,
+            estimator: None,
+            optional_providers: Vec::new(),
+            teams: TeamMapping::default(),
+            cost_centers: CostCenterRules::default(),
+            label_limits: LabelLimits::default(),
+            label_policy: LabelPolicy::default(),
+            series_ttls: SeriesTtls::default(),
+            leadership: Leadership::default(),
+            retry: RetryPolicy::default(),
+            poll_timeouts: PollTimeouts::default(),
+            poll_mode: PollMode::Interval,
+            min_refresh_interval: DEFAULT_MIN_REFRESH_INTERVAL,
+            refresh_timeout: DEFAULT_REFRESH_TIMEOUT,
+            reload: None,
+        }
+    }
+}
+
+impl ExporterBuilder {
+    /// Adds a provider to poll.
+    pub fn monitor(mut self, monitor: impl LLMMonitor + 'static) -> Self {
+        self.monitors.push(Box::new(monitor));
+        self
+    }
+
+    /// Adds providers to poll, e.g. those of [`crate::providers::from_env`].
+    pub fn monitors(mut self, monitors: impl IntoIterator<Item = Box<dyn LLMMonitor>>) -> Self {
+        self.monitors.extend(monitors);
+        self
+    }
+
+    /// Adds a destination for every poll's usage.
+    pub fn sink(mut self, sink: impl UsageSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Adds destinations for every poll's usage, e.g. those of [`crate::sinks::from_env`].
+    pub fn sinks(mut self, sinks: impl IntoIterator<Item = Box<dyn UsageSink>>) -> Self {
+        self.sinks.extend(sinks);
+        self
+    }
+
+    /// Registers the metrics in an existing registry, such as the embedding service's own.
+    pub fn registry(mut self, registry: Registry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn metrics_mode(mut self, mode: MetricsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Also exports cost converted to this currency.
+    pub fn exchange_rate(mut self, exchange_rate: ExchangeRate) -> Self {
+        self.exchange_rate = Some(exchange_rate);
+        self
+    }
+
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Serves the registry, `/healthz`, `/readyz` and the usage API over HTTP while running.
+    pub fn server(mut self, server: ServerConfig) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    /// Serves `POST /api/v1/estimate` with the estimator on the metrics server.
+    pub fn estimator(mut self, estimator: CostEstimator) -> Self {
+        self.estimator = Some(estimator);
+        self
+    }
+
+    /// Providers that do not have to report before `/readyz` succeeds.
+    pub fn optional_providers(mut self, providers: Vec<String>) -> Self {
+        self.optional_providers = providers;
+        self
+    }
+
+    /// Labels polled usage with the team owning its project or role.
+    pub fn teams(mut self, teams: TeamMapping) -> Self {
+        self.teams = teams;
+        self
+    }
+
+    /// Labels polled usage with its cost center, splitting usage shared by several.
+    pub fn cost_centers(mut self, rules: CostCenterRules) -> Self {
+        self.cost_centers = rules;
+        self
+    }
+
+    /// Caps the distinct values of each label, by default at 1000.
+    pub fn label_limits(mut self, limits: LabelLimits) -> Self {
+        self.label_limits = limits;
+        self
+    }
+
+    /// Exports only the labels and label values the policy allows.
+    pub fn label_policy(mut self, policy: LabelPolicy) -> Self {
+        self.label_policy = policy;
+        self
+    }
+
+    /// Removes series that go unupdated for too many poll cycles; by default none expire.
+    pub fn series_ttls(mut self, ttls: SeriesTtls) -> Self {
+        self.series_ttls = ttls;
+        self
+    }
+
+    /// Only polls while this replica is leader, e.g. of a [`crate::leader::LeaseElection`].
+    pub fn leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    /// Retries provider calls that fail with a transient error, by default up to 3 attempts.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Abandons provider polls that take longer, by default after 2 minutes.
+    pub fn poll_timeouts(mut self, timeouts: PollTimeouts) -> Self {
+        self.poll_timeouts = timeouts;
+        self
+    }
+
+    /// Polls when `/metrics` is scraped rather than on the poll interval, see [`PollMode`].
+    pub fn poll_mode(mut self, mode: PollMode) -> Self {
+        self.poll_mode = mode;
+        self
+    }
+
+    /// Serves scrapes the last values rather than polling again when the last poll is more
+    /// recent, by default a minute. Only applies to the scrape-triggered poll modes.
+    pub fn min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// Longest a scrape waits for the poll it triggered, by default 10 seconds; past it the
+    /// scrape gets the last values while the poll carries on in the background.
+    pub fn refresh_timeout(mut self, timeout: Duration) -> Self {
+        self.refresh_timeout = timeout;
+        self
+    }
+
+    /// Serves `POST /-/reload` with the handle on the metrics server, when it requires auth.
+    pub fn reload(mut self, handle: ReloadHandle) -> Self {
+        self.reload = Some(handle);
+        self
+    }
+
+    pub fn build(self) -> Exporter {
+        let registry = self.registry.unwrap_or_default();
+        let metrics = Arc::new(
+            LLMMetrics::new(&registry, self.mode, self.exchange_rate)
+                .label_limits(self.label_limits)
+                .label_policy(self.label_policy)
+                .series_ttls(self.series_ttls),
+        );
+        metrics.set_monitors_active(self.monitors.len());
+        metrics.set_leader(self.leadership.is_leader());
+        let readiness = Arc::new(
+            Readiness::new(
+                self.monitors.iter().map(|monitor| monitor.provider()),
+                &self.optional_providers,
+            )
+            .leadership(self.leadership.clone()),
+        );
+        let store = UsageStore::default();
+        let mut sinks = self.sinks;
+        sinks.push(Box::new(store.clone()));
+        let monitors = Monitors {
+            monitors: Arc::new(RwLock::new(
+                self.monitors.into_iter().map(Arc::from).collect(),
+            )),
+            readiness: readiness.clone(),
+            metrics: metrics.clone(),
+        };
+        Exporter {
+            monitors,
+            sinks,
+            registry,
+            metrics,
+            store,
+            readiness,
+            poll_interval: self.poll_interval,
+            server: self.server,
+            estimator: self.estimator.map(Arc::new),
+            teams: self.teams,
+            cost_centers: self.cost_centers,
+            leadership: self.leadership,
+            retry: self.retry,
+            throttle: Throttle::default(),
+            poll_timeouts: self.poll_timeouts,
+            poll_mode: self.poll_mode,
+            min_refresh_interval: self.min_refresh_interval,
+            refresh_timeout: self.refresh_timeout,
+            refreshes: Mutex::new(Refreshes::default()),
+            reload: self.reload,
+            openmetrics: OpenMetricsEncoder::default(),
+        }
+    }
+}
+
+/// Polls every provider on an interval or when scraped, keeps the metrics up to date and hands
+/// each poll's usage to the sinks.
+pub struct Exporter {
+    monitors: Monitors,
+    sinks: Vec<Box<dyn UsageSink>>,
+    registry: Registry,
+    metrics: Arc<LLMMetrics>,
+    store: UsageStore,
+    readiness: Arc<Readiness>,
+    poll_interval: Duration,
+    server: Option<ServerConfig>,
+    estimator: Option<Arc<CostEstimator>>,
+    teams: TeamMapping,
+    cost_centers: CostCenterRules,
+    leadership: Leadership,
+    retry: RetryPolicy,
+    throttle: Throttle,
+    poll_timeouts: PollTimeouts,
+    poll_mode: PollMode,
+    min_refresh_interval: Duration,
+    refresh_timeout: Duration,
+    refreshes: Mutex<Refreshes>,
+    reload: Option<ReloadHandle>,
+    openmetrics: OpenMetricsEncoder,
+}
+
+impl Exporter {
+    pub fn builder() -> ExporterBuilder {
+        ExporterBuilder::default()
+    }
+
+    pub fn metrics(&self) -> &Arc<LLMMetrics> {
+        &self.metrics
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Latest usage of every provider, as served by the usage API.
+    pub fn usage_store(&self) -> &UsageStore {
+        &self.store
+    }
+
+    /// The `provider` label of every monitor.
+    pub fn providers(&self) -> Vec<&'static str> {
+        self.monitors.providers()
+    }
+
+    /// The providers polled, to swap them while running.
+    pub fn monitors(&self) -> &Monitors {
+        &self.monitors
+    }
+
+    /// Adds a sink once the exporter is built, for sinks that need its metrics.
+    pub fn add_sink(&mut self, sink: Box<dyn UsageSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// Polls every monitor that isn't throttled once, all at the same time, records the
+    /// results and returns the usage of the monitors that succeeded. Each provider's metrics
+    /// are updated as soon as its own poll finishes.
+    async fn poll_monitors(&self) -> Vec<ProviderUsage> {
+        let poll_started = Instant::now();
+        let monitors = self.monitors.current();
+        let polls = monitors.iter().map(|monitor| async move {
+            let provider = monitor.provider();
+            if let Some(remaining) = self.throttle.remaining(provider) {
+                debug!(provider, ?remaining, "rate limited, skipping poll");
+                return None;
+            }
+            let timeout = self.poll_timeouts.timeout(provider);
+            let poll = poll_monitor(
+                monitor.as_ref(),
+                &self.metrics,
+                &self.readiness,
+                &self.teams,
+                &self.cost_centers,
+                &self.retry,
+            )
+            .instrument(tracing::info_span!("poll", provider));
+            let poll = ApiTimer::new(self.metrics.clone(), provider).scope(poll);
+            let (usage, rate_limited) = match time::timeout(timeout, poll).await {
+                Ok(polled) => polled,
+                Err(_) => {
+                    let e = MonitorError::Timeout(timeout);
+                    warn!(provider, error = %e, "poll abandoned");
+                    self.metrics.record_error(provider, &e);
+                    return None;
+                }
+            };
+            match rate_limited {
+                Some(retry_after) => {
+                    let interval = match self.poll_mode {
+                        PollMode::Interval => self.poll_interval,
+                        PollMode::Scrape | PollMode::Refresh => self.min_refresh_interval,
+                    };
+                    let wait = self.throttle.back_off(provider, retry_after, interval);
+                    warn!(provider, ?wait, "rate limited, putting off polls");
+                }
+                None => self.throttle.reset(provider),
+            }
+            usage.map(|usage| ProviderUsage { provider, usage })
+        });
+        let polled: Vec<ProviderUsage> = join_all(polls).await.into_iter().flatten().collect();
+        self.metrics.expire_stale_series();
+        self.metrics.observe_poll_duration(poll_started.elapsed());
+        debug!(elapsed = ?poll_started.elapsed(), "poll cycle finished");
+        polled
+    }
+
+    /// Polls every provider once, except those whose polls are put off after they rate
+    /// limited the exporter, and publishes the usage to the sinks.
+    pub async fn poll(&self) -> Vec<ProviderUsage> {
+        let usage = self.poll_monitors().await;
+        publish(&self.sinks, &usage).await;
+        usage
+    }
+
+    /// Polls for a scrape, unless this replica is a standby or the last poll is more recent
+    /// than the minimum refresh interval, and returns the poll to wait for. The poll runs in a
+    /// task of its own that scrapes arriving meanwhile share, so a scrape that times out or
+    /// disconnects doesn't cut it short.
+    fn refresh(self: &Arc<Self>) -> Option<Shared<BoxFuture<'static, ()>>> {
+        if !self.leadership.is_leader() {
+            return None;
+        }
+        let mut refreshes = self.refreshes.lock().unwrap();
+        // a poll that panicked is finished without having cleared itself
+        match &refreshes.in_flight {
+            Some((in_flight, abort)) if !abort.is_finished() => return Some(in_flight.clone()),
+            _ => refreshes.in_flight = None,
+        }
+        if refreshes
+            .last
+            .is_some_and(|refreshed| refreshed.elapsed() < self.min_refresh_interval)
+        {
+            return None;
+        }
+        let exporter = self.clone();
+        let task = tokio::spawn(async move {
+            exporter.poll().await;
+            let mut refreshes = exporter.refreshes.lock().unwrap();
+            refreshes.in_flight = None;
+            refreshes.last = Some(Instant::now());
+        });
+        let abort = task.abort_handle();
+        let in_flight = async move {
+            let _ = task.await;
+        }
+        .boxed()
+        .shared();
+        refreshes.in_flight = Some((in_flight.clone(), abort));
+        Some(in_flight)
+    }
+
+    /// Waits for the poll a scrape triggered, at most the refresh timeout.
+    async fn refresh_for_scrape(self: &Arc<Self>) {
+        let Some(in_flight) = self.refresh() else {
+            return;
+        };
+        if time::timeout(self.refresh_timeout, in_flight)
+            .await
+            .is_err()
+        {
+            warn!(
+                timeout = ?self.refresh_timeout,
+                "poll still running, serving the last values"
+            );
+        }
+    }
+
+    /// Polls until `shutdown` resolves, then shuts the sinks down and waits for in-flight
+    /// scrapes to finish.
+    pub async fn run(self, shutdown: Shutdown) {
+        let exporter = Arc::new(self);
+        let server = exporter.server.clone().map(|config| {
+            let exporter = exporter.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                if let Err(e) = run_metrics_server(exporter, config, shutdown).await {
+                    error!(error = %e, "metrics server failed");
+                }
+            })
+        });
+
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        if exporter.poll_mode == PollMode::Interval {
+            let mut interval = time::interval(exporter.poll_interval);
+            let mut leadership = exporter.leadership.subscribe();
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    // a new leader polls right away rather than at its next tick
+                    Ok(()) = leadership.changed() => interval.reset(),
+                    _ = &mut stop => break,
+                }
+                if !exporter.leadership.is_leader() {
+                    debug!("standby, skipping poll");
+                    continue;
+                }
+                tokio::select! {
+                    _ = exporter.poll() => {}
+                    _ = &mut stop => break,
+                }
+            }
+        } else {
+            // poll once at startup, so readiness doesn't wait for the first scrape
+            exporter.refresh();
+            stop.await;
+            if let Some((_, abort)) = exporter.refreshes.lock().unwrap().in_flight.take() {
+                abort.abort();
+            }
+        }
+
+        for sink in &exporter.sinks {
+            if let Err(e) = sink.shutdown().await {
+                warn!(sink = sink.name(), error = %e, "failed to shut down sink");
+            }
+        }
+
+        // let in-flight scrapes finish before returning
+        if let Some(server) = server {
+            if let Err(e) = server.await {
+                error!(error = %e, "metrics server task failed");
+            }
+        }
+    }
+}
+
+/// Scrape-triggered polls: the one in flight, with the handle that aborts it at shutdown, and
+/// when the last one finished.
+#[derive(Default)]
+struct Refreshes {
+    in_flight: Option<(Shared<BoxFuture<'static, ()>>, tokio::task::AbortHandle)>,
+    last: Option<Instant>,
+}
+
+/// Matches a request path given as a string, such as `/internal/metrics`.
+fn route_path(path: &str) -> BoxedFilter<()> {
+    path.trim_matches('/')
+        .split('/')
+        .fold(warp::any().boxed(), |filter, segment| {
+            filter.and(warp::path(segment.to_string())).boxed()
+        })
+        .and(warp::path::end())
+        .boxed()
+}
+
+async fn run_metrics_server(
+    exporter: Arc<Exporter>,
+    config: ServerConfig,
+    shutdown: Shutdown,
+) -> Result<(), MonitorError> {
+    let scraped = exporter.clone();
+    let metrics_route = route_path(&config.metrics_path)
+        .and(auth::require(config.auth.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::optional::<String>("accept"))
+        .then(
+            move |query: HashMap<String, String>, accept: Option<String>| {
+                let exporter = scraped.clone();
+                async move {
+                    let refresh = match exporter.poll_mode {
+                        PollMode::Interval => false,
+                        PollMode::Scrape => true,
+                        PollMode::Refresh => query.get("refresh").is_some_and(|v| v == "true"),
+                    };
+                    if refresh {
+                        exporter.refresh_for_scrape().await;
+                    }
+                    let metric_families = exporter.registry.gather();
+                    if openmetrics::is_accepted(accept.as_deref()) {
+                        let body = exporter
+                            .openmetrics
+                            .encode(&metric_families, exporter.metrics.exemplars());
+                        return warp::reply::with_header(
+                            body,
+                            "content-type",
+                            openmetrics::CONTENT_TYPE,
+                        )
+                        .into_response();
+                    }
+                    let encoder = TextEncoder::new();
+                    let mut buffer = vec![];
+                    encoder.encode(&metric_families, &mut buffer).unwrap();
+                    warp::reply::with_header(buffer, "content-type", encoder.format_type())
+                        .into_response()
+                }
+            },
+        );
+    let mut api_routes = api::routes(exporter.store.clone(), config.auth.clone())
+        .or(dashboard::routes(
+            exporter.monitors.clone(),
+            exporter.metrics.clone(),
+            config.auth.clone(),
+        ))
+        .unify()
+        .boxed();
+    if let Some(estimator) = exporter.estimator.clone() {
+        api_routes = api_routes
+            .or(estimate::routes(estimator, config.auth.clone()))
+            .unify()
+            .boxed();
+    }
+    match (exporter.reload.clone(), &config.auth) {
+        (Some(handle), Some(_)) => {
+            api_routes = api_routes
+                .or(reload::routes(handle, config.auth.clone()))
+                .unify()
+                .boxed();
+        }
+        // anyone reaching the server could reload the config otherwise
+        (Some(_), None) => warn!("POST /-/reload needs metrics auth, reload with SIGHUP instead"),
+        (None, _) => {}
+    }
+    let routes = health::routes(exporter.readiness.clone())
+        .or(api_routes)
+        .or(metrics_route)
+        .recover(auth::handle_rejection)
+        .with(warp::trace::request());
+
+    let addr = config.socket_addr();
+    info!(%addr, path = %config.metrics_path, tls = config.tls.is_some(), "serving metrics");
+    match config.tls {
+        Some(tls) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to bind {}: {}", addr, e))?;
+            let incoming = tls::incoming(listener, tls).await?;
+            warp::serve(routes)
+                .serve_incoming_with_graceful_shutdown(incoming, shutdown.wait())
+                .await;
+        }
+        None => {
+            let (_, server) = warp::serve(routes)
+                .try_bind_with_graceful_shutdown(addr, shutdown.wait())
+                .map_err(|e| anyhow::anyhow!("failed to bind {}: {}", addr, e))?;
+            server.await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a call to a provider API endpoint, retries included, in a span of its own within the
+/// poll's, marked as failed when it fails.
+async fn traced<T>(
+    endpoint: &'static str,
+    call: impl Future<Output = Result<T, MonitorError>>,
+) -> Result<T, MonitorError> {
+    let span = tracing::info_span!(
+        "api_call",
+        endpoint,
+        otel.status_code = tracing::field::Empty,
+        error.type = tracing::field::Empty,
+    );
+    let result = call.instrument(span.clone()).await;
+    if let Err(e) = &result {
+        span.record("otel.status_code", "ERROR");
+        span.record("error.type", e.error_type());
+    }
+    result
+}
+
+/// Polls one monitor. Also returns, when a call ended rate limited, the longest wait the
+/// provider asked for; once the usage call is rate limited, the rest are skipped.
+async fn poll_monitor(
+    monitor: &dyn LLMMonitor,
+    metrics: &LLMMetrics,
+    readiness: &Readiness,
+    teams: &TeamMapping,
+    cost_centers: &CostCenterRules,
+    retry: &RetryPolicy,
+) -> (Option<Vec<LLMUsage>>, Option<Option<Duration>>) {
+    let provider = monitor.provider();
+    let retried = |endpoint| {
+        move |e: &MonitorError| {
+            metrics.record_retry(provider, endpoint, e);
+            if e.is_rate_limited() {
+                metrics.record_throttled(provider);
+            }
+        }
+    };
+    let mut rate_limited = None;
+    let mut failed = |call: &str, e: MonitorError| {
+        warn!(error = %e, error_type = e.error_type(), "{} poll failed", call);
+        metrics.record_error(provider, &e);
+        if e.is_rate_limited() {
+            metrics.record_throttled(provider);
+            rate_limited = rate_limited.max(Some(e.retry_after()));
+        }
+    };
+
+    let started = Instant::now();
+    let usage = traced("usage", retry.run(|| monitor.get_usage(), retried("usage"))).await;
+    let usage = match usage {
+        Ok(mut usage) => {
+            for model_usage in &mut usage {
+                teams.assign(model_usage);
+            }
+            let usage = cost_centers.apply(monitor.provider(), usage);
+            metrics.set_billing_period(monitor.provider());
+            metrics.update_all(monitor.provider(), &usage);
+            metrics.record_success(monitor.provider());
+            readiness.mark_success(monitor.provider());
+            debug!(models = usage.len(), elapsed = ?started.elapsed(), "usage polled");
+            Some(usage)
+        }
+        Err(e) => {
+            let throttled = e.is_rate_limited().then(|| e.retry_after());
+            failed("usage", e);
+            // The other endpoints share the provider's rate limit, so asking them now would
+            // only be refused as well and prolong the throttling.
+            if throttled.is_some() {
+                return (None, throttled);
+            }
+            None
+        }
+    };
+
+    let balance = traced(
+        "credit_balance",
+        retry.run(|| monitor.get_credit_balance(), retried("credit_balance")),
+    )
+    .await;
+    match balance {
+        Ok(Some(balance)) => metrics.set_credit_balance(monitor.provider(), balance),
+        Ok(None) => {}
+        Err(e) => failed("credit balance", e),
+    }
+
+    let tool_costs = traced(
+        "tool_costs",
+        retry.run(|| monitor.get_tool_costs(), retried("tool_costs")),
+    )
+    .await;
+    match tool_costs {
+        Ok(tool_costs) => {
+            for tool_cost in tool_costs {
+                metrics.set_tool_cost(monitor.provider(), tool_cost.tool, tool_cost.cost_usd);
+            }
+        }
+        Err(e) => failed("tool cost", e),
+    }
+
+    let jobs = traced(
+        "fine_tuning",
+        retry.run(|| monitor.get_fine_tuning_jobs(), retried("fine_tuning")),
+    )
+    .await;
+    match jobs {
+        Ok(jobs) => metrics.set_fine_tuning_jobs(monitor.provider(), &jobs),
+        Err(e) => failed("fine-tuning", e),
+    }
+    (usage, rate_limited)
+}
+
+/// Hands a poll cycle's usage to every configured sink.
+async fn publish(sinks: &[Box<dyn UsageSink>], usage: &[ProviderUsage]) {
+    for sink in sinks {
+        if let Err(e) = sink.publish(usage).await {
+            warn!(sink = sink.name(), error = %e, "failed to publish usage");
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::LLMMetrics;
+use crate::providers::{billing_period_end, billing_period_start};
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+const DEFAULT_HALF_LIFE_HOURS: f64 = 72.0;
+
+/// How the spend so far in the billing period is extrapolated to the end of the period.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForecastMethod {
+    /// Average spend rate since the start of the billing period.
+    Linear,
+    /// Exponentially weighted spend rate between polls, which follows recent changes in usage.
+    /// The weight of a rate halves every `half_life_hours`.
+    Ewma { half_life_hours: f64 },
+}
+
+impl ForecastMethod {
+    pub fn from_env() -> Result<Self, MonitorError> {
+        match std::env::var("FORECAST_METHOD").as_deref() {
+            Ok("linear") | Ok("") | Err(_) => Ok(Self::Linear),
+            Ok("ewma") => {
+                let half_life_hours = match std::env::var("FORECAST_EWMA_HALF_LIFE_HOURS") {
+                    Ok(hours) => hours
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|hours| hours.is_finite() && *hours > 0.0)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("invalid FORECAST_EWMA_HALF_LIFE_HOURS {:?}", hours)
+                        })?,
+                    Err(_) => DEFAULT_HALF_LIFE_HOURS,
+                };
+                Ok(Self::Ewma { half_life_hours })
+            }
+            Ok(other) => Err(anyhow::anyhow!(
+                "invalid FORECAST_METHOD {:?}, expected linear or ewma",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProviderState {
+    period_start: DateTime<Utc>,
+    last_poll: DateTime<Utc>,
+    last_total: f64,
+    /// Smoothed spend in USD per second, for the EWMA method.
+    rate: f64,
+}
+
+/// Projects each provider's cost at the end of its billing period from the cost so far in
+/// every poll.
+pub struct Forecaster {
+    method: ForecastMethod,
+    metrics: Arc<LLMMetrics>,
+    state: Mutex<HashMap<&'static str, ProviderState>>,
+}
+
+impl Forecaster {
+    pub fn new(method: ForecastMethod, metrics: Arc<LLMMetrics>) -> Self {
+        Self {
+            method,
+            metrics,
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn forecast(&self, provider: &'static str, total: f64, now: DateTime<Utc>) -> f64 {
+        let period_start = billing_period_start(provider);
+        let period_end = billing_period_end(provider, period_start);
+        let elapsed = (now - period_start).num_seconds().max(1) as f64;
+        let remaining = (period_end - now).num_seconds().max(0) as f64;
+        let average_rate = total / elapsed;
+
+        let rate = match self.method {
+            ForecastMethod::Linear => average_rate,
+            ForecastMethod::Ewma { half_life_hours } => {
+                let mut state = self.state.lock().unwrap();
+                match state.get_mut(provider) {
+                    Some(previous) if previous.period_start == period_start => {
+                        let dt = (now - previous.last_poll).num_seconds().max(1) as f64;
+                        let rate = (total - previous.last_total).max(0.0) / dt;
+                        let weight =
+                            1.0 - (-dt * std::f64::consts::LN_2 / (half_life_hours * 3600.0)).exp();
+                        previous.rate += weight * (rate - previous.rate);
+                        previous.last_poll = now;
+                        previous.last_total = total;
+                        previous.rate
+                    }
+                    // the first poll of a period starts from the average rate so far
+                    _ => {
+                        state.insert(
+                            provider,
+                            ProviderState {
+                                period_start,
+                                last_poll: now,
+                                last_total: total,
+                                rate: average_rate,
+                            },
+                        );
+                        average_rate
+                    }
+                }
+            }
+        };
+        total + rate * remaining
+    }
+}
+
+#[async_trait]
+impl UsageSink for Forecaster {
+    fn name(&self) -> &'static str {
+        "forecast"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let now = Utc::now();
+        for provider_usage in usage {
+            let total = provider_usage
+                .usage
+                .iter()
+                .fold(0.0, |total, model_usage| total + model_usage.cost_usd);
+            let forecast = self.forecast(provider_usage.provider, total, now);
+            self.metrics
+                .set_cost_forecast(provider_usage.provider, forecast);
+        }
+        Ok(())
+    }
+}
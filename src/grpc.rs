@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+use crate::api::{ProviderSnapshot, UsageStore};
+use crate::auth::AuthConfig;
+use crate::history::{HistorySnapshot, UsageHistory};
+use crate::providers::LLMUsage;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+pub mod proto {
+    tonic::include_proto!("llm_cost_exporter.v1");
+}
+
+use proto::usage_service_server::{UsageService, UsageServiceServer};
+use proto::{
+    GetUsageHistoryRequest, GetUsageHistoryResponse, GetUsageRequest, GetUsageResponse, ImageCount,
+    ModelUsage, ProviderUsage,
+};
+
+/// Serves the usage kept for the JSON API over gRPC, and the usage history when it is
+/// recorded.
+pub struct UsageServer {
+    store: UsageStore,
+    history: Option<Arc<dyn UsageHistory>>,
+}
+
+fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<LLMUsage> for ModelUsage {
+    fn from(usage: LLMUsage) -> Self {
+        ModelUsage {
+            model: usage.model,
+            cost_usd: usage.cost_usd,
+            request_cost_usd: usage.request_cost_usd,
+            prompt_tokens: usage.prompt_tokens,
+            cached_prompt_tokens: usage.cached_prompt_tokens,
+            cache_write_tokens: usage.cache_write_tokens,
+            completion_tokens: usage.completion_tokens,
+            embedding_tokens: usage.embedding_tokens,
+            request_count: usage.request_count,
+            images_generated: usage
+                .images_generated
+                .into_iter()
+                .map(|images| ImageCount {
+                    size: images.size,
+                    quality: images.quality,
+                    count: images.count,
+                })
+                .collect(),
+            transcription_minutes: usage.transcription_minutes,
+            speech_characters: usage.speech_characters,
+            labels: usage
+                .labels
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+}
+
+impl From<ProviderSnapshot> for ProviderUsage {
+    fn from(snapshot: ProviderSnapshot) -> Self {
+        ProviderUsage {
+            provider: snapshot.provider.to_string(),
+            last_updated: Some(timestamp(snapshot.last_updated)),
+            models: snapshot.models.into_iter().map(ModelUsage::from).collect(),
+        }
+    }
+}
+
+impl From<HistorySnapshot> for ProviderUsage {
+    fn from(snapshot: HistorySnapshot) -> Self {
+        ProviderUsage {
+            provider: snapshot.provider,
+            last_updated: Some(timestamp(snapshot.polled_at)),
+            models: snapshot.models.into_iter().map(ModelUsage::from).collect(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UsageService for UsageServer {
+    async fn get_usage(
+        &self,
+        request: Request<GetUsageRequest>,
+    ) -> Result<Response<GetUsageResponse>, Status> {
+        let provider = Some(request.into_inner().provider).filter(|p| !p.is_empty());
+        Ok(Response::new(GetUsageResponse {
+            providers: self
+                .store
+                .snapshot(provider.as_deref())
+                .into_iter()
+                .map(ProviderUsage::from)
+                .collect(),
+        }))
+    }
+
+    async fn get_usage_history(
+        &self,
+        request: Request<GetUsageHistoryRequest>,
+    ) -> Result<Response<GetUsageHistoryResponse>, Status> {
+        let Some(history) = &self.history else {
+            return Err(Status::unimplemented(
+                "usage history requires persistence, which is not configured",
+            ));
+        };
+        let request = request.into_inner();
+        let provider = Some(request.provider).filter(|p| !p.is_empty());
+        let time = |timestamp: Option<prost_types::Timestamp>, default: DateTime<Utc>| {
+            timestamp.map_or(Ok(default), |t| {
+                DateTime::from_timestamp(t.seconds, t.nanos.max(0) as u32)
+                    .ok_or_else(|| Status::invalid_argument("timestamp out of range"))
+            })
+        };
+        let start = time(request.start, DateTime::UNIX_EPOCH)?;
+        let end = time(request.end, Utc::now())?;
+        let snapshots = history
+            .query(provider.as_deref(), start, end)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(GetUsageHistoryResponse {
+            snapshots: snapshots.into_iter().map(ProviderUsage::from).collect(),
+        }))
+    }
+}
+
+/// Address of the gRPC server from `GRPC_LISTEN_ADDRESS` (e.g. `0.0.0.0:9000`), if enabled.
+pub fn address_from_env() -> Result<Option<SocketAddr>, MonitorError> {
+    match std::env::var("GRPC_LISTEN_ADDRESS") {
+        Ok(address) if !address.is_empty() => {
+            Ok(Some(address.parse().map_err(|e| {
+                anyhow::anyhow!("invalid GRPC_LISTEN_ADDRESS {:?}: {}", address, e)
+            })?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Serves `UsageService` until shutdown, requiring the metrics credentials in the
+/// `authorization` metadata when they are configured.
+pub async fn serve(
+    address: SocketAddr,
+    store: UsageStore,
+    history: Option<Arc<dyn UsageHistory>>,
+    auth: Option<AuthConfig>,
+    shutdown: Shutdown,
+) -> Result<(), MonitorError> {
+    let check_auth = move |request: Request<()>| match &auth {
+        Some(auth) => {
+            let provided = request
+                .metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok());
+            if auth.verify(provided) {
+                Ok(request)
+            } else {
+                Err(Status::unauthenticated("invalid credentials"))
+            }
+        }
+        None => Ok(request),
+    };
+    tracing::info!(%address, "serving gRPC usage service");
+    tonic::transport::Server::builder()
+        .add_service(UsageServiceServer::with_interceptor(
+            UsageServer { store, history },
+            check_auth,
+        ))
+        .serve_with_shutdown(address, shutdown.wait())
+        .await
+        .map_err(|e| anyhow::anyhow!("gRPC server failed: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use crate::leader::Leadership;
+
+/// Tracks which required providers have not completed a successful poll yet.
+#[derive(Debug, Default)]
+pub struct Readiness {
+    pending: Mutex<BTreeSet<String>>,
+    leadership: Leadership,
+}
+
+impl Readiness {
+    /// Every provider must report once before the exporter is ready, except the optional ones.
+    pub fn new<'a>(providers: impl IntoIterator<Item = &'a str>, optional: &[String]) -> Self {
+        Self {
+            pending: Mutex::new(
+                providers
+                    .into_iter()
+                    .filter(|provider| !optional.iter().any(|o| o == provider))
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            leadership: Leadership::default(),
+        }
+    }
+
+    /// Standbys don't poll, so they are ready as long as they aren't leader.
+    pub fn leadership(mut self, leadership: Leadership) -> Self {
+        self.leadership = leadership;
+        self
+    }
+
+    /// Stops waiting for providers that are no longer polled, e.g. after a config reload.
+    /// Providers added meanwhile aren't waited for, so a reload doesn't make a ready exporter
+    /// unready.
+    pub fn retain(&self, providers: &[&str]) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|provider| providers.contains(&provider.as_str()));
+    }
+
+    pub fn mark_success(&self, provider: &str) {
+        self.pending.lock().unwrap().remove(provider);
+    }
+
+    fn pending(&self) -> Vec<String> {
+        if !self.leadership.is_leader() {
+            return Vec::new();
+        }
+        self.pending.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// `/healthz` answers as soon as the server is up, `/readyz` once every required provider
+/// has reported, or right away on a standby.
+pub fn routes(readiness: Arc<Readiness>) -> BoxedFilter<(warp::reply::Response,)> {
+    let healthz = warp::path!("healthz").map(|| "ok".into_response());
+    let readyz = warp::path!("readyz").map(move || {
+        let pending = readiness.pending();
+        if pending.is_empty() {
+            "ok".into_response()
+        } else {
+            warp::reply::with_status(
+                format!("waiting for first successful poll: {}", pending.join(", ")),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+            .into_response()
+        }
+    });
+    healthz.or(readyz).unify().boxed()
+}
@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Usage one provider reported in one poll.
+#[derive(Debug, Clone)]
+pub struct HistorySnapshot {
+    pub provider: String,
+    pub polled_at: DateTime<Utc>,
+    pub models: Vec<LLMUsage>,
+}
+
+/// Durable record of every poll's usage, which survives restarts.
+#[async_trait]
+pub trait UsageHistory: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Stores the usage of one poll cycle.
+    async fn record(
+        &self,
+        polled_at: DateTime<Utc>,
+        usage: &[ProviderUsage],
+    ) -> Result<(), MonitorError>;
+
+    /// Snapshots polled in `[start, end)`, of every provider or of one, oldest first.
+    async fn query(
+        &self,
+        provider: Option<&str>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<HistorySnapshot>, MonitorError>;
+
+    /// The most recent snapshot of every provider.
+    async fn latest(&self) -> Result<Vec<HistorySnapshot>, MonitorError>;
+}
+
+/// Opens the history store configured in the environment, if any.
+pub async fn from_env() -> Result<Option<Arc<dyn UsageHistory>>, MonitorError> {
+    let setting = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    match (
+        setting("HISTORY_SQLITE_PATH"),
+        setting("HISTORY_POSTGRES_URL"),
+    ) {
+        (Some(_), Some(_)) => Err(anyhow::anyhow!(
+            "set only one of HISTORY_SQLITE_PATH and HISTORY_POSTGRES_URL"
+        )
+        .into()),
+        (Some(path), None) => {
+            #[cfg(feature = "sqlite")]
+            return Ok(Some(Arc::new(
+                sqlite::SqliteHistory::open(&path, retention_days_from_env()?).await?,
+            )));
+            #[cfg(not(feature = "sqlite"))]
+            return Err(anyhow::anyhow!(
+                "HISTORY_SQLITE_PATH is set to {:?} but the exporter was built without the sqlite feature",
+                path
+            )
+            .into());
+        }
+        (None, Some(_url)) => {
+            #[cfg(feature = "postgres")]
+            return Ok(Some(Arc::new(
+                postgres::PostgresHistory::connect(
+                    &_url,
+                    setting("HISTORY_INSTANCE").unwrap_or_default(),
+                    retention_days_from_env()?,
+                )
+                .await?,
+            )));
+            #[cfg(not(feature = "postgres"))]
+            return Err(anyhow::anyhow!(
+                "HISTORY_POSTGRES_URL is set but the exporter was built without the postgres feature"
+            )
+            .into());
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Days of history to keep from `HISTORY_RETENTION_DAYS`; everything is kept when unset.
+#[cfg_attr(not(any(feature = "sqlite", feature = "postgres")), allow(dead_code))]
+fn retention_days_from_env() -> Result<Option<u32>, MonitorError> {
+    match std::env::var("HISTORY_RETENTION_DAYS") {
+        Ok(days) if !days.is_empty() => Ok(Some(
+            days.parse::<u32>()
+                .ok()
+                .filter(|days| *days > 0)
+                .ok_or_else(|| anyhow::anyhow!("invalid HISTORY_RETENTION_DAYS {:?}", days))?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Maps stored label names back to [`USAGE_LABELS`], dropping labels this version doesn't know.
+#[cfg_attr(not(any(feature = "sqlite", feature = "postgres")), allow(dead_code))]
+fn usage_labels(labels: BTreeMap<String, String>) -> BTreeMap<&'static str, String> {
+    labels
+        .into_iter()
+        .filter_map(|(name, value)| {
+            USAGE_LABELS
+                .iter()
+                .find(|label| **label == name)
+                .map(|label| (*label, value))
+        })
+        .collect()
+}
+
+/// Groups rows ordered by poll time and provider into snapshots.
+#[cfg_attr(not(any(feature = "sqlite", feature = "postgres")), allow(dead_code))]
+fn snapshots(rows: Vec<(DateTime<Utc>, String, LLMUsage)>) -> Vec<HistorySnapshot> {
+    let mut snapshots: Vec<HistorySnapshot> = Vec::new();
+    for (polled_at, provider, usage) in rows {
+        match snapshots.last_mut() {
+            Some(last) if last.polled_at == polled_at && last.provider == provider => {
+                last.models.push(usage)
+            }
+            _ => snapshots.push(HistorySnapshot {
+                provider,
+                polled_at,
+                models: vec![usage],
+            }),
+        }
+    }
+    snapshots
+}
+
+/// Writes every poll to the history store.
+pub struct HistoryRecorder {
+    history: Arc<dyn UsageHistory>,
+}
+
+impl HistoryRecorder {
+    pub fn new(history: Arc<dyn UsageHistory>) -> Self {
+        Self { history }
+    }
+}
+
+#[async_trait]
+impl UsageSink for HistoryRecorder {
+    fn name(&self) -> &'static str {
+        self.history.name()
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        self.history.record(Utc::now(), usage).await
+    }
+}
@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgRow};
+use sqlx::types::Json;
+use sqlx::{AssertSqlSafe, QueryBuilder, Row};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use super::{snapshots, usage_labels, HistorySnapshot, UsageHistory};
+use crate::providers::{ImageCount, LLMUsage};
+use crate::sinks::ProviderUsage;
+use crate::MonitorError;
+
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS llm_usage (
+        id BIGSERIAL PRIMARY KEY,
+        instance TEXT NOT NULL,
+        polled_at TIMESTAMPTZ NOT NULL,
+        provider TEXT NOT NULL,
+        model TEXT NOT NULL,
+        labels JSONB NOT NULL,
+        cost_usd DOUBLE PRECISION NOT NULL,
+        request_cost_usd DOUBLE PRECISION NOT NULL,
+        prompt_tokens BIGINT NOT NULL,
+        cached_prompt_tokens BIGINT NOT NULL,
+        cache_write_tokens BIGINT NOT NULL,
+        completion_tokens BIGINT NOT NULL,
+        embedding_tokens BIGINT NOT NULL,
+        request_count BIGINT NOT NULL,
+        images JSONB NOT NULL,
+        transcription_minutes DOUBLE PRECISION NOT NULL,
+        speech_characters BIGINT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS llm_usage_polled_at ON llm_usage (polled_at)",
+    "CREATE INDEX IF NOT EXISTS llm_usage_instance_provider_polled_at
+        ON llm_usage (instance, provider, polled_at)",
+];
+
+const COLUMNS: &str = "polled_at, provider, model, labels, cost_usd, request_cost_usd, \
+    prompt_tokens, cached_prompt_tokens, cache_write_tokens, completion_tokens, \
+    embedding_tokens, request_count, images, transcription_minutes, speech_characters";
+
+/// Rows per insert statement, well under the 65535 bind parameters Postgres allows.
+const INSERT_BATCH: usize = 1000;
+
+/// Keeps every poll in a PostgreSQL table that several exporters can share. Rows carry the
+/// `instance` that wrote them, so each exporter resumes its counters from its own polls while
+/// queries and reports see every instance.
+pub struct PostgresHistory {
+    pool: PgPool,
+    instance: String,
+    retention_days: Option<u32>,
+}
+
+impl PostgresHistory {
+    pub async fn connect(
+        url: &str,
+        instance: String,
+        retention_days: Option<u32>,
+    ) -> Result<Self, MonitorError> {
+        let options = PgConnectOptions::from_str(url)
+            .map_err(postgres_error)?
+            // keeps "already exists" notices of the schema statements out of the log
+            .options([("client_min_messages", "warning")]);
+        let pool = PgPoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .map_err(postgres_error)?;
+        for statement in SCHEMA {
+            sqlx::query(*statement)
+                .execute(&pool)
+                .await
+                .map_err(postgres_error)?;
+        }
+        tracing::info!(
+            instance,
+            retention_days,
+            "recording usage history in PostgreSQL"
+        );
+        Ok(Self {
+            pool,
+            instance,
+            retention_days,
+        })
+    }
+}
+
+fn postgres_error(e: sqlx::Error) -> MonitorError {
+    anyhow::anyhow!("PostgreSQL: {}", e).into()
+}
+
+/// Reads a row selected with [`COLUMNS`] into its poll time, provider and usage.
+fn read_row(row: PgRow) -> Result<(DateTime<Utc>, String, LLMUsage), sqlx::Error> {
+    let labels: Json<BTreeMap<String, String>> = row.try_get("labels")?;
+    let images: Json<Vec<ImageCount>> = row.try_get("images")?;
+    let count = |column: &str| row.try_get::<i64, _>(column).map(|count| count as u64);
+    let usage = LLMUsage {
+        model: row.try_get("model")?,
+        cost_usd: row.try_get("cost_usd")?,
+        request_cost_usd: row.try_get("request_cost_usd")?,
+        prompt_tokens: count("prompt_tokens")?,
+        cached_prompt_tokens: count("cached_prompt_tokens")?,
+        cache_write_tokens: count("cache_write_tokens")?,
+        completion_tokens: count("completion_tokens")?,
+        embedding_tokens: count("embedding_tokens")?,
+        request_count: count("request_count")?,
+        images_generated: images.0,
+        transcription_minutes: row.try_get("transcription_minutes")?,
+        speech_characters: count("speech_characters")?,
+        labels: usage_labels(labels.0),
+        request_tier: None,
+    };
+    Ok((row.try_get("polled_at")?, row.try_get("provider")?, usage))
+}
+
+#[async_trait]
+impl UsageHistory for PostgresHistory {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn record(
+        &self,
+        polled_at: DateTime<Utc>,
+        usage: &[ProviderUsage],
+    ) -> Result<(), MonitorError> {
+        let rows: Vec<(&str, &LLMUsage)> = usage
+            .iter()
+            .flat_map(|provider_usage| {
+                provider_usage
+                    .usage
+                    .iter()
+                    .map(|model_usage| (provider_usage.provider, model_usage))
+            })
+            .collect();
+        let mut transaction = self.pool.begin().await.map_err(postgres_error)?;
+        for batch in rows.chunks(INSERT_BATCH) {
+            let mut insert =
+                QueryBuilder::new(format!("INSERT INTO llm_usage (instance, {}) ", COLUMNS));
+            insert.push_values(batch, |mut row, (provider, model_usage)| {
+                row.push_bind(&self.instance)
+                    .push_bind(polled_at)
+                    .push_bind(*provider)
+                    .push_bind(&model_usage.model)
+                    .push_bind(Json(&model_usage.labels))
+                    .push_bind(model_usage.cost_usd)
+                    .push_bind(model_usage.request_cost_usd)
+                    .push_bind(model_usage.prompt_tokens as i64)
+                    .push_bind(model_usage.cached_prompt_tokens as i64)
+                    .push_bind(model_usage.cache_write_tokens as i64)
+                    .push_bind(model_usage.completion_tokens as i64)
+                    .push_bind(model_usage.embedding_tokens as i64)
+                    .push_bind(model_usage.request_count as i64)
+                    .push_bind(Json(&model_usage.images_generated))
+                    .push_bind(model_usage.transcription_minutes)
+                    .push_bind(model_usage.speech_characters as i64);
+            });
+            insert
+                .build()
+                .execute(&mut *transaction)
+                .await
+                .map_err(postgres_error)?;
+        }
+        if let Some(days) = self.retention_days {
+            // every instance prunes the shared table with its own retention
+            sqlx::query("DELETE FROM llm_usage WHERE polled_at < $1")
+                .bind(polled_at - Duration::days(days.into()))
+                .execute(&mut *transaction)
+                .await
+                .map_err(postgres_error)?;
+        }
+        transaction.commit().await.map_err(postgres_error)
+    }
+
+    async fn query(
+        &self,
+        provider: Option<&str>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<HistorySnapshot>, MonitorError> {
+        // only constants are formatted into the statement
+        let rows = sqlx::query(AssertSqlSafe(format!(
+            "SELECT {} FROM llm_usage WHERE polled_at >= $1 AND polled_at < $2 \
+             AND ($3::TEXT IS NULL OR provider = $3) ORDER BY polled_at, provider, id",
+            COLUMNS
+        )))
+        .bind(start)
+        .bind(end)
+        .bind(provider)
+        .try_map(read_row)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(postgres_error)?;
+        Ok(snapshots(rows))
+    }
+
+    async fn latest(&self) -> Result<Vec<HistorySnapshot>, MonitorError> {
+        let rows = sqlx::query(AssertSqlSafe(format!(
+            "SELECT {} FROM llm_usage WHERE instance = $1 AND (provider, polled_at) IN \
+             (SELECT provider, MAX(polled_at) FROM llm_usage WHERE instance = $1 \
+             GROUP BY provider) ORDER BY polled_at, provider, id",
+            COLUMNS
+        )))
+        .bind(&self.instance)
+        .try_map(read_row)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(postgres_error)?;
+        Ok(snapshots(rows))
+    }
+}
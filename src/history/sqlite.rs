@@ -0,0 +1,211 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, Row};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use super::{snapshots, usage_labels, HistorySnapshot, UsageHistory};
+use crate::providers::{ImageCount, LLMUsage};
+use crate::sinks::ProviderUsage;
+use crate::MonitorError;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS usage (
+    polled_at INTEGER NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    labels TEXT NOT NULL,
+    cost_usd REAL NOT NULL,
+    request_cost_usd REAL NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    cached_prompt_tokens INTEGER NOT NULL,
+    cache_write_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    embedding_tokens INTEGER NOT NULL,
+    request_count INTEGER NOT NULL,
+    images TEXT NOT NULL,
+    transcription_minutes REAL NOT NULL,
+    speech_characters INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS usage_polled_at ON usage (polled_at);
+CREATE INDEX IF NOT EXISTS usage_provider_polled_at ON usage (provider, polled_at);
+";
+
+const COLUMNS: &str = "polled_at, provider, model, labels, cost_usd, request_cost_usd, \
+    prompt_tokens, cached_prompt_tokens, cache_write_tokens, completion_tokens, \
+    embedding_tokens, request_count, images, transcription_minutes, speech_characters";
+
+/// Keeps every poll in an SQLite database file, one row per series. Labels and image counts
+/// are stored as JSON; poll times as Unix seconds.
+pub struct SqliteHistory {
+    connection: Arc<Mutex<Connection>>,
+    retention_days: Option<u32>,
+}
+
+impl SqliteHistory {
+    pub async fn open(path: &str, retention_days: Option<u32>) -> Result<Self, MonitorError> {
+        let path = path.to_string();
+        let connection = blocking(move || {
+            let connection = Connection::open(&path)?;
+            connection.pragma_update(None, "journal_mode", "WAL")?;
+            connection.execute_batch(SCHEMA)?;
+            Ok(connection)
+        })
+        .await?;
+        tracing::info!(retention_days, "recording usage history in SQLite");
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            retention_days,
+        })
+    }
+
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, MonitorError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        blocking(move || f(&mut connection.lock().unwrap())).await
+    }
+}
+
+/// Runs SQLite calls off the async runtime.
+async fn blocking<T, F>(f: F) -> Result<T, MonitorError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> rusqlite::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| anyhow::anyhow!("SQLite task failed: {}", e))?
+        .map_err(|e| anyhow::anyhow!("SQLite: {}", e).into())
+}
+
+fn json_error(e: serde_json::Error) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
+
+/// Reads a row selected with [`COLUMNS`] into its poll time, provider and usage.
+fn read_row(row: &Row) -> rusqlite::Result<(DateTime<Utc>, String, LLMUsage)> {
+    let labels: BTreeMap<String, String> =
+        serde_json::from_str(&row.get::<_, String>(3)?).map_err(json_error)?;
+    let images: Vec<ImageCount> =
+        serde_json::from_str(&row.get::<_, String>(12)?).map_err(json_error)?;
+    let usage = LLMUsage {
+        model: row.get(2)?,
+        cost_usd: row.get(4)?,
+        request_cost_usd: row.get(5)?,
+        prompt_tokens: row.get::<_, i64>(6)? as u64,
+        cached_prompt_tokens: row.get::<_, i64>(7)? as u64,
+        cache_write_tokens: row.get::<_, i64>(8)? as u64,
+        completion_tokens: row.get::<_, i64>(9)? as u64,
+        embedding_tokens: row.get::<_, i64>(10)? as u64,
+        request_count: row.get::<_, i64>(11)? as u64,
+        images_generated: images,
+        transcription_minutes: row.get(13)?,
+        speech_characters: row.get::<_, i64>(14)? as u64,
+        labels: usage_labels(labels),
+        request_tier: None,
+    };
+    let polled_at = DateTime::from_timestamp(row.get(0)?, 0).unwrap_or_default();
+    Ok((polled_at, row.get(1)?, usage))
+}
+
+#[async_trait]
+impl UsageHistory for SqliteHistory {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn record(
+        &self,
+        polled_at: DateTime<Utc>,
+        usage: &[ProviderUsage],
+    ) -> Result<(), MonitorError> {
+        let usage = usage.to_vec();
+        let cutoff = self
+            .retention_days
+            .map(|days| (polled_at - Duration::days(days.into())).timestamp());
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut insert = transaction.prepare_cached(&format!(
+                    "INSERT INTO usage ({}) VALUES \
+                     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                    COLUMNS
+                ))?;
+                for provider_usage in &usage {
+                    for model_usage in &provider_usage.usage {
+                        insert.execute(params![
+                            polled_at.timestamp(),
+                            provider_usage.provider,
+                            model_usage.model,
+                            serde_json::to_string(&model_usage.labels).map_err(json_error)?,
+                            model_usage.cost_usd,
+                            model_usage.request_cost_usd,
+                            model_usage.prompt_tokens as i64,
+                            model_usage.cached_prompt_tokens as i64,
+                            model_usage.cache_write_tokens as i64,
+                            model_usage.completion_tokens as i64,
+                            model_usage.embedding_tokens as i64,
+                            model_usage.request_count as i64,
+                            serde_json::to_string(&model_usage.images_generated)
+                                .map_err(json_error)?,
+                            model_usage.transcription_minutes,
+                            model_usage.speech_characters as i64,
+                        ])?;
+                    }
+                }
+            }
+            if let Some(cutoff) = cutoff {
+                transaction.execute("DELETE FROM usage WHERE polled_at < ?1", [cutoff])?;
+            }
+            transaction.commit()
+        })
+        .await
+    }
+
+    async fn query(
+        &self,
+        provider: Option<&str>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<HistorySnapshot>, MonitorError> {
+        let provider = provider.map(str::to_string);
+        let rows = self
+            .with_connection(move |connection| {
+                let mut select = connection.prepare_cached(&format!(
+                    "SELECT {} FROM usage WHERE polled_at >= ?1 AND polled_at < ?2 \
+                     AND (?3 IS NULL OR provider = ?3) ORDER BY polled_at, provider, rowid",
+                    COLUMNS
+                ))?;
+                let rows = select
+                    .query_map(
+                        params![start.timestamp(), end.timestamp(), provider],
+                        read_row,
+                    )?
+                    .collect::<rusqlite::Result<Vec<_>>>();
+                rows
+            })
+            .await?;
+        Ok(snapshots(rows))
+    }
+
+    async fn latest(&self) -> Result<Vec<HistorySnapshot>, MonitorError> {
+        let rows = self
+            .with_connection(|connection| {
+                let mut select = connection.prepare_cached(&format!(
+                    "SELECT {} FROM usage WHERE (provider, polled_at) IN \
+                     (SELECT provider, MAX(polled_at) FROM usage GROUP BY provider) \
+                     ORDER BY polled_at, provider, rowid",
+                    COLUMNS
+                ))?;
+                let rows = select
+                    .query_map([], read_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>();
+                rows
+            })
+            .await?;
+        Ok(snapshots(rows))
+    }
+}
@@ -0,0 +1,63 @@
+use reqwest::{Method, RequestBuilder};
+use std::time::Duration;
+
+use crate::MonitorError;
+
+/// Where Kubernetes mounts the pod's service account token, namespace and the cluster CA.
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// Client of the API server of the cluster the exporter runs in, authenticated as the pod's
+/// service account.
+pub(crate) struct KubeClient {
+    client: reqwest::Client,
+    api_url: String,
+}
+
+impl KubeClient {
+    /// Requests time out after `timeout`.
+    pub(crate) fn in_cluster(timeout: Duration) -> Result<Self, MonitorError> {
+        let (Ok(host), Ok(port)) = (
+            std::env::var("KUBERNETES_SERVICE_HOST"),
+            std::env::var("KUBERNETES_SERVICE_PORT"),
+        ) else {
+            return Err(anyhow::anyhow!("the exporter doesn't run in a Kubernetes cluster").into());
+        };
+        let ca = std::fs::read(format!("{}/ca.crt", SERVICE_ACCOUNT_DIR))
+            .map_err(|e| anyhow::anyhow!("failed to read the cluster CA: {}", e))?;
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca)?)
+            .timeout(timeout)
+            .build()?;
+        // IPv6 hosts need brackets in a URL
+        let host = match host.parse::<std::net::IpAddr>() {
+            Ok(std::net::IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => host,
+        };
+        Ok(Self {
+            client,
+            api_url: format!("https://{}:{}", host, port),
+        })
+    }
+
+    /// Namespace the exporter's pod runs in.
+    pub(crate) fn namespace() -> Result<String, MonitorError> {
+        std::fs::read_to_string(format!("{}/namespace", SERVICE_ACCOUNT_DIR))
+            .map(|namespace| namespace.trim().to_string())
+            .map_err(|e| anyhow::anyhow!("failed to read the pod's namespace: {}", e).into())
+    }
+
+    /// A request to `path` of the API, e.g. `/api/v1/pods`.
+    pub(crate) fn request(
+        &self,
+        method: Method,
+        path: &str,
+    ) -> Result<RequestBuilder, MonitorError> {
+        // bound service account tokens are rotated on disk, so read the current one each time
+        let token = std::fs::read_to_string(format!("{}/token", SERVICE_ACCOUNT_DIR))
+            .map_err(|e| anyhow::anyhow!("failed to read the service account token: {}", e))?;
+        Ok(self
+            .client
+            .request(method, format!("{}{}", self.api_url, path))
+            .bearer_auth(token.trim()))
+    }
+}
@@ -0,0 +1,118 @@
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use yaml_rust::Yaml;
+
+use crate::providers::USAGE_LABELS;
+
+/// Labels the policy can remove or filter: the model, the optional usage labels and the caller
+/// of tracked calls. `provider` and the fixed labels of each metric are always exported.
+fn is_policy_label(label: &str) -> bool {
+    ["model", "user", "namespace", "deployment"].contains(&label) || USAGE_LABELS.contains(&label)
+}
+
+/// Values a label is exported with: those matching `keep`, if set, and not matching `drop`.
+#[derive(Debug, Clone)]
+pub struct ValueFilter {
+    pub keep: Option<Regex>,
+    pub drop: Option<Regex>,
+}
+
+impl ValueFilter {
+    fn allows(&self, value: &str) -> bool {
+        self.keep.as_ref().is_none_or(|keep| keep.is_match(value))
+            && !self.drop.as_ref().is_some_and(|drop| drop.is_match(value))
+    }
+}
+
+/// Which labels are exported and which of their values, so privacy and cardinality policies
+/// live in the exporter's config rather than in relabel rules. A removed label or filtered
+/// value is exported empty, which Prometheus treats the same as the label being absent, and
+/// its usage is added to the series without it.
+#[derive(Debug, Clone, Default)]
+pub struct LabelPolicy {
+    /// When not empty, the only labels exported.
+    allow: BTreeSet<String>,
+    deny: BTreeSet<String>,
+    filters: BTreeMap<String, ValueFilter>,
+}
+
+impl LabelPolicy {
+    /// Exports only these labels.
+    pub fn allow(mut self, label: &str) -> Self {
+        self.allow.insert(label.to_string());
+        self
+    }
+
+    pub fn deny(mut self, label: &str) -> Self {
+        self.deny.insert(label.to_string());
+        self
+    }
+
+    pub fn filter(mut self, label: &str, filter: ValueFilter) -> Self {
+        self.filters.insert(label.to_string(), filter);
+        self
+    }
+
+    /// Reads the `labels` section of the config file.
+    pub fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let mut policy = Self::default();
+        for (field, list) in [("allow", &node["allow"]), ("deny", &node["deny"])] {
+            let labels = match list {
+                Yaml::BadValue | Yaml::Null => continue,
+                Yaml::Array(labels) => labels,
+                _ => return Err(format!("{} must be a list of labels", field)),
+            };
+            for label in labels {
+                let label = label
+                    .as_str()
+                    .ok_or_else(|| format!("{} must be a list of labels", field))?;
+                if !is_policy_label(label) {
+                    return Err(format!("{} lists an unknown label {:?}", field, label));
+                }
+                policy = match field {
+                    "allow" => policy.allow(label),
+                    _ => policy.deny(label),
+                };
+            }
+        }
+        for (label, filter) in node["filters"].as_hash().into_iter().flatten() {
+            let label = label.as_str().ok_or("filters must be keyed by label")?;
+            if !is_policy_label(label) {
+                return Err(format!("filters has an unknown label {:?}", label));
+            }
+            let regex = |field: &str| {
+                filter[field]
+                    .as_str()
+                    .map(|pattern| {
+                        Regex::new(pattern).map_err(|e| {
+                            format!("invalid {} pattern of label {}: {}", field, label, e)
+                        })
+                    })
+                    .transpose()
+            };
+            let filter = ValueFilter {
+                keep: regex("keep")?,
+                drop: regex("drop")?,
+            };
+            if filter.keep.is_none() && filter.drop.is_none() {
+                return Err(format!("filter of label {} needs keep or drop", label));
+            }
+            policy = policy.filter(label, filter);
+        }
+        Ok(policy)
+    }
+
+    /// Whether `label` is exported at all, with some of its values.
+    pub fn exports(&self, label: &str) -> bool {
+        (self.allow.is_empty() || self.allow.contains(label)) && !self.deny.contains(label)
+    }
+
+    /// Whether `label` is exported with `value`.
+    pub fn allows(&self, label: &str, value: &str) -> bool {
+        self.exports(label)
+            && self
+                .filters
+                .get(label)
+                .is_none_or(|filter| filter.allows(value))
+    }
+}
@@ -0,0 +1,292 @@
+use chrono::{SecondsFormat, Utc};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time;
+use tracing::{debug, info, warn};
+
+use crate::kubernetes::KubeClient;
+use crate::metrics::LLMMetrics;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+const DEFAULT_LEASE_NAME: &str = "llm-cost-exporter";
+/// How long a lease is valid without being renewed, after which a standby takes over.
+const LEASE_DURATION: Duration = Duration::from_secs(15);
+/// How long the leader keeps polling after its last renewal while the API server can't be
+/// reached, well before a standby may take over.
+const RENEW_DEADLINE: Duration = Duration::from_secs(10);
+/// How often the lease is renewed, or tried by standbys.
+const RETRY_PERIOD: Duration = Duration::from_secs(2);
+
+/// Whether this replica polls the providers and reports to the push sinks. Without leader
+/// election every replica does.
+#[derive(Debug, Clone)]
+pub struct Leadership(Arc<watch::Sender<bool>>);
+
+impl Default for Leadership {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Leadership {
+    pub fn new(leader: bool) -> Self {
+        Self(Arc::new(watch::Sender::new(leader)))
+    }
+
+    pub fn is_leader(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Notified whenever this replica becomes leader or standby.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.0.subscribe()
+    }
+
+    fn set(&self, leader: bool) {
+        self.0.send_replace(leader);
+    }
+}
+
+/// Elects one leader among replicas with a Kubernetes Lease, so running several replicas for
+/// availability doesn't poll the billing APIs or report to the push sinks more than once.
+/// Standbys keep serving scrapes, the proxy and the usage API, and stay ready.
+pub struct LeaseElection {
+    client: KubeClient,
+    namespace: String,
+    name: String,
+    identity: String,
+    leadership: Leadership,
+    /// Resource version of the lease last seen held by another replica, and when it was first
+    /// seen. The lease expires when it hasn't changed for its duration, by the local clock, so
+    /// clock skew between nodes doesn't matter.
+    observed: Option<(String, Instant)>,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+impl LeaseElection {
+    /// Enabled with `LEADER_ELECTION=kubernetes`. The lease is `LEADER_ELECTION_LEASE_NAME`
+    /// (default `llm-cost-exporter`) in `LEADER_ELECTION_NAMESPACE` (default the pod's own),
+    /// held under the pod name.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        match std::env::var("LEADER_ELECTION").as_deref() {
+            Err(_) | Ok("") | Ok("off") => return Ok(None),
+            Ok("kubernetes") => {}
+            Ok(other) => {
+                return Err(anyhow::anyhow!(
+                    "invalid LEADER_ELECTION {:?}, expected off or kubernetes",
+                    other
+                )
+                .into())
+            }
+        }
+        let setting = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let identity = setting("HOSTNAME")
+            .ok_or_else(|| anyhow::anyhow!("leader election needs HOSTNAME, the pod name"))?;
+        let namespace = match setting("LEADER_ELECTION_NAMESPACE") {
+            Some(namespace) => namespace,
+            None => KubeClient::namespace()?,
+        };
+        Ok(Some(Self {
+            client: KubeClient::in_cluster(RETRY_PERIOD)?,
+            namespace,
+            name: setting("LEADER_ELECTION_LEASE_NAME")
+                .unwrap_or_else(|| DEFAULT_LEASE_NAME.to_string()),
+            identity,
+            leadership: Leadership::new(false),
+            observed: None,
+        }))
+    }
+
+    /// Starts out as standby and becomes leader once it holds the lease.
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.clone()
+    }
+
+    fn leases_path(&self) -> String {
+        format!(
+            "/apis/coordination.k8s.io/v1/namespaces/{}/leases",
+            self.namespace
+        )
+    }
+
+    /// Takes or renews the lease. Returns whether this replica holds it.
+    async fn try_acquire_or_renew(&mut self) -> Result<bool, MonitorError> {
+        let path = format!("{}/{}", self.leases_path(), self.name);
+        let response = self.client.request(Method::GET, &path)?.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            let lease = json!({
+                "apiVersion": "coordination.k8s.io/v1",
+                "kind": "Lease",
+                "metadata": {"name": self.name, "namespace": self.namespace},
+                "spec": {
+                    "holderIdentity": self.identity,
+                    "leaseDurationSeconds": LEASE_DURATION.as_secs(),
+                    "acquireTime": now(),
+                    "renewTime": now(),
+                    "leaseTransitions": 0,
+                },
+            });
+            let path = self.leases_path();
+            let response = self
+                .client
+                .request(Method::POST, &path)?
+                .json(&lease)
+                .send()
+                .await?;
+            // another replica created it first
+            if response.status() == StatusCode::CONFLICT {
+                return Ok(false);
+            }
+            response.error_for_status()?;
+            return Ok(true);
+        }
+        let mut lease: Value = response.error_for_status()?.json().await?;
+
+        let holder = lease["spec"]["holderIdentity"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if !holder.is_empty() && holder != self.identity {
+            let version = lease["metadata"]["resourceVersion"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let duration = lease["spec"]["leaseDurationSeconds"]
+                .as_u64()
+                .map_or(LEASE_DURATION, Duration::from_secs);
+            match &self.observed {
+                Some((observed, since)) if *observed == version => {
+                    if since.elapsed() < duration {
+                        return Ok(false);
+                    }
+                    info!(holder, "lease expired, taking over");
+                }
+                _ => {
+                    self.observed = Some((version, Instant::now()));
+                    return Ok(false);
+                }
+            }
+        }
+
+        let spec = lease["spec"]
+            .as_object_mut()
+            .ok_or(MonitorError::InvalidResponse)?;
+        if holder != self.identity {
+            let transitions = spec
+                .get("leaseTransitions")
+                .and_then(Value::as_u64)
+                .unwrap_or_default();
+            spec.insert("leaseTransitions".to_string(), json!(transitions + 1));
+            spec.insert("acquireTime".to_string(), json!(now()));
+        }
+        spec.insert("holderIdentity".to_string(), json!(self.identity));
+        spec.insert(
+            "leaseDurationSeconds".to_string(),
+            json!(LEASE_DURATION.as_secs()),
+        );
+        spec.insert("renewTime".to_string(), json!(now()));
+        // the resource version in the body makes this fail if another replica wrote first
+        let response = self
+            .client
+            .request(Method::PUT, &path)?
+            .json(&lease)
+            .send()
+            .await?;
+        if response.status() == StatusCode::CONFLICT {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+        self.observed = None;
+        Ok(true)
+    }
+
+    /// Gives up the lease at shutdown, so a standby takes over without waiting for it to
+    /// expire.
+    async fn release(&self) -> Result<(), MonitorError> {
+        let path = format!("{}/{}", self.leases_path(), self.name);
+        let mut lease: Value = self
+            .client
+            .request(Method::GET, &path)?
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if lease["spec"]["holderIdentity"].as_str() != Some(self.identity.as_str()) {
+            return Ok(());
+        }
+        lease["spec"]["holderIdentity"] = Value::Null;
+        lease["spec"]["renewTime"] = json!(now());
+        self.client
+            .request(Method::PUT, &path)?
+            .json(&lease)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Stops polling and pushing as leader, so the shutdown after a lost lease doesn't push
+    /// next to the new leader.
+    fn step_down(&self, metrics: &LLMMetrics) {
+        self.leadership.set(false);
+        metrics.set_leader(false);
+    }
+
+    /// Holds or waits for the lease until shutdown, then releases it. Returns an error when
+    /// the lease is lost while leading, after stepping down: the metrics of the polls made as
+    /// leader would go stale, so the caller is expected to shut down and start over as a
+    /// standby.
+    pub async fn run(
+        mut self,
+        metrics: Arc<LLMMetrics>,
+        shutdown: Shutdown,
+    ) -> Result<(), MonitorError> {
+        info!(lease = %self.name, namespace = %self.namespace, identity = %self.identity, "starting leader election");
+        metrics.set_leader(false);
+        let mut interval = time::interval(RETRY_PERIOD);
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        let mut renewed = Instant::now();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut stop => break,
+            }
+            let leader = self.leadership.is_leader();
+            match self.try_acquire_or_renew().await {
+                Ok(true) => {
+                    renewed = Instant::now();
+                    if !leader {
+                        info!("became leader, polling providers");
+                        self.leadership.set(true);
+                        metrics.set_leader(true);
+                    }
+                }
+                Ok(false) if leader => {
+                    self.step_down(&metrics);
+                    return Err(anyhow::anyhow!("lost the lease to another replica").into());
+                }
+                Ok(false) => debug!("standing by"),
+                Err(e) if leader && renewed.elapsed() > RENEW_DEADLINE => {
+                    self.step_down(&metrics);
+                    return Err(anyhow::anyhow!("failed to renew the lease: {}", e).into());
+                }
+                Err(e) => warn!(error = %e, "leader election request failed"),
+            }
+        }
+        if self.leadership.is_leader() {
+            if let Err(e) = self.release().await {
+                warn!(error = %e, "failed to release the lease");
+            }
+        }
+        Ok(())
+    }
+}
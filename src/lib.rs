@@ -0,0 +1,135 @@
+//! Polls LLM providers for usage and cost and exports it as Prometheus metrics.
+//!
+//! The `llm_cost_exporter` binary is a thin wrapper around this crate; services that would
+//! rather not run it next to them can embed the same machinery: implement or pick
+//! [`LLMMonitor`]s, price usage with [`Pricing`], and let an [`Exporter`] poll them into
+//! [`LLMMetrics`] registered in their own registry.
+
+use thiserror::Error;
+
+pub mod alerts;
+pub mod anomaly;
+pub mod api;
+pub mod auth;
+mod aws;
+pub mod budget;
+pub mod cardinality;
+pub mod chargeback;
+pub mod config;
+pub mod cost_centers;
+pub mod currency;
+pub mod dashboard;
+pub mod estimate;
+pub mod expiry;
+pub mod exporter;
+pub mod forecast;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod health;
+pub mod history;
+mod kubernetes;
+pub mod labels;
+pub mod leader;
+pub mod logging;
+pub mod metrics;
+pub mod openmetrics;
+pub mod output;
+pub mod pricing;
+pub mod providers;
+pub mod proxy;
+pub mod push;
+pub mod reload;
+pub mod report;
+pub mod retry;
+pub mod shutdown;
+pub mod sinks;
+pub mod state;
+pub mod teams;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
+pub mod tls;
+pub mod tracker;
+pub mod validate;
+
+pub use estimate::CostEstimator;
+pub use exporter::{Exporter, ExporterBuilder};
+pub use metrics::{LLMMetrics, MetricsMode};
+pub use pricing::Pricing;
+pub use providers::{LLMMonitor, LLMUsage};
+pub use sinks::{ProviderUsage, UsageSink};
+pub use teams::TeamMapping;
+pub use tracker::LLMTracker;
+
+#[derive(Error, Debug)]
+pub enum MonitorError {
+    #[error("API request failed: {0}")]
+    ApiError(#[from] reqwest::Error),
+    #[error("AWS SDK error")]
+    AwsError(#[from] Box<aws_sdk_bedrockruntime::Error>),
+    #[error("Invalid response format")]
+    InvalidResponse,
+    #[error("Configuration error: {0}")]
+    ConfigError(#[from] anyhow::Error),
+    #[error("Request failed with {0}: {1}")]
+    StatusError(reqwest::StatusCode, String),
+    #[error("Plugin failed: {0}")]
+    PluginError(String),
+    #[error("Rate limited by the provider")]
+    RateLimited {
+        /// How long the provider asked to wait, from its `Retry-After` header.
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error("Poll timed out after {0:?}")]
+    Timeout(std::time::Duration),
+}
+
+impl MonitorError {
+    /// Coarse failure class used as the `error_type` label.
+    pub fn error_type(&self) -> &'static str {
+        let status = match self {
+            MonitorError::ApiError(e) if e.is_decode() => return "parse",
+            MonitorError::ApiError(e) => e.status(),
+            MonitorError::StatusError(status, _) => Some(*status),
+            MonitorError::AwsError(_) => None,
+            MonitorError::InvalidResponse => return "parse",
+            MonitorError::ConfigError(_) => return "config",
+            MonitorError::PluginError(_) => return "plugin",
+            MonitorError::RateLimited { .. } => return "rate_limit",
+            MonitorError::Timeout(_) => return "timeout",
+        };
+        match status.map(|s| s.as_u16()) {
+            Some(401) | Some(403) => "auth",
+            Some(429) => "rate_limit",
+            _ => "network",
+        }
+    }
+
+    /// Whether the call may succeed when repeated: network failures, timeouts, rate limits
+    /// and server errors. AWS SDK calls are retried by the SDK itself.
+    pub fn is_transient(&self) -> bool {
+        let transient_status =
+            |status: reqwest::StatusCode| status.is_server_error() || status.as_u16() == 429;
+        match self {
+            MonitorError::ApiError(e) => match e.status() {
+                Some(status) => transient_status(status),
+                None => !e.is_decode() && !e.is_builder(),
+            },
+            MonitorError::StatusError(status, _) => transient_status(*status),
+            MonitorError::RateLimited { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the provider rejected the call for exceeding its rate limit.
+    pub fn is_rate_limited(&self) -> bool {
+        self.error_type() == "rate_limit"
+    }
+
+    /// How long the provider asked to wait before the next call, if it said.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            MonitorError::RateLimited { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
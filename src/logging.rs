@@ -0,0 +1,45 @@
+use clap::ValueEnum;
+use std::io;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregation.
+    Json,
+}
+
+/// Installs the global subscriber. The level is a filter such as `info` or
+/// `llm_cost_exporter=debug,warp=warn`, `info` when unset or invalid. Logs go to stdout, or
+/// to stderr for commands whose output is on stdout. `traces`, such as the layer of
+/// [`crate::telemetry::Tracing`], gets the spans whatever the level.
+pub fn init(
+    format: LogFormat,
+    level: Option<&str>,
+    to_stderr: bool,
+    traces: Option<Box<dyn Layer<Registry> + Send + Sync>>,
+) {
+    let filter = level
+        .and_then(|level| EnvFilter::try_new(level).ok())
+        .unwrap_or_else(|| EnvFilter::new("info"));
+    let writer = BoxMakeWriter::new(move || -> Box<dyn io::Write> {
+        match to_stderr {
+            true => Box::new(io::stderr()),
+            false => Box::new(io::stdout()),
+        }
+    });
+    let logs = tracing_subscriber::fmt::layer().with_writer(writer);
+    let logs = match format {
+        LogFormat::Text => logs.boxed(),
+        LogFormat::Json => logs.json().flatten_event(true).boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(traces)
+        .with(logs.with_filter(filter))
+        .init();
+}
@@ -1,150 +1,579 @@
-use anyhow::Context;
-use async_trait::async_trait;
-use prometheus::{opts, Encoder, GaugeVec, Registry, TextEncoder};
+use chrono::NaiveDate;
+use prometheus::Registry;
+use std::sync::Arc;
 use std::time::Duration;
-use thiserror::Error;
 use tokio::time;
+use tracing::{error, info, warn};
 
-#[derive(Debug)]
-pub struct BedrockConfig {
-    pub assume_role: AssumeRoleConfig,
-}
+use clap::Parser;
+use llm_cost_exporter::alerts::AlertEvaluator;
+use llm_cost_exporter::anomaly::AnomalyDetector;
+use llm_cost_exporter::budget::BudgetTracker;
+use llm_cost_exporter::cardinality::LabelLimits;
+use llm_cost_exporter::chargeback::{self, ChargebackReporter};
+use llm_cost_exporter::config::{Cli, Command, Config};
+use llm_cost_exporter::expiry::SeriesTtls;
+use llm_cost_exporter::exporter::{PollMode, PollTimeouts};
+use llm_cost_exporter::forecast::{ForecastMethod, Forecaster};
+#[cfg(feature = "grpc")]
+use llm_cost_exporter::grpc;
+use llm_cost_exporter::history::{self, HistoryRecorder};
+use llm_cost_exporter::leader::{Leadership, LeaseElection};
+use llm_cost_exporter::output::{self, OutputFormat};
+use llm_cost_exporter::pricing::{OffPeakWindow, Pricing, RemoteCatalog};
+use llm_cost_exporter::providers::bedrock::BedrockConfig;
+use llm_cost_exporter::providers::cost_explorer::CostExplorerClient;
+use llm_cost_exporter::providers::ollama::OllamaTokens;
+use llm_cost_exporter::providers::{ApiTimer, ProviderRegistry, Shard};
+use llm_cost_exporter::proxy::{self, ProxyConfig};
+use llm_cost_exporter::push::pushgateway::Pushgateway;
+use llm_cost_exporter::push::remote_write::RemoteWriter;
+use llm_cost_exporter::reload::{self, ConfigReloader};
+use llm_cost_exporter::report::{self, ReportGroup, ReportRow};
+use llm_cost_exporter::retry::RetryPolicy;
+use llm_cost_exporter::shutdown::Shutdown;
+use llm_cost_exporter::sinks::CallSink;
+use llm_cost_exporter::state::StateFile;
+#[cfg(feature = "otlp")]
+use llm_cost_exporter::telemetry::Tracing;
+use llm_cost_exporter::tracker::UserLabels;
+use llm_cost_exporter::{
+    currency, logging, providers, sinks, validate, CostEstimator, Exporter, LLMTracker,
+    MetricsMode, MonitorError,
+};
 
-#[derive(Debug)]
-pub struct AssumeRoleConfig {
-    pub enabled: bool,
-    pub role_arn: String,
-    pub session_name: String,
+#[tokio::main]
+async fn main() -> Result<(), MonitorError> {
+    let cli = Cli::parse();
+    let command = cli.command.clone().unwrap_or(match cli.once {
+        true => Command::Once {
+            format: OutputFormat::default(),
+            push: false,
+        },
+        false => Command::Serve,
+    });
+    #[cfg(feature = "otlp")]
+    let tracing = Tracing::from_env()?;
+    #[cfg(feature = "otlp")]
+    let traces = tracing.as_ref().map(Tracing::layer);
+    #[cfg(not(feature = "otlp"))]
+    let traces = None;
+    logging::init(
+        cli.log_format,
+        cli.log_level.as_deref(),
+        !matches!(command, Command::Serve),
+        traces,
+    );
+    let result = match command {
+        Command::Serve => serve(cli).await,
+        Command::Once { format, push } => once(cli, format, push).await,
+        Command::Validate => {
+            validate::run(cli).await;
+            Ok(())
+        }
+        Command::Report {
+            start,
+            end,
+            group_by,
+            format,
+        } => report(start, end, &group_by, format).await,
+        Command::Chargeback { start, end } => chargeback(start, end).await,
+        Command::PrintPricing { provider, format } => {
+            print_pricing(cli, provider.as_deref(), format)
+        }
+    };
+    #[cfg(feature = "otlp")]
+    if let Some(tracing) = tracing {
+        tracing.shutdown();
+    }
+    result
 }
 
-#[derive(Error, Debug)]
-pub enum MonitorError {
-    #[error("API request failed")]
-    ApiError(#[from] reqwest::Error),
-    #[error("AWS SDK error")]
-    AwsError(#[from] aws_sdk_bedrockruntime::Error),
-    #[error("Invalid response format")]
-    InvalidResponse,
-    #[error("Configuration error: {0}")]
-    ConfigError(#[from] anyhow::Error),
-}
+/// Polls every provider once and prints the usage. Fails when a provider's poll failed.
+async fn once(cli: Cli, format: OutputFormat, push: bool) -> Result<(), MonitorError> {
+    let config = Config::load(cli)?;
+    providers::init_billing_periods()?;
+    let pricing = Pricing::load(config.pricing_file.as_deref())?;
+    let mut provider_registry = ProviderRegistry::builtin();
+    if let Some(shard) = Shard::from_env()? {
+        provider_registry = provider_registry.shard(shard);
+    }
+    let monitors = provider_registry
+        .monitors(config.providers.as_deref(), &pricing)
+        .await?;
+    if monitors.is_empty() {
+        return Err(anyhow::anyhow!("no provider configured, set at least one API key").into());
+    }
+    let registry = Registry::new_custom(None, Some(config.extra_labels.clone()))
+        .map_err(anyhow::Error::from)?;
+    let pushgateway = match Pushgateway::from_env(registry.clone())? {
+        Some(pushgateway) if push => Some(pushgateway),
+        None if push => return Err(anyhow::anyhow!("--push needs PUSHGATEWAY_URL").into()),
+        _ => None,
+    };
+    let exporter = Exporter::builder()
+        .registry(registry)
+        .metrics_mode(MetricsMode::from_env()?)
+        .monitors(monitors)
+        .teams(config.teams.clone())
+        .cost_centers(config.cost_centers.clone())
+        .label_limits(LabelLimits::from_env()?)
+        .label_policy(config.labels.clone())
+        .retry_policy(RetryPolicy::from_env()?)
+        .poll_timeouts(PollTimeouts::from_env()?)
+        .build();
+    let usage = exporter.poll().await;
+    if let Some(pushgateway) = pushgateway {
+        pushgateway.push().await?;
+    }
 
-#[async_trait]
-trait LLMMonitor {
-    async fn get_usage(&self) -> Result<LLMUsage, MonitorError>;
-}
+    match format {
+        OutputFormat::Json => {
+            let providers: Vec<_> = usage
+                .iter()
+                .map(|provider_usage| {
+                    serde_json::json!({
+                        "provider": provider_usage.provider,
+                        "models": provider_usage.usage,
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&providers).map_err(anyhow::Error::from)?
+            );
+        }
+        OutputFormat::Table | OutputFormat::Csv => {
+            let mut rows: Vec<Vec<String>> = usage
+                .iter()
+                .flat_map(|provider_usage| {
+                    provider_usage.usage.iter().map(|model_usage| {
+                        vec![
+                            provider_usage.provider.to_string(),
+                            model_usage.model.clone(),
+                            format!("{:.4}", model_usage.cost_usd),
+                            model_usage.prompt_tokens.to_string(),
+                            model_usage.completion_tokens.to_string(),
+                            model_usage.request_count.to_string(),
+                        ]
+                    })
+                })
+                .collect();
+            let header = [
+                "PROVIDER",
+                "MODEL",
+                "COST_USD",
+                "PROMPT_TOKENS",
+                "COMPLETION_TOKENS",
+                "REQUESTS",
+            ];
+            if format == OutputFormat::Csv {
+                print!("{}", output::csv(&header, &rows));
+            } else {
+                let total: f64 = usage
+                    .iter()
+                    .flat_map(|provider_usage| &provider_usage.usage)
+                    // folded from 0.0 because an empty f64 sum is -0.0
+                    .fold(0.0, |total, model_usage| total + model_usage.cost_usd);
+                rows.push(vec![
+                    "total".to_string(),
+                    String::new(),
+                    format!("{:.4}", total),
+                ]);
+                print!("{}", output::table(&header, &rows));
+            }
+        }
+    }
 
-#[derive(Debug, Default)]
-struct LLMUsage {
-    pub cost_usd: f64,
-    pub prompt_tokens: u64,
-    pub completion_tokens: u64,
-    pub request_count: u64,
+    let failed: Vec<&str> = exporter
+        .providers()
+        .into_iter()
+        .filter(|provider| !usage.iter().any(|polled| polled.provider == *provider))
+        .collect();
+    if !failed.is_empty() {
+        return Err(anyhow::anyhow!("usage poll failed for {}", failed.join(", ")).into());
+    }
+    Ok(())
 }
 
-struct OpenAIMonitor {
-    #[allow(dead_code)]
-    api_key: String,
+/// Prints the built-in prices with the pricing file applied.
+fn print_pricing(
+    cli: Cli,
+    provider: Option<&str>,
+    format: OutputFormat,
+) -> Result<(), MonitorError> {
+    let config = Config::load(cli)?;
+    let catalog = Pricing::load(config.pricing_file.as_deref())?.current();
+    let mut prices = catalog.prices();
+    if let Some(provider) = provider {
+        prices.retain(|priced, _| *priced == provider);
+        if prices.is_empty() {
+            return Err(anyhow::anyhow!("no prices for provider {:?}", provider).into());
+        }
+    }
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&prices).map_err(anyhow::Error::from)?
+        ),
+        OutputFormat::Table | OutputFormat::Csv => {
+            let optional = |price: Option<f64>| price.map_or("-".to_string(), |p| p.to_string());
+            let rows: Vec<Vec<String>> = prices
+                .iter()
+                .flat_map(|(provider, models)| {
+                    models.iter().map(move |(model, price)| {
+                        vec![
+                            provider.to_string(),
+                            model.to_string(),
+                            price.input.to_string(),
+                            price.output.to_string(),
+                            optional(price.cached_input),
+                            optional(price.cache_write_input),
+                            price.request_fee.to_string(),
+                        ]
+                    })
+                })
+                .collect();
+            let header = [
+                "PROVIDER",
+                "MODEL",
+                "INPUT",
+                "OUTPUT",
+                "CACHED_INPUT",
+                "CACHE_WRITE",
+                "REQUEST_FEE",
+            ];
+            let write = match format {
+                OutputFormat::Csv => output::csv,
+                _ => output::table,
+            };
+            print!("{}", write(&header, &rows));
+        }
+    }
+    Ok(())
 }
 
-#[async_trait]
-impl LLMMonitor for OpenAIMonitor {
-    async fn get_usage(&self) -> Result<LLMUsage, MonitorError> {
-        Ok(LLMUsage::default())
+/// Prints the usage of a date range from the usage history, by default last month's.
+async fn report(
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    group_by: &[ReportGroup],
+    format: OutputFormat,
+) -> Result<(), MonitorError> {
+    providers::init_billing_periods()?;
+    let history = history::from_env().await?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "report needs usage history, set HISTORY_SQLITE_PATH or HISTORY_POSTGRES_URL"
+        )
+    })?;
+    let (last_month_start, last_month_end) = report::last_month();
+    let start = start.unwrap_or(last_month_start);
+    let end = end.unwrap_or(last_month_end);
+    if end < start {
+        return Err(anyhow::anyhow!("report ends on {} before it starts on {}", end, start).into());
     }
+    let rows = report::usage(history.as_ref(), start, end, group_by).await?;
+    match format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&rows).map_err(anyhow::Error::from)?
+        ),
+        OutputFormat::Table | OutputFormat::Csv => {
+            let cells: Vec<Vec<String>> = rows.iter().map(ReportRow::cells).collect();
+            let write = match format {
+                OutputFormat::Csv => output::csv,
+                _ => output::table,
+            };
+            print!("{}", write(&ReportRow::HEADER, &cells));
+        }
+    }
+    Ok(())
 }
 
-struct LLMMetrics {
-    cost: GaugeVec,
-    tokens: GaugeVec,
-    requests: GaugeVec,
+/// Generates, prints and delivers a chargeback report, by default of the last billing period.
+async fn chargeback(start: Option<NaiveDate>, end: Option<NaiveDate>) -> Result<(), MonitorError> {
+    providers::init_billing_periods()?;
+    let reporter = ChargebackReporter::from_env(history::from_env().await?)
+        .await?
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "no chargeback destination configured, set CHARGEBACK_WEBHOOK_URLS, CHARGEBACK_EMAIL_TO or CHARGEBACK_S3_URL"
+            )
+        })?;
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => chargeback::last_period(),
+    };
+    if end < start {
+        return Err(anyhow::anyhow!("report ends on {} before it starts on {}", end, start).into());
+    }
+    let report = reporter.generate(start, end).await?;
+    print!("{}", report.text());
+    reporter.deliver(&report).await
 }
 
-impl LLMMetrics {
-    fn new(registry: &Registry) -> Self {
-        let cost = GaugeVec::new(
-            opts!("llm_cost_usd", "Cost of LLM API usage in USD"),
-            &["provider", "model"],
-        )
-        .unwrap();
+/// A duration in whole seconds from the environment, `default` when unset.
+fn secs_setting(name: &str, default: u64) -> Result<Duration, MonitorError> {
+    let secs = match std::env::var(name) {
+        Ok(secs) if !secs.is_empty() => secs
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid {} {:?}", name, secs))?,
+        _ => default,
+    };
+    Ok(Duration::from_secs(secs))
+}
 
-        let tokens = GaugeVec::new(
-            opts!("llm_tokens", "Tokens used by LLM API"),
-            &["provider", "model", "type"],
-        )
-        .unwrap();
+/// Runs the exporter until SIGTERM or SIGINT.
+async fn serve(cli: Cli) -> Result<(), MonitorError> {
+    let config = Config::load(cli.clone())?;
+    let (stop, shutdown) = Shutdown::listen_or_request()
+        .map_err(|e| anyhow::anyhow!("failed to install signal handlers: {}", e))?;
 
-        let requests = GaugeVec::new(
-            opts!("llm_requests", "Number of LLM API requests"),
-            &["provider", "model"],
+    providers::init_billing_periods()?;
+    let pricing = Pricing::load(config.pricing_file.as_deref())?;
+    let mut provider_registry = ProviderRegistry::builtin();
+    let shard = Shard::from_env()?;
+    if let Some(shard) = shard {
+        info!(
+            index = shard.index,
+            count = shard.count,
+            "polling one shard of the providers"
+        );
+        provider_registry = provider_registry.shard(shard);
+    }
+    let monitors = provider_registry
+        .monitors(config.providers.as_deref(), &pricing)
+        .await?;
+    let proxy = ProxyConfig::from_env()?;
+    if monitors.is_empty() && shard.is_some() {
+        warn!("no provider belongs to this shard");
+    } else if monitors.is_empty() && proxy.is_none() {
+        return Err(anyhow::anyhow!(
+            "no provider configured, set at least one API key or PROXY_LISTEN_ADDRESS"
         )
-        .unwrap();
-
-        registry.register(Box::new(cost.clone())).unwrap();
-        registry.register(Box::new(tokens.clone())).unwrap();
-        registry.register(Box::new(requests.clone())).unwrap();
+        .into());
+    }
 
-        Self {
-            cost,
-            tokens,
-            requests,
+    let election = LeaseElection::from_env()?;
+    let leadership = election
+        .as_ref()
+        .map_or_else(Leadership::default, LeaseElection::leadership);
+    let registry = Registry::new_custom(None, Some(config.extra_labels.clone()))
+        .map_err(anyhow::Error::from)?;
+    let mut builder = Exporter::builder()
+        .registry(registry.clone())
+        .metrics_mode(MetricsMode::from_env()?)
+        .monitors(monitors)
+        .sinks(sinks::from_env().await?)
+        .server(config.server.clone())
+        .estimator(CostEstimator::from_env(pricing.clone()))
+        .optional_providers(config.optional_providers.clone())
+        .teams(config.teams.clone())
+        .cost_centers(config.cost_centers.clone())
+        .label_limits(LabelLimits::from_env()?)
+        .label_policy(config.labels.clone())
+        .series_ttls(SeriesTtls::from_env()?)
+        .leadership(leadership.clone())
+        .retry_policy(RetryPolicy::from_env()?)
+        .poll_timeouts(PollTimeouts::from_env()?)
+        .poll_mode(PollMode::from_env()?)
+        .min_refresh_interval(secs_setting("MIN_REFRESH_INTERVAL_SECS", 60)?)
+        .refresh_timeout(secs_setting("REFRESH_TIMEOUT_SECS", 10)?);
+    // there is only something to reload with a config file
+    let reload = cli.config.is_some().then(reload::channel);
+    if let Some((handle, _)) = &reload {
+        builder = builder.reload(handle.clone());
+    }
+    let rate_fetcher = match currency::from_env()? {
+        Some((rate, fetcher)) => {
+            builder = builder.exchange_rate(rate);
+            fetcher
         }
+        None => None,
+    };
+    if let Some(pushgateway) = Pushgateway::from_env(registry.clone())? {
+        builder = builder.sink(pushgateway);
     }
-
-    fn update(&self, provider: &str, model: &str, usage: &LLMUsage) {
-        self.cost
-            .with_label_values(&[provider, model])
-            .set(usage.cost_usd);
-        self.tokens
-            .with_label_values(&[provider, model, "prompt"])
-            .set(usage.prompt_tokens as f64);
-        self.tokens
-            .with_label_values(&[provider, model, "completion"])
-            .set(usage.completion_tokens as f64);
-        self.requests
-            .with_label_values(&[provider, model])
-            .set(usage.request_count as f64);
+    let mut exporter = builder.build();
+    let metrics = exporter.metrics().clone();
+    if let Some(fetcher) = &rate_fetcher {
+        if let Err(e) = fetcher.update(&metrics).await {
+            warn!(error = %e, "initial exchange rate fetch failed");
+        }
     }
-}
+    let history = history::from_env().await?;
+    if let Some(history) = &history {
+        for snapshot in history.latest().await? {
+            metrics.restore_all(&snapshot.provider, &snapshot.models, snapshot.polled_at);
+        }
+        exporter.add_sink(Box::new(HistoryRecorder::new(history.clone())));
+    }
+    if let Some(state_file) = StateFile::from_env(metrics.clone()) {
+        let state_file = state_file.monitors(exporter.monitors().clone());
+        state_file.restore().await?;
+        exporter.add_sink(Box::new(state_file));
+    }
+    exporter.add_sink(Box::new(Forecaster::new(
+        ForecastMethod::from_env()?,
+        metrics.clone(),
+    )));
+    exporter.add_sink(Box::new(AnomalyDetector::from_env(metrics.clone())?));
+    let alerts = AlertEvaluator::from_env(&config.budgets)?;
+    if config.budgets.is_empty() && alerts.is_some() {
+        warn!("budget alerts are configured but no budgets are defined");
+    }
+    // kept even without budgets, so a reload can add some
+    let budgets = BudgetTracker::new(config.budgets.clone(), metrics.clone(), alerts);
+    exporter.add_sink(Box::new(budgets.clone()));
+    metrics.record_config_reload(true);
+    metrics.set_pricing_catalog("builtin", env!("CARGO_PKG_VERSION"));
+    let remote_catalog = RemoteCatalog::from_env()?;
+    if let Some(remote_catalog) = &remote_catalog {
+        remote_catalog.load_cache(&pricing, &metrics);
+    }
+    info!(providers = ?exporter.providers(), "starting exporter");
 
-async fn run_metrics_server(registry: Registry) -> Result<(), std::io::Error> {
-    use warp::Filter;
+    // account-wide like the environment's bedrock provider, so polled by the shard owning it
+    let owns_bedrock = shard.is_none_or(|shard| shard.contains("bedrock"));
+    if owns_bedrock && std::env::var("BEDROCK_COST_EXPLORER_ENABLED").is_ok_and(|v| v == "true") {
+        let accounts = BedrockConfig::from_env().load_account_configs().await?;
+        let cost_explorer = CostExplorerClient::new(&accounts)?;
+        let interval_secs = std::env::var("COST_EXPLORER_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+        let metrics = metrics.clone();
+        let leadership = leadership.clone();
+        let stop = shutdown.clone().wait();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(interval_secs));
+            tokio::pin!(stop);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = &mut stop => break,
+                }
+                if !leadership.is_leader() {
+                    continue;
+                }
+                let costs = ApiTimer::new(metrics.clone(), "bedrock")
+                    .scope(cost_explorer.bedrock_costs())
+                    .await;
+                match costs {
+                    Ok(costs) => {
+                        for ((account_id, usage_type), amount) in costs {
+                            metrics.set_billed_cost("bedrock", &usage_type, &account_id, amount);
+                        }
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Cost Explorer poll failed");
+                        metrics.record_error("bedrock", &e);
+                    }
+                }
+            }
+        });
+    }
 
-    let metrics_route = warp::path!("metrics").map(move || {
-        let encoder = TextEncoder::new();
-        let mut buffer = vec![];
-        let metric_families = registry.gather();
-        encoder.encode(&metric_families, &mut buffer).unwrap();
-        String::from_utf8(buffer).unwrap()
+    // push and auxiliary server tasks, awaited at shutdown
+    let mut tasks = Vec::new();
+    let election = election.map(|election| {
+        let metrics = metrics.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let result = election.run(metrics, shutdown).await;
+            if let Err(e) = &result {
+                // the metrics of the polls made as leader are stale now; start over as standby
+                error!(error = %e, "lost leadership, shutting down");
+                let _ = stop.send(true);
+            }
+            result
+        })
     });
+    if let Some((_, requests)) = reload {
+        let reloader = ConfigReloader::new(
+            cli,
+            provider_registry,
+            pricing.clone(),
+            exporter.monitors().clone(),
+            budgets,
+            metrics.clone(),
+        );
+        let shutdown = shutdown.clone();
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = reloader.run(requests, shutdown).await {
+                error!(error = %e, "config reloads failed");
+            }
+        }));
+    }
+    if let Some(writer) = RemoteWriter::from_env()? {
+        tasks.push(tokio::spawn(writer.run(
+            registry.clone(),
+            leadership.clone(),
+            shutdown.clone(),
+        )));
+    }
 
-    warp::serve(metrics_route).run(([0, 0, 0, 0], 8000)).await;
-
-    Ok(())
-}
-
-#[tokio::main]
-async fn main() -> Result<(), MonitorError> {
-    let api_key = std::env::var("OPENAI_API_KEY").context("OPENAI_API_KEY not set")?;
-    let openai_monitor = OpenAIMonitor { api_key: api_key };
-
-    let registry = Registry::new();
-    let metrics = LLMMetrics::new(&registry);
+    if let Some(fetcher) = rate_fetcher {
+        tasks.push(tokio::spawn(fetcher.run(metrics.clone(), shutdown.clone())));
+    }
+    if let Some(remote_catalog) = remote_catalog {
+        tasks.push(tokio::spawn(remote_catalog.run(
+            pricing.clone(),
+            metrics.clone(),
+            shutdown.clone(),
+        )));
+    }
+    if let Some(reporter) = ChargebackReporter::from_env(history.clone()).await? {
+        tasks.push(tokio::spawn(
+            reporter.run(leadership.clone(), shutdown.clone()),
+        ));
+    }
+    if let Some(proxy) = proxy {
+        let tracker = LLMTracker::new(metrics.clone(), pricing.clone())
+            .off_peak_window(
+                "deepseek",
+                OffPeakWindow::from_env("DEEPSEEK_OFF_PEAK_WINDOW", OffPeakWindow::deepseek())?,
+            )
+            .teams(config.teams.clone())
+            .cost_centers(config.cost_centers.clone())
+            .user_labels(UserLabels::from_env()?)
+            .call_sinks(sinks::call_sinks_from_env()?)
+            .call_sinks([Box::new(OllamaTokens) as Box<dyn CallSink>]);
+        let tracker = Arc::new(tracker);
+        let shutdown = shutdown.clone();
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = proxy::serve(proxy, tracker.clone(), shutdown).await {
+                error!(error = %e, "LLM proxy failed");
+            }
+            tracker.shutdown().await;
+        }));
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(address) = grpc::address_from_env()? {
+        let store = exporter.usage_store().clone();
+        let history = history.clone();
+        let auth = config.server.auth.clone();
+        let shutdown = shutdown.clone();
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = grpc::serve(address, store, history, auth, shutdown).await {
+                error!(error = %e, "gRPC server failed");
+            }
+        }));
+    }
 
-    tokio::spawn(async move {
-        if let Err(e) = run_metrics_server(registry).await {
-            eprintln!("Metrics server error: {}", e);
-            // log this or return wrapped error
+    // polls until shutdown, then lets in-flight scrapes and final pushes finish
+    exporter.run(shutdown).await;
+    for task in tasks {
+        if let Err(e) = task.await {
+            error!(error = %e, "background task failed");
         }
-    });
-
-    // monitoring loop
-    let mut interval = time::interval(Duration::from_secs(300)); // 5 minutes
-
-    loop {
-        interval.tick().await;
-        if let Ok(usage) = openai_monitor.get_usage().await {
-            metrics.update("openai", "gpt-4", &usage);
+    }
+    // exit with an error after losing the lease, so the pod is restarted as a standby
+    if let Some(election) = election {
+        match election.await {
+            Ok(result) => result?,
+            Err(e) => error!(error = %e, "background task failed"),
         }
     }
+    Ok(())
 }
@@ -0,0 +1,1513 @@
+use chrono::{DateTime, Utc};
+use prometheus::core::Collector;
+use prometheus::{
+    histogram_opts, opts, CounterVec, Gauge, GaugeVec, Histogram, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge, IntGaugeVec, Registry,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::cardinality::{CardinalityGuard, LabelLimits};
+use crate::currency::ExchangeRate;
+use crate::expiry::{metric_name, SeriesExpiry, SeriesTtls};
+use crate::labels::LabelPolicy;
+use crate::openmetrics::Exemplars;
+use crate::providers::{
+    billing_period_start, billing_period_start_at, FineTuningJob, LLMUsage, USAGE_LABELS,
+};
+use crate::tracker::Caller;
+use crate::MonitorError;
+
+/// Labels of the exporter's own metrics besides [`USAGE_LABELS`], which constant labels
+/// can't reuse.
+pub const METRIC_LABELS: &[&str] = &[
+    "provider",
+    "model",
+    "currency",
+    "type",
+    "size",
+    "quality",
+    "tool",
+    "job_id",
+    "usage_type",
+    "user",
+    "namespace",
+    "deployment",
+    "estimated",
+    "status",
+    "endpoint",
+    "error_type",
+    "metric",
+    "budget",
+    "label",
+    "source",
+    "version",
+];
+
+const TRACKED_DURATION_BUCKETS: &[f64] = &[0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+const TRACKED_REQUEST_COST_BUCKETS: &[f64] =
+    &[0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// How cumulative usage totals are exposed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsMode {
+    /// Month-to-date totals as gauges, the value a provider reports.
+    Gauge,
+    /// Monotonic `_total` counters, advanced by the change between polls.
+    Counter,
+}
+
+impl MetricsMode {
+    pub fn from_env() -> Result<Self, MonitorError> {
+        match std::env::var("METRICS_MODE").as_deref() {
+            Ok("gauge") | Ok("") | Err(_) => Ok(Self::Gauge),
+            Ok("counter") => Ok(Self::Counter),
+            Ok(other) => Err(anyhow::anyhow!(
+                "invalid METRICS_MODE {:?}, expected gauge or counter",
+                other
+            )
+            .into()),
+        }
+    }
+}
+
+/// A usage metric exposed either as a gauge or as a counter.
+enum UsageVec {
+    Gauge(GaugeVec),
+    Counter(CounterVec),
+}
+
+impl UsageVec {
+    fn new(mode: MetricsMode, name: &str, help: &str, labels: &[&str]) -> Self {
+        match mode {
+            MetricsMode::Gauge => Self::Gauge(GaugeVec::new(opts!(name, help), labels).unwrap()),
+            MetricsMode::Counter => Self::Counter(
+                CounterVec::new(opts!(format!("{}_total", name), help), labels).unwrap(),
+            ),
+        }
+    }
+
+    fn register(&self, registry: &Registry) {
+        match self {
+            Self::Gauge(gauge) => registry.register(Box::new(gauge.clone())).unwrap(),
+            Self::Counter(counter) => registry.register(Box::new(counter.clone())).unwrap(),
+        }
+    }
+
+    fn track(&self, expiry: &mut SeriesExpiry) {
+        match self {
+            Self::Gauge(gauge) => expiry.track(gauge),
+            Self::Counter(counter) => expiry.track(counter),
+        }
+    }
+
+    fn touch(&self, expiry: &SeriesExpiry, labels: &[&str]) {
+        match self {
+            Self::Gauge(gauge) => expiry.touch(gauge, labels),
+            Self::Counter(counter) => expiry.touch(counter, labels),
+        }
+    }
+
+    /// Exported name, with the `_total` suffix in counter mode.
+    fn name(&self) -> String {
+        match self {
+            Self::Gauge(gauge) => metric_name(gauge),
+            Self::Counter(counter) => metric_name(counter),
+        }
+    }
+}
+
+/// Exported values of the usage counters and the totals they were last advanced from, saved
+/// so counters continue where they left off after a restart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CounterState {
+    counters: Vec<CounterSeries>,
+    last_totals: Vec<(Vec<String>, SeriesTotal)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CounterSeries {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: f64,
+}
+
+/// Where a counter series stands within the billing period of the provider's cumulative
+/// month-to-date totals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SeriesTotal {
+    period_start: DateTime<Utc>,
+    total: f64,
+    /// Decreases of the total within the period, such as credits, refunds or corrected usage,
+    /// that later increases have not made up for yet. Counters can't go down, so this much of
+    /// the following increases is held back instead.
+    pending_decrease: f64,
+}
+
+impl SeriesTotal {
+    /// Moves a series to a newly reported total and returns it with the increment for the
+    /// counter and the decrease the total just showed, if any. The first total of a billing
+    /// period counts in full.
+    fn advance(
+        previous: Option<&SeriesTotal>,
+        period_start: DateTime<Utc>,
+        total: f64,
+    ) -> (SeriesTotal, f64, f64) {
+        let fresh = SeriesTotal {
+            period_start,
+            total,
+            pending_decrease: 0.0,
+        };
+        let Some(previous) = previous.filter(|previous| previous.period_start == period_start)
+        else {
+            return (fresh, total, 0.0);
+        };
+        if total < previous.total {
+            let decrease = previous.total - total;
+            let series = SeriesTotal {
+                pending_decrease: previous.pending_decrease + decrease,
+                ..fresh
+            };
+            return (series, 0.0, decrease);
+        }
+        let increase = total - previous.total;
+        let held_back = increase.min(previous.pending_decrease);
+        let series = SeriesTotal {
+            pending_decrease: previous.pending_decrease - held_back,
+            ..fresh
+        };
+        (series, increase - held_back, 0.0)
+    }
+}
+
+pub struct LLMMetrics {
+    cost: UsageVec,
+    /// Cost in USD and in the reporting currency, by `currency` label.
+    converted_cost: UsageVec,
+    exchange_rate: GaugeVec,
+    reporting_currency: Option<ExchangeRate>,
+    request_cost: UsageVec,
+    tokens: UsageVec,
+    requests: UsageVec,
+    images: UsageVec,
+    transcription_minutes: UsageVec,
+    speech_characters: UsageVec,
+    credits: GaugeVec,
+    budget_limit: GaugeVec,
+    budget_spend: GaugeVec,
+    budget_utilization: GaugeVec,
+    tool_cost: GaugeVec,
+    cost_forecast: GaugeVec,
+    billing_period: GaugeVec,
+    cost_anomaly_score: GaugeVec,
+    cost_anomalies: IntCounterVec,
+    fine_tuning_cost: GaugeVec,
+    fine_tuning_tokens: GaugeVec,
+    /// `[model, job_id]` of the fine-tuning jobs last reported per provider, so jobs from
+    /// earlier billing periods can be dropped.
+    fine_tuning_jobs: Mutex<HashMap<String, Vec<[String; 2]>>>,
+    billed_cost: GaugeVec,
+    tracked_cost: CounterVec,
+    tracked_tokens: CounterVec,
+    tracked_requests: IntCounterVec,
+    tracked_duration: HistogramVec,
+    tracked_request_cost: HistogramVec,
+    /// Trace IDs of tracked calls, for the buckets of the tracked histograms.
+    exemplars: Exemplars,
+    ratelimit_remaining: GaugeVec,
+    api_duration: HistogramVec,
+    errors: IntCounterVec,
+    retries: IntCounterVec,
+    throttled: IntCounterVec,
+    last_success: GaugeVec,
+    poll_duration: Histogram,
+    monitors_active: IntGauge,
+    leader: IntGauge,
+    pricing_catalog: IntGaugeVec,
+    pricing_catalog_updated: Gauge,
+    pricing_catalog_errors: IntCounter,
+    config_reload_successful: IntGauge,
+    config_reload_time: Gauge,
+    mode: MetricsMode,
+    /// Last cumulative value seen per counter series, to turn totals into increments.
+    last_totals: Mutex<HashMap<Vec<String>, SeriesTotal>>,
+    usage_decreases: IntCounterVec,
+    cardinality: CardinalityGuard,
+    expiry: SeriesExpiry,
+}
+
+/// Appends the optional usage labels to a metric's base labels.
+fn with_usage_labels<'a>(base: &[&'a str], extra: &[&'a str]) -> Vec<&'a str> {
+    base.iter().chain(extra).copied().collect()
+}
+
+impl LLMMetrics {
+    pub fn new(
+        registry: &Registry,
+        mode: MetricsMode,
+        reporting_currency: Option<ExchangeRate>,
+    ) -> Self {
+        let cost = UsageVec::new(
+            mode,
+            "llm_cost_usd",
+            "Cost of LLM API usage in USD",
+            &with_usage_labels(&["provider", "model"], USAGE_LABELS),
+        );
+
+        let converted_cost = UsageVec::new(
+            mode,
+            "llm_cost",
+            "Cost of LLM API usage in the currency of the currency label",
+            &with_usage_labels(&["provider", "model", "currency"], USAGE_LABELS),
+        );
+
+        let exchange_rate = GaugeVec::new(
+            opts!(
+                "llm_exchange_rate",
+                "Units of the reporting currency per USD used for llm_cost"
+            ),
+            &["currency"],
+        )
+        .unwrap();
+
+        let request_cost = UsageVec::new(
+            mode,
+            "llm_request_cost_usd",
+            "Portion of LLM cost billed per request rather than per token, in USD",
+            &with_usage_labels(&["provider", "model"], USAGE_LABELS),
+        );
+
+        let tokens = UsageVec::new(
+            mode,
+            "llm_tokens",
+            "Tokens used by LLM API",
+            &with_usage_labels(&["provider", "model", "type"], USAGE_LABELS),
+        );
+
+        let requests = UsageVec::new(
+            mode,
+            "llm_requests",
+            "Number of LLM API requests",
+            &with_usage_labels(&["provider", "model"], USAGE_LABELS),
+        );
+
+        let images = UsageVec::new(
+            mode,
+            "llm_images",
+            "Images generated by LLM API",
+            &with_usage_labels(&["provider", "model", "size", "quality"], USAGE_LABELS),
+        );
+
+        let transcription_minutes = UsageVec::new(
+            mode,
+            "llm_audio_transcribed_minutes",
+            "Minutes of audio transcribed by speech-to-text models",
+            &with_usage_labels(&["provider", "model"], USAGE_LABELS),
+        );
+
+        let speech_characters = UsageVec::new(
+            mode,
+            "llm_speech_characters",
+            "Characters synthesized by text-to-speech models",
+            &with_usage_labels(&["provider", "model"], USAGE_LABELS),
+        );
+
+        let credits = GaugeVec::new(
+            opts!(
+                "llm_credits_remaining_usd",
+                "Remaining prepaid credit balance in USD"
+            ),
+            &["provider"],
+        )
+        .unwrap();
+
+        let budget_limit = GaugeVec::new(
+            opts!("llm_budget_limit_usd", "Monthly limit of the budget in USD"),
+            &["budget"],
+        )
+        .unwrap();
+
+        let budget_spend = GaugeVec::new(
+            opts!(
+                "llm_budget_spend_usd",
+                "Month-to-date spend counted against the budget in USD"
+            ),
+            &["budget"],
+        )
+        .unwrap();
+
+        let budget_utilization = GaugeVec::new(
+            opts!(
+                "llm_budget_utilization_ratio",
+                "Month-to-date spend as a fraction of the budget limit"
+            ),
+            &["budget"],
+        )
+        .unwrap();
+
+        let tool_cost = GaugeVec::new(
+            opts!(
+                "llm_tool_cost_usd",
+                "Month-to-date cost of built-in tools billed outside token usage, in USD"
+            ),
+            &["provider", "tool"],
+        )
+        .unwrap();
+
+        let cost_forecast = GaugeVec::new(
+            opts!(
+                "llm_cost_forecast_month_end_usd",
+                "Projected cost at the end of the month from the current spend rate, in USD"
+            ),
+            &["provider"],
+        )
+        .unwrap();
+
+        let billing_period = GaugeVec::new(
+            opts!(
+                "llm_billing_period_start_timestamp_seconds",
+                "Start of the provider's current billing period as a Unix timestamp"
+            ),
+            &["provider"],
+        )
+        .unwrap();
+
+        let cost_anomaly_score = GaugeVec::new(
+            opts!(
+                "llm_cost_anomaly_score",
+                "Robust z-score of the latest spend rate against the model's recent spend rates"
+            ),
+            &["provider", "model"],
+        )
+        .unwrap();
+
+        let cost_anomalies = IntCounterVec::new(
+            opts!(
+                "llm_cost_anomalies_total",
+                "Polls whose spend rate scored above the anomaly threshold"
+            ),
+            &["provider", "model"],
+        )
+        .unwrap();
+
+        let fine_tuning_cost = GaugeVec::new(
+            opts!(
+                "llm_finetuning_cost_usd",
+                "Training cost of fine-tuning jobs in the current billing period, in USD"
+            ),
+            &["provider", "model", "job_id"],
+        )
+        .unwrap();
+
+        let fine_tuning_tokens = GaugeVec::new(
+            opts!(
+                "llm_finetuning_trained_tokens",
+                "Tokens trained on by fine-tuning jobs in the current billing period"
+            ),
+            &["provider", "model", "job_id"],
+        )
+        .unwrap();
+
+        let billed_cost = GaugeVec::new(
+            opts!(
+                "llm_billed_cost_usd",
+                "Month-to-date cost as billed by the provider, in USD"
+            ),
+            &["provider", "usage_type", "aws_account_id"],
+        )
+        .unwrap();
+
+        let tracked_cost = CounterVec::new(
+            opts!(
+                "llm_tracked_cost_usd_total",
+                "Cost of LLM calls tracked in process, in USD"
+            ),
+            &[
+                "provider",
+                "model",
+                "team",
+                "cost_center",
+                "user",
+                "namespace",
+                "deployment",
+                "estimated",
+            ],
+        )
+        .unwrap();
+
+        let tracked_tokens = CounterVec::new(
+            opts!(
+                "llm_tracked_tokens_total",
+                "Tokens used by LLM calls tracked in process"
+            ),
+            &[
+                "provider",
+                "model",
+                "team",
+                "cost_center",
+                "user",
+                "namespace",
+                "deployment",
+                "type",
+                "estimated",
+            ],
+        )
+        .unwrap();
+
+        let tracked_requests = IntCounterVec::new(
+            opts!(
+                "llm_tracked_requests_total",
+                "LLM calls tracked in process by outcome"
+            ),
+            &[
+                "provider",
+                "model",
+                "team",
+                "user",
+                "namespace",
+                "deployment",
+                "status",
+            ],
+        )
+        .unwrap();
+
+        let tracked_duration = HistogramVec::new(
+            histogram_opts!(
+                "llm_tracked_request_duration_seconds",
+                "Time taken by LLM calls tracked in process",
+                TRACKED_DURATION_BUCKETS.to_vec()
+            ),
+            &["provider", "model"],
+        )
+        .unwrap();
+
+        let tracked_request_cost = HistogramVec::new(
+            histogram_opts!(
+                "llm_tracked_request_cost_usd",
+                "Cost of single LLM calls tracked in process, in USD",
+                TRACKED_REQUEST_COST_BUCKETS.to_vec()
+            ),
+            &["provider", "model"],
+        )
+        .unwrap();
+
+        let ratelimit_remaining = GaugeVec::new(
+            opts!(
+                "llm_ratelimit_remaining",
+                "Requests or tokens left in the provider's rate limit, from the headers of the last tracked response"
+            ),
+            &["provider", "model", "type"],
+        )
+        .unwrap();
+
+        let api_duration = HistogramVec::new(
+            histogram_opts!(
+                "llm_provider_api_duration_seconds",
+                "Time taken by upstream provider API calls",
+                vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]
+            ),
+            &["provider", "endpoint"],
+        )
+        .unwrap();
+
+        let usage_decreases = IntCounterVec::new(
+            opts!(
+                "llm_usage_decreases_total",
+                "Polls in which a month-to-date total went down, e.g. after a credit or refund"
+            ),
+            &["provider", "metric"],
+        )
+        .unwrap();
+
+        let label_values_dropped = IntCounterVec::new(
+            opts!(
+                "llm_label_values_dropped_total",
+                "Label values exported as other because the label reached its limit of distinct values"
+            ),
+            &["label"],
+        )
+        .unwrap();
+
+        let retries = IntCounterVec::new(
+            opts!(
+                "llm_provider_retries_total",
+                "Provider calls retried after a transient error, by endpoint and error type"
+            ),
+            &["provider", "endpoint", "error_type"],
+        )
+        .unwrap();
+
+        let throttled = IntCounterVec::new(
+            opts!(
+                "llm_provider_throttled_total",
+                "Provider calls rejected by the provider's rate limit, retried or not"
+            ),
+            &["provider"],
+        )
+        .unwrap();
+
+        let errors = IntCounterVec::new(
+            opts!(
+                "llm_provider_errors_total",
+                "Failed provider polls by error type"
+            ),
+            &["provider", "error_type"],
+        )
+        .unwrap();
+
+        let last_success = GaugeVec::new(
+            opts!(
+                "llm_provider_last_success_timestamp_seconds",
+                "Unix time of the last successful usage poll per provider"
+            ),
+            &["provider"],
+        )
+        .unwrap();
+
+        let build_info = IntGaugeVec::new(
+            opts!(
+                "llm_exporter_build_info",
+                "Exporter build information, always 1"
+            ),
+            &["version"],
+        )
+        .unwrap();
+        build_info
+            .with_label_values(&[env!("CARGO_PKG_VERSION")])
+            .set(1);
+
+        let poll_duration = Histogram::with_opts(histogram_opts!(
+            "llm_exporter_poll_duration_seconds",
+            "Time taken to poll every configured monitor once",
+            vec![1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]
+        ))
+        .unwrap();
+
+        let monitors_active = IntGauge::new(
+            "llm_exporter_monitors_active",
+            "Number of configured provider monitors",
+        )
+        .unwrap();
+
+        let leader = IntGauge::new(
+            "llm_exporter_leader",
+            "1 on the replica polling the providers, 0 on standbys",
+        )
+        .unwrap();
+
+        let pricing_catalog = IntGaugeVec::new(
+            opts!(
+                "llm_pricing_catalog_info",
+                "Pricing catalog in use, always 1"
+            ),
+            &["source", "version"],
+        )
+        .unwrap();
+
+        let pricing_catalog_updated = Gauge::new(
+            "llm_pricing_catalog_last_update_timestamp_seconds",
+            "Unix time the pricing catalog in use was loaded",
+        )
+        .unwrap();
+
+        let pricing_catalog_errors = IntCounter::new(
+            "llm_pricing_catalog_update_errors_total",
+            "Failed remote pricing catalog updates",
+        )
+        .unwrap();
+
+        let config_reload_successful = IntGauge::new(
+            "llm_exporter_config_last_reload_successful",
+            "1 when the last config reload succeeded, 0 when the previous config is still in use",
+        )
+        .unwrap();
+        config_reload_successful.set(1);
+
+        let config_reload_time = Gauge::new(
+            "llm_exporter_config_last_reload_success_timestamp_seconds",
+            "Unix time the config in use was loaded",
+        )
+        .unwrap();
+
+        cost.register(registry);
+        converted_cost.register(registry);
+        registry.register(Box::new(exchange_rate.clone())).unwrap();
+        request_cost.register(registry);
+        tokens.register(registry);
+        requests.register(registry);
+        images.register(registry);
+        transcription_minutes.register(registry);
+        speech_characters.register(registry);
+        registry.register(Box::new(credits.clone())).unwrap();
+        registry.register(Box::new(budget_limit.clone())).unwrap();
+        registry.register(Box::new(budget_spend.clone())).unwrap();
+        registry
+            .register(Box::new(budget_utilization.clone()))
+            .unwrap();
+        registry.register(Box::new(tool_cost.clone())).unwrap();
+        registry.register(Box::new(cost_forecast.clone())).unwrap();
+        registry.register(Box::new(billing_period.clone())).unwrap();
+        registry
+            .register(Box::new(cost_anomaly_score.clone()))
+            .unwrap();
+        registry.register(Box::new(cost_anomalies.clone())).unwrap();
+        if mode == MetricsMode::Counter {
+            registry
+                .register(Box::new(usage_decreases.clone()))
+                .unwrap();
+        }
+        registry
+            .register(Box::new(fine_tuning_cost.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(fine_tuning_tokens.clone()))
+            .unwrap();
+        registry.register(Box::new(billed_cost.clone())).unwrap();
+        registry.register(Box::new(tracked_cost.clone())).unwrap();
+        registry.register(Box::new(tracked_tokens.clone())).unwrap();
+        registry
+            .register(Box::new(tracked_requests.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(tracked_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(tracked_request_cost.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(ratelimit_remaining.clone()))
+            .unwrap();
+        registry.register(Box::new(api_duration.clone())).unwrap();
+        registry
+            .register(Box::new(label_values_dropped.clone()))
+            .unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry.register(Box::new(throttled.clone())).unwrap();
+        registry.register(Box::new(last_success.clone())).unwrap();
+        registry.register(Box::new(build_info)).unwrap();
+        registry.register(Box::new(poll_duration.clone())).unwrap();
+        registry
+            .register(Box::new(monitors_active.clone()))
+            .unwrap();
+        registry.register(Box::new(leader.clone())).unwrap();
+        registry
+            .register(Box::new(pricing_catalog.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pricing_catalog_updated.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pricing_catalog_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(config_reload_successful.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(config_reload_time.clone()))
+            .unwrap();
+
+        if let Some(rate) = reporting_currency.as_ref().and_then(|currency| {
+            currency
+                .get()
+                .map(|rate| (currency.currency().to_string(), rate))
+        }) {
+            exchange_rate.with_label_values(&[&rate.0]).set(rate.1);
+        }
+
+        let mut expiry = SeriesExpiry::default();
+        for usage in [
+            &cost,
+            &converted_cost,
+            &request_cost,
+            &tokens,
+            &requests,
+            &images,
+            &transcription_minutes,
+            &speech_characters,
+        ] {
+            usage.track(&mut expiry);
+        }
+        for gauge in [
+            &credits,
+            &tool_cost,
+            &cost_forecast,
+            &billing_period,
+            &cost_anomaly_score,
+            &billed_cost,
+            &ratelimit_remaining,
+            &last_success,
+        ] {
+            expiry.track(gauge);
+        }
+        expiry.track(&tracked_cost);
+        expiry.track(&tracked_tokens);
+        expiry.track(&tracked_requests);
+        expiry.track(&tracked_duration);
+        expiry.track(&tracked_request_cost);
+
+        Self {
+            cost,
+            converted_cost,
+            exchange_rate,
+            reporting_currency,
+            request_cost,
+            tokens,
+            requests,
+            images,
+            transcription_minutes,
+            speech_characters,
+            credits,
+            budget_limit,
+            budget_spend,
+            budget_utilization,
+            tool_cost,
+            cost_forecast,
+            billing_period,
+            cost_anomaly_score,
+            cost_anomalies,
+            fine_tuning_cost,
+            fine_tuning_tokens,
+            fine_tuning_jobs: Mutex::new(HashMap::new()),
+            billed_cost,
+            tracked_cost,
+            tracked_tokens,
+            tracked_requests,
+            tracked_duration,
+            tracked_request_cost,
+            exemplars: Exemplars::default(),
+            ratelimit_remaining,
+            api_duration,
+            errors,
+            retries,
+            throttled,
+            last_success,
+            poll_duration,
+            monitors_active,
+            leader,
+            pricing_catalog,
+            pricing_catalog_updated,
+            pricing_catalog_errors,
+            config_reload_successful,
+            config_reload_time,
+            mode,
+            last_totals: Mutex::new(HashMap::new()),
+            usage_decreases,
+            cardinality: CardinalityGuard::new(label_values_dropped),
+            expiry,
+        }
+    }
+
+    /// Caps the distinct values of each label, see [`LabelLimits`].
+    pub fn label_limits(mut self, limits: LabelLimits) -> Self {
+        self.cardinality.set_limits(limits);
+        self
+    }
+
+    /// Removes series that go unupdated for too many poll cycles, see [`SeriesTtls`].
+    pub fn series_ttls(mut self, ttls: SeriesTtls) -> Self {
+        self.expiry.set_ttls(ttls);
+        self
+    }
+
+    /// Ends a poll cycle, removing the series that have gone their TTL without an update along
+    /// with the totals their counters were advanced from, so a series that comes back starts
+    /// over from its first total.
+    pub fn expire_stale_series(&self) {
+        let removed = self.expiry.expire();
+        if removed.is_empty() || matches!(self.cost, UsageVec::Gauge(_)) {
+            return;
+        }
+        let names: HashMap<String, &str> = [
+            (&self.cost, "cost"),
+            (&self.request_cost, "request_cost"),
+            (&self.tokens, "tokens"),
+            (&self.requests, "requests"),
+            (&self.transcription_minutes, "transcription_minutes"),
+            (&self.speech_characters, "speech_characters"),
+            (&self.images, "images"),
+        ]
+        .into_iter()
+        .map(|(metric, name)| (metric.name(), name))
+        .collect();
+        let mut last_totals = self.last_totals.lock().unwrap();
+        for (metric, labels) in removed {
+            if let Some(name) = names.get(&metric) {
+                let key: Vec<String> = std::iter::once(name.to_string()).chain(labels).collect();
+                last_totals.remove(&key);
+            }
+        }
+    }
+
+    /// Trace IDs of tracked calls, exported with the OpenMetrics format.
+    pub fn exemplars(&self) -> &Exemplars {
+        &self.exemplars
+    }
+
+    pub fn mode(&self) -> MetricsMode {
+        self.mode
+    }
+
+    /// Whether the label policy exports `label`, with at least some of its values.
+    pub fn exports_label(&self, label: &str) -> bool {
+        self.cardinality.exports(label)
+    }
+
+    /// Exports only the labels and values the policy allows.
+    pub fn label_policy(mut self, policy: LabelPolicy) -> Self {
+        self.cardinality.set_policy(policy);
+        self
+    }
+
+    /// Records a cumulative total of the billing period starting at `period_start`: sets the
+    /// gauge, or advances the counter as [`SeriesTotal::advance`] decides. Returns the increment,
+    /// which for a gauge is the total. When not `advancing`, a counter only remembers the total
+    /// as its starting point.
+    fn record(
+        &self,
+        metric: &UsageVec,
+        name: &str,
+        labels: &[&str],
+        total: f64,
+        period_start: DateTime<Utc>,
+        advancing: bool,
+    ) -> f64 {
+        metric.touch(&self.expiry, labels);
+        match metric {
+            UsageVec::Gauge(gauge) => {
+                gauge.with_label_values(labels).set(total);
+                total
+            }
+            UsageVec::Counter(counter) => {
+                let key: Vec<String> = std::iter::once(name)
+                    .chain(labels.iter().copied())
+                    .map(str::to_string)
+                    .collect();
+                let mut last_totals = self.last_totals.lock().unwrap();
+                let (series, delta, decrease) =
+                    SeriesTotal::advance(last_totals.get(&key), period_start, total);
+                last_totals.insert(key, series);
+                if !advancing {
+                    return 0.0;
+                }
+                if decrease > 0.0 {
+                    debug!(
+                        metric = name,
+                        labels = ?labels,
+                        decrease,
+                        "month-to-date total decreased, holding back later increases"
+                    );
+                    self.usage_decreases
+                        .with_label_values(&[labels[0], name])
+                        .inc();
+                }
+                counter.with_label_values(labels).inc_by(delta);
+                delta
+            }
+        }
+    }
+
+    /// Records cost under `llm_cost` in USD and the reporting currency. Counters convert
+    /// each increment at the rate current when it is recorded, so rate changes never make
+    /// them go backwards.
+    fn record_converted_cost(&self, labels: &[&str], total: f64, increment: f64) {
+        let mut currencies = vec![("USD", 1.0)];
+        if let Some(currency) = &self.reporting_currency {
+            if let Some(rate) = currency.get() {
+                currencies.push((currency.currency(), rate));
+            }
+        }
+        for (currency, rate) in currencies {
+            let mut series = vec![labels[0], labels[1], currency];
+            series.extend_from_slice(&labels[2..]);
+            self.converted_cost.touch(&self.expiry, &series);
+            match &self.converted_cost {
+                UsageVec::Gauge(gauge) => gauge.with_label_values(&series).set(total * rate),
+                UsageVec::Counter(counter) => {
+                    counter.with_label_values(&series).inc_by(increment * rate)
+                }
+            }
+        }
+    }
+
+    /// The usage counters, empty in gauge mode.
+    fn usage_counters(&self) -> impl Iterator<Item = &CounterVec> {
+        [
+            &self.cost,
+            &self.converted_cost,
+            &self.request_cost,
+            &self.tokens,
+            &self.requests,
+            &self.images,
+            &self.transcription_minutes,
+            &self.speech_characters,
+        ]
+        .into_iter()
+        .filter_map(|metric| match metric {
+            UsageVec::Counter(counter) => Some(counter),
+            UsageVec::Gauge(_) => None,
+        })
+    }
+
+    /// Current state of the usage counters, or `None` in gauge mode.
+    pub fn counter_state(&self) -> Option<CounterState> {
+        if let UsageVec::Gauge(_) = self.cost {
+            return None;
+        }
+        let counters = self
+            .usage_counters()
+            .flat_map(|counter| counter.collect())
+            .flat_map(|family| {
+                let name = family.get_name().to_string();
+                family
+                    .get_metric()
+                    .iter()
+                    .map(|metric| CounterSeries {
+                        name: name.clone(),
+                        labels: metric
+                            .get_label()
+                            .iter()
+                            .map(|pair| (pair.get_name().to_string(), pair.get_value().to_string()))
+                            .collect(),
+                        value: metric.get_counter().get_value(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let last_totals = self
+            .last_totals
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, total)| (key.clone(), total.clone()))
+            .collect();
+        Some(CounterState {
+            counters,
+            last_totals,
+        })
+    }
+
+    /// Continues the usage counters from a saved state. Series whose labels no longer match
+    /// the metric are skipped.
+    pub fn restore_counter_state(&self, state: CounterState) {
+        for series in state.counters {
+            let Some(counter) = self.usage_counters().find(|counter| {
+                counter
+                    .desc()
+                    .first()
+                    .is_some_and(|desc| desc.fq_name == series.name)
+            }) else {
+                continue;
+            };
+            let labels: HashMap<&str, &str> = series
+                .labels
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+                .collect();
+            match counter.get_metric_with(&labels) {
+                Ok(counter) => counter.inc_by(series.value),
+                Err(e) => {
+                    tracing::warn!(metric = series.name, error = %e, "skipping saved counter")
+                }
+            }
+        }
+        self.last_totals.lock().unwrap().extend(state.last_totals);
+    }
+
+    pub fn update(&self, provider: &str, model: &str, usage: &LLMUsage) {
+        self.record_usage(provider, model, usage, billing_period_start(provider), true);
+    }
+
+    /// Updates the series of every record a provider reported, with the label values the
+    /// [`LabelPolicy`] removes exported empty and those beyond their limit as `other`.
+    pub fn update_all(&self, provider: &str, usage: &[LLMUsage]) {
+        for model_usage in self.cardinality.limit_usage(usage) {
+            self.update(provider, &model_usage.model, &model_usage);
+        }
+    }
+
+    /// Resumes counters from totals polled at `polled_at` before a restart, so the first poll
+    /// afterwards only counts what changed since then. Gauges are left for the first poll to
+    /// set.
+    pub fn restore(&self, provider: &str, model: &str, usage: &LLMUsage, polled_at: DateTime<Utc>) {
+        if let UsageVec::Counter(_) = self.cost {
+            let period_start = billing_period_start_at(provider, polled_at);
+            self.record_usage(provider, model, usage, period_start, false);
+        }
+    }
+
+    /// [`Self::restore`] for every record of a provider, limited like [`Self::update_all`].
+    pub fn restore_all(&self, provider: &str, usage: &[LLMUsage], polled_at: DateTime<Utc>) {
+        for model_usage in self.cardinality.limit_usage(usage) {
+            self.restore(provider, &model_usage.model, &model_usage, polled_at);
+        }
+    }
+
+    fn record_usage(
+        &self,
+        provider: &str,
+        model: &str,
+        usage: &LLMUsage,
+        period_start: DateTime<Utc>,
+        advancing: bool,
+    ) {
+        let extra: Vec<&str> = USAGE_LABELS
+            .iter()
+            .map(|label| usage.labels.get(label).map(String::as_str).unwrap_or(""))
+            .collect();
+
+        let cost_labels = with_usage_labels(&[provider, model], &extra);
+        let increment = self.record(
+            &self.cost,
+            "cost",
+            &cost_labels,
+            usage.cost_usd,
+            period_start,
+            advancing,
+        );
+        self.record_converted_cost(&cost_labels, usage.cost_usd, increment);
+        self.record(
+            &self.request_cost,
+            "request_cost",
+            &with_usage_labels(&[provider, model], &extra),
+            usage.request_cost_usd,
+            period_start,
+            advancing,
+        );
+        for (token_type, count) in usage.token_counts() {
+            self.record(
+                &self.tokens,
+                "tokens",
+                &with_usage_labels(&[provider, model, token_type], &extra),
+                count as f64,
+                period_start,
+                advancing,
+            );
+        }
+        self.record(
+            &self.requests,
+            "requests",
+            &with_usage_labels(&[provider, model], &extra),
+            usage.request_count as f64,
+            period_start,
+            advancing,
+        );
+        self.record(
+            &self.transcription_minutes,
+            "transcription_minutes",
+            &with_usage_labels(&[provider, model], &extra),
+            usage.transcription_minutes,
+            period_start,
+            advancing,
+        );
+        self.record(
+            &self.speech_characters,
+            "speech_characters",
+            &with_usage_labels(&[provider, model], &extra),
+            usage.speech_characters as f64,
+            period_start,
+            advancing,
+        );
+        for images in &usage.images_generated {
+            self.record(
+                &self.images,
+                "images",
+                &with_usage_labels(&[provider, model, &images.size, &images.quality], &extra),
+                images.count as f64,
+                period_start,
+                advancing,
+            );
+        }
+    }
+
+    pub fn set_credit_balance(&self, provider: &str, balance: f64) {
+        self.expiry.touch(&self.credits, &[provider]);
+        self.credits.with_label_values(&[provider]).set(balance);
+    }
+
+    pub fn set_budget(&self, budget: &str, limit: f64, spend: f64) {
+        self.budget_limit.with_label_values(&[budget]).set(limit);
+        self.budget_spend.with_label_values(&[budget]).set(spend);
+        self.budget_utilization
+            .with_label_values(&[budget])
+            .set(spend / limit);
+    }
+
+    /// Names of the budgets exported, in order.
+    pub fn budgets(&self) -> Vec<String> {
+        let mut budgets: Vec<String> = self
+            .budget_limit
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .flat_map(|metric| metric.get_label())
+            .map(|label| label.get_value().to_string())
+            .collect();
+        budgets.sort();
+        budgets
+    }
+
+    /// Removes the series of a budget that is no longer configured.
+    pub fn remove_budget(&self, budget: &str) {
+        let _ = self.budget_limit.remove_label_values(&[budget]);
+        let _ = self.budget_spend.remove_label_values(&[budget]);
+        let _ = self.budget_utilization.remove_label_values(&[budget]);
+    }
+
+    pub fn set_tool_cost(&self, provider: &str, tool: &str, cost: f64) {
+        self.expiry.touch(&self.tool_cost, &[provider, tool]);
+        self.tool_cost
+            .with_label_values(&[provider, tool])
+            .set(cost);
+    }
+
+    pub fn set_cost_forecast(&self, provider: &str, forecast: f64) {
+        self.expiry.touch(&self.cost_forecast, &[provider]);
+        self.cost_forecast
+            .with_label_values(&[provider])
+            .set(forecast);
+    }
+
+    /// Sets the start of the provider's billing period, logging when a new period begins.
+    pub fn set_billing_period(&self, provider: &str) {
+        let start = billing_period_start(provider);
+        self.expiry.touch(&self.billing_period, &[provider]);
+        let gauge = self.billing_period.with_label_values(&[provider]);
+        let previous = gauge.get();
+        if previous != 0.0 && previous != start.timestamp() as f64 {
+            info!(provider, period_start = %start, "billing period rolled over");
+        }
+        gauge.set(start.timestamp() as f64);
+    }
+
+    pub fn set_cost_anomaly_score(&self, provider: &str, model: &str, score: f64) {
+        self.expiry
+            .touch(&self.cost_anomaly_score, &[provider, model]);
+        self.cost_anomaly_score
+            .with_label_values(&[provider, model])
+            .set(score);
+    }
+
+    pub fn record_cost_anomaly(&self, provider: &str, model: &str) {
+        self.cost_anomalies
+            .with_label_values(&[provider, model])
+            .inc();
+    }
+
+    /// Replaces the provider's fine-tuning jobs with the ones just polled.
+    pub fn set_fine_tuning_jobs(&self, provider: &str, jobs: &[FineTuningJob]) {
+        let current: Vec<[String; 2]> = jobs
+            .iter()
+            .map(|job| [job.model.clone(), job.job_id.clone()])
+            .collect();
+        let mut reported = self.fine_tuning_jobs.lock().unwrap();
+        for [model, job_id] in reported.remove(provider).unwrap_or_default() {
+            if !current.contains(&[model.clone(), job_id.clone()]) {
+                let labels = [provider, model.as_str(), job_id.as_str()];
+                let _ = self.fine_tuning_cost.remove_label_values(&labels);
+                let _ = self.fine_tuning_tokens.remove_label_values(&labels);
+            }
+        }
+        for job in jobs {
+            let labels = [provider, job.model.as_str(), job.job_id.as_str()];
+            self.fine_tuning_cost
+                .with_label_values(&labels)
+                .set(job.cost_usd);
+            self.fine_tuning_tokens
+                .with_label_values(&labels)
+                .set(job.trained_tokens as f64);
+        }
+        reported.insert(provider.to_string(), current);
+    }
+
+    /// Sets the month-to-date cost billed for a usage type in an AWS account.
+    pub fn set_billed_cost(&self, provider: &str, usage_type: &str, account_id: &str, amount: f64) {
+        let labels = [provider, usage_type, account_id];
+        self.expiry.touch(&self.billed_cost, &labels);
+        self.billed_cost.with_label_values(&labels).set(amount);
+    }
+
+    /// Sets the requests or tokens (`limit`) left in a provider's rate limit for `model`.
+    pub fn set_ratelimit_remaining(
+        &self,
+        provider: &str,
+        model: &str,
+        limit: &str,
+        remaining: f64,
+    ) {
+        let model = &*self.cardinality.value("model", model);
+        self.expiry
+            .touch(&self.ratelimit_remaining, &[provider, model, limit]);
+        self.ratelimit_remaining
+            .with_label_values(&[provider, model, limit])
+            .set(remaining);
+    }
+
+    /// Counts an LLM call tracked in process, with its usage when it succeeded, the cost and
+    /// tokens split across the caller's cost centers.
+    pub fn record_tracked_call(
+        &self,
+        provider: &str,
+        model: &str,
+        caller: &Caller,
+        usage: Option<&LLMUsage>,
+        estimated: bool,
+        duration: Duration,
+    ) {
+        let status = if usage.is_some() { "success" } else { "error" };
+        let model = &*self.cardinality.value("model", model);
+        let team = &*self.cardinality.value("team", &caller.team);
+        let user = &*self.cardinality.value("user", &caller.user);
+        let namespace = &*self.cardinality.value("namespace", &caller.namespace);
+        let deployment = &*self.cardinality.value("deployment", &caller.deployment);
+        let requests = [provider, model, team, user, namespace, deployment, status];
+        self.expiry.touch(&self.tracked_requests, &requests);
+        self.tracked_requests.with_label_values(&requests).inc();
+        self.expiry
+            .touch(&self.tracked_duration, &[provider, model]);
+        self.tracked_duration
+            .with_label_values(&[provider, model])
+            .observe(duration.as_secs_f64());
+        let series = [("provider", provider), ("model", model)];
+        if !caller.trace_id.is_empty() {
+            self.exemplars.observe(
+                "llm_tracked_request_duration_seconds",
+                &series,
+                TRACKED_DURATION_BUCKETS,
+                duration.as_secs_f64(),
+                &caller.trace_id,
+            );
+        }
+        let Some(usage) = usage else {
+            return;
+        };
+        self.expiry
+            .touch(&self.tracked_request_cost, &[provider, model]);
+        self.tracked_request_cost
+            .with_label_values(&[provider, model])
+            .observe(usage.cost_usd);
+        if !caller.trace_id.is_empty() {
+            self.exemplars.observe(
+                "llm_tracked_request_cost_usd",
+                &series,
+                TRACKED_REQUEST_COST_BUCKETS,
+                usage.cost_usd,
+                &caller.trace_id,
+            );
+        }
+        let estimated = if estimated { "true" } else { "false" };
+        let unallocated = [(String::new(), 1.0)];
+        let cost_centers = match caller.cost_centers.as_slice() {
+            [] => &unallocated[..],
+            shares => shares,
+        };
+        for (cost_center, share) in cost_centers {
+            let cost_center = &*self.cardinality.value("cost_center", cost_center);
+            let cost = [
+                provider,
+                model,
+                team,
+                cost_center,
+                user,
+                namespace,
+                deployment,
+                estimated,
+            ];
+            self.expiry.touch(&self.tracked_cost, &cost);
+            self.tracked_cost
+                .with_label_values(&cost)
+                .inc_by(usage.cost_usd * share);
+            for (token_type, count) in usage.token_counts() {
+                if count > 0 {
+                    let tokens = [
+                        provider,
+                        model,
+                        team,
+                        cost_center,
+                        user,
+                        namespace,
+                        deployment,
+                        token_type,
+                        estimated,
+                    ];
+                    self.expiry.touch(&self.tracked_tokens, &tokens);
+                    self.tracked_tokens
+                        .with_label_values(&tokens)
+                        .inc_by(count as f64 * share);
+                }
+            }
+        }
+    }
+
+    pub fn observe_api_duration(&self, provider: &str, endpoint: &str, duration: Duration) {
+        self.api_duration
+            .with_label_values(&[provider, endpoint])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn record_error(&self, provider: &str, error: &MonitorError) {
+        self.errors
+            .with_label_values(&[provider, error.error_type()])
+            .inc();
+    }
+
+    pub fn record_throttled(&self, provider: &str) {
+        self.throttled.with_label_values(&[provider]).inc();
+    }
+
+    pub fn record_retry(&self, provider: &str, endpoint: &str, error: &MonitorError) {
+        self.retries
+            .with_label_values(&[provider, endpoint, error.error_type()])
+            .inc();
+    }
+
+    pub fn record_success(&self, provider: &str) {
+        self.expiry.touch(&self.last_success, &[provider]);
+        self.last_success
+            .with_label_values(&[provider])
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    pub fn observe_poll_duration(&self, duration: Duration) {
+        self.poll_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn set_monitors_active(&self, count: usize) {
+        self.monitors_active.set(count as i64);
+    }
+
+    pub fn set_leader(&self, leader: bool) {
+        self.leader.set(i64::from(leader));
+    }
+
+    /// Records the catalog now in use, replacing the previous one.
+    pub fn set_pricing_catalog(&self, source: &str, version: &str) {
+        self.pricing_catalog.reset();
+        self.pricing_catalog
+            .with_label_values(&[source, version])
+            .set(1);
+        self.pricing_catalog_updated
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+
+    pub fn set_exchange_rate(&self, currency: &str, rate: f64) {
+        self.exchange_rate.with_label_values(&[currency]).set(rate);
+    }
+
+    pub fn record_pricing_catalog_error(&self) {
+        self.pricing_catalog_errors.inc();
+    }
+
+    /// Records a load of the config file, at startup or reloaded.
+    pub fn record_config_reload(&self, success: bool) {
+        self.config_reload_successful.set(success as i64);
+        if success {
+            self.config_reload_time
+                .set(chrono::Utc::now().timestamp() as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::USAGE_LABELS;
+    use chrono::TimeZone;
+
+    fn period(month: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, month, 1, 0, 0, 0).unwrap()
+    }
+
+    /// Advances one series through the totals a provider reported and returns the increments.
+    fn increments(totals: &[(u32, f64)]) -> Vec<f64> {
+        let mut series = None;
+        totals
+            .iter()
+            .map(|&(month, total)| {
+                let (next, increment, _) =
+                    SeriesTotal::advance(series.as_ref(), period(month), total);
+                series = Some(next);
+                increment
+            })
+            .collect()
+    }
+
+    fn labels() -> Vec<&'static str> {
+        let mut labels = vec!["openai", "gpt-4o"];
+        labels.resize(2 + USAGE_LABELS.len(), "");
+        labels
+    }
+
+    fn cost_total(metrics: &LLMMetrics) -> f64 {
+        match &metrics.cost {
+            UsageVec::Counter(counter) => counter.with_label_values(&labels()).get(),
+            UsageVec::Gauge(_) => unreachable!("counter mode"),
+        }
+    }
+
+    fn record_cost(metrics: &LLMMetrics, total: f64) -> f64 {
+        metrics.record(
+            &metrics.cost,
+            "llm_cost_usd",
+            &labels(),
+            total,
+            period(10),
+            true,
+        )
+    }
+
+    #[test]
+    fn credit_is_held_back_until_usage_catches_up() {
+        // a credit of 3 takes the total from 10 to 7; the next 3 of usage make up for it
+        assert_eq!(
+            increments(&[(10, 10.0), (10, 7.0), (10, 9.0), (10, 14.0), (10, 15.0)]),
+            [10.0, 0.0, 0.0, 4.0, 1.0]
+        );
+        let (series, increment, decrease) = SeriesTotal::advance(
+            Some(&SeriesTotal {
+                period_start: period(10),
+                total: 10.0,
+                pending_decrease: 0.0,
+            }),
+            period(10),
+            7.0,
+        );
+        assert_eq!((increment, decrease), (0.0, 3.0));
+        assert_eq!(series.pending_decrease, 3.0);
+    }
+
+    #[test]
+    fn new_billing_period_counts_in_full() {
+        // the pending decrease of the old period doesn't carry over either
+        assert_eq!(
+            increments(&[(9, 50.0), (9, 40.0), (10, 5.0), (10, 8.0)]),
+            [50.0, 0.0, 5.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn counters_resume_from_saved_state() {
+        let metrics = LLMMetrics::new(&Registry::new(), MetricsMode::Counter, None);
+        assert_eq!(record_cost(&metrics, 10.0), 10.0);
+        assert_eq!(record_cost(&metrics, 8.0), 0.0);
+        let state = serde_json::to_string(&metrics.counter_state().unwrap()).unwrap();
+
+        let restarted = LLMMetrics::new(&Registry::new(), MetricsMode::Counter, None);
+        restarted.restore_counter_state(serde_json::from_str(&state).unwrap());
+        assert_eq!(cost_total(&restarted), 10.0);
+        // the month isn't counted again, and the credit is still held back
+        assert_eq!(record_cost(&restarted, 12.0), 2.0);
+        assert_eq!(cost_total(&restarted), 12.0);
+    }
+
+    #[test]
+    fn gauges_keep_no_state() {
+        let metrics = LLMMetrics::new(&Registry::new(), MetricsMode::Gauge, None);
+        assert_eq!(record_cost(&metrics, 10.0), 10.0);
+        assert_eq!(record_cost(&metrics, 8.0), 8.0);
+        assert!(metrics.counter_state().is_none());
+    }
+}
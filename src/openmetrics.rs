@@ -0,0 +1,440 @@
+use prometheus::proto::{LabelPair, Metric, MetricFamily, MetricType};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Content type of the OpenMetrics text format.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Units a metric name can end in, declared with `# UNIT`.
+const UNITS: &[&str] = &["seconds", "bytes", "usd", "ratio", "minutes"];
+
+/// Whether an `Accept` header prefers the OpenMetrics text format, as Prometheus asks for it
+/// first when it supports it.
+pub fn is_accepted(accept: Option<&str>) -> bool {
+    accept.is_some_and(|accept| {
+        accept
+            .split(',')
+            .filter_map(|range| range.split(';').next())
+            .any(|media_type| media_type.trim() == "application/openmetrics-text")
+    })
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+/// A sample of an observation linked to the trace it was made in.
+#[derive(Debug, Clone)]
+pub struct Exemplar {
+    pub trace_id: String,
+    pub value: f64,
+    /// Unix time of the observation in seconds.
+    pub timestamp: f64,
+}
+
+/// Exemplars by the label pairs of a histogram's own labels, sorted by name, and then by
+/// bucket. The labels don't include the registry's constant labels, which are added when it
+/// gathers.
+type SeriesExemplars = HashMap<Vec<(String, String)>, HashMap<u64, Exemplar>>;
+
+/// The last exemplar of every histogram bucket, keyed by the bucket's upper bound, so the
+/// OpenMetrics format can link a bucket to a trace.
+#[derive(Debug, Default)]
+pub struct Exemplars {
+    families: Mutex<HashMap<String, SeriesExemplars>>,
+}
+
+impl Exemplars {
+    /// Keeps `value` observed in the histogram `family` with `buckets` as the exemplar of the
+    /// bucket it falls in.
+    pub fn observe(
+        &self,
+        family: &str,
+        labels: &[(&str, &str)],
+        buckets: &[f64],
+        value: f64,
+        trace_id: &str,
+    ) {
+        let bound = buckets
+            .iter()
+            .copied()
+            .find(|bound| value <= *bound)
+            .unwrap_or(f64::INFINITY);
+        let mut labels: Vec<(String, String)> = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        labels.sort();
+        self.families
+            .lock()
+            .unwrap()
+            .entry(family.to_string())
+            .or_default()
+            .entry(labels)
+            .or_default()
+            .insert(
+                bound.to_bits(),
+                Exemplar {
+                    trace_id: trace_id.to_string(),
+                    value,
+                    timestamp: now(),
+                },
+            );
+    }
+
+    /// The exemplars of a gathered series, matched on the histogram's own labels only, so
+    /// constant labels such as `EXTRA_LABELS` are left out.
+    fn of_series(&self, family: &str, labels: &[LabelPair]) -> HashMap<u64, Exemplar> {
+        let families = self.families.lock().unwrap();
+        let Some(series) = families.get(family) else {
+            return HashMap::new();
+        };
+        let Some(names) = series.keys().next() else {
+            return HashMap::new();
+        };
+        let key: Vec<(String, String)> = labels
+            .iter()
+            .filter(|label| names.iter().any(|(name, _)| name == label.get_name()))
+            .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+            .collect();
+        series.get(&key).cloned().unwrap_or_default()
+    }
+}
+
+/// When a cumulative series was created, and its count at the last scrape to tell a reset.
+struct Created {
+    at: f64,
+    count: f64,
+}
+
+/// Encodes metric families in the OpenMetrics text format, with the `_created` series of
+/// counters, histograms and summaries, a `# UNIT` for names ending in a unit and the
+/// [`Exemplars`] of histogram buckets.
+///
+/// The registry doesn't know when a series was created, so the encoder keeps track: a series
+/// first exposed, or found reset, counts as created at the previous scrape, the last time it
+/// was known not to exist, or when the encoder was created for the first scrape.
+pub struct OpenMetricsEncoder {
+    scrapes: Mutex<Scrapes>,
+}
+
+/// The series of the last scrape, keyed by family name and label names and values.
+struct Scrapes {
+    last: f64,
+    series: HashMap<(String, Vec<String>), Created>,
+}
+
+impl Default for OpenMetricsEncoder {
+    fn default() -> Self {
+        Self {
+            scrapes: Mutex::new(Scrapes {
+                last: now(),
+                series: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl OpenMetricsEncoder {
+    pub fn encode(&self, families: &[MetricFamily], exemplars: &Exemplars) -> String {
+        let mut scrapes = self.scrapes.lock().unwrap();
+        let mut seen = HashMap::with_capacity(scrapes.series.len());
+        let mut out = String::new();
+        for family in families {
+            let kind = family.get_field_type();
+            // gauges named `_info` are info metrics, which OpenMetrics has a type for
+            let info = family
+                .get_name()
+                .strip_suffix("_info")
+                .filter(|_| kind == MetricType::GAUGE);
+            let name = match kind {
+                MetricType::COUNTER => family
+                    .get_name()
+                    .strip_suffix("_total")
+                    .unwrap_or(family.get_name()),
+                _ => info.unwrap_or(family.get_name()),
+            };
+            let type_name = match info {
+                Some(_) => "info",
+                None => type_name(kind),
+            };
+            let _ = writeln!(out, "# TYPE {} {}", name, type_name);
+            if let Some(unit) = UNITS
+                .iter()
+                .find(|unit| name.ends_with(&format!("_{}", unit)))
+            {
+                let _ = writeln!(out, "# UNIT {} {}", name, unit);
+            }
+            if !family.get_help().is_empty() {
+                let _ = writeln!(out, "# HELP {} {}", name, escape(family.get_help()));
+            }
+            for metric in family.get_metric() {
+                let count = match kind {
+                    MetricType::COUNTER => {
+                        sample(
+                            &mut out,
+                            name,
+                            "_total",
+                            metric,
+                            None,
+                            metric.get_counter().get_value(),
+                        );
+                        Some(metric.get_counter().get_value())
+                    }
+                    MetricType::GAUGE => {
+                        sample(
+                            &mut out,
+                            name,
+                            if info.is_some() { "_info" } else { "" },
+                            metric,
+                            None,
+                            metric.get_gauge().get_value(),
+                        );
+                        None
+                    }
+                    MetricType::HISTOGRAM => {
+                        let histogram = metric.get_histogram();
+                        let count = histogram.get_sample_count() as f64;
+                        let bucket_exemplars = exemplars.of_series(name, metric.get_label());
+                        let mut infinite = false;
+                        let mut bucket_sample = |bound: f64, count: f64| {
+                            sample_with_exemplar(
+                                &mut out,
+                                name,
+                                "_bucket",
+                                metric,
+                                Some(("le", &float(bound))),
+                                count,
+                                bucket_exemplars.get(&bound.to_bits()),
+                            );
+                        };
+                        for bucket in histogram.get_bucket() {
+                            let bound = bucket.get_upper_bound();
+                            infinite |= bound == f64::INFINITY;
+                            bucket_sample(bound, bucket.get_cumulative_count() as f64);
+                        }
+                        if !infinite {
+                            bucket_sample(f64::INFINITY, count);
+                        }
+                        sample(
+                            &mut out,
+                            name,
+                            "_sum",
+                            metric,
+                            None,
+                            histogram.get_sample_sum(),
+                        );
+                        sample(&mut out, name, "_count", metric, None, count);
+                        Some(count)
+                    }
+                    MetricType::SUMMARY => {
+                        let summary = metric.get_summary();
+                        let count = summary.get_sample_count() as f64;
+                        for quantile in summary.get_quantile() {
+                            sample(
+                                &mut out,
+                                name,
+                                "",
+                                metric,
+                                Some(("quantile", &float(quantile.get_quantile()))),
+                                quantile.get_value(),
+                            );
+                        }
+                        sample(
+                            &mut out,
+                            name,
+                            "_sum",
+                            metric,
+                            None,
+                            summary.get_sample_sum(),
+                        );
+                        sample(&mut out, name, "_count", metric, None, count);
+                        Some(count)
+                    }
+                    MetricType::UNTYPED => {
+                        sample(
+                            &mut out,
+                            name,
+                            "",
+                            metric,
+                            None,
+                            metric.get_untyped().get_value(),
+                        );
+                        None
+                    }
+                };
+                let Some(count) = count else {
+                    continue;
+                };
+                let key = (
+                    name.to_string(),
+                    metric
+                        .get_label()
+                        .iter()
+                        .flat_map(|label| [label.get_name(), label.get_value()])
+                        .map(str::to_string)
+                        .collect(),
+                );
+                let at = match scrapes.series.remove(&key) {
+                    Some(series) if series.count <= count => series.at,
+                    _ => scrapes.last,
+                };
+                sample(&mut out, name, "_created", metric, None, at);
+                seen.insert(key, Created { at, count });
+            }
+        }
+        out.push_str("# EOF\n");
+        // series gone from this scrape are created anew if they come back
+        scrapes.series = seen;
+        scrapes.last = now();
+        out
+    }
+}
+
+fn type_name(kind: MetricType) -> &'static str {
+    match kind {
+        MetricType::COUNTER => "counter",
+        MetricType::GAUGE => "gauge",
+        MetricType::HISTOGRAM => "histogram",
+        MetricType::SUMMARY => "summary",
+        MetricType::UNTYPED => "unknown",
+    }
+}
+
+/// A float as OpenMetrics writes it, e.g. `+Inf`, and bucket bounds with a decimal point.
+fn float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{:.1}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(out: &mut String, labels: &[LabelPair], extra: Option<(&str, &str)>) {
+    let mut pairs = labels
+        .iter()
+        .map(|label| (label.get_name(), label.get_value()))
+        .chain(extra)
+        .peekable();
+    if pairs.peek().is_none() {
+        return;
+    }
+    out.push('{');
+    for (index, (name, value)) in pairs.enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        let _ = write!(out, "{}=\"{}\"", name, escape(value));
+    }
+    out.push('}');
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    metric: &Metric,
+    extra: Option<(&str, &str)>,
+    value: f64,
+) {
+    sample_with_exemplar(out, name, suffix, metric, extra, value, None);
+}
+
+fn sample_with_exemplar(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    metric: &Metric,
+    extra: Option<(&str, &str)>,
+    value: f64,
+    exemplar: Option<&Exemplar>,
+) {
+    out.push_str(name);
+    out.push_str(suffix);
+    labels(out, metric.get_label(), extra);
+    out.push(' ');
+    out.push_str(&float(value));
+    if metric.get_timestamp_ms() != 0 {
+        let _ = write!(out, " {}", metric.get_timestamp_ms() as f64 / 1000.0);
+    }
+    if let Some(exemplar) = exemplar {
+        let _ = write!(
+            out,
+            " # {{trace_id=\"{}\"}} {} {}",
+            escape(&exemplar.trace_id),
+            float(exemplar.value),
+            exemplar.timestamp
+        );
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{histogram_opts, HistogramVec, Registry};
+
+    #[test]
+    fn exemplars_match_series_with_constant_labels() {
+        let registry = Registry::new_custom(
+            None,
+            Some(HashMap::from([("env".to_string(), "prod".to_string())])),
+        )
+        .unwrap();
+        let buckets = [0.5, 1.0];
+        let histogram = HistogramVec::new(
+            histogram_opts!(
+                "llm_tracked_request_duration_seconds",
+                "help",
+                buckets.to_vec()
+            ),
+            &["provider", "model"],
+        )
+        .unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        histogram
+            .with_label_values(&["openai", "gpt-4o"])
+            .observe(0.7);
+        let exemplars = Exemplars::default();
+        exemplars.observe(
+            "llm_tracked_request_duration_seconds",
+            &[("provider", "openai"), ("model", "gpt-4o")],
+            &buckets,
+            0.7,
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+        );
+
+        let out = OpenMetricsEncoder::default().encode(&registry.gather(), &exemplars);
+        let bucket = out
+            .lines()
+            .find(|line| line.contains("le=\"1.0\""))
+            .unwrap();
+        assert!(bucket.contains("env=\"prod\""), "{}", bucket);
+        assert!(
+            bucket.contains(" # {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} 0.7 "),
+            "{}",
+            bucket
+        );
+        let other = out
+            .lines()
+            .find(|line| line.contains("le=\"0.5\""))
+            .unwrap();
+        assert!(!other.contains('#'), "{}", other);
+    }
+}
@@ -0,0 +1,58 @@
+use clap::ValueEnum;
+
+/// How commands print their results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns, for reading.
+    #[default]
+    Table,
+    /// Comma-separated values with a header line, for spreadsheets.
+    Csv,
+    /// One JSON document, for scripts.
+    Json,
+}
+
+/// Lays out rows in columns as wide as their longest cell, under a header.
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|title| title.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut output = line(header.to_vec());
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
+
+/// Writes rows as CSV under a header, quoting the cells that need it.
+pub fn csv(header: &[&str], rows: &[Vec<String>]) -> String {
+    let line = |cells: Vec<&str>| {
+        let quoted: Vec<String> = cells
+            .iter()
+            .map(|cell| {
+                if cell.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.to_string()
+                }
+            })
+            .collect();
+        format!("{}\n", quoted.join(","))
+    };
+    let mut output = line(header.to_vec());
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
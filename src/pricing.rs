@@ -0,0 +1,632 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time;
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::metrics::LLMMetrics;
+use crate::providers::LLMUsage;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+/// Prices shipped with the exporter, see `pricing.yaml` for the format.
+const BUILTIN_PRICING: &str = include_str!("../pricing.yaml");
+
+/// Price of one model. Token prices are USD per 1M tokens, request fees USD per 1K requests.
+/// Embedding tokens are billed at the `input` price.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// Price of input tokens served from the prompt cache, `input` when not discounted.
+    pub cached_input: Option<f64>,
+    /// Price of input tokens written to the prompt cache, `input` when not surcharged.
+    pub cache_write_input: Option<f64>,
+    pub request_fee: f64,
+    /// Request fees that depend on a per-request option such as the search context size.
+    pub request_fee_tiers: BTreeMap<String, f64>,
+    /// Applied to the whole cost of usage during the provider's off-peak hours.
+    pub off_peak_multiplier: Option<f64>,
+    /// Applied to the token cost of usage with `service_tier="batch"`.
+    pub batch_multiplier: Option<f64>,
+    /// USD per generated image, keyed by `size/quality` or by quality alone.
+    pub image_prices: BTreeMap<String, f64>,
+    /// USD per minute of audio transcribed.
+    pub audio_minute: f64,
+    /// USD per 1M characters of speech synthesized.
+    pub characters: f64,
+    /// USD per 1M tokens trained on when fine-tuning the model.
+    pub training: f64,
+    /// USD per hour of training, for fine-tuning billed by time.
+    pub training_hour: f64,
+}
+
+impl ModelPrice {
+    /// Cost of the usage's tokens, with cache reads and writes at their own rates and the
+    /// batch discount applied to batch usage.
+    pub fn token_cost(&self, usage: &LLMUsage) -> f64 {
+        let cost = (usage.prompt_tokens as f64 * self.input
+            + usage.cached_prompt_tokens as f64 * self.cached_input.unwrap_or(self.input)
+            + usage.cache_write_tokens as f64 * self.cache_write_input.unwrap_or(self.input)
+            + usage.completion_tokens as f64 * self.output
+            + usage.embedding_tokens as f64 * self.input)
+            / 1_000_000.0;
+        match self.batch_multiplier {
+            Some(multiplier) if usage.service_tier() == Some("batch") => cost * multiplier,
+            _ => cost,
+        }
+    }
+
+    /// Cost of the usage's generated images. Images of unreported quality are priced as
+    /// `standard`, and images without a matching price cost nothing.
+    pub fn image_cost(&self, usage: &LLMUsage) -> f64 {
+        usage
+            .images_generated
+            .iter()
+            .map(|images| {
+                let quality = match images.quality.as_str() {
+                    "" => "standard",
+                    quality => quality,
+                };
+                let price = self
+                    .image_prices
+                    .get(&format!("{}/{}", images.size, quality))
+                    .or_else(|| self.image_prices.get(quality))
+                    .copied()
+                    .unwrap_or_default();
+                images.count as f64 * price
+            })
+            .sum()
+    }
+
+    /// Cost of the usage's transcribed audio and synthesized speech.
+    pub fn audio_cost(&self, usage: &LLMUsage) -> f64 {
+        usage.transcription_minutes * self.audio_minute
+            + usage.speech_characters as f64 * self.characters / 1_000_000.0
+    }
+
+    /// Cost of everything in the usage that is priced per unit: tokens, images and audio.
+    pub fn usage_cost(&self, usage: &LLMUsage) -> f64 {
+        self.token_cost(usage) + self.image_cost(usage) + self.audio_cost(usage)
+    }
+
+    /// Cost of a fine-tuning job that trained on `trained_tokens` tokens for `hours` hours.
+    pub fn training_cost(&self, trained_tokens: u64, hours: f64) -> f64 {
+        trained_tokens as f64 * self.training / 1_000_000.0 + hours * self.training_hour
+    }
+
+    /// Request fees of the usage, at the tier's rate when it has one.
+    pub fn request_cost(&self, usage: &LLMUsage, tier: Option<&str>) -> f64 {
+        let rate = tier
+            .and_then(|tier| self.request_fee_tiers.get(tier))
+            .copied()
+            .unwrap_or(self.request_fee);
+        usage.request_count as f64 * rate / 1000.0
+    }
+
+    fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let number = |node: &Yaml| match node {
+            Yaml::Real(_) => node.as_f64(),
+            Yaml::Integer(value) => Some(*value as f64),
+            _ => None,
+        };
+        let field = |key: &str| -> Result<Option<f64>, String> {
+            match &node[key] {
+                Yaml::BadValue | Yaml::Null => Ok(None),
+                value => number(value)
+                    .map(Some)
+                    .ok_or_else(|| format!("{} is not a number", key)),
+            }
+        };
+        if node.as_hash().is_none() {
+            return Err("expected a mapping of prices".to_string());
+        }
+        let table = |key: &str| -> Result<BTreeMap<String, f64>, String> {
+            let mut table = BTreeMap::new();
+            if let Some(entries) = node[key].as_hash() {
+                for (name, value) in entries {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| format!("{} keys must be strings", key))?;
+                    let value =
+                        number(value).ok_or_else(|| format!("{}.{} is not a number", key, name))?;
+                    table.insert(name.to_string(), value);
+                }
+            }
+            Ok(table)
+        };
+        Ok(Self {
+            input: field("input")?.unwrap_or_default(),
+            output: field("output")?.unwrap_or_default(),
+            cached_input: field("cached_input")?,
+            cache_write_input: field("cache_write_input")?,
+            request_fee: field("request_fee")?.unwrap_or_default(),
+            request_fee_tiers: table("request_fee_tiers")?,
+            off_peak_multiplier: field("off_peak_multiplier")?,
+            batch_multiplier: field("batch_multiplier")?,
+            image_prices: table("images")?,
+            audio_minute: field("audio_minute")?.unwrap_or_default(),
+            characters: field("characters")?.unwrap_or_default(),
+            training: field("training")?.unwrap_or_default(),
+            training_hour: field("training_hour")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// Hours of the day, in UTC, a provider bills at the `off_peak_multiplier` of its prices. The
+/// window may wrap past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffPeakWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl OffPeakWindow {
+    /// DeepSeek's discount hours, 16:30 to 00:30 UTC
+    /// (<https://api-docs.deepseek.com/quick_start/pricing>).
+    pub fn deepseek() -> Self {
+        Self {
+            start: NaiveTime::from_hms_opt(16, 30, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
+        }
+    }
+
+    /// Parses a window written as `HH:MM-HH:MM`.
+    pub fn parse(window: &str) -> Result<Self, String> {
+        let parse = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .map_err(|e| format!("invalid time {:?}: {}", value, e))
+        };
+        let (start, end) = window
+            .split_once('-')
+            .ok_or("expected a window like HH:MM-HH:MM")?;
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+
+    /// Reads the window from the environment variable `name`, `default` when unset.
+    pub fn from_env(name: &str, default: Self) -> Result<Self, MonitorError> {
+        match std::env::var(name) {
+            Ok(window) if !window.is_empty() => Ok(Self::parse(&window)
+                .map_err(|e| anyhow::anyhow!("invalid {} {:?}: {}", name, window, e))?),
+            _ => Ok(default),
+        }
+    }
+
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let time = at.time();
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Per-model prices of every provider whose cost the exporter computes from usage.
+#[derive(Debug, Clone, Default)]
+pub struct PricingCatalog {
+    providers: HashMap<String, HashMap<String, ModelPrice>>,
+}
+
+impl PricingCatalog {
+    pub fn builtin() -> Result<Self, MonitorError> {
+        Ok(Self::parse(BUILTIN_PRICING)
+            .map_err(|e| anyhow::anyhow!("invalid built-in pricing: {}", e))?)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, MonitorError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read pricing file {}: {}", path, e))?;
+        Ok(Self::parse(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid pricing file {}: {}", path, e))?)
+    }
+
+    /// Parses a YAML (or JSON) document of `provider -> model -> price`.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let documents = YamlLoader::load_from_str(contents).map_err(|e| e.to_string())?;
+        let mut catalog = Self::default();
+        let Some(root) = documents.first() else {
+            return Ok(catalog);
+        };
+        let providers = root
+            .as_hash()
+            .ok_or("expected a mapping of providers to models")?;
+        for (provider, models) in providers {
+            let provider = provider.as_str().ok_or("provider names must be strings")?;
+            let models = models
+                .as_hash()
+                .ok_or_else(|| format!("{}: expected a mapping of models", provider))?;
+            let prices = catalog.providers.entry(provider.to_string()).or_default();
+            for (model, price) in models {
+                let model = model
+                    .as_str()
+                    .ok_or_else(|| format!("{}: model names must be strings", provider))?;
+                let price = ModelPrice::from_yaml(price)
+                    .map_err(|e| format!("{}.{}: {}", provider, model, e))?;
+                prices.insert(model.to_string(), price);
+            }
+        }
+        Ok(catalog)
+    }
+
+    /// Parses LiteLLM's `model_prices_and_context_window.json`. Only token prices are taken
+    /// over; request fees and off-peak multipliers stay as configured locally.
+    pub fn parse_litellm(contents: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct Entry {
+            litellm_provider: Option<String>,
+            input_cost_per_token: Option<f64>,
+            output_cost_per_token: Option<f64>,
+            cache_read_input_token_cost: Option<f64>,
+            cache_creation_input_token_cost: Option<f64>,
+        }
+        let entries: HashMap<String, serde_json::Value> =
+            serde_json::from_str(contents).map_err(|e| e.to_string())?;
+        let mut catalog = Self::default();
+        for (key, value) in entries {
+            // entries of other shapes, such as `sample_spec`, are skipped
+            let Ok(entry) = serde_json::from_value::<Entry>(value) else {
+                continue;
+            };
+            let (Some(litellm_provider), Some(input)) =
+                (entry.litellm_provider, entry.input_cost_per_token)
+            else {
+                continue;
+            };
+            let provider = match litellm_provider.as_str() {
+                "openai" | "anthropic" | "perplexity" | "deepseek" | "xai" => {
+                    litellm_provider.as_str()
+                }
+                "bedrock" | "bedrock_converse" => "bedrock",
+                "together_ai" => "together",
+                "fireworks_ai" => "fireworks",
+                _ => continue,
+            };
+            let model = key
+                .strip_prefix(&format!("{}/", litellm_provider))
+                .unwrap_or(&key);
+            catalog
+                .providers
+                .entry(provider.to_string())
+                .or_default()
+                .insert(
+                    model.to_string(),
+                    ModelPrice {
+                        input: input * 1_000_000.0,
+                        output: entry.output_cost_per_token.unwrap_or_default() * 1_000_000.0,
+                        cached_input: entry.cache_read_input_token_cost.map(|c| c * 1_000_000.0),
+                        cache_write_input: entry
+                            .cache_creation_input_token_cost
+                            .map(|c| c * 1_000_000.0),
+                        ..ModelPrice::default()
+                    },
+                );
+        }
+        Ok(catalog)
+    }
+
+    /// Rejects catalogs that are empty or contain negative or non-finite prices.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut models = 0;
+        for (provider, prices) in &self.providers {
+            for (model, price) in prices {
+                models += 1;
+                let values = [
+                    price.input,
+                    price.output,
+                    price.request_fee,
+                    price.audio_minute,
+                    price.characters,
+                    price.training,
+                    price.training_hour,
+                ]
+                .into_iter()
+                .chain(price.cached_input)
+                .chain(price.cache_write_input)
+                .chain(price.off_peak_multiplier)
+                .chain(price.batch_multiplier)
+                .chain(price.request_fee_tiers.values().copied())
+                .chain(price.image_prices.values().copied());
+                for value in values {
+                    if !value.is_finite() || value < 0.0 {
+                        return Err(format!("{}.{}: invalid price {}", provider, model, value));
+                    }
+                }
+            }
+        }
+        if models == 0 {
+            return Err("no model prices".to_string());
+        }
+        Ok(())
+    }
+
+    /// Adds the models of `other`, replacing existing prices of the same model.
+    pub fn merge(&mut self, other: PricingCatalog) {
+        for (provider, models) in other.providers {
+            self.providers.entry(provider).or_default().extend(models);
+        }
+    }
+
+    /// Like [`merge`](Self::merge), but only replaces the token prices of models that already
+    /// have a price, for sources that only know token prices.
+    fn merge_token_prices(&mut self, other: PricingCatalog) {
+        for (provider, models) in other.providers {
+            let prices = self.providers.entry(provider).or_default();
+            for (model, mut price) in models {
+                if let Some(existing) = prices.get(&model) {
+                    price = ModelPrice {
+                        input: price.input,
+                        output: price.output,
+                        cached_input: price.cached_input.or(existing.cached_input),
+                        cache_write_input: price.cache_write_input.or(existing.cache_write_input),
+                        ..existing.clone()
+                    };
+                }
+                prices.insert(model, price);
+            }
+        }
+    }
+
+    pub fn price(&self, provider: &str, model: &str) -> Option<&ModelPrice> {
+        self.providers.get(provider)?.get(model)
+    }
+
+    /// Providers with at least one model price.
+    pub fn providers(&self) -> impl Iterator<Item = &str> {
+        self.providers.keys().map(String::as_str)
+    }
+
+    /// Every price, sorted by provider and model.
+    pub fn prices(&self) -> BTreeMap<&str, BTreeMap<&str, &ModelPrice>> {
+        self.providers
+            .iter()
+            .map(|(provider, models)| {
+                let models = models
+                    .iter()
+                    .map(|(model, price)| (model.as_str(), price))
+                    .collect();
+                (provider.as_str(), models)
+            })
+            .collect()
+    }
+}
+
+/// The catalog in use, shared by every monitor. Layers the built-in prices, the remote
+/// catalog once fetched, and the local pricing file, which always wins.
+#[derive(Debug, Clone)]
+pub struct Pricing {
+    builtin: Arc<PricingCatalog>,
+    remote: Arc<RwLock<Option<PricingCatalog>>>,
+    file: Arc<RwLock<Option<PricingCatalog>>>,
+    current: Arc<RwLock<Arc<PricingCatalog>>>,
+}
+
+impl Pricing {
+    pub fn load(path: Option<&str>) -> Result<Self, MonitorError> {
+        let builtin = PricingCatalog::builtin()?;
+        let file = path.map(PricingCatalog::from_file).transpose()?;
+        let mut current = builtin.clone();
+        if let Some(file) = &file {
+            current.merge(file.clone());
+        }
+        Ok(Self {
+            builtin: Arc::new(builtin),
+            remote: Arc::new(RwLock::new(None)),
+            file: Arc::new(RwLock::new(file)),
+            current: Arc::new(RwLock::new(Arc::new(current))),
+        })
+    }
+
+    /// Swaps in a new remote catalog on top of the built-in prices.
+    pub fn apply_remote(&self, remote: PricingCatalog) {
+        *self.remote.write().unwrap() = Some(remote);
+        self.rebuild();
+    }
+
+    /// Swaps in a new local pricing file, or drops it, e.g. when the config is reloaded.
+    pub fn apply_file(&self, file: Option<PricingCatalog>) {
+        *self.file.write().unwrap() = file;
+        self.rebuild();
+    }
+
+    fn rebuild(&self) {
+        let mut catalog = (*self.builtin).clone();
+        if let Some(remote) = self.remote.read().unwrap().as_ref() {
+            catalog.merge_token_prices(remote.clone());
+        }
+        if let Some(file) = self.file.read().unwrap().as_ref() {
+            catalog.merge(file.clone());
+        }
+        *self.current.write().unwrap() = Arc::new(catalog);
+    }
+
+    /// The catalog in use, with every layer applied.
+    pub fn current(&self) -> Arc<PricingCatalog> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn price(&self, provider: &str, model: &str) -> Option<ModelPrice> {
+        self.current().price(provider, model).cloned()
+    }
+
+    /// Token cost of the usage, zero for models without a price.
+    pub fn token_cost(&self, provider: &str, usage: &LLMUsage) -> f64 {
+        self.price(provider, &usage.model)
+            .map(|price| price.token_cost(usage))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CatalogFormat {
+    /// Same shape as `pricing.yaml`.
+    Native,
+    LiteLlm,
+}
+
+/// Periodically fetches a pricing catalog from a URL and applies it when it validates. The
+/// last good catalog is cached on disk so restarts do not depend on the URL being reachable.
+pub struct RemoteCatalog {
+    client: reqwest::Client,
+    url: String,
+    format: CatalogFormat,
+    interval: Duration,
+    cache_file: Option<String>,
+}
+
+impl RemoteCatalog {
+    /// Enabled when `PRICING_CATALOG_URL` is set.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let url = match std::env::var("PRICING_CATALOG_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let format = match std::env::var("PRICING_CATALOG_FORMAT").as_deref() {
+            Ok("native") | Err(_) => CatalogFormat::Native,
+            Ok("litellm") => CatalogFormat::LiteLlm,
+            Ok(other) => {
+                return Err(anyhow::anyhow!(
+                    "PRICING_CATALOG_FORMAT must be native or litellm, got {:?}",
+                    other
+                )
+                .into())
+            }
+        };
+        let interval_secs = std::env::var("PRICING_CATALOG_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(86400);
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            url,
+            format,
+            interval: Duration::from_secs(interval_secs),
+            cache_file: std::env::var("PRICING_CATALOG_CACHE_FILE")
+                .ok()
+                .filter(|path| !path.is_empty()),
+        }))
+    }
+
+    fn parse(&self, contents: &str) -> Result<PricingCatalog, String> {
+        let catalog = match self.format {
+            CatalogFormat::Native => PricingCatalog::parse(contents)?,
+            CatalogFormat::LiteLlm => PricingCatalog::parse_litellm(contents)?,
+        };
+        catalog.validate()?;
+        Ok(catalog)
+    }
+
+    /// Applies the cached catalog, if any, so prices are current before the first fetch.
+    pub fn load_cache(&self, pricing: &Pricing, metrics: &LLMMetrics) {
+        let Some(path) = &self.cache_file else {
+            return;
+        };
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return;
+        };
+        match self.parse(&contents) {
+            Ok(catalog) => {
+                pricing.apply_remote(catalog);
+                metrics.set_pricing_catalog("cache", &version(&contents));
+                tracing::info!(path, "loaded cached pricing catalog");
+            }
+            Err(e) => tracing::warn!(path, error = %e, "ignoring invalid cached pricing catalog"),
+        }
+    }
+
+    /// Fetches on every interval until shutdown.
+    pub async fn run(self, pricing: Pricing, metrics: Arc<LLMMetrics>, shutdown: Shutdown) {
+        let mut interval = time::interval(self.interval);
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut stop => break,
+            }
+            match self.update(&pricing).await {
+                Ok(version) => {
+                    metrics.set_pricing_catalog("remote", &version);
+                    tracing::info!(url = %self.url, version, "updated pricing catalog");
+                }
+                Err(e) => {
+                    metrics.record_pricing_catalog_error();
+                    tracing::warn!(url = %self.url, error = %e, "pricing catalog update failed");
+                }
+            }
+        }
+    }
+
+    async fn update(&self, pricing: &Pricing) -> Result<String, MonitorError> {
+        let contents = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let catalog = self
+            .parse(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid pricing catalog: {}", e))?;
+        pricing.apply_remote(catalog);
+        if let Some(path) = &self.cache_file {
+            if let Err(e) = std::fs::write(path, &contents) {
+                tracing::warn!(path, error = %e, "failed to cache pricing catalog");
+            }
+        }
+        Ok(version(&contents))
+    }
+}
+
+/// Identifies a catalog by the CRC32 of its contents.
+fn version(contents: &str) -> String {
+    let mut crc = flate2::Crc::new();
+    crc.update(contents.as_bytes());
+    format!("{:08x}", crc.sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 16, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn off_peak_window_wraps_past_midnight() {
+        let window = OffPeakWindow::parse("16:30-00:30").unwrap();
+        assert_eq!(window, OffPeakWindow::deepseek());
+        assert!(!window.contains(at(16, 29)));
+        assert!(window.contains(at(16, 30)));
+        assert!(window.contains(at(23, 59)));
+        assert!(window.contains(at(0, 0)));
+        assert!(window.contains(at(0, 29)));
+        assert!(!window.contains(at(0, 30)));
+        assert!(!window.contains(at(12, 0)));
+    }
+
+    #[test]
+    fn off_peak_window_within_a_day() {
+        let window = OffPeakWindow::parse(" 01:00 - 05:00 ").unwrap();
+        assert!(!window.contains(at(0, 59)));
+        assert!(window.contains(at(1, 0)));
+        assert!(window.contains(at(4, 59)));
+        assert!(!window.contains(at(5, 0)));
+        assert!(!window.contains(at(23, 0)));
+    }
+
+    #[test]
+    fn off_peak_window_rejects_malformed_windows() {
+        assert!(OffPeakWindow::parse("16:30").is_err());
+        assert!(OffPeakWindow::parse("16:30-25:00").is_err());
+        assert!(OffPeakWindow::parse("evening-night").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Reads organization-level usage and cost from the Anthropic Admin API, which requires an
+/// admin key (`sk-ant-admin...`) rather than a regular API key.
+pub struct ClaudeMonitor {
+    admin_key: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    data: Vec<Bucket<T>>,
+    has_more: bool,
+    next_page: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bucket<T> {
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageResult {
+    model: Option<String>,
+    #[serde(default)]
+    uncached_input_tokens: u64,
+    #[serde(default)]
+    cache_read_input_tokens: u64,
+    #[serde(default)]
+    cache_creation: CacheCreation,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CacheCreation {
+    #[serde(default)]
+    ephemeral_1h_input_tokens: u64,
+    #[serde(default)]
+    ephemeral_5m_input_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CostResult {
+    model: Option<String>,
+    /// Decimal string in the lowest currency unit (cents).
+    amount: String,
+}
+
+impl ClaudeMonitor {
+    pub fn new(admin_key: String) -> Self {
+        let base_url =
+            std::env::var("ANTHROPIC_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            admin_key,
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Fetches every page of daily buckets for a report since the start of the billing period.
+    async fn report<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        group_by: &str,
+    ) -> Result<Vec<T>, MonitorError> {
+        let mut results = Vec::new();
+        let mut page: Option<String> = None;
+
+        loop {
+            let mut query = vec![
+                (
+                    "starting_at",
+                    billing_period_start(self.provider()).to_rfc3339(),
+                ),
+                ("ending_at", Utc::now().to_rfc3339()),
+                ("bucket_width", "1d".to_string()),
+                ("group_by[]", group_by.to_string()),
+                ("limit", "31".to_string()),
+            ];
+            if let Some(page) = page {
+                query.push(("page", page));
+            }
+
+            let response: Page<T> = self
+                .client
+                .get(format!("{}{}", self.base_url, path))
+                .header("x-api-key", &self.admin_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .query(&query)
+                .send_timed()
+                .await?
+                .check_status()?
+                .json()
+                .await?;
+
+            results.extend(response.data.into_iter().flat_map(|bucket| bucket.results));
+            match response.next_page {
+                Some(next) if response.has_more => page = Some(next),
+                _ => return Ok(results),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for ClaudeMonitor {
+    fn provider(&self) -> &'static str {
+        "anthropic"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let usage: Vec<UsageResult> = self
+            .report("/v1/organizations/usage_report/messages", "model")
+            .await?;
+        let costs: Vec<CostResult> = self
+            .report("/v1/organizations/cost_report", "description")
+            .await?;
+
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        for result in usage {
+            let totals = model_entry(&mut by_model, result.model);
+            totals.prompt_tokens += result.uncached_input_tokens;
+            totals.cached_prompt_tokens += result.cache_read_input_tokens;
+            totals.cache_write_tokens += result.cache_creation.ephemeral_1h_input_tokens
+                + result.cache_creation.ephemeral_5m_input_tokens;
+            totals.completion_tokens += result.output_tokens;
+        }
+
+        for result in costs {
+            let cents: f64 = result
+                .amount
+                .parse()
+                .map_err(|_| MonitorError::InvalidResponse)?;
+            let totals = model_entry(&mut by_model, result.model);
+            totals.cost_usd += cents / 100.0;
+        }
+
+        Ok(by_model.into_values().collect())
+    }
+}
+
+/// Rows without a model (e.g. organization-wide charges) are reported as `unknown`.
+fn model_entry(by_model: &mut HashMap<String, LLMUsage>, model: Option<String>) -> &mut LLMUsage {
+    let model = model.unwrap_or_else(|| "unknown".to_string());
+    by_model.entry(model.clone()).or_insert_with(|| LLMUsage {
+        model,
+        ..LLMUsage::default()
+    })
+}
+
+#[async_trait]
+impl ProviderFactory for ClaudeMonitor {
+    const TYPE: &'static str = "anthropic";
+    const ENV: &'static [(&'static str, &'static str)] = &[("admin_key", "ANTHROPIC_ADMIN_KEY")];
+    const ENABLED_BY: &'static [&'static str] = &["admin_key"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(settings.require("admin_key")?)))
+    }
+}
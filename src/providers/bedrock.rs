@@ -0,0 +1,290 @@
+use async_trait::async_trait;
+use aws_config::sts::AssumeRoleProvider;
+use aws_config::{Region, SdkConfig};
+use aws_credential_types::provider::SharedCredentialsProvider;
+
+use super::bedrock_logs::{BedrockLogsMonitor, InvocationLogSource};
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, is_embedding_model, LLMMonitor, LLMUsage};
+use crate::aws::{AwsJsonClient, Dimension, RefreshingCredentials};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+#[derive(Debug)]
+pub struct BedrockConfig {
+    /// Roles to assume, one per member account; empty means the default credentials.
+    pub assume_roles: Vec<AssumeRoleConfig>,
+    /// Regions to poll; empty means the region from the default AWS configuration.
+    pub regions: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct AssumeRoleConfig {
+    pub role_arn: String,
+    pub session_name: String,
+}
+
+impl AssumeRoleConfig {
+    /// Account ID embedded in the role ARN (`arn:aws:iam::<account>:role/<name>`).
+    pub fn account_id(&self) -> Option<&str> {
+        self.role_arn.split(':').nth(4).filter(|id| !id.is_empty())
+    }
+}
+
+/// SDK configuration for one account, with credentials that refresh before they expire.
+pub struct AccountConfig {
+    pub account_id: String,
+    pub sdk_config: SdkConfig,
+}
+
+fn list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl BedrockConfig {
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("AWS_ROLE_ARN").ok(),
+            std::env::var("AWS_ROLE_SESSION_NAME").ok(),
+            std::env::var("BEDROCK_REGIONS").ok(),
+        )
+    }
+
+    /// Reads the `role_arns`, `role_session_name` and `regions` settings of a provider entry,
+    /// which fall back to the same environment variables as [`BedrockConfig::from_env`].
+    pub fn from_settings(settings: &ProviderSettings) -> Self {
+        Self::new(
+            settings.get("role_arns"),
+            settings.get("role_session_name"),
+            settings.get("regions"),
+        )
+    }
+
+    /// Takes comma separated role ARNs and regions.
+    fn new(
+        role_arns: Option<String>,
+        session_name: Option<String>,
+        regions: Option<String>,
+    ) -> Self {
+        let session_name = session_name
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "llm-cost-exporter".to_string());
+        Self {
+            assume_roles: list(role_arns)
+                .into_iter()
+                .map(|role_arn| AssumeRoleConfig {
+                    role_arn,
+                    session_name: session_name.clone(),
+                })
+                .collect(),
+            regions: list(regions),
+        }
+    }
+
+    /// Loads one configuration per account: each assumed role, or the default credentials
+    /// when no roles are configured.
+    pub async fn load_account_configs(&self) -> Result<Vec<AccountConfig>, MonitorError> {
+        let base = aws_config::load_from_env().await;
+        if self.assume_roles.is_empty() {
+            let identity = aws_sdk_sts::Client::new(&base)
+                .get_caller_identity()
+                .send()
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to resolve AWS account: {}", e))?;
+            return Ok(vec![AccountConfig {
+                account_id: identity.account().unwrap_or_default().to_string(),
+                sdk_config: base,
+            }]);
+        }
+
+        let mut accounts = Vec::with_capacity(self.assume_roles.len());
+        for role in &self.assume_roles {
+            let account_id = role
+                .account_id()
+                .ok_or_else(|| anyhow::anyhow!("invalid role ARN {:?}", role.role_arn))?
+                .to_string();
+            let provider = AssumeRoleProvider::builder(&role.role_arn)
+                .session_name(&role.session_name)
+                .configure(&base)
+                .build()
+                .await;
+            accounts.push(AccountConfig {
+                account_id,
+                sdk_config: base
+                    .to_builder()
+                    .credentials_provider(SharedCredentialsProvider::new(
+                        RefreshingCredentials::new(provider),
+                    ))
+                    .build(),
+            });
+        }
+        Ok(accounts)
+    }
+
+    /// Expands an account configuration into one per configured region.
+    pub fn regional_configs(&self, sdk_config: &SdkConfig) -> Vec<SdkConfig> {
+        if self.regions.is_empty() {
+            return vec![sdk_config.clone()];
+        }
+        self.regions
+            .iter()
+            .map(|region| {
+                sdk_config
+                    .to_builder()
+                    .region(Region::new(region.clone()))
+                    .build()
+            })
+            .collect()
+    }
+}
+
+/// Reads per-model token, image and invocation counts from the `AWS/Bedrock` CloudWatch
+/// namespace of every configured region and prices them from the on-demand Bedrock price list.
+pub struct BedrockMonitor {
+    targets: Vec<BedrockTarget>,
+    pricing: Pricing,
+}
+
+/// One account and region pair to poll.
+struct BedrockTarget {
+    account_id: String,
+    cloudwatch: AwsJsonClient,
+}
+
+impl BedrockMonitor {
+    pub async fn new(config: &BedrockConfig, pricing: Pricing) -> Result<Self, MonitorError> {
+        let mut targets = Vec::new();
+        for account in config.load_account_configs().await? {
+            for sdk_config in config.regional_configs(&account.sdk_config) {
+                targets.push(BedrockTarget {
+                    account_id: account.account_id.clone(),
+                    cloudwatch: AwsJsonClient::cloudwatch(&sdk_config)?,
+                });
+            }
+        }
+        Ok(Self { targets, pricing })
+    }
+
+    async fn target_usage(&self, target: &BedrockTarget) -> Result<Vec<LLMUsage>, MonitorError> {
+        let cloudwatch = &target.cloudwatch;
+        let start = billing_period_start(self.provider());
+        let model_ids = cloudwatch
+            .dimension_values("AWS/Bedrock", "Invocations", "ModelId")
+            .await?;
+
+        let mut usage = Vec::with_capacity(model_ids.len());
+        for model_id in model_ids {
+            let dimensions = [Dimension {
+                name: "ModelId".to_string(),
+                value: model_id.clone(),
+            }];
+            let sums = cloudwatch
+                .metric_sums(
+                    "AWS/Bedrock",
+                    &[
+                        ("InputTokenCount", &dimensions),
+                        ("OutputTokenCount", &dimensions),
+                        ("Invocations", &dimensions),
+                        ("CacheReadInputTokenCount", &dimensions),
+                        ("CacheWriteInputTokenCount", &dimensions),
+                        ("OutputImageCount", &dimensions),
+                    ],
+                    start,
+                )
+                .await?;
+
+            let mut model_usage = LLMUsage {
+                model: model_id,
+                prompt_tokens: sums[0] as u64,
+                completion_tokens: sums[1] as u64,
+                request_count: sums[2] as u64,
+                cached_prompt_tokens: sums[3] as u64,
+                cache_write_tokens: sums[4] as u64,
+                ..LLMUsage::default()
+            };
+            if is_embedding_model(&model_usage.model) {
+                model_usage.embedding_tokens = std::mem::take(&mut model_usage.prompt_tokens);
+            }
+            if sums[5] > 0.0 {
+                // CloudWatch doesn't break images down, so they are priced at standard quality
+                model_usage.add_images("", "", sums[5] as u64);
+            }
+            model_usage.cost_usd = calculate_bedrock_cost(&self.pricing, &model_usage);
+            model_usage
+                .labels
+                .insert("region", cloudwatch.region().to_string());
+            model_usage
+                .labels
+                .insert("aws_account_id", target.account_id.clone());
+            usage.push(model_usage);
+        }
+
+        Ok(usage)
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for BedrockMonitor {
+    fn provider(&self) -> &'static str {
+        "bedrock"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let mut usage = Vec::new();
+        for target in &self.targets {
+            usage.extend(self.target_usage(target).await?);
+        }
+        Ok(usage)
+    }
+}
+
+/// Prices usage from the `bedrock` catalog entries. Cross-region inference profile IDs
+/// (`us.`, `eu.`, `apac.`) are priced like the underlying model.
+pub(crate) fn calculate_bedrock_cost(pricing: &Pricing, usage: &LLMUsage) -> f64 {
+    let base_model = ["us.", "eu.", "apac."]
+        .iter()
+        .find_map(|prefix| usage.model.strip_prefix(prefix))
+        .unwrap_or(&usage.model);
+    pricing
+        .price("bedrock", base_model)
+        .map(|price| price.usage_cost(usage))
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl ProviderFactory for BedrockMonitor {
+    const TYPE: &'static str = "bedrock";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("enabled", "BEDROCK_ENABLED"),
+        ("invocation_logs", "BEDROCK_INVOCATION_LOGS"),
+        ("role_arns", "AWS_ROLE_ARN"),
+        ("role_session_name", "AWS_ROLE_SESSION_NAME"),
+        ("regions", "BEDROCK_REGIONS"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["enabled"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        let config = BedrockConfig::from_settings(settings);
+        match settings.get("invocation_logs") {
+            Some(source) => {
+                let source = InvocationLogSource::parse(&source)?;
+                let accounts = config.load_account_configs().await?;
+                Ok(Box::new(BedrockLogsMonitor::new(
+                    &accounts,
+                    source,
+                    pricing.clone(),
+                )?))
+            }
+            None => Ok(Box::new(Self::new(&config, pricing.clone()).await?)),
+        }
+    }
+}
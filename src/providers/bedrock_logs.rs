@@ -0,0 +1,582 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::bedrock::{calculate_bedrock_cost, AccountConfig};
+use super::{billing_period_start, is_embedding_model, timed, ImageCount, LLMMonitor, LLMUsage};
+use crate::aws::AwsJsonClient;
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+/// How long the key of an S3 log object is remembered as read after it was last modified.
+/// Bedrock writes objects under one prefix per account and region with random names, so a new
+/// object doesn't sort after the ones read before it: objects are told apart by key while they
+/// are this recent, and any object older than that was read by an earlier poll.
+const S3_KEY_WINDOW_MINUTES: i64 = 60;
+
+/// Where Bedrock model invocation logging delivers its records. `{account_id}` in a log group,
+/// bucket or prefix stands for each account's ID.
+pub enum InvocationLogSource {
+    CloudWatch { log_group: String },
+    S3 { bucket: String, prefix: String },
+}
+
+impl InvocationLogSource {
+    /// Parses `cloudwatch:<log-group>` or `s3://<bucket>/<prefix>`.
+    pub fn parse(value: &str) -> Result<Self, MonitorError> {
+        if let Some(log_group) = value.strip_prefix("cloudwatch:") {
+            return Ok(Self::CloudWatch {
+                log_group: log_group.to_string(),
+            });
+        }
+        if let Some(location) = value.strip_prefix("s3://") {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            return Ok(Self::S3 {
+                bucket: bucket.to_string(),
+                prefix: prefix.to_string(),
+            });
+        }
+        Err(anyhow::anyhow!(
+            "invocation log source must be cloudwatch:<log-group> or s3://<bucket>/<prefix>, got {:?}",
+            value
+        )
+        .into())
+    }
+}
+
+impl fmt::Display for InvocationLogSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CloudWatch { log_group } => write!(f, "cloudwatch:{}", log_group),
+            Self::S3 { bucket, prefix } => write!(f, "s3://{}/{}", bucket, prefix),
+        }
+    }
+}
+
+impl InvocationLogSource {
+    /// The source of one account's logs.
+    fn of_account(&self, account_id: &str) -> Self {
+        let expand = |value: &str| value.replace("{account_id}", account_id);
+        match self {
+            Self::CloudWatch { log_group } => Self::CloudWatch {
+                log_group: expand(log_group),
+            },
+            Self::S3 { bucket, prefix } => Self::S3 {
+                bucket: expand(bucket),
+                prefix: expand(prefix),
+            },
+        }
+    }
+}
+
+enum LogReader {
+    CloudWatch {
+        client: AwsJsonClient,
+        log_group: String,
+    },
+    S3 {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+        prefix: String,
+    },
+}
+
+/// Tails Bedrock model invocation logs and attributes tokens per model, caller IAM role and
+/// inference profile, which the account-level CloudWatch metrics can't break down. How far the
+/// logs have been read is kept in its checkpoint, so a restart resumes there.
+///
+/// Log groups are read in every account. A bucket is read once with the first account's
+/// credentials, as the logs of all accounts are usually delivered to one bucket, unless its
+/// name or prefix varies by `{account_id}`.
+pub struct BedrockLogsMonitor {
+    /// The source as configured, to tell whether a checkpoint is of the same logs.
+    source: String,
+    /// Readers by the location they read.
+    readers: Vec<(String, LogReader)>,
+    state: Mutex<LogState>,
+    pricing: Pricing,
+}
+
+/// How far one location's logs have been read.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Cursor {
+    /// Last CloudWatch event timestamp (ms) consumed.
+    last_timestamp_ms: i64,
+    /// Every S3 object last modified before this has been read.
+    read_before: Option<DateTime<Utc>>,
+    /// S3 objects read that were modified since `read_before`, by key, with when.
+    read_keys: HashMap<String, DateTime<Utc>>,
+}
+
+impl Cursor {
+    fn new(period_start: DateTime<Utc>) -> Self {
+        Self {
+            last_timestamp_ms: period_start.timestamp_millis(),
+            read_before: Some(period_start),
+            read_keys: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct LogState {
+    period_start: Option<DateTime<Utc>>,
+    /// Cursors by the location they are of.
+    cursors: HashMap<String, Cursor>,
+    usage: HashMap<[String; 5], LLMUsage>,
+}
+
+/// Series of the usage read so far by model, caller role, inference profile, region and
+/// account.
+fn series(key: &[String; 5]) -> LLMUsage {
+    let [model, role, profile, region, account_id] = key.clone();
+    let mut usage = LLMUsage {
+        model,
+        ..LLMUsage::default()
+    };
+    usage.labels.insert("iam_role", role);
+    usage.labels.insert("inference_profile", profile);
+    usage.labels.insert("region", region);
+    usage.labels.insert("aws_account_id", account_id);
+    usage
+}
+
+/// [`LogState`] as saved across restarts.
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    source: String,
+    period_start: Option<DateTime<Utc>>,
+    cursors: HashMap<String, Cursor>,
+    usage: Vec<SavedSeries>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedSeries {
+    key: [String; 5],
+    prompt_tokens: u64,
+    cached_prompt_tokens: u64,
+    cache_write_tokens: u64,
+    completion_tokens: u64,
+    embedding_tokens: u64,
+    request_count: u64,
+    images_generated: Vec<ImageCount>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InvocationRecord {
+    timestamp: DateTime<Utc>,
+    model_id: String,
+    #[serde(default)]
+    region: String,
+    #[serde(default)]
+    account_id: String,
+    #[serde(default)]
+    identity: Identity,
+    #[serde(default)]
+    input: TokenCounts,
+    #[serde(default)]
+    output: TokenCounts,
+}
+
+impl InvocationRecord {
+    /// Size, quality and number of images an image model generated. The size and quality
+    /// come from the request (`imageGenerationConfig` for Titan and Nova Canvas, `width`,
+    /// `height` and `steps` for Stability), the count from the images in the response.
+    /// Bodies too large to be logged inline carry no images.
+    fn images(&self) -> Option<(String, String, u64)> {
+        let output = self.output.output_body_json.as_ref()?;
+        let count = ["images", "artifacts"]
+            .iter()
+            .find_map(|key| output[key].as_array())?
+            .len() as u64;
+        let input = self
+            .input
+            .input_body_json
+            .as_ref()
+            .unwrap_or(&serde_json::Value::Null);
+        let config = input.get("imageGenerationConfig").unwrap_or(input);
+        let size = match (config["width"].as_u64(), config["height"].as_u64()) {
+            (Some(width), Some(height)) => format!("{}x{}", width, height),
+            _ => String::new(),
+        };
+        let quality = match (config["quality"].as_str(), config["steps"].as_u64()) {
+            (Some(quality), _) => quality.to_string(),
+            // Stability bills more than 50 steps as premium
+            (None, Some(steps)) if steps > 50 => "premium".to_string(),
+            (None, Some(_)) => "standard".to_string(),
+            (None, None) => String::new(),
+        };
+        Some((size, quality, count))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Identity {
+    #[serde(default)]
+    arn: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenCounts {
+    #[serde(default)]
+    input_token_count: u64,
+    #[serde(default)]
+    cache_read_input_token_count: u64,
+    #[serde(default)]
+    cache_write_input_token_count: u64,
+    #[serde(default)]
+    output_token_count: u64,
+    input_body_json: Option<serde_json::Value>,
+    output_body_json: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FilterLogEventsRequest<'a> {
+    log_group_name: &'a str,
+    start_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FilterLogEventsResponse {
+    #[serde(default)]
+    events: Vec<LogEvent>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogEvent {
+    timestamp: i64,
+    message: String,
+}
+
+impl BedrockLogsMonitor {
+    pub fn new(
+        accounts: &[AccountConfig],
+        source: InvocationLogSource,
+        pricing: Pricing,
+    ) -> Result<Self, MonitorError> {
+        let mut readers: Vec<(String, LogReader)> = Vec::new();
+        for account in accounts {
+            let location = source.of_account(&account.account_id);
+            let (name, reader) = match location {
+                InvocationLogSource::CloudWatch { log_group } => (
+                    format!("{}:{}", account.account_id, log_group),
+                    LogReader::CloudWatch {
+                        client: AwsJsonClient::logs(&account.sdk_config)?,
+                        log_group,
+                    },
+                ),
+                InvocationLogSource::S3 { bucket, prefix } => (
+                    format!("s3://{}/{}", bucket, prefix),
+                    LogReader::S3 {
+                        client: aws_sdk_s3::Client::new(&account.sdk_config),
+                        bucket,
+                        prefix,
+                    },
+                ),
+            };
+            if readers.iter().all(|(existing, _)| *existing != name) {
+                readers.push((name, reader));
+            }
+        }
+        Ok(Self {
+            source: source.to_string(),
+            readers,
+            state: Mutex::new(LogState::default()),
+            pricing,
+        })
+    }
+
+    /// Reads the events after the cursor, advancing it.
+    async fn read_cloudwatch(
+        client: &AwsJsonClient,
+        log_group: &str,
+        cursor: &mut Cursor,
+    ) -> Result<Vec<String>, MonitorError> {
+        let mut messages = Vec::new();
+        let start_time = cursor.last_timestamp_ms + 1;
+        let mut last_timestamp_ms = cursor.last_timestamp_ms;
+        let mut next_token = None;
+        loop {
+            let response: FilterLogEventsResponse = client
+                .call(
+                    "FilterLogEvents",
+                    &FilterLogEventsRequest {
+                        log_group_name: log_group,
+                        start_time,
+                        next_token,
+                    },
+                )
+                .await?;
+            for event in response.events {
+                last_timestamp_ms = last_timestamp_ms.max(event.timestamp);
+                messages.push(event.message);
+            }
+            match response.next_token {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+        cursor.last_timestamp_ms = last_timestamp_ms;
+        Ok(messages)
+    }
+
+    /// Reads the objects not read before, advancing the cursor.
+    async fn read_s3(
+        client: &aws_sdk_s3::Client,
+        bucket: &str,
+        prefix: &str,
+        cursor: &mut Cursor,
+    ) -> Result<Vec<String>, MonitorError> {
+        let listed_at = Utc::now();
+        let read_before = cursor.read_before.unwrap_or_default();
+        let mut messages = Vec::new();
+        let mut read_keys = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let list = client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send();
+            let listing = timed("ListObjectsV2", list)
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to list s3://{}/{}: {}", bucket, prefix, e))?;
+
+            for object in listing.contents() {
+                let (Some(key), Some(modified)) = (object.key(), object.last_modified()) else {
+                    continue;
+                };
+                let modified = DateTime::from_timestamp(modified.secs(), modified.subsec_nanos())
+                    .unwrap_or_default();
+                if modified < read_before || cursor.read_keys.contains_key(key) {
+                    continue;
+                }
+                let get = client.get_object().bucket(bucket).key(key).send();
+                let object = timed("GetObject", get).await.map_err(|e| {
+                    anyhow::anyhow!("failed to read s3://{}/{}: {}", bucket, key, e)
+                })?;
+                let bytes = object
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("failed to read s3://{}/{}: {}", bucket, key, e))?
+                    .into_bytes();
+                let mut text = String::new();
+                if key.ends_with(".gz") {
+                    GzDecoder::new(bytes.as_ref())
+                        .read_to_string(&mut text)
+                        .map_err(|_| MonitorError::InvalidResponse)?;
+                } else {
+                    text = String::from_utf8_lossy(&bytes).into_owned();
+                }
+                messages.extend(text.lines().map(str::to_string));
+                read_keys.push((key.to_string(), modified));
+            }
+
+            match listing.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => break,
+            }
+        }
+
+        let read_before = read_before.max(listed_at - TimeDelta::minutes(S3_KEY_WINDOW_MINUTES));
+        cursor.read_keys.extend(read_keys);
+        cursor
+            .read_keys
+            .retain(|_, modified| *modified >= read_before);
+        cursor.read_before = Some(read_before);
+        Ok(messages)
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for BedrockLogsMonitor {
+    fn provider(&self) -> &'static str {
+        "bedrock"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let period_start = billing_period_start(self.provider());
+        let mut state = self.state.lock().await;
+        if state.period_start != Some(period_start) {
+            *state = LogState {
+                period_start: Some(period_start),
+                ..LogState::default()
+            };
+        }
+
+        // cursors only advance once every location is read, so a failed poll reads the same
+        // logs again instead of losing what was read before the failure
+        let mut messages = Vec::new();
+        let mut cursors = Vec::with_capacity(self.readers.len());
+        for (name, reader) in &self.readers {
+            let mut cursor = state
+                .cursors
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| Cursor::new(period_start));
+            messages.extend(match reader {
+                LogReader::CloudWatch { client, log_group } => {
+                    Self::read_cloudwatch(client, log_group, &mut cursor).await?
+                }
+                LogReader::S3 {
+                    client,
+                    bucket,
+                    prefix,
+                } => Self::read_s3(client, bucket, prefix, &mut cursor).await?,
+            });
+            cursors.push((name.clone(), cursor));
+        }
+        state.cursors.extend(cursors);
+
+        for message in messages.iter().filter(|m| !m.trim().is_empty()) {
+            // Skip anything that isn't an invocation record, such as delivery test files.
+            let Ok(record) = serde_json::from_str::<InvocationRecord>(message) else {
+                continue;
+            };
+            if record.timestamp < period_start {
+                continue;
+            }
+            let (model, profile) = resolve_model(&record.model_id);
+            let role = caller_role(&record.identity.arn);
+            let images = record.images();
+            let usage = state
+                .usage
+                .entry([model, role, profile, record.region, record.account_id])
+                .or_insert_with_key(series);
+            if is_embedding_model(&usage.model) {
+                usage.embedding_tokens += record.input.input_token_count;
+            } else {
+                usage.prompt_tokens += record.input.input_token_count;
+            }
+            usage.cached_prompt_tokens += record.input.cache_read_input_token_count;
+            usage.cache_write_tokens += record.input.cache_write_input_token_count;
+            usage.completion_tokens += record.output.output_token_count;
+            usage.request_count += 1;
+            if let Some((size, quality, count)) = images {
+                usage.add_images(&size, &quality, count);
+            }
+        }
+
+        Ok(state
+            .usage
+            .values()
+            .map(|usage| {
+                let mut usage = usage.clone();
+                usage.cost_usd = calculate_bedrock_cost(&self.pricing, &usage);
+                usage
+            })
+            .collect())
+    }
+
+    async fn checkpoint(&self) -> Option<serde_json::Value> {
+        let state = self.state.lock().await;
+        let checkpoint = Checkpoint {
+            source: self.source.clone(),
+            period_start: state.period_start,
+            cursors: state.cursors.clone(),
+            usage: state
+                .usage
+                .iter()
+                .map(|(key, usage)| SavedSeries {
+                    key: key.clone(),
+                    prompt_tokens: usage.prompt_tokens,
+                    cached_prompt_tokens: usage.cached_prompt_tokens,
+                    cache_write_tokens: usage.cache_write_tokens,
+                    completion_tokens: usage.completion_tokens,
+                    embedding_tokens: usage.embedding_tokens,
+                    request_count: usage.request_count,
+                    images_generated: usage.images_generated.clone(),
+                })
+                .collect(),
+        };
+        serde_json::to_value(checkpoint).ok()
+    }
+
+    async fn restore(&self, checkpoint: serde_json::Value) -> Result<(), MonitorError> {
+        let checkpoint: Checkpoint = match serde_json::from_value(checkpoint) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                warn!(error = %e, "ignoring invalid Bedrock invocation log checkpoint");
+                return Ok(());
+            }
+        };
+        // the logs of another source are read from the start of the period
+        if checkpoint.source != self.source {
+            return Ok(());
+        }
+        *self.state.lock().await = LogState {
+            period_start: checkpoint.period_start,
+            cursors: checkpoint.cursors,
+            usage: checkpoint
+                .usage
+                .into_iter()
+                .map(|saved| {
+                    let mut usage = series(&saved.key);
+                    usage.prompt_tokens = saved.prompt_tokens;
+                    usage.cached_prompt_tokens = saved.cached_prompt_tokens;
+                    usage.cache_write_tokens = saved.cache_write_tokens;
+                    usage.completion_tokens = saved.completion_tokens;
+                    usage.embedding_tokens = saved.embedding_tokens;
+                    usage.request_count = saved.request_count;
+                    usage.images_generated = saved.images_generated;
+                    (saved.key, usage)
+                })
+                .collect(),
+        };
+        Ok(())
+    }
+}
+
+/// Splits a logged `modelId` into the base model and the inference profile it went through.
+/// Application inference profiles don't reveal their model, so the profile ID stands in.
+fn resolve_model(model_id: &str) -> (String, String) {
+    let resource = model_id
+        .rsplit_once(":inference-profile/")
+        .map(|(_, id)| id);
+    if let Some(profile) = resource.or_else(|| {
+        ["us.", "eu.", "apac."]
+            .iter()
+            .any(|prefix| model_id.starts_with(prefix))
+            .then_some(model_id)
+    }) {
+        let model = profile.split_once('.').map(|(_, m)| m).unwrap_or(profile);
+        return (model.to_string(), profile.to_string());
+    }
+    if let Some((_, profile)) = model_id.rsplit_once(":application-inference-profile/") {
+        return (profile.to_string(), profile.to_string());
+    }
+    let model = model_id
+        .rsplit_once(":foundation-model/")
+        .map(|(_, m)| m)
+        .unwrap_or(model_id);
+    (model.to_string(), String::new())
+}
+
+/// Normalizes an assumed-role session ARN to the role it was assumed from.
+fn caller_role(arn: &str) -> String {
+    let Some((prefix, resource)) = arn.split_once(":assumed-role/") else {
+        return arn.to_string();
+    };
+    let role_name = resource.split('/').next().unwrap_or(resource);
+    format!(
+        "{}:role/{}",
+        prefix.replacen(":sts:", ":iam:", 1),
+        role_name
+    )
+}
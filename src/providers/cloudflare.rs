@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const GRAPHQL_URL: &str = "https://api.cloudflare.com/client/v4/graphql";
+const USD_PER_1K_NEURONS: f64 = 0.011;
+const FREE_NEURONS_PER_DAY: f64 = 10_000.0;
+
+const USAGE_QUERY: &str = r#"
+query ($accountTag: string!, $start: Time!, $end: Time!) {
+  viewer {
+    accounts(filter: { accountTag: $accountTag }) {
+      aiInferenceAdaptiveGroups(
+        limit: 10000
+        filter: { datetime_geq: $start, datetime_leq: $end }
+      ) {
+        count
+        sum { totalNeurons totalInputTokens totalOutputTokens }
+        dimensions { modelId date }
+      }
+    }
+  }
+}
+"#;
+
+/// Workers AI bills in neurons, with a daily free allocation shared across all models.
+pub struct CloudflareMonitor {
+    api_token: String,
+    account_id: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLResponse {
+    data: Option<GraphQLData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLData {
+    viewer: Viewer,
+}
+
+#[derive(Debug, Deserialize)]
+struct Viewer {
+    accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    ai_inference_adaptive_groups: Vec<InferenceGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InferenceGroup {
+    count: u64,
+    sum: InferenceSum,
+    dimensions: InferenceDimensions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InferenceSum {
+    total_neurons: f64,
+    #[serde(default)]
+    total_input_tokens: u64,
+    #[serde(default)]
+    total_output_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InferenceDimensions {
+    model_id: String,
+    date: String,
+}
+
+impl CloudflareMonitor {
+    pub fn new(api_token: String, account_id: String) -> Self {
+        Self {
+            api_token,
+            account_id,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for CloudflareMonitor {
+    fn provider(&self) -> &'static str {
+        "cloudflare"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let response: GraphQLResponse = self
+            .client
+            .post(GRAPHQL_URL)
+            .bearer_auth(&self.api_token)
+            .json(&json!({
+                "query": USAGE_QUERY,
+                "variables": {
+                    "accountTag": self.account_id,
+                    "start": billing_period_start(self.provider()).to_rfc3339(),
+                    "end": Utc::now().to_rfc3339(),
+                },
+            }))
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        let groups: Vec<InferenceGroup> = response
+            .data
+            .ok_or(MonitorError::InvalidResponse)?
+            .viewer
+            .accounts
+            .into_iter()
+            .flat_map(|account| account.ai_inference_adaptive_groups)
+            .collect();
+
+        let mut neurons_per_day: HashMap<&str, f64> = HashMap::new();
+        for group in &groups {
+            *neurons_per_day.entry(&group.dimensions.date).or_default() += group.sum.total_neurons;
+        }
+
+        // The free allocation is spread across that day's models in proportion to their neurons.
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        for group in &groups {
+            let day_total = neurons_per_day[group.dimensions.date.as_str()];
+            let billable_share = if day_total > 0.0 {
+                (day_total - FREE_NEURONS_PER_DAY).max(0.0) / day_total
+            } else {
+                0.0
+            };
+            let usage = by_model
+                .entry(group.dimensions.model_id.clone())
+                .or_insert_with(|| LLMUsage {
+                    model: group.dimensions.model_id.clone(),
+                    ..LLMUsage::default()
+                });
+            usage.cost_usd +=
+                group.sum.total_neurons * billable_share * USD_PER_1K_NEURONS / 1000.0;
+            usage.prompt_tokens += group.sum.total_input_tokens;
+            usage.completion_tokens += group.sum.total_output_tokens;
+            usage.request_count += group.count;
+        }
+
+        Ok(by_model.into_values().collect())
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for CloudflareMonitor {
+    const TYPE: &'static str = "cloudflare";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("api_token", "CLOUDFLARE_API_TOKEN"),
+        ("account_id", "CLOUDFLARE_ACCOUNT_ID"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["api_token", "account_id"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("api_token")?,
+            settings.require("account_id")?,
+        )))
+    }
+}
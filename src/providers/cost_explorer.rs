@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use chrono::Days;
+use serde::{Deserialize, Serialize};
+
+use super::bedrock::AccountConfig;
+use super::{billing_period_start_date, reporting_date};
+use crate::aws::AwsJsonClient;
+use crate::MonitorError;
+
+/// Fetches what AWS actually billed for Amazon Bedrock, grouped by usage type, as a check on
+/// the token-derived estimates. Every Cost Explorer request is charged, so poll sparingly.
+///
+/// Each account is queried with its own credentials for its own costs only, so a management
+/// account among them doesn't also report the costs of its members.
+pub struct CostExplorerClient {
+    accounts: Vec<(String, AwsJsonClient)>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetCostAndUsageRequest {
+    time_period: DateInterval,
+    granularity: &'static str,
+    metrics: [&'static str; 1],
+    filter: serde_json::Value,
+    group_by: [GroupDefinition; 1],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DateInterval {
+    start: String,
+    end: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GroupDefinition {
+    #[serde(rename = "Type")]
+    group_type: &'static str,
+    key: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetCostAndUsageResponse {
+    #[serde(default)]
+    results_by_time: Vec<ResultByTime>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResultByTime {
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Group {
+    keys: Vec<String>,
+    metrics: HashMap<String, MetricValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetricValue {
+    amount: String,
+}
+
+impl CostExplorerClient {
+    pub fn new(accounts: &[AccountConfig]) -> Result<Self, MonitorError> {
+        Ok(Self {
+            accounts: accounts
+                .iter()
+                .map(|account| {
+                    Ok((
+                        account.account_id.clone(),
+                        AwsJsonClient::cost_explorer(&account.sdk_config)?,
+                    ))
+                })
+                .collect::<Result<_, MonitorError>>()?,
+        })
+    }
+
+    /// Month-to-date unblended Bedrock cost in USD per account and usage type.
+    pub async fn bedrock_costs(&self) -> Result<HashMap<(String, String), f64>, MonitorError> {
+        let mut costs = HashMap::new();
+        for (account_id, client) in &self.accounts {
+            for (usage_type, amount) in Self::account_costs(client, account_id).await? {
+                costs.insert((account_id.clone(), usage_type), amount);
+            }
+        }
+        Ok(costs)
+    }
+
+    async fn account_costs(
+        client: &AwsJsonClient,
+        account_id: &str,
+    ) -> Result<HashMap<String, f64>, MonitorError> {
+        let today = reporting_date();
+        let service = serde_json::json!({
+            "Dimensions": { "Key": "SERVICE", "Values": ["Amazon Bedrock"] }
+        });
+        let filter = match account_id {
+            "" => service,
+            account_id => serde_json::json!({
+                "And": [
+                    service,
+                    { "Dimensions": { "Key": "LINKED_ACCOUNT", "Values": [account_id] } },
+                ]
+            }),
+        };
+        let mut costs = HashMap::new();
+        let mut next_page_token = None;
+
+        loop {
+            let request = GetCostAndUsageRequest {
+                time_period: DateInterval {
+                    start: billing_period_start_date("bedrock").to_string(),
+                    // The end date is exclusive.
+                    end: (today + Days::new(1)).to_string(),
+                },
+                granularity: "MONTHLY",
+                metrics: ["UnblendedCost"],
+                filter: filter.clone(),
+                group_by: [GroupDefinition {
+                    group_type: "DIMENSION",
+                    key: "USAGE_TYPE",
+                }],
+                next_page_token,
+            };
+            let response: GetCostAndUsageResponse =
+                client.call("GetCostAndUsage", &request).await?;
+
+            for group in response.results_by_time.into_iter().flat_map(|r| r.groups) {
+                let usage_type = group.keys.into_iter().next().unwrap_or_default();
+                let amount: f64 = group
+                    .metrics
+                    .get("UnblendedCost")
+                    .and_then(|metric| metric.amount.parse().ok())
+                    .ok_or(MonitorError::InvalidResponse)?;
+                *costs.entry(usage_type).or_default() += amount;
+            }
+
+            match response.next_page_token {
+                Some(token) => next_page_token = Some(token),
+                None => return Ok(costs),
+            }
+        }
+    }
+}
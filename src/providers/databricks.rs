@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start_date, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_DBU_RATE_USD: f64 = 0.07;
+
+/// Reads model serving DBUs from `system.billing.usage` and Foundation Model API token counts
+/// from `system.serving.endpoint_usage` through the SQL Statement Execution API.
+pub struct DatabricksMonitor {
+    host: String,
+    token: String,
+    warehouse_id: String,
+    dbu_rate_usd: f64,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize)]
+struct StatementRequest<'a> {
+    warehouse_id: &'a str,
+    statement: String,
+    wait_timeout: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatementResponse {
+    status: StatementStatus,
+    #[serde(default)]
+    result: StatementResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatementStatus {
+    state: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct StatementResult {
+    #[serde(default)]
+    data_array: Vec<Vec<Option<String>>>,
+}
+
+impl DatabricksMonitor {
+    pub fn new(host: String, token: String, warehouse_id: String) -> Result<Self, MonitorError> {
+        let dbu_rate_usd = match std::env::var("DATABRICKS_DBU_RATE_USD") {
+            Ok(value) => value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid DATABRICKS_DBU_RATE_USD: {}", e))?,
+            Err(_) => DEFAULT_DBU_RATE_USD,
+        };
+        Ok(Self {
+            host: host.trim_end_matches('/').to_string(),
+            token,
+            warehouse_id,
+            dbu_rate_usd,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    async fn query(&self, statement: String) -> Result<Vec<Vec<Option<String>>>, MonitorError> {
+        let response: StatementResponse = self
+            .client
+            .post(format!("{}/api/2.0/sql/statements", self.host))
+            .bearer_auth(&self.token)
+            .json(&StatementRequest {
+                warehouse_id: &self.warehouse_id,
+                statement,
+                wait_timeout: "30s",
+            })
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        if response.status.state != "SUCCEEDED" {
+            return Err(MonitorError::InvalidResponse);
+        }
+        Ok(response.result.data_array)
+    }
+}
+
+fn column<T: std::str::FromStr + Default>(row: &[Option<String>], index: usize) -> T {
+    row.get(index)
+        .and_then(|value| value.as_deref())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl LLMMonitor for DatabricksMonitor {
+    fn provider(&self) -> &'static str {
+        "databricks"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let start = billing_period_start_date(self.provider());
+        let mut by_endpoint: HashMap<String, LLMUsage> = HashMap::new();
+
+        let dbus = self
+            .query(format!(
+                "SELECT usage_metadata.endpoint_name, SUM(usage_quantity) \
+                 FROM system.billing.usage \
+                 WHERE billing_origin_product = 'MODEL_SERVING' \
+                 AND usage_unit = 'DBU' AND usage_date >= '{}' \
+                 GROUP BY usage_metadata.endpoint_name",
+                start
+            ))
+            .await?;
+        for row in dbus {
+            let endpoint: String = column(&row, 0);
+            let quantity: f64 = column(&row, 1);
+            let usage = by_endpoint
+                .entry(endpoint.clone())
+                .or_insert_with(|| LLMUsage {
+                    model: endpoint,
+                    ..LLMUsage::default()
+                });
+            usage.cost_usd += quantity * self.dbu_rate_usd;
+        }
+
+        let tokens = self
+            .query(format!(
+                "SELECT se.endpoint_name, COUNT(*), \
+                 SUM(eu.input_token_count), SUM(eu.output_token_count) \
+                 FROM system.serving.endpoint_usage eu \
+                 JOIN system.serving.served_entities se \
+                 ON eu.served_entity_id = se.served_entity_id \
+                 WHERE eu.request_time >= '{}' \
+                 GROUP BY se.endpoint_name",
+                start
+            ))
+            .await?;
+        for row in tokens {
+            let endpoint: String = column(&row, 0);
+            let usage = by_endpoint
+                .entry(endpoint.clone())
+                .or_insert_with(|| LLMUsage {
+                    model: endpoint,
+                    ..LLMUsage::default()
+                });
+            usage.request_count += column::<u64>(&row, 1);
+            usage.prompt_tokens += column::<u64>(&row, 2);
+            usage.completion_tokens += column::<u64>(&row, 3);
+        }
+
+        Ok(by_endpoint.into_values().collect())
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for DatabricksMonitor {
+    const TYPE: &'static str = "databricks";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("host", "DATABRICKS_HOST"),
+        ("token", "DATABRICKS_TOKEN"),
+        ("warehouse_id", "DATABRICKS_WAREHOUSE_ID"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["host", "token", "warehouse_id"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("host")?,
+            settings.require("token")?,
+            settings.require("warehouse_id")?,
+        )?))
+    }
+}
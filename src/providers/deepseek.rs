@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.deepseek.com";
+
+/// Reports the remaining balance of a DeepSeek account from `GET /user/balance`
+/// (<https://api-docs.deepseek.com/api/get-user-balance>). DeepSeek has no usage API, so
+/// usage comes from calls tracked through the proxy or in process, priced at the off-peak rate
+/// inside [`crate::pricing::OffPeakWindow::deepseek`].
+pub struct DeepSeekMonitor {
+    api_key: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceResponse {
+    #[serde(default)]
+    balance_infos: Vec<BalanceInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BalanceInfo {
+    currency: String,
+    /// Decimal amount as a string, e.g. `"110.00"`.
+    total_balance: String,
+}
+
+impl DeepSeekMonitor {
+    pub fn new(api_key: String) -> Self {
+        let base_url =
+            std::env::var("DEEPSEEK_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            api_key,
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for DeepSeekMonitor {
+    fn provider(&self) -> &'static str {
+        "deepseek"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        Ok(Vec::new())
+    }
+
+    /// The USD balance; accounts topped up in CNY report none.
+    async fn get_credit_balance(&self) -> Result<Option<f64>, MonitorError> {
+        let response: BalanceResponse = self
+            .client
+            .get(format!("{}/user/balance", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        response
+            .balance_infos
+            .iter()
+            .find(|info| info.currency == "USD")
+            .map(|info| {
+                info.total_balance
+                    .parse()
+                    .map_err(|_| MonitorError::InvalidResponse)
+            })
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for DeepSeekMonitor {
+    const TYPE: &'static str = "deepseek";
+    const ENV: &'static [(&'static str, &'static str)] = &[("api_key", "DEEPSEEK_API_KEY")];
+    const ENABLED_BY: &'static [&'static str] = &["api_key"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(settings.require("api_key")?)))
+    }
+}
@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.fireworks.ai";
+
+pub struct FireworksMonitor {
+    api_key: String,
+    account_id: String,
+    base_url: String,
+    client: reqwest::Client,
+    pricing: Pricing,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageResponse {
+    #[serde(default)]
+    usage: Vec<ServerlessUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerlessUsage {
+    model: String,
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    request_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentsResponse {
+    #[serde(default)]
+    deployments: Vec<Deployment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Deployment {
+    base_model: String,
+    state: String,
+    accelerator_type: String,
+    #[serde(default = "default_accelerator_count")]
+    accelerator_count: u32,
+    create_time: DateTime<Utc>,
+}
+
+fn default_accelerator_count() -> u32 {
+    1
+}
+
+impl FireworksMonitor {
+    pub fn new(api_key: String, account_id: String, pricing: Pricing) -> Self {
+        let base_url =
+            std::env::var("FIREWORKS_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            api_key,
+            account_id,
+            base_url,
+            client: reqwest::Client::new(),
+            pricing,
+        }
+    }
+
+    async fn serverless_usage(&self, start: DateTime<Utc>) -> Result<Vec<LLMUsage>, MonitorError> {
+        let response: UsageResponse = self
+            .client
+            .get(format!(
+                "{}/v1/accounts/{}/usage",
+                self.base_url, self.account_id
+            ))
+            .bearer_auth(&self.api_key)
+            .query(&[
+                ("startTime", start.to_rfc3339()),
+                ("endTime", Utc::now().to_rfc3339()),
+            ])
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .usage
+            .into_iter()
+            .map(|entry| {
+                let mut usage = LLMUsage {
+                    model: entry.model,
+                    prompt_tokens: entry.prompt_tokens,
+                    completion_tokens: entry.completion_tokens,
+                    request_count: entry.request_count,
+                    ..LLMUsage::default()
+                };
+                usage.cost_usd = self.pricing.token_cost("fireworks", &usage);
+                usage
+            })
+            .collect())
+    }
+
+    /// On-demand deployments are billed per GPU-hour while they are up, regardless of traffic.
+    async fn deployment_usage(&self, start: DateTime<Utc>) -> Result<Vec<LLMUsage>, MonitorError> {
+        let response: DeploymentsResponse = self
+            .client
+            .get(format!(
+                "{}/v1/accounts/{}/deployments",
+                self.base_url, self.account_id
+            ))
+            .bearer_auth(&self.api_key)
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        let now = Utc::now();
+        Ok(response
+            .deployments
+            .into_iter()
+            .filter(|deployment| deployment.state == "READY")
+            .map(|deployment| {
+                let running_since = deployment.create_time.max(start);
+                let hours = (now - running_since).num_seconds().max(0) as f64 / 3600.0;
+                LLMUsage {
+                    cost_usd: hours
+                        * deployment.accelerator_count as f64
+                        * gpu_hour_rate(&deployment.accelerator_type),
+                    model: deployment.base_model,
+                    ..LLMUsage::default()
+                }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for FireworksMonitor {
+    fn provider(&self) -> &'static str {
+        "fireworks"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let start = billing_period_start(self.provider());
+        let mut usage = self.serverless_usage(start).await?;
+
+        for deployed in self.deployment_usage(start).await? {
+            match usage.iter_mut().find(|u| u.model == deployed.model) {
+                Some(existing) => existing.cost_usd += deployed.cost_usd,
+                None => usage.push(deployed),
+            }
+        }
+
+        Ok(usage)
+    }
+}
+
+/// On-demand deployment prices in USD per GPU-hour.
+fn gpu_hour_rate(accelerator_type: &str) -> f64 {
+    match accelerator_type {
+        "NVIDIA_A100_80GB" => 2.90,
+        "NVIDIA_H100_80GB" => 5.80,
+        "NVIDIA_H200_141GB" => 6.99,
+        "AMD_MI300X_192GB" => 4.99,
+        _ => 0.0,
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for FireworksMonitor {
+    const TYPE: &'static str = "fireworks";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("api_key", "FIREWORKS_API_KEY"),
+        ("account_id", "FIREWORKS_ACCOUNT_ID"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["api_key", "account_id"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("api_key")?,
+            settings.require("account_id")?,
+            pricing.clone(),
+        )))
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Method;
+use serde_json::Value;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{
+    billing_period_start, billing_period_start_date, expand_env, parse_map, reporting_date,
+    LLMMonitor, LLMUsage, RequestExt, ResponseExt,
+};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+/// Model label of a response without per-model records.
+const ALL_MODELS: &str = "all";
+
+/// Reads usage from any JSON endpoint: one request, built from templates, and JSON paths to
+/// the numbers in the response. For providers without a dedicated monitor.
+///
+/// The URL, headers and body may contain `{start}` and `{end}` (RFC 3339) or `{start_date}`
+/// and `{end_date}` (`YYYY-MM-DD` in the reporting timezone) for the current billing period,
+/// and `${VAR}` for environment variables such as API keys. Paths look like
+/// `$.data[*].usage.cost`: fields separated by dots, `[n]` for an index and `[*]` for every
+/// element. Values matched more than once are summed, and numbers may be JSON numbers or
+/// numeric strings.
+pub struct GenericHttpMonitor {
+    provider: &'static str,
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+    rules: ExtractionRules,
+    pricing: Pricing,
+    client: reqwest::Client,
+}
+
+/// Where the usage is in the response.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractionRules {
+    /// Path to the per-model records; without it the whole response is one record.
+    pub items: Option<String>,
+    /// Paths within a record. Without `cost`, the tokens are priced from the pricing catalog.
+    pub model: Option<String>,
+    pub cost: Option<String>,
+    pub prompt_tokens: Option<String>,
+    pub completion_tokens: Option<String>,
+    pub requests: Option<String>,
+    /// Factor turning the reported cost into USD, e.g. `0.01` for cents.
+    pub cost_scale: f64,
+}
+
+enum Segment<'a> {
+    Field(&'a str),
+    Index(usize),
+    All,
+}
+
+fn parse_path(path: &str) -> Vec<Segment<'_>> {
+    path.trim_start_matches('$')
+        .split(['.', '['])
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| match segment.strip_suffix(']') {
+            Some("*") => Segment::All,
+            Some(index) => index.parse().map_or(Segment::Field(index), Segment::Index),
+            None if segment == "*" => Segment::All,
+            None => Segment::Field(segment),
+        })
+        .collect()
+}
+
+/// Every value the path matches.
+fn select<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut matched = vec![value];
+    for segment in parse_path(path) {
+        matched = matched
+            .into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (&segment, value) {
+                    (Segment::Field(field), Value::Object(fields)) => {
+                        fields.get(*field).into_iter().collect()
+                    }
+                    (Segment::Index(index), Value::Array(items)) => {
+                        items.get(*index).into_iter().collect()
+                    }
+                    (Segment::All, Value::Array(items)) => items.iter().collect(),
+                    (Segment::All, Value::Object(fields)) => fields.values().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    matched
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Sum of the numbers a path matches, zero without a path.
+fn sum(record: &Value, path: Option<&str>) -> f64 {
+    path.map_or(0.0, |path| {
+        select(record, path).into_iter().filter_map(number).sum()
+    })
+}
+
+impl GenericHttpMonitor {
+    pub fn new(
+        provider: String,
+        url: String,
+        rules: ExtractionRules,
+        pricing: Pricing,
+    ) -> Result<Self, MonitorError> {
+        if rules.cost.is_none()
+            && rules.prompt_tokens.is_none()
+            && rules.completion_tokens.is_none()
+        {
+            return Err(anyhow::anyhow!(
+                "provider {} needs a cost or token path to read usage from",
+                provider
+            )
+            .into());
+        }
+        Ok(Self {
+            // the label lives as long as the exporter
+            provider: Box::leak(provider.into_boxed_str()),
+            method: Method::GET,
+            url,
+            headers: Vec::new(),
+            body: None,
+            rules,
+            pricing,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Sends `body` with `method` instead of a plain GET.
+    pub fn request(mut self, method: Method, body: Option<String>) -> Self {
+        self.method = method;
+        self.body = body;
+        self
+    }
+
+    pub fn header(mut self, name: String, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    fn render(&self, template: &str) -> String {
+        let start = billing_period_start(self.provider);
+        let end = Utc::now();
+        expand_env(template)
+            .replace("{start}", &start.to_rfc3339())
+            .replace("{end}", &end.to_rfc3339())
+            .replace(
+                "{start_date}",
+                &billing_period_start_date(self.provider).to_string(),
+            )
+            .replace("{end_date}", &reporting_date().to_string())
+    }
+
+    fn record_usage(&self, record: &Value) -> LLMUsage {
+        let rules = &self.rules;
+        let model = rules
+            .model
+            .as_deref()
+            .and_then(|path| select(record, path).into_iter().next())
+            .map(|model| match model {
+                Value::String(model) => model.clone(),
+                model => model.to_string(),
+            })
+            .unwrap_or_else(|| ALL_MODELS.to_string());
+        let mut usage = LLMUsage {
+            model,
+            prompt_tokens: sum(record, rules.prompt_tokens.as_deref()) as u64,
+            completion_tokens: sum(record, rules.completion_tokens.as_deref()) as u64,
+            request_count: sum(record, rules.requests.as_deref()) as u64,
+            ..Default::default()
+        };
+        usage.cost_usd = match &rules.cost {
+            Some(path) => sum(record, Some(path)) * rules.cost_scale,
+            None => self.pricing.token_cost(self.provider, &usage),
+        };
+        usage
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for GenericHttpMonitor {
+    fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let mut request = self
+            .client
+            .request(self.method.clone(), self.render(&self.url));
+        for (name, value) in &self.headers {
+            request = request.header(name, self.render(value));
+        }
+        if let Some(body) = &self.body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(self.render(body));
+        }
+        let response: Value = request.send_timed().await?.check_status()?.json().await?;
+
+        let records = match &self.rules.items {
+            Some(path) => select(&response, path),
+            None => vec![&response],
+        };
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        for record in records {
+            let usage = self.record_usage(record);
+            match by_model.get_mut(&usage.model) {
+                Some(total) => {
+                    total.cost_usd += usage.cost_usd;
+                    total.prompt_tokens += usage.prompt_tokens;
+                    total.completion_tokens += usage.completion_tokens;
+                    total.request_count += usage.request_count;
+                }
+                None => {
+                    by_model.insert(usage.model.clone(), usage);
+                }
+            }
+        }
+        Ok(by_model.into_values().collect())
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for GenericHttpMonitor {
+    const TYPE: &'static str = "http";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("name", "HTTP_PROVIDER_NAME"),
+        ("url", "HTTP_PROVIDER_URL"),
+        ("method", "HTTP_PROVIDER_METHOD"),
+        ("headers", "HTTP_PROVIDER_HEADERS"),
+        ("body", "HTTP_PROVIDER_BODY"),
+        ("items", "HTTP_PROVIDER_ITEMS"),
+        ("model", "HTTP_PROVIDER_MODEL"),
+        ("cost", "HTTP_PROVIDER_COST"),
+        ("cost_scale", "HTTP_PROVIDER_COST_SCALE"),
+        ("prompt_tokens", "HTTP_PROVIDER_PROMPT_TOKENS"),
+        ("completion_tokens", "HTTP_PROVIDER_COMPLETION_TOKENS"),
+        ("requests", "HTTP_PROVIDER_REQUESTS"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["name", "url"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        let name = settings.require("name")?;
+        let cost_scale = match settings.get("cost_scale") {
+            Some(scale) => scale
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid cost_scale {:?} of {}", scale, name))?,
+            None => 1.0,
+        };
+        let method = match settings.get("method") {
+            Some(method) => method
+                .to_uppercase()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid method {:?} of {}", method, name))?,
+            None => Method::GET,
+        };
+        let rules = ExtractionRules {
+            items: settings.get("items"),
+            model: settings.get("model"),
+            cost: settings.get("cost"),
+            prompt_tokens: settings.get("prompt_tokens"),
+            completion_tokens: settings.get("completion_tokens"),
+            requests: settings.get("requests"),
+            cost_scale,
+        };
+        let mut monitor = Self::new(name, settings.require("url")?, rules, pricing.clone())?
+            .request(method, settings.get("body"));
+        // `name=value` pairs, or a map in the config file
+        for (header, value) in parse_map(&settings.get("headers").unwrap_or_default()) {
+            monitor = monitor.header(header, value);
+        }
+        Ok(Box::new(monitor))
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt, UptimeMeter};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.endpoints.huggingface.cloud";
+
+/// Dedicated Inference Endpoints are billed per instance-hour while running, so uptime is
+/// sampled on every poll and combined with each endpoint's request count.
+pub struct HuggingFaceMonitor {
+    token: String,
+    namespace: String,
+    base_url: String,
+    client: reqwest::Client,
+    uptime: Mutex<UptimeMeter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EndpointList {
+    items: Vec<Endpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Endpoint {
+    name: String,
+    model: EndpointModel,
+    compute: EndpointCompute,
+    status: EndpointStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct EndpointModel {
+    repository: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointCompute {
+    instance_type: String,
+    instance_size: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointStatus {
+    state: String,
+    #[serde(default)]
+    ready_replica: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetricSeries {
+    #[serde(default)]
+    data: Vec<MetricPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetricPoint {
+    value: f64,
+}
+
+impl HuggingFaceMonitor {
+    pub fn new(token: String, namespace: String) -> Self {
+        let base_url =
+            std::env::var("HF_ENDPOINTS_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            token,
+            namespace,
+            base_url,
+            client: reqwest::Client::new(),
+            uptime: Mutex::new(UptimeMeter::default()),
+        }
+    }
+
+    async fn request_count(
+        &self,
+        endpoint: &str,
+        start: DateTime<Utc>,
+    ) -> Result<u64, MonitorError> {
+        let series: MetricSeries = self
+            .client
+            .get(format!(
+                "{}/v2/endpoint/{}/{}/metrics/request_count",
+                self.base_url, self.namespace, endpoint
+            ))
+            .bearer_auth(&self.token)
+            .query(&[
+                ("from", start.to_rfc3339()),
+                ("to", Utc::now().to_rfc3339()),
+            ])
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        Ok(series.data.iter().map(|point| point.value).sum::<f64>() as u64)
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for HuggingFaceMonitor {
+    fn provider(&self) -> &'static str {
+        "huggingface"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let endpoints: EndpointList = self
+            .client
+            .get(format!("{}/v2/endpoint/{}", self.base_url, self.namespace))
+            .bearer_auth(&self.token)
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        {
+            let mut uptime = self.uptime.lock().unwrap();
+            let hours = uptime.sample(self.provider());
+            for endpoint in endpoints
+                .items
+                .iter()
+                .filter(|e| e.status.state == "running")
+            {
+                uptime.accrue(
+                    &endpoint.name,
+                    hours
+                        * endpoint.status.ready_replica as f64
+                        * instance_hour_rate(&endpoint.compute),
+                );
+            }
+        }
+
+        // Several endpoints can serve the same repository; they share one `model` series.
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        for endpoint in endpoints.items {
+            let request_count = self
+                .request_count(&endpoint.name, billing_period_start(self.provider()))
+                .await?;
+            let cost_usd = self.uptime.lock().unwrap().cost(&endpoint.name);
+            let usage = by_model
+                .entry(endpoint.model.repository.clone())
+                .or_insert_with(|| LLMUsage {
+                    model: endpoint.model.repository,
+                    ..LLMUsage::default()
+                });
+            usage.cost_usd += cost_usd;
+            usage.request_count += request_count;
+        }
+
+        Ok(by_model.into_values().collect())
+    }
+}
+
+/// Instance prices in USD per hour for a single accelerator, scaled by instance size (`x1`,
+/// `x4`, ...).
+fn instance_hour_rate(compute: &EndpointCompute) -> f64 {
+    let per_unit = match compute.instance_type.as_str() {
+        "nvidia-t4" => 0.50,
+        "nvidia-l4" => 0.80,
+        "nvidia-a10g" => 1.00,
+        "nvidia-l40s" => 1.80,
+        "nvidia-a100" => 4.00,
+        "nvidia-h100" => 10.00,
+        "intel-spr" | "intel-icl" => 0.067,
+        _ => 0.0,
+    };
+    let units: f64 = compute
+        .instance_size
+        .trim_start_matches('x')
+        .parse()
+        .unwrap_or(1.0);
+    per_unit * units
+}
+
+#[async_trait]
+impl ProviderFactory for HuggingFaceMonitor {
+    const TYPE: &'static str = "huggingface";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("token", "HF_TOKEN"),
+        ("namespace", "HF_ENDPOINTS_NAMESPACE"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["token", "namespace"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("token")?,
+            settings.require("namespace")?,
+        )))
+    }
+}
@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::LLMMetrics;
+use crate::pricing::Pricing;
+use crate::MonitorError;
+pub use registry::{ProviderEntry, ProviderFactory, ProviderRegistry, ProviderSettings, Shard};
+
+pub mod anthropic;
+pub mod bedrock;
+pub mod bedrock_logs;
+pub mod cloudflare;
+pub mod cost_explorer;
+pub mod databricks;
+pub mod deepseek;
+pub mod fireworks;
+pub mod http;
+pub mod huggingface;
+pub mod nim;
+pub mod ollama;
+pub mod openai;
+pub mod openrouter;
+pub mod registry;
+pub mod replicate;
+pub mod sagemaker;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod xai;
+
+#[async_trait]
+pub trait LLMMonitor: Send + Sync {
+    /// Value used for the `provider` label on every series this monitor produces.
+    fn provider(&self) -> &'static str;
+
+    /// Returns month-to-date usage, one entry per model.
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError>;
+
+    /// Remaining prepaid balance in USD, for providers that sell credits.
+    async fn get_credit_balance(&self) -> Result<Option<f64>, MonitorError> {
+        Ok(None)
+    }
+
+    /// Fine-tuning jobs created in the current billing period, for providers that bill
+    /// training separately from inference.
+    async fn get_fine_tuning_jobs(&self) -> Result<Vec<FineTuningJob>, MonitorError> {
+        Ok(Vec::new())
+    }
+
+    /// Month-to-date cost of built-in tools billed outside token usage, such as web search
+    /// calls or file storage, for providers that itemize them. Called after `get_usage` in the
+    /// same poll, so a monitor may return what that read.
+    async fn get_tool_costs(&self) -> Result<Vec<ToolCost>, MonitorError> {
+        Ok(Vec::new())
+    }
+
+    /// Progress to save across restarts, such as how far logs have been read, for monitors
+    /// that can't read their whole billing period again on every poll.
+    async fn checkpoint(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Resumes from the checkpoint a previous run saved.
+    async fn restore(&self, _checkpoint: serde_json::Value) -> Result<(), MonitorError> {
+        Ok(())
+    }
+}
+
+/// Month-to-date cost of one built-in tool, e.g. `web_search` or `code_interpreter`.
+#[derive(Debug, Clone)]
+pub struct ToolCost {
+    pub tool: &'static str,
+    pub cost_usd: f64,
+}
+
+/// A fine-tuning job and the cost of its training so far.
+#[derive(Debug, Clone)]
+pub struct FineTuningJob {
+    pub job_id: String,
+    /// Base model being fine-tuned.
+    pub model: String,
+    pub trained_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Optional dimensions some monitors attach to usage. Series from monitors that don't set a
+/// label leave it empty, which Prometheus treats the same as the label being absent.
+pub const USAGE_LABELS: &[&str] = &[
+    "organization_id",
+    "project_id",
+    "project_name",
+    "iam_role",
+    "inference_profile",
+    "region",
+    "aws_account_id",
+    "service_tier",
+    "team",
+    "cost_center",
+];
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct LLMUsage {
+    pub model: String,
+    pub cost_usd: f64,
+    /// Portion of `cost_usd` billed per request rather than per token (e.g. search fees).
+    pub request_cost_usd: f64,
+    /// Input tokens billed at the regular rate, excluding cache reads and writes.
+    pub prompt_tokens: u64,
+    /// Input tokens read from the prompt cache.
+    pub cached_prompt_tokens: u64,
+    /// Input tokens written to the prompt cache.
+    pub cache_write_tokens: u64,
+    pub completion_tokens: u64,
+    /// Input tokens of embedding models, which have no completion.
+    pub embedding_tokens: u64,
+    pub request_count: u64,
+    /// Images generated, by size and quality.
+    pub images_generated: Vec<ImageCount>,
+    /// Minutes of audio transcribed or translated by speech-to-text models.
+    pub transcription_minutes: f64,
+    /// Characters synthesized by text-to-speech models.
+    pub speech_characters: u64,
+    /// Values for [`USAGE_LABELS`], keyed by label name.
+    pub labels: BTreeMap<&'static str, String>,
+    /// Tier the request fees are billed at, e.g. the search context size of a Perplexity call.
+    #[serde(skip)]
+    pub request_tier: Option<String>,
+}
+
+impl LLMUsage {
+    /// How the usage was served, e.g. `batch`, if the monitor distinguishes it.
+    pub fn service_tier(&self) -> Option<&str> {
+        self.labels.get("service_tier").map(String::as_str)
+    }
+
+    /// Token counts by the `type` label they are exported under.
+    pub fn token_counts(&self) -> [(&'static str, u64); 5] {
+        [
+            ("prompt", self.prompt_tokens),
+            ("cached_prompt", self.cached_prompt_tokens),
+            ("cache_write", self.cache_write_tokens),
+            ("completion", self.completion_tokens),
+            ("embedding", self.embedding_tokens),
+        ]
+    }
+
+    /// Adds generated images to the count of their size and quality.
+    pub fn add_images(&mut self, size: &str, quality: &str, count: u64) {
+        match self
+            .images_generated
+            .iter_mut()
+            .find(|images| images.size == size && images.quality == quality)
+        {
+            Some(images) => images.count += count,
+            None => self.images_generated.push(ImageCount {
+                size: size.to_string(),
+                quality: quality.to_string(),
+                count,
+            }),
+        }
+    }
+
+    /// Adds another record's usage to this one, keeping this record's model and labels.
+    pub fn add(&mut self, other: &LLMUsage) {
+        self.cost_usd += other.cost_usd;
+        self.request_cost_usd += other.request_cost_usd;
+        self.prompt_tokens += other.prompt_tokens;
+        self.cached_prompt_tokens += other.cached_prompt_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.embedding_tokens += other.embedding_tokens;
+        self.request_count += other.request_count;
+        for images in &other.images_generated {
+            self.add_images(&images.size, &images.quality, images.count);
+        }
+        self.transcription_minutes += other.transcription_minutes;
+        self.speech_characters += other.speech_characters;
+    }
+
+    pub fn images_total(&self) -> u64 {
+        self.images_generated
+            .iter()
+            .map(|images| images.count)
+            .sum()
+    }
+}
+
+/// Images generated at one size (e.g. `1024x1024`) and quality (e.g. `standard`, `hd`,
+/// `premium`). Either is empty when the provider doesn't report it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageCount {
+    pub size: String,
+    pub quality: String,
+    pub count: u64,
+}
+
+/// Creates a monitor for every provider configured in the environment.
+pub async fn from_env(pricing: &Pricing) -> Result<Vec<Box<dyn LLMMonitor>>, MonitorError> {
+    ProviderRegistry::builtin().monitors(None, pricing).await
+}
+
+/// Whether a model ID names an embedding model, e.g. `amazon.titan-embed-text-v2:0` or
+/// `cohere.embed-english-v3`.
+pub(crate) fn is_embedding_model(model: &str) -> bool {
+    model.contains("embed")
+}
+
+/// Day of the month each provider's billing period starts on, and the timezone whose midnight
+/// it starts at.
+#[derive(Debug)]
+struct BillingAnchors {
+    default: u32,
+    providers: HashMap<String, u32>,
+    timezone: Tz,
+}
+
+static BILLING_ANCHORS: OnceLock<BillingAnchors> = OnceLock::new();
+
+/// Reads the billing period anchor days: `BILLING_ANCHOR_DAY` (default 1) for every provider
+/// and `BILLING_ANCHOR_DAYS` per provider, e.g. `openai=1,bedrock=15`. Days past the end of a
+/// month anchor on its last day. Periods start at midnight in `REPORTING_TIMEZONE`, an IANA
+/// name such as `America/New_York` (default `UTC`). Until this runs every period starts on the
+/// 1st at midnight UTC.
+pub fn init_billing_periods() -> Result<(), MonitorError> {
+    let parse_day = |name: &str, value: &str| {
+        value
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|day| (1..=31).contains(day))
+            .ok_or_else(|| {
+                anyhow::anyhow!("invalid {} {:?}, expected a day from 1 to 31", name, value)
+            })
+    };
+    let default = match std::env::var("BILLING_ANCHOR_DAY") {
+        Ok(day) if !day.is_empty() => parse_day("BILLING_ANCHOR_DAY", &day)?,
+        _ => 1,
+    };
+    let providers = env_map("BILLING_ANCHOR_DAYS")
+        .into_iter()
+        .map(|(provider, day)| Ok((provider, parse_day("BILLING_ANCHOR_DAYS", &day)?)))
+        .collect::<Result<_, MonitorError>>()?;
+    let timezone = match std::env::var("REPORTING_TIMEZONE") {
+        Ok(timezone) if !timezone.is_empty() => timezone.trim().parse::<Tz>().map_err(|_| {
+            anyhow::anyhow!(
+                "invalid REPORTING_TIMEZONE {:?}, expected an IANA timezone such as America/New_York",
+                timezone
+            )
+        })?,
+        _ => Tz::UTC,
+    };
+    // a second call keeps the anchors of the first
+    let _ = BILLING_ANCHORS.set(BillingAnchors {
+        default,
+        providers,
+        timezone,
+    });
+    Ok(())
+}
+
+fn anchor_day(provider: &str) -> u32 {
+    BILLING_ANCHORS.get().map_or(1, |anchors| {
+        anchors
+            .providers
+            .get(provider)
+            .copied()
+            .unwrap_or(anchors.default)
+    })
+}
+
+/// Timezone that billing periods and reports follow.
+pub(crate) fn reporting_timezone() -> Tz {
+    BILLING_ANCHORS
+        .get()
+        .map_or(Tz::UTC, |anchors| anchors.timezone)
+}
+
+/// Current date in the reporting timezone, the last day of usage queries that take dates.
+pub(crate) fn reporting_date() -> NaiveDate {
+    Utc::now().with_timezone(&reporting_timezone()).date_naive()
+}
+
+/// First instant of `date` in the reporting timezone. Where a DST change skips midnight the day
+/// starts at the first hour that exists.
+pub(crate) fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    let timezone = reporting_timezone();
+    (0..24)
+        .find_map(|hour| {
+            timezone
+                .from_local_datetime(&date.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .map_or_else(
+            || date.and_time(NaiveTime::MIN).and_utc(),
+            |start| start.with_timezone(&Utc),
+        )
+}
+
+/// The anchor day in a month, or the month's last day when it is shorter.
+fn anchor_date(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or_default()
+}
+
+/// Start of the provider's current billing period, used as the lower bound of usage queries.
+pub(crate) fn billing_period_start(provider: &str) -> DateTime<Utc> {
+    billing_period_start_at(provider, Utc::now())
+}
+
+/// First day of the provider's current billing period in the reporting timezone, for usage
+/// APIs that take dates rather than instants.
+pub(crate) fn billing_period_start_date(provider: &str) -> NaiveDate {
+    billing_period_start(provider)
+        .with_timezone(&reporting_timezone())
+        .date_naive()
+}
+
+/// Start of the provider's billing period that `time` falls in, at midnight in the reporting
+/// timezone.
+pub(crate) fn billing_period_start_at(provider: &str, time: DateTime<Utc>) -> DateTime<Utc> {
+    let day = anchor_day(provider);
+    let date = time.with_timezone(&reporting_timezone()).date_naive();
+    let mut start = anchor_date(date.year(), date.month(), day);
+    if start > date {
+        let previous = date - Months::new(1);
+        start = anchor_date(previous.year(), previous.month(), day);
+    }
+    start_of_day(start)
+}
+
+/// End of the provider's billing period starting at `period_start`, i.e. the next one's start.
+pub(crate) fn billing_period_end(provider: &str, period_start: DateTime<Utc>) -> DateTime<Utc> {
+    let next = period_start
+        .with_timezone(&reporting_timezone())
+        .date_naive()
+        .with_day(1)
+        .unwrap_or_default()
+        + Months::new(1);
+    start_of_day(anchor_date(next.year(), next.month(), anchor_day(provider)))
+}
+
+/// Times the API requests of the monitor being polled in `llm_provider_api_duration_seconds`.
+#[derive(Clone)]
+pub struct ApiTimer {
+    metrics: Arc<LLMMetrics>,
+    provider: &'static str,
+}
+
+tokio::task_local! {
+    static API_TIMER: ApiTimer;
+}
+
+impl ApiTimer {
+    pub fn new(metrics: Arc<LLMMetrics>, provider: &'static str) -> Self {
+        Self { metrics, provider }
+    }
+
+    /// Runs `poll`, timing the requests it makes with [`timed`].
+    pub async fn scope<T>(self, poll: impl Future<Output = T>) -> T {
+        API_TIMER.scope(self, poll).await
+    }
+
+    /// The timer of the poll running in this task, to carry over to tasks it hands work to.
+    pub(crate) fn current() -> Option<Self> {
+        API_TIMER.try_with(Clone::clone).ok()
+    }
+}
+
+/// Runs one request to a provider API and observes how long it took to respond under
+/// `endpoint`, when made by a poll in an [`ApiTimer::scope`].
+pub(crate) async fn timed<T>(endpoint: &str, request: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let response = request.await;
+    if let Some(timer) = ApiTimer::current() {
+        timer
+            .metrics
+            .observe_api_duration(timer.provider, endpoint, started.elapsed());
+    }
+    response
+}
+
+/// Sends provider requests.
+pub(crate) trait RequestExt {
+    /// Like `send`, but [`timed`] under the request's URL path, e.g.
+    /// `/v1/organization/costs`.
+    async fn send_timed(self) -> Result<reqwest::Response, reqwest::Error>;
+}
+
+impl RequestExt for reqwest::RequestBuilder {
+    async fn send_timed(self) -> Result<reqwest::Response, reqwest::Error> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let path = request.url().path().to_string();
+        timed(&path, client.execute(request)).await
+    }
+}
+
+/// Checks the status of provider responses.
+pub(crate) trait ResponseExt: Sized {
+    /// Like `error_for_status`, but turns a `429` into [`MonitorError::RateLimited`] with the
+    /// wait the provider asked for in `Retry-After`.
+    fn check_status(self) -> Result<Self, MonitorError>;
+}
+
+impl ResponseExt for reqwest::Response {
+    fn check_status(self) -> Result<Self, MonitorError> {
+        if self.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = self
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after);
+            return Err(MonitorError::RateLimited { retry_after });
+        }
+        Ok(self.error_for_status()?)
+    }
+}
+
+/// Reads a `Retry-After` header, given either in seconds or as an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Reads a `key=value,key=value` environment variable into a map, ignoring malformed pairs.
+pub(crate) fn env_map(name: &str) -> HashMap<String, String> {
+    parse_map(&std::env::var(name).unwrap_or_default())
+}
+
+/// Reads `key=value,key=value` pairs into a map, ignoring malformed pairs. Pairs on separate
+/// lines are split at the line breaks alone, for values that contain commas.
+pub(crate) fn parse_map(pairs: &str) -> HashMap<String, String> {
+    let separator = if pairs.contains('\n') { '\n' } else { ',' };
+    pairs
+        .split(separator)
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Replaces `${VAR}` with the environment variable, empty when unset.
+pub(crate) fn expand_env(template: &str) -> String {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&std::env::var(&rest[start + 2..start + end]).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Accrues time-based charges (instance-hours, GPU-hours) between polls, resetting whenever a
+/// new billing period starts.
+#[derive(Default)]
+pub(crate) struct UptimeMeter {
+    period_start: Option<DateTime<Utc>>,
+    last_poll: Option<DateTime<Utc>>,
+    cost: HashMap<String, f64>,
+}
+
+impl UptimeMeter {
+    /// Starts a new sample and returns the hours elapsed since the previous one.
+    pub(crate) fn sample(&mut self, provider: &str) -> f64 {
+        let now = Utc::now();
+        let period_start = billing_period_start(provider);
+        if self.period_start != Some(period_start) {
+            self.period_start = Some(period_start);
+            self.last_poll = None;
+            self.cost.clear();
+        }
+        let hours = self
+            .last_poll
+            .map(|last_poll| (now - last_poll).num_seconds().max(0) as f64 / 3600.0)
+            .unwrap_or_default();
+        self.last_poll = Some(now);
+        hours
+    }
+
+    pub(crate) fn accrue(&mut self, key: &str, cost: f64) {
+        *self.cost.entry(key.to_string()).or_default() += cost;
+    }
+
+    pub(crate) fn cost(&self, key: &str) -> f64 {
+        self.cost.get(key).copied().unwrap_or_default()
+    }
+}
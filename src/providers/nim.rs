@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{env_map, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::{ModelPrice, Pricing};
+use crate::MonitorError;
+
+/// Scrapes the Prometheus metrics of NVIDIA NIM containers and prices their token counters
+/// with the `nim` catalog entries, overridden by `NIM_PRICING` (`model=input:output`, USD per
+/// 1M tokens).
+/// Counters are cumulative since each container started.
+pub struct NimMonitor {
+    endpoints: Vec<String>,
+    api_key: Option<String>,
+    overrides: HashMap<String, ModelPrice>,
+    pricing: Pricing,
+    client: reqwest::Client,
+}
+
+impl NimMonitor {
+    pub fn new(
+        endpoints: Vec<String>,
+        api_key: Option<String>,
+        pricing: Pricing,
+    ) -> Result<Self, MonitorError> {
+        let mut overrides = HashMap::new();
+        for (model, rates) in env_map("NIM_PRICING") {
+            let parsed = rates
+                .split_once(':')
+                .and_then(|(input, output)| {
+                    Some(ModelPrice {
+                        input: input.parse().ok()?,
+                        output: output.parse().ok()?,
+                        ..ModelPrice::default()
+                    })
+                })
+                .ok_or_else(|| {
+                    anyhow::anyhow!("invalid NIM_PRICING rates for {}: {:?}", model, rates)
+                })?;
+            overrides.insert(model, parsed);
+        }
+        Ok(Self {
+            endpoints,
+            api_key,
+            overrides,
+            pricing,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn cost(&self, usage: &LLMUsage) -> f64 {
+        self.overrides
+            .get(&usage.model)
+            .cloned()
+            .or_else(|| self.pricing.price("nim", &usage.model))
+            .map(|price| price.token_cost(usage))
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for NimMonitor {
+    fn provider(&self) -> &'static str {
+        "nim"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+
+        for endpoint in &self.endpoints {
+            let mut request = self
+                .client
+                .get(format!("{}/v1/metrics", endpoint.trim_end_matches('/')));
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            let body = request.send_timed().await?.check_status()?.text().await?;
+
+            for (name, model, value) in parse_samples(&body) {
+                let usage = by_model.entry(model.clone()).or_insert_with(|| LLMUsage {
+                    model,
+                    ..LLMUsage::default()
+                });
+                match name {
+                    "prompt_tokens_total" => usage.prompt_tokens += value as u64,
+                    "generation_tokens_total" => usage.completion_tokens += value as u64,
+                    "request_success_total" => usage.request_count += value as u64,
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(by_model
+            .into_values()
+            .map(|mut usage| {
+                usage.cost_usd = self.cost(&usage);
+                usage
+            })
+            .collect())
+    }
+}
+
+/// Extracts `(metric, model_name, value)` from the token and request counters in a
+/// Prometheus text exposition, skipping everything else.
+fn parse_samples(body: &str) -> Vec<(&'static str, String, f64)> {
+    const COUNTERS: [&str; 3] = [
+        "prompt_tokens_total",
+        "generation_tokens_total",
+        "request_success_total",
+    ];
+
+    body.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (series, value) = line.rsplit_once(' ')?;
+            let (name, labels) = series.split_once('{')?;
+            let name = COUNTERS.iter().find(|counter| **counter == name)?;
+            let model = labels
+                .trim_end_matches('}')
+                .split(',')
+                .filter_map(|label| label.split_once('='))
+                .find(|(key, _)| *key == "model_name")
+                .map(|(_, value)| value.trim_matches('"').to_string())?;
+            Some((*name, model, value.parse().ok()?))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ProviderFactory for NimMonitor {
+    const TYPE: &'static str = "nim";
+    const ENV: &'static [(&'static str, &'static str)] =
+        &[("endpoints", "NIM_ENDPOINTS"), ("api_key", "NIM_API_KEY")];
+    const ENABLED_BY: &'static [&'static str] = &["endpoints"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        let endpoints = settings
+            .require("endpoints")?
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .collect();
+        Ok(Box::new(Self::new(
+            endpoints,
+            settings.get("api_key"),
+            pricing.clone(),
+        )?))
+    }
+}
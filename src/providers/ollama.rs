@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::sinks::{CallEvent, CallSink};
+use crate::MonitorError;
+
+/// Prompt and completion tokens and requests of the Ollama calls tracked in process since the
+/// last poll, by model. Ollama doesn't count tokens across requests, so only calls made through
+/// the proxy or a tracker with [`OllamaTokens`] are counted.
+static SERVED_TOKENS: Mutex<BTreeMap<String, (u64, u64, u64)>> = Mutex::new(BTreeMap::new());
+
+/// Passes the tokens of tracked calls to `provider="ollama"` on to the Ollama monitor, which
+/// prices them at `OLLAMA_COST_PER_1K_TOKENS`.
+pub struct OllamaTokens;
+
+impl CallSink for OllamaTokens {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn publish(&self, event: &CallEvent) -> Result<(), MonitorError> {
+        if event.provider != "ollama" || event.status != "success" {
+            return Ok(());
+        }
+        let mut served = SERVED_TOKENS.lock().unwrap();
+        let (prompt, completion, requests) = served.entry(event.model.clone()).or_default();
+        *prompt += event.prompt_tokens + event.cached_prompt_tokens;
+        *completion += event.completion_tokens;
+        *requests += 1;
+        Ok(())
+    }
+}
+
+/// Estimates the cost of self-hosted inference. Ollama has no bill, so cost is derived from
+/// how long each model stays resident on the GPU (`/api/ps`) and from the tokens it served.
+pub struct OllamaMonitor {
+    base_url: String,
+    cost_per_1k_tokens: f64,
+    cost_per_gpu_hour: f64,
+    client: reqwest::Client,
+    state: Mutex<OllamaState>,
+}
+
+#[derive(Default)]
+struct OllamaState {
+    period_start: Option<DateTime<Utc>>,
+    last_poll: Option<DateTime<Utc>>,
+    usage: HashMap<String, LLMUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunningModels {
+    models: Vec<RunningModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunningModel {
+    name: String,
+    #[serde(default)]
+    size_vram: u64,
+}
+
+impl OllamaMonitor {
+    /// `host` follows `OLLAMA_HOST` conventions, so the scheme may be omitted.
+    pub fn new(host: String) -> Result<Self, MonitorError> {
+        let base_url = if host.contains("://") {
+            host
+        } else {
+            format!("http://{}", host)
+        };
+        Ok(Self {
+            base_url,
+            cost_per_1k_tokens: env_rate("OLLAMA_COST_PER_1K_TOKENS")?,
+            cost_per_gpu_hour: env_rate("OLLAMA_COST_PER_GPU_HOUR")?,
+            client: reqwest::Client::new(),
+            state: Mutex::new(OllamaState::default()),
+        })
+    }
+
+    /// Adds tokens served by a local model, priced at the per-1K-token rate.
+    fn record_tokens(
+        &self,
+        state: &mut OllamaState,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        requests: u64,
+    ) {
+        let usage = state.usage(model);
+        usage.prompt_tokens += prompt_tokens;
+        usage.completion_tokens += completion_tokens;
+        usage.request_count += requests;
+        usage.cost_usd +=
+            (prompt_tokens + completion_tokens) as f64 * self.cost_per_1k_tokens / 1000.0;
+    }
+}
+
+impl OllamaState {
+    fn usage(&mut self, model: &str) -> &mut LLMUsage {
+        self.usage
+            .entry(model.to_string())
+            .or_insert_with(|| LLMUsage {
+                model: model.to_string(),
+                ..LLMUsage::default()
+            })
+    }
+}
+
+fn env_rate(name: &str) -> Result<f64, MonitorError> {
+    match std::env::var(name) {
+        Ok(value) => Ok(value
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid {}: {}", name, e))?),
+        Err(_) => Ok(0.0),
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for OllamaMonitor {
+    fn provider(&self) -> &'static str {
+        "ollama"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let running: RunningModels = self
+            .client
+            .get(format!("{}/api/ps", self.base_url))
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        let now = Utc::now();
+        let period_start = billing_period_start(self.provider());
+        let mut state = self.state.lock().unwrap();
+
+        if state.period_start != Some(period_start) {
+            state.period_start = Some(period_start);
+            state.last_poll = None;
+            state.usage.clear();
+        }
+
+        // Only models loaded into VRAM occupy the GPU; time is attributed since the last poll.
+        if let Some(last_poll) = state.last_poll {
+            let hours = (now - last_poll).num_seconds().max(0) as f64 / 3600.0;
+            for model in running.models.iter().filter(|m| m.size_vram > 0) {
+                state.usage(&model.name).cost_usd += hours * self.cost_per_gpu_hour;
+            }
+        }
+        let served = std::mem::take(&mut *SERVED_TOKENS.lock().unwrap());
+        for (model, (prompt_tokens, completion_tokens, requests)) in served {
+            self.record_tokens(
+                &mut state,
+                &model,
+                prompt_tokens,
+                completion_tokens,
+                requests,
+            );
+        }
+        state.last_poll = Some(now);
+
+        Ok(state.usage.values().cloned().collect())
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for OllamaMonitor {
+    const TYPE: &'static str = "ollama";
+    const ENV: &'static [(&'static str, &'static str)] = &[("host", "OLLAMA_HOST")];
+    const ENABLED_BY: &'static [&'static str] = &["host"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(settings.require("host")?)?))
+    }
+}
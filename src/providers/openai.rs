@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{
+    billing_period_start, FineTuningJob, LLMMonitor, LLMUsage, RequestExt, ResponseExt, ToolCost,
+};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com";
+
+/// Reads organization usage and costs from the OpenAI Usage and Costs APIs, which require an
+/// admin key. Embedding models are read from the embeddings usage endpoint and reported as
+/// `type="embedding"` tokens, image and audio models from their own usage endpoints. Batch API
+/// usage is reported separately with `service_tier="batch"`. With `by_project` set, usage is
+/// also split by project so every series carries `project_id` and `project_name` labels.
+/// Every series is labeled with the `organization_id` configured, or else the one the API
+/// reports in its `openai-organization` response header.
+///
+/// Built-in tools (web search, file search, file search storage, code interpreter) are billed
+/// per call, session or GB-day rather than per token; their cost line items are reported by tool
+/// only, and left out of the model costs. They are read from the same costs pages as the model
+/// costs, so the costs are fetched once per poll.
+///
+/// Fine-tuning jobs are listed with a project API key, since admin keys cannot read them, and
+/// priced from the `training` and `training_hour` prices of the base model.
+pub struct OpenAIMonitor {
+    admin_key: String,
+    base_url: String,
+    by_project: bool,
+    fine_tuning_key: Option<String>,
+    /// Organization ID the usage belongs to, once configured or reported by the API.
+    organization: Mutex<Option<String>>,
+    /// Tool costs read along with the usage, until the poll reports them.
+    tool_costs: Mutex<Option<Vec<ToolCost>>>,
+    pricing: Pricing,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    data: Vec<Bucket<T>>,
+    has_more: bool,
+    next_page: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Bucket<T> {
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectsPage {
+    data: Vec<Project>,
+    has_more: bool,
+    last_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Project {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FineTuningJobsPage {
+    data: Vec<FineTuningJobObject>,
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FineTuningJobObject {
+    id: String,
+    model: String,
+    created_at: i64,
+    finished_at: Option<i64>,
+    trained_tokens: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionsResult {
+    model: Option<String>,
+    project_id: Option<String>,
+    batch: Option<bool>,
+    /// Includes `input_cached_tokens`.
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    input_cached_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    num_model_requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResult {
+    model: Option<String>,
+    project_id: Option<String>,
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    num_model_requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImagesResult {
+    model: Option<String>,
+    project_id: Option<String>,
+    /// e.g. `1024x1024`; quality is not reported.
+    size: Option<String>,
+    #[serde(default)]
+    images: u64,
+    #[serde(default)]
+    num_model_requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioSpeechesResult {
+    model: Option<String>,
+    project_id: Option<String>,
+    #[serde(default)]
+    characters: u64,
+    #[serde(default)]
+    num_model_requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioTranscriptionsResult {
+    model: Option<String>,
+    project_id: Option<String>,
+    #[serde(default)]
+    seconds: u64,
+    #[serde(default)]
+    num_model_requests: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CostsResult {
+    amount: Amount,
+    project_id: Option<String>,
+    /// e.g. `"gpt-4o-2024-08-06, input"`; the model is everything before the comma and batch
+    /// usage is marked in the rest, e.g. `"gpt-4o-2024-08-06, batch input"`.
+    line_item: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Amount {
+    value: f64,
+}
+
+impl OpenAIMonitor {
+    pub fn new(
+        admin_key: String,
+        by_project: bool,
+        fine_tuning_key: Option<String>,
+        organization: Option<String>,
+        pricing: Pricing,
+    ) -> Self {
+        let base_url =
+            std::env::var("OPENAI_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            admin_key,
+            base_url,
+            by_project,
+            fine_tuning_key,
+            organization: Mutex::new(organization),
+            tool_costs: Mutex::new(None),
+            pricing,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Keeps the organization of the admin key from a response unless one is configured.
+    fn remember_organization(&self, response: &reqwest::Response) {
+        let mut organization = self.organization.lock().unwrap();
+        if organization.is_some() {
+            return;
+        }
+        *organization = response
+            .headers()
+            .get("openai-organization")
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+    }
+
+    /// Maps project IDs to names, including archived projects that may still carry usage.
+    async fn projects(&self) -> Result<HashMap<String, String>, MonitorError> {
+        let mut projects = HashMap::new();
+        let mut after: Option<String> = None;
+
+        loop {
+            let mut query = vec![
+                ("limit", "100".to_string()),
+                ("include_archived", "true".to_string()),
+            ];
+            if let Some(after) = after {
+                query.push(("after", after));
+            }
+
+            let page: ProjectsPage = self
+                .client
+                .get(format!("{}/v1/organization/projects", self.base_url))
+                .bearer_auth(&self.admin_key)
+                .query(&query)
+                .send_timed()
+                .await?
+                .check_status()?
+                .json()
+                .await?;
+
+            projects.extend(page.data.into_iter().map(|p| (p.id, p.name)));
+            match page.last_id {
+                Some(last_id) if page.has_more => after = Some(last_id),
+                _ => return Ok(projects),
+            }
+        }
+    }
+
+    /// Fetches every page of daily buckets since the start of the billing period.
+    async fn buckets<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        group_by: &[&str],
+    ) -> Result<Vec<T>, MonitorError> {
+        let mut group_by: Vec<_> = group_by
+            .iter()
+            .map(|field| ("group_by", field.to_string()))
+            .collect();
+        if self.by_project {
+            group_by.push(("group_by", "project_id".to_string()));
+        }
+
+        let mut results = Vec::new();
+        let mut page: Option<String> = None;
+
+        loop {
+            let mut query = vec![
+                (
+                    "start_time",
+                    billing_period_start(self.provider())
+                        .timestamp()
+                        .to_string(),
+                ),
+                ("bucket_width", "1d".to_string()),
+                ("limit", "31".to_string()),
+            ];
+            query.extend(group_by.iter().cloned());
+            if let Some(page) = page {
+                query.push(("page", page));
+            }
+
+            let response = self
+                .client
+                .get(format!("{}{}", self.base_url, path))
+                .bearer_auth(&self.admin_key)
+                .query(&query)
+                .send_timed()
+                .await?
+                .check_status()?;
+            self.remember_organization(&response);
+            let response: Page<T> = response.json().await?;
+
+            results.extend(response.data.into_iter().flat_map(|bucket| bucket.results));
+            match response.next_page {
+                Some(next) if response.has_more => page = Some(next),
+                _ => return Ok(results),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for OpenAIMonitor {
+    fn provider(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let completions: Vec<CompletionsResult> = self
+            .buckets("/v1/organization/usage/completions", &["model", "batch"])
+            .await?;
+        let embeddings: Vec<EmbeddingsResult> = self
+            .buckets("/v1/organization/usage/embeddings", &["model"])
+            .await?;
+        let images: Vec<ImagesResult> = self
+            .buckets("/v1/organization/usage/images", &["model", "size"])
+            .await?;
+        let speeches: Vec<AudioSpeechesResult> = self
+            .buckets("/v1/organization/usage/audio_speeches", &["model"])
+            .await?;
+        let transcriptions: Vec<AudioTranscriptionsResult> = self
+            .buckets("/v1/organization/usage/audio_transcriptions", &["model"])
+            .await?;
+        let costs: Vec<CostsResult> = self
+            .buckets("/v1/organization/costs", &["line_item"])
+            .await?;
+
+        let projects = if self.by_project {
+            self.projects().await?
+        } else {
+            HashMap::new()
+        };
+
+        let mut by_key: HashMap<UsageKey, LLMUsage> = HashMap::new();
+        for result in completions {
+            let tier = service_tier(result.batch.unwrap_or(false));
+            let usage = usage_entry(
+                &mut by_key,
+                &projects,
+                result.model,
+                result.project_id,
+                tier,
+            );
+            usage.prompt_tokens += result
+                .input_tokens
+                .saturating_sub(result.input_cached_tokens);
+            usage.cached_prompt_tokens += result.input_cached_tokens;
+            usage.completion_tokens += result.output_tokens;
+            usage.request_count += result.num_model_requests;
+        }
+        for result in embeddings {
+            let usage = usage_entry(
+                &mut by_key,
+                &projects,
+                result.model,
+                result.project_id,
+                service_tier(false),
+            );
+            usage.embedding_tokens += result.input_tokens;
+            usage.request_count += result.num_model_requests;
+        }
+        for result in images {
+            let usage = usage_entry(
+                &mut by_key,
+                &projects,
+                result.model,
+                result.project_id,
+                service_tier(false),
+            );
+            usage.add_images(&result.size.unwrap_or_default(), "", result.images);
+            usage.request_count += result.num_model_requests;
+        }
+        for result in speeches {
+            let usage = usage_entry(
+                &mut by_key,
+                &projects,
+                result.model,
+                result.project_id,
+                service_tier(false),
+            );
+            usage.speech_characters += result.characters;
+            usage.request_count += result.num_model_requests;
+        }
+        for result in transcriptions {
+            let usage = usage_entry(
+                &mut by_key,
+                &projects,
+                result.model,
+                result.project_id,
+                service_tier(false),
+            );
+            usage.transcription_minutes += result.seconds as f64 / 60.0;
+            usage.request_count += result.num_model_requests;
+        }
+        // every tool is reported, so a tool unused in this billing period reads zero
+        let mut tool_costs: Vec<ToolCost> = TOOLS
+            .iter()
+            .map(|(tool, _)| ToolCost {
+                tool,
+                cost_usd: 0.0,
+            })
+            .collect();
+        for result in costs {
+            let line_item = result.line_item.unwrap_or_default();
+            // tool line items are reported by get_tool_costs alone
+            if let Some(tool) = tool_of(&line_item) {
+                if let Some(tool_cost) = tool_costs.iter_mut().find(|cost| cost.tool == tool) {
+                    tool_cost.cost_usd += result.amount.value;
+                }
+                continue;
+            }
+            let (model, kind) = line_item.split_once(',').unwrap_or((&line_item, ""));
+            let tier = service_tier(kind.contains("batch"));
+            usage_entry(
+                &mut by_key,
+                &projects,
+                Some(model.trim().to_string()),
+                result.project_id,
+                tier,
+            )
+            .cost_usd += result.amount.value;
+        }
+
+        *self.tool_costs.lock().unwrap() = Some(tool_costs);
+
+        let organization = self.organization.lock().unwrap().clone();
+        Ok(by_key
+            .into_values()
+            .map(|mut usage| {
+                if let Some(organization) = &organization {
+                    usage.labels.insert("organization_id", organization.clone());
+                }
+                usage
+            })
+            .collect())
+    }
+
+    /// The tool costs read by the last `get_usage`; none when it failed, so the tool costs of
+    /// the poll before stay.
+    async fn get_tool_costs(&self) -> Result<Vec<ToolCost>, MonitorError> {
+        Ok(self.tool_costs.lock().unwrap().take().unwrap_or_default())
+    }
+
+    async fn get_fine_tuning_jobs(&self) -> Result<Vec<FineTuningJob>, MonitorError> {
+        let Some(api_key) = &self.fine_tuning_key else {
+            return Ok(Vec::new());
+        };
+        let period_start = billing_period_start(self.provider()).timestamp();
+        let now = chrono::Utc::now().timestamp();
+        let mut jobs = Vec::new();
+        let mut after: Option<String> = None;
+
+        // jobs are listed newest first, so paging stops at the first one from an earlier period
+        loop {
+            let mut query = vec![("limit", "100".to_string())];
+            if let Some(after) = after {
+                query.push(("after", after));
+            }
+
+            let page: FineTuningJobsPage = self
+                .client
+                .get(format!("{}/v1/fine_tuning/jobs", self.base_url))
+                .bearer_auth(api_key)
+                .query(&query)
+                .send_timed()
+                .await?
+                .check_status()?
+                .json()
+                .await?;
+
+            let has_more = page.has_more;
+            after = page.data.last().map(|job| job.id.clone());
+            for job in page.data {
+                if job.created_at < period_start {
+                    return Ok(jobs);
+                }
+                let trained_tokens = job.trained_tokens.unwrap_or_default();
+                let hours =
+                    (job.finished_at.unwrap_or(now) - job.created_at).max(0) as f64 / 3600.0;
+                let cost_usd = self
+                    .pricing
+                    .price("openai", &job.model)
+                    .map(|price| price.training_cost(trained_tokens, hours))
+                    .unwrap_or_default();
+                jobs.push(FineTuningJob {
+                    job_id: job.id,
+                    model: job.model,
+                    trained_tokens,
+                    cost_usd,
+                });
+            }
+            if !has_more || after.is_none() {
+                return Ok(jobs);
+            }
+        }
+    }
+}
+
+/// Built-in tools by the cost line items that bill them, most specific first.
+const TOOLS: &[(&str, &str)] = &[
+    ("file_search_storage", "storage"),
+    ("file_search", "file search"),
+    ("web_search", "web search"),
+    ("code_interpreter", "code interpreter"),
+];
+
+/// Tool that a cost line item bills, if any.
+fn tool_of(line_item: &str) -> Option<&'static str> {
+    let line_item = line_item.to_lowercase();
+    TOOLS
+        .iter()
+        .find(|(_, pattern)| line_item.contains(pattern))
+        .map(|(tool, _)| *tool)
+}
+
+/// Model, project and service tier of a usage series.
+type UsageKey = (String, Option<String>, &'static str);
+
+fn service_tier(batch: bool) -> &'static str {
+    if batch {
+        "batch"
+    } else {
+        "default"
+    }
+}
+
+/// Rows without a model (e.g. organization-wide charges) are reported as `unknown`.
+fn usage_entry<'a>(
+    by_key: &'a mut HashMap<UsageKey, LLMUsage>,
+    projects: &HashMap<String, String>,
+    model: Option<String>,
+    project_id: Option<String>,
+    service_tier: &'static str,
+) -> &'a mut LLMUsage {
+    let model = model
+        .filter(|model| !model.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    by_key
+        .entry((model.clone(), project_id.clone(), service_tier))
+        .or_insert_with(|| {
+            let mut usage = LLMUsage {
+                model,
+                ..LLMUsage::default()
+            };
+            usage
+                .labels
+                .insert("service_tier", service_tier.to_string());
+            if let Some(project_id) = project_id {
+                let name = projects.get(&project_id).cloned().unwrap_or_default();
+                usage.labels.insert("project_name", name);
+                usage.labels.insert("project_id", project_id);
+            }
+            usage
+        })
+}
+
+#[async_trait]
+impl ProviderFactory for OpenAIMonitor {
+    const TYPE: &'static str = "openai";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("admin_key", "OPENAI_ADMIN_KEY"),
+        ("admin_key", "OPENAI_API_KEY"),
+        ("project_labels", "OPENAI_PROJECT_LABELS"),
+        ("fine_tuning_key", "OPENAI_FINE_TUNING_API_KEY"),
+        ("organization_id", "OPENAI_ORG_ID"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["admin_key"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("admin_key")?,
+            settings.flag("project_labels"),
+            settings.get("fine_tuning_key"),
+            settings.get("organization_id"),
+            pricing.clone(),
+        )))
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start_date, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://openrouter.ai/api";
+
+/// Reports spend per routed upstream model, e.g. `anthropic/claude-3.5-sonnet`.
+pub struct OpenRouterMonitor {
+    api_key: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActivityResponse {
+    data: Vec<ActivityEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActivityEntry {
+    date: String,
+    model: String,
+    #[serde(default)]
+    usage: f64,
+    #[serde(default)]
+    requests: u64,
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditsResponse {
+    data: Credits,
+}
+
+#[derive(Debug, Deserialize)]
+struct Credits {
+    total_credits: f64,
+    total_usage: f64,
+}
+
+impl OpenRouterMonitor {
+    pub fn new(api_key: String) -> Self {
+        let base_url =
+            std::env::var("OPENROUTER_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self {
+            api_key,
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for OpenRouterMonitor {
+    fn provider(&self) -> &'static str {
+        "openrouter"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        // The activity endpoint returns daily rows for the last 30 days; keep this period's.
+        let response: ActivityResponse = self
+            .client
+            .get(format!("{}/v1/activity", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        let start = billing_period_start_date(self.provider());
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        for entry in response.data {
+            let date = entry
+                .date
+                .get(..10)
+                .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+                .ok_or(MonitorError::InvalidResponse)?;
+            if date < start {
+                continue;
+            }
+            let usage = by_model
+                .entry(entry.model.clone())
+                .or_insert_with(|| LLMUsage {
+                    model: entry.model,
+                    ..LLMUsage::default()
+                });
+            usage.cost_usd += entry.usage;
+            usage.prompt_tokens += entry.prompt_tokens;
+            usage.completion_tokens += entry.completion_tokens;
+            usage.request_count += entry.requests;
+        }
+
+        Ok(by_model.into_values().collect())
+    }
+
+    async fn get_credit_balance(&self) -> Result<Option<f64>, MonitorError> {
+        let response: CreditsResponse = self
+            .client
+            .get(format!("{}/v1/credits", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        Ok(Some(
+            response.data.total_credits - response.data.total_usage,
+        ))
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for OpenRouterMonitor {
+    const TYPE: &'static str = "openrouter";
+    const ENV: &'static [(&'static str, &'static str)] = &[("api_key", "OPENROUTER_API_KEY")];
+    const ENABLED_BY: &'static [&'static str] = &["api_key"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(settings.require("api_key")?)))
+    }
+}
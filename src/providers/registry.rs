@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use yaml_rust::Yaml;
+
+use super::anthropic::ClaudeMonitor;
+use super::bedrock::BedrockMonitor;
+use super::cloudflare::CloudflareMonitor;
+use super::databricks::DatabricksMonitor;
+use super::deepseek::DeepSeekMonitor;
+use super::fireworks::FireworksMonitor;
+use super::http::GenericHttpMonitor;
+use super::huggingface::HuggingFaceMonitor;
+use super::nim::NimMonitor;
+use super::ollama::OllamaMonitor;
+use super::openai::OpenAIMonitor;
+use super::openrouter::OpenRouterMonitor;
+use super::replicate::ReplicateMonitor;
+use super::sagemaker::SageMakerMonitor;
+#[cfg(feature = "wasm")]
+use super::wasm::WasmMonitor;
+use super::xai::XAIMonitor;
+use super::LLMMonitor;
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+/// A monitor the [`ProviderRegistry`] can create from configuration, registered under its
+/// config `type`.
+#[async_trait]
+pub trait ProviderFactory {
+    /// Config `type` of the provider, e.g. `openai`.
+    const TYPE: &'static str;
+
+    /// Environment variable of each setting, as `(setting, variable)` pairs. A setting with
+    /// several variables takes the first one set.
+    const ENV: &'static [(&'static str, &'static str)];
+
+    /// Settings that enable the provider when all of them are in the environment and none is
+    /// `false`, without a `providers` list in the config file. An `enabled` flag has to be
+    /// `true`.
+    const ENABLED_BY: &'static [&'static str];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError>;
+}
+
+/// One entry of the config file's `providers` list: its `type` and its other fields.
+#[derive(Debug, Clone)]
+pub struct ProviderEntry {
+    pub provider_type: String,
+    pub fields: HashMap<String, String>,
+}
+
+impl ProviderEntry {
+    /// Reads the `providers` list of the config file. Lists of strings, such as NIM
+    /// `endpoints`, are joined with commas like their environment variables, and maps, such as
+    /// HTTP `headers`, become `key=value` pairs on separate lines, so values may hold commas.
+    pub fn list_from_yaml(node: &Yaml) -> Result<Vec<Self>, String> {
+        let entries = node.as_vec().ok_or("providers must be a list")?;
+        entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                Self::from_yaml(entry).map_err(|e| format!("providers[{}]: {}", index, e))
+            })
+            .collect()
+    }
+
+    fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let provider_type = node["type"].as_str().ok_or("type is required")?.to_string();
+        let mut fields = HashMap::new();
+        for (key, value) in node.as_hash().into_iter().flatten() {
+            let key = key.as_str().ok_or("setting names must be strings")?;
+            if key == "type" {
+                continue;
+            }
+            let value = match value {
+                Yaml::Array(items) => items
+                    .iter()
+                    .map(yaml_string)
+                    .collect::<Option<Vec<_>>>()
+                    .map(|items| items.join(",")),
+                Yaml::Hash(pairs) => pairs
+                    .iter()
+                    .map(|(name, value)| {
+                        Some(format!("{}={}", yaml_string(name)?, yaml_string(value)?))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .map(|pairs| pairs.join("\n")),
+                value => yaml_string(value),
+            }
+            .ok_or_else(|| format!("{} must be a string, number, boolean, list or map", key))?;
+            fields.insert(key.to_string(), value);
+        }
+        Ok(Self {
+            provider_type,
+            fields,
+        })
+    }
+
+    /// Identifies the entry for sharding: its type and settings, independent of its position
+    /// in the list.
+    fn shard_key(&self) -> String {
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort();
+        fields
+            .into_iter()
+            .fold(self.provider_type.clone(), |key, (name, value)| {
+                format!("{},{}={}", key, name, value)
+            })
+    }
+}
+
+fn yaml_string(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(value) | Yaml::Real(value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// The part of the configured providers one exporter instance polls, so polling load spreads
+/// over several instances. Every instance reads the same configuration and keeps the
+/// providers whose key hashes to its index, so no coordination is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    /// Enabled when `SHARD_COUNT` is above 1. `SHARD_INDEX` defaults to the ordinal at the end
+    /// of `HOSTNAME`, the pod name of a StatefulSet replica such as `llm-cost-exporter-2`.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let setting = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let count = match setting("SHARD_COUNT") {
+            Some(count) => count
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("invalid SHARD_COUNT {:?}", count))?,
+            None => return Ok(None),
+        };
+        if count <= 1 {
+            return Ok(None);
+        }
+        let index = match setting("SHARD_INDEX") {
+            Some(index) => index
+                .parse::<u64>()
+                .map_err(|_| anyhow::anyhow!("invalid SHARD_INDEX {:?}", index))?,
+            None => setting("HOSTNAME")
+                .and_then(|hostname| hostname.rsplit('-').next()?.parse().ok())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("SHARD_COUNT needs SHARD_INDEX or a HOSTNAME ending in it")
+                })?,
+        };
+        if index >= count {
+            return Err(anyhow::anyhow!(
+                "SHARD_INDEX {} is out of range for SHARD_COUNT {}",
+                index,
+                count
+            )
+            .into());
+        }
+        Ok(Some(Self { index, count }))
+    }
+
+    /// Whether the provider with `key` belongs to this shard. FNV-1a rather than the standard
+    /// hasher, which may change between Rust versions and split instances built apart.
+    pub fn contains(&self, key: &str) -> bool {
+        let hash = key.bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+        hash % self.count == self.index
+    }
+}
+
+/// Settings of one provider: the fields of its config file entry, falling back to the
+/// provider's environment variables for settings the entry leaves out, e.g. to keep the API
+/// key in a secret.
+pub struct ProviderSettings {
+    provider_type: &'static str,
+    fields: HashMap<String, String>,
+    env: &'static [(&'static str, &'static str)],
+}
+
+impl ProviderSettings {
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.fields.get(key).cloned().or_else(|| {
+            self.env
+                .iter()
+                .filter(|(setting, _)| *setting == key)
+                .find_map(|(_, variable)| std::env::var(variable).ok())
+                .filter(|value| !value.is_empty())
+        })
+    }
+
+    pub fn require(&self, key: &str) -> Result<String, MonitorError> {
+        self.get(key).ok_or_else(|| {
+            anyhow::anyhow!("provider {} needs the {} setting", self.provider_type, key).into()
+        })
+    }
+
+    /// Whether a setting is `true`.
+    pub fn flag(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|value| value == "true")
+    }
+}
+
+type Create = for<'a> fn(
+    &'a ProviderSettings,
+    &'a Pricing,
+) -> BoxFuture<'a, Result<Box<dyn LLMMonitor>, MonitorError>>;
+
+struct Registration {
+    provider_type: &'static str,
+    env: &'static [(&'static str, &'static str)],
+    enabled_by: &'static [&'static str],
+    create: Create,
+}
+
+fn create<'a, F: ProviderFactory>(
+    settings: &'a ProviderSettings,
+    pricing: &'a Pricing,
+) -> BoxFuture<'a, Result<Box<dyn LLMMonitor>, MonitorError>> {
+    F::create(settings, pricing)
+}
+
+/// The providers the exporter can monitor, by config `type`. Which of them run is decided by
+/// configuration alone: the entries of the config file's `providers` list, or every provider
+/// whose settings are in the environment.
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Registration>,
+    shard: Option<Shard>,
+}
+
+impl ProviderRegistry {
+    /// Every provider shipped with the exporter.
+    pub fn builtin() -> Self {
+        let registry = Self::default()
+            .register::<OpenAIMonitor>()
+            .register::<ClaudeMonitor>()
+            .register::<BedrockMonitor>()
+            .register::<FireworksMonitor>()
+            .register::<ReplicateMonitor>()
+            .register::<DeepSeekMonitor>()
+            .register::<XAIMonitor>()
+            .register::<OpenRouterMonitor>()
+            .register::<OllamaMonitor>()
+            .register::<HuggingFaceMonitor>()
+            .register::<SageMakerMonitor>()
+            .register::<DatabricksMonitor>()
+            .register::<CloudflareMonitor>()
+            .register::<NimMonitor>()
+            .register::<GenericHttpMonitor>();
+        #[cfg(feature = "wasm")]
+        let registry = registry.register::<WasmMonitor>();
+        registry
+    }
+
+    /// Adds a provider, replacing a registered one of the same type.
+    pub fn register<F: ProviderFactory>(mut self) -> Self {
+        self.providers
+            .retain(|registration| registration.provider_type != F::TYPE);
+        self.providers.push(Registration {
+            provider_type: F::TYPE,
+            env: F::ENV,
+            enabled_by: F::ENABLED_BY,
+            create: create::<F>,
+        });
+        self
+    }
+
+    /// Only creates the providers of one shard.
+    pub fn shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    fn in_shard(&self, key: &str) -> bool {
+        self.shard.is_none_or(|shard| shard.contains(key))
+    }
+
+    pub fn types(&self) -> Vec<&'static str> {
+        self.providers
+            .iter()
+            .map(|registration| registration.provider_type)
+            .collect()
+    }
+
+    /// Creates a monitor for every config file entry, or without a `providers` list for every
+    /// registered provider enabled by the environment, keeping those of the shard if set.
+    /// Environment providers are sharded by type and entries by type and settings.
+    pub async fn monitors(
+        &self,
+        entries: Option<&[ProviderEntry]>,
+        pricing: &Pricing,
+    ) -> Result<Vec<Box<dyn LLMMonitor>>, MonitorError> {
+        let mut monitors = Vec::new();
+        let Some(entries) = entries else {
+            for registration in &self.providers {
+                let settings = ProviderSettings {
+                    provider_type: registration.provider_type,
+                    fields: HashMap::new(),
+                    env: registration.env,
+                };
+                let enabled = registration
+                    .enabled_by
+                    .iter()
+                    .all(|setting| match *setting {
+                        "enabled" => settings.flag(setting),
+                        setting => settings.get(setting).is_some_and(|value| value != "false"),
+                    });
+                if enabled && self.in_shard(registration.provider_type) {
+                    monitors.push((registration.create)(&settings, pricing).await?);
+                }
+            }
+            return Ok(monitors);
+        };
+
+        for entry in entries {
+            let registration = self
+                .providers
+                .iter()
+                .find(|registration| registration.provider_type == entry.provider_type)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "unknown provider type {:?}, expected one of {}",
+                        entry.provider_type,
+                        self.types().join(", ")
+                    )
+                })?;
+            if !self.in_shard(&entry.shard_key()) {
+                continue;
+            }
+            let settings = ProviderSettings {
+                provider_type: registration.provider_type,
+                fields: entry.fields.clone(),
+                env: registration.env,
+            };
+            monitors.push((registration.create)(&settings, pricing).await?);
+        }
+        Ok(monitors)
+    }
+}
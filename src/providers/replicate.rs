@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, env_map, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_BASE_URL: &str = "https://api.replicate.com";
+const DEFAULT_HARDWARE: &str = "gpu-t4";
+
+pub struct ReplicateMonitor {
+    api_token: String,
+    base_url: String,
+    default_hardware: String,
+    /// Hardware SKU per `owner/name`, since predictions don't report what they ran on.
+    model_hardware: HashMap<String, String>,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct PredictionsPage {
+    results: Vec<Prediction>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Prediction {
+    model: String,
+    created_at: DateTime<Utc>,
+    #[serde(default)]
+    metrics: PredictionMetrics,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PredictionMetrics {
+    predict_time: Option<f64>,
+}
+
+impl ReplicateMonitor {
+    pub fn new(api_token: String) -> Self {
+        let base_url =
+            std::env::var("REPLICATE_API_BASE").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        let default_hardware = std::env::var("REPLICATE_DEFAULT_HARDWARE")
+            .unwrap_or_else(|_| DEFAULT_HARDWARE.to_string());
+        Self {
+            api_token,
+            base_url,
+            default_hardware,
+            model_hardware: env_map("REPLICATE_MODEL_HARDWARE"),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn hardware_for(&self, model: &str) -> &str {
+        self.model_hardware
+            .get(model)
+            .map(String::as_str)
+            .unwrap_or(&self.default_hardware)
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for ReplicateMonitor {
+    fn provider(&self) -> &'static str {
+        "replicate"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let start = billing_period_start(self.provider());
+        let mut by_model: HashMap<String, LLMUsage> = HashMap::new();
+        let mut next = Some(format!("{}/v1/predictions", self.base_url));
+
+        // Predictions are listed newest first, so stop paging once we pass the period start.
+        'pages: while let Some(url) = next {
+            let page: PredictionsPage = self
+                .client
+                .get(&url)
+                .bearer_auth(&self.api_token)
+                .send_timed()
+                .await?
+                .check_status()?
+                .json()
+                .await?;
+
+            for prediction in page.results {
+                if prediction.created_at < start {
+                    break 'pages;
+                }
+                let seconds = prediction.metrics.predict_time.unwrap_or(0.0);
+                let rate = hardware_second_rate(self.hardware_for(&prediction.model));
+                let usage = by_model
+                    .entry(prediction.model.clone())
+                    .or_insert_with(|| LLMUsage {
+                        model: prediction.model,
+                        ..LLMUsage::default()
+                    });
+                usage.request_count += 1;
+                usage.cost_usd += seconds * rate;
+            }
+
+            next = page.next;
+        }
+
+        Ok(by_model.into_values().collect())
+    }
+}
+
+/// Published hardware prices in USD per second of prediction time.
+fn hardware_second_rate(hardware: &str) -> f64 {
+    match hardware {
+        "cpu" => 0.000100,
+        "gpu-t4" => 0.000225,
+        "gpu-l40s" => 0.000975,
+        "gpu-l40s-2x" => 0.001950,
+        "gpu-a100-large" => 0.001400,
+        "gpu-a100-large-2x" => 0.002800,
+        "gpu-h100" => 0.001525,
+        "gpu-h100-2x" => 0.003050,
+        _ => 0.0,
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for ReplicateMonitor {
+    const TYPE: &'static str = "replicate";
+    const ENV: &'static [(&'static str, &'static str)] = &[("api_token", "REPLICATE_API_TOKEN")];
+    const ENABLED_BY: &'static [&'static str] = &["api_token"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(settings.require("api_token")?)))
+    }
+}
@@ -0,0 +1,254 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use aws_config::SdkConfig;
+use serde::{Deserialize, Serialize};
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, UptimeMeter};
+use crate::aws::{AwsJsonClient, Dimension};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+/// Estimates serving cost of SageMaker real-time endpoints from instance uptime, with
+/// invocation counts pulled from CloudWatch. The `model` label carries the endpoint name.
+pub struct SageMakerMonitor {
+    sagemaker: AwsJsonClient,
+    cloudwatch: AwsJsonClient,
+    uptime: Mutex<UptimeMeter>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListEndpointsRequest {
+    status_equals: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListEndpointsResponse {
+    endpoints: Vec<EndpointSummary>,
+    next_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EndpointSummary {
+    endpoint_name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeEndpointRequest<'a> {
+    endpoint_name: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeEndpointResponse {
+    endpoint_config_name: String,
+    #[serde(default)]
+    production_variants: Vec<VariantSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VariantSummary {
+    variant_name: String,
+    #[serde(default)]
+    current_instance_count: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeEndpointConfigRequest<'a> {
+    endpoint_config_name: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeEndpointConfigResponse {
+    #[serde(default)]
+    production_variants: Vec<VariantConfig>,
+}
+
+/// Serverless variants have no instance type and are left out of instance-hour pricing.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VariantConfig {
+    variant_name: String,
+    instance_type: Option<String>,
+}
+
+struct EndpointVariant {
+    name: String,
+    instance_count: u32,
+    instance_type: Option<String>,
+}
+
+impl SageMakerMonitor {
+    pub fn new(config: &SdkConfig) -> Result<Self, MonitorError> {
+        Ok(Self {
+            sagemaker: AwsJsonClient::sagemaker(config)?,
+            cloudwatch: AwsJsonClient::cloudwatch(config)?,
+            uptime: Mutex::new(UptimeMeter::default()),
+        })
+    }
+
+    async fn list_endpoints(&self) -> Result<Vec<String>, MonitorError> {
+        let mut names = Vec::new();
+        let mut next_token = None;
+        loop {
+            let response: ListEndpointsResponse = self
+                .sagemaker
+                .call(
+                    "ListEndpoints",
+                    &ListEndpointsRequest {
+                        status_equals: "InService",
+                        next_token,
+                    },
+                )
+                .await?;
+            names.extend(response.endpoints.into_iter().map(|e| e.endpoint_name));
+            match response.next_token {
+                Some(token) => next_token = Some(token),
+                None => return Ok(names),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for SageMakerMonitor {
+    fn provider(&self) -> &'static str {
+        "sagemaker"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let endpoints = self.list_endpoints().await?;
+        let mut described = Vec::with_capacity(endpoints.len());
+        for endpoint_name in endpoints {
+            let endpoint: DescribeEndpointResponse = self
+                .sagemaker
+                .call(
+                    "DescribeEndpoint",
+                    &DescribeEndpointRequest {
+                        endpoint_name: &endpoint_name,
+                    },
+                )
+                .await?;
+            let config: DescribeEndpointConfigResponse = self
+                .sagemaker
+                .call(
+                    "DescribeEndpointConfig",
+                    &DescribeEndpointConfigRequest {
+                        endpoint_config_name: &endpoint.endpoint_config_name,
+                    },
+                )
+                .await?;
+            let variants: Vec<EndpointVariant> = endpoint
+                .production_variants
+                .into_iter()
+                .map(|variant| EndpointVariant {
+                    instance_type: config
+                        .production_variants
+                        .iter()
+                        .find(|c| c.variant_name == variant.variant_name)
+                        .and_then(|c| c.instance_type.clone()),
+                    instance_count: variant.current_instance_count,
+                    name: variant.variant_name,
+                })
+                .collect();
+            described.push((endpoint_name, variants));
+        }
+
+        {
+            let mut uptime = self.uptime.lock().unwrap();
+            let hours = uptime.sample(self.provider());
+            for (endpoint_name, variants) in &described {
+                for variant in variants {
+                    let rate = variant
+                        .instance_type
+                        .as_deref()
+                        .map(instance_hour_rate)
+                        .unwrap_or_default();
+                    uptime.accrue(endpoint_name, hours * variant.instance_count as f64 * rate);
+                }
+            }
+        }
+
+        let start = billing_period_start(self.provider());
+        let mut usage = Vec::with_capacity(described.len());
+        for (endpoint_name, variants) in described {
+            let dimensions: Vec<Vec<Dimension>> = variants
+                .iter()
+                .map(|variant| {
+                    vec![
+                        Dimension {
+                            name: "EndpointName".to_string(),
+                            value: endpoint_name.clone(),
+                        },
+                        Dimension {
+                            name: "VariantName".to_string(),
+                            value: variant.name.clone(),
+                        },
+                    ]
+                })
+                .collect();
+            let metrics: Vec<(&str, &[Dimension])> = dimensions
+                .iter()
+                .map(|dims| ("Invocations", dims.as_slice()))
+                .collect();
+            let invocations = self
+                .cloudwatch
+                .metric_sums("AWS/SageMaker", &metrics, start)
+                .await?;
+
+            usage.push(LLMUsage {
+                cost_usd: self.uptime.lock().unwrap().cost(&endpoint_name),
+                request_count: invocations.iter().sum::<f64>() as u64,
+                model: endpoint_name,
+                ..LLMUsage::default()
+            });
+        }
+
+        Ok(usage)
+    }
+}
+
+/// On-demand real-time inference prices in USD per instance-hour (us-east-1).
+fn instance_hour_rate(instance_type: &str) -> f64 {
+    match instance_type {
+        "ml.g4dn.xlarge" => 0.736,
+        "ml.g4dn.2xlarge" => 1.053,
+        "ml.g5.xlarge" => 1.408,
+        "ml.g5.2xlarge" => 1.515,
+        "ml.g5.4xlarge" => 2.03,
+        "ml.g5.12xlarge" => 7.09,
+        "ml.g5.48xlarge" => 20.36,
+        "ml.g6.xlarge" => 1.006,
+        "ml.g6.12xlarge" => 5.752,
+        "ml.inf2.xlarge" => 0.99,
+        "ml.inf2.48xlarge" => 15.58,
+        "ml.p4d.24xlarge" => 37.688,
+        "ml.p5.48xlarge" => 113.07,
+        _ => 0.0,
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for SageMakerMonitor {
+    const TYPE: &'static str = "sagemaker";
+    const ENV: &'static [(&'static str, &'static str)] = &[("enabled", "SAGEMAKER_ENABLED")];
+    const ENABLED_BY: &'static [&'static str] = &["enabled"];
+
+    async fn create(
+        _settings: &ProviderSettings,
+        _pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        let aws_config = aws_config::load_from_env().await;
+        Ok(Box::new(Self::new(&aws_config)?))
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::runtime::Handle;
+use tracing::info;
+use wasmtime::{
+    Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{
+    billing_period_end, billing_period_start, expand_env, parse_map, ApiTimer, LLMMonitor,
+    LLMUsage, RequestExt, USAGE_LABELS,
+};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+/// Host functions are imported from this module.
+const HOST_MODULE: &str = "env";
+
+/// Instructions one `get_usage` call may execute, so a plugin stuck in a loop fails the poll
+/// instead of holding a thread forever. Time spent in `http_request` is not counted.
+const FUEL: u64 = 10_000_000_000;
+/// Largest linear memory a plugin may grow to; growing past it fails the poll.
+const MEMORY_BYTES: usize = 256 << 20;
+
+/// A provider implemented by a WebAssembly plugin, for logic that doesn't fit the `http`
+/// provider, such as request signing or token exchanges, and that can't or shouldn't live in
+/// the exporter.
+///
+/// A plugin is a core WebAssembly module without WASI. It exports:
+///
+/// - `memory`
+/// - `alloc(len: i32) -> i32`, returning a buffer of `len` bytes the host writes into
+/// - `get_usage(ptr: i32, len: i32) -> i64`, called once per poll with a JSON object of the
+///   `provider` name, the `period_start` and `period_end` of the billing period (RFC 3339) and
+///   the plugin's `settings`. It returns `(ptr << 32) | len` of a JSON array of usage records,
+///   or of `{"error": "...", "status": 429}` when the poll failed (`status` optional).
+///
+/// A usage record has a `model`, `cost_usd`, `request_cost_usd`, `prompt_tokens`,
+/// `cached_prompt_tokens`, `cache_write_tokens`, `completion_tokens`, `embedding_tokens`,
+/// `request_count` and `labels` (see [`USAGE_LABELS`]), all optional. Without `cost_usd` the
+/// tokens are priced from the pricing catalog.
+///
+/// The host provides, in module `env`:
+///
+/// - `http_request(ptr: i32, len: i32) -> i64`, sending a JSON `{"method", "url", "headers",
+///   "body"}` request and returning `{"status", "body"}`, or `{"error"}` when it could not be
+///   sent, packed like the result of `get_usage`
+/// - `log(ptr: i32, len: i32)`, logging a message
+pub struct WasmMonitor {
+    provider: &'static str,
+    plugin: Arc<Plugin>,
+    settings: HashMap<String, String>,
+    pricing: Pricing,
+}
+
+struct Plugin {
+    engine: Engine,
+    module: Module,
+    linker: Linker<HostState>,
+}
+
+struct HostState {
+    provider: &'static str,
+    client: reqwest::Client,
+    runtime: Handle,
+    /// Times the plugin's requests on the blocking thread it runs on.
+    timer: Option<ApiTimer>,
+    limits: StoreLimits,
+}
+
+#[derive(Deserialize)]
+struct PluginRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PluginResult {
+    Usage(Vec<PluginUsage>),
+    Error { error: String, status: Option<u16> },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PluginUsage {
+    model: String,
+    cost_usd: Option<f64>,
+    request_cost_usd: f64,
+    prompt_tokens: u64,
+    cached_prompt_tokens: u64,
+    cache_write_tokens: u64,
+    completion_tokens: u64,
+    embedding_tokens: u64,
+    request_count: u64,
+    labels: HashMap<String, String>,
+}
+
+fn pack(ptr: i32, len: usize) -> i64 {
+    (i64::from(ptr) << 32) | len as i64
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    ((packed >> 32) as u32 as usize, packed as u32 as usize)
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow::anyhow!("plugin exports no memory"))
+}
+
+fn read_guest(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> wasmtime::Result<Vec<u8>> {
+    let memory = memory(caller)?;
+    let start = ptr as u32 as usize;
+    memory
+        .data(&*caller)
+        .get(start..start + len as u32 as usize)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| anyhow::anyhow!("plugin passed a buffer outside its memory"))
+}
+
+/// Copies `bytes` into a buffer from the plugin's `alloc`.
+fn write_guest(caller: &mut Caller<'_, HostState>, bytes: &[u8]) -> wasmtime::Result<i64> {
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(|export| export.into_func())
+        .ok_or_else(|| anyhow::anyhow!("plugin exports no alloc"))?
+        .typed::<i32, i32>(&*caller)?;
+    let ptr = alloc.call(&mut *caller, bytes.len() as i32)?;
+    memory(caller)?.write(&mut *caller, ptr as u32 as usize, bytes)?;
+    Ok(pack(ptr, bytes.len()))
+}
+
+fn http_request(state: &HostState, request: &[u8]) -> serde_json::Value {
+    let request: PluginRequest = match serde_json::from_slice(request) {
+        Ok(request) => request,
+        Err(e) => return serde_json::json!({ "error": format!("invalid request: {}", e) }),
+    };
+    let send = async {
+        let method = reqwest::Method::from_bytes(request.method.to_uppercase().as_bytes())
+            .map_err(|e| e.to_string())?;
+        let mut builder = state.client.request(method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        let response = builder.send_timed().await.map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        Ok::<_, String>((status, body))
+    };
+    let response = match state.timer.clone() {
+        Some(timer) => state.runtime.block_on(timer.scope(send)),
+        None => state.runtime.block_on(send),
+    };
+    match response {
+        Ok((status, body)) => serde_json::json!({ "status": status, "body": body }),
+        Err(error) => serde_json::json!({ "error": error }),
+    }
+}
+
+impl Plugin {
+    fn load(path: &str) -> Result<Self, MonitorError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)
+            .map_err(|e| e.context(format!("loading plugin {}", path)))?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            HOST_MODULE,
+            "http_request",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let request = read_guest(&mut caller, ptr, len)?;
+                let response = http_request(caller.data(), &request);
+                write_guest(&mut caller, response.to_string().as_bytes())
+            },
+        )?;
+        linker.func_wrap(
+            HOST_MODULE,
+            "log",
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let message = read_guest(&mut caller, ptr, len)?;
+                let provider = caller.data().provider;
+                info!(provider, "plugin: {}", String::from_utf8_lossy(&message));
+                Ok(())
+            },
+        )?;
+        Ok(Self {
+            engine,
+            module,
+            linker,
+        })
+    }
+
+    /// Runs `get_usage` in a fresh instance, so no state carries over between polls.
+    fn get_usage(&self, state: HostState, input: &[u8]) -> wasmtime::Result<Vec<u8>> {
+        let mut store = Store::new(&self.engine, state);
+        store.set_fuel(FUEL)?;
+        store.limiter(|state| &mut state.limits);
+        let instance = self.linker.instantiate(&mut store, &self.module)?;
+        let alloc: TypedFunc<i32, i32> = instance.get_typed_func(&mut store, "alloc")?;
+        let get_usage: TypedFunc<(i32, i32), i64> =
+            instance.get_typed_func(&mut store, "get_usage")?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow::anyhow!("plugin exports no memory"))?;
+
+        let ptr = alloc.call(&mut store, input.len() as i32)?;
+        memory.write(&mut store, ptr as u32 as usize, input)?;
+        let (start, len) = unpack(get_usage.call(&mut store, (ptr, input.len() as i32))?);
+        memory
+            .data(&store)
+            .get(start..start + len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| anyhow::anyhow!("plugin returned a buffer outside its memory"))
+    }
+}
+
+impl WasmMonitor {
+    /// Compiles the plugin at `path`; `settings` are passed to every `get_usage` call.
+    pub fn new(
+        provider: String,
+        path: &str,
+        settings: HashMap<String, String>,
+        pricing: Pricing,
+    ) -> Result<Self, MonitorError> {
+        Ok(Self {
+            // the label lives as long as the exporter
+            provider: Box::leak(provider.into_boxed_str()),
+            plugin: Arc::new(Plugin::load(path)?),
+            settings,
+            pricing,
+        })
+    }
+
+    fn usage(&self, record: PluginUsage) -> LLMUsage {
+        let mut usage = LLMUsage {
+            model: record.model,
+            request_cost_usd: record.request_cost_usd,
+            prompt_tokens: record.prompt_tokens,
+            cached_prompt_tokens: record.cached_prompt_tokens,
+            cache_write_tokens: record.cache_write_tokens,
+            completion_tokens: record.completion_tokens,
+            embedding_tokens: record.embedding_tokens,
+            request_count: record.request_count,
+            ..Default::default()
+        };
+        for (label, value) in record.labels {
+            if let Some(label) = USAGE_LABELS.iter().find(|known| **known == label) {
+                usage.labels.insert(label, value);
+            }
+        }
+        usage.cost_usd = record
+            .cost_usd
+            .unwrap_or_else(|| self.pricing.token_cost(self.provider, &usage));
+        usage
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for WasmMonitor {
+    fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let period_start = billing_period_start(self.provider);
+        let input = serde_json::json!({
+            "provider": self.provider,
+            "period_start": period_start.to_rfc3339(),
+            "period_end": billing_period_end(self.provider, period_start).to_rfc3339(),
+            "settings": self.settings,
+        })
+        .to_string();
+        let state = HostState {
+            provider: self.provider,
+            client: reqwest::Client::new(),
+            runtime: Handle::current(),
+            timer: ApiTimer::current(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(MEMORY_BYTES)
+                .trap_on_grow_failure(true)
+                .build(),
+        };
+        let plugin = self.plugin.clone();
+        let output = tokio::task::spawn_blocking(move || plugin.get_usage(state, input.as_bytes()))
+            .await
+            .map_err(anyhow::Error::from)?
+            .map_err(|e| MonitorError::PluginError(format!("{:#}", e)))?;
+
+        match serde_json::from_slice(&output).map_err(|_| MonitorError::InvalidResponse)? {
+            PluginResult::Usage(records) => Ok(records
+                .into_iter()
+                .map(|record| self.usage(record))
+                .collect()),
+            PluginResult::Error {
+                error,
+                status: Some(status),
+            } => Err(MonitorError::StatusError(
+                reqwest::StatusCode::from_u16(status).unwrap_or(reqwest::StatusCode::BAD_GATEWAY),
+                error,
+            )),
+            PluginResult::Error {
+                error,
+                status: None,
+            } => Err(MonitorError::PluginError(error)),
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for WasmMonitor {
+    const TYPE: &'static str = "wasm";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("name", "WASM_PLUGIN_NAME"),
+        ("path", "WASM_PLUGIN_PATH"),
+        ("settings", "WASM_PLUGIN_SETTINGS"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["name", "path"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        // `${VAR}` keeps secrets in the environment, since plugins can't read it themselves
+        let plugin_settings = parse_map(&settings.get("settings").unwrap_or_default())
+            .into_iter()
+            .map(|(name, value)| (name, expand_env(&value)))
+            .collect();
+        Ok(Box::new(Self::new(
+            settings.require("name")?,
+            &settings.require("path")?,
+            plugin_settings,
+            pricing.clone(),
+        )?))
+    }
+}
@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+
+use super::registry::{ProviderFactory, ProviderSettings};
+use super::{billing_period_start, LLMMonitor, LLMUsage, RequestExt, ResponseExt};
+use crate::pricing::Pricing;
+use crate::MonitorError;
+
+const DEFAULT_MANAGEMENT_URL: &str = "https://management-api.x.ai";
+
+/// Polls account-level usage through the xAI Management API. Inference itself is
+/// OpenAI-compatible, so per-request tracking can reuse the OpenAI usage format.
+pub struct XAIMonitor {
+    management_key: String,
+    team_id: String,
+    base_url: String,
+    client: reqwest::Client,
+    pricing: Pricing,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageResponse {
+    #[serde(default)]
+    usage: Vec<UsageEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageEntry {
+    model: String,
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    num_requests: u64,
+}
+
+impl XAIMonitor {
+    pub fn new(management_key: String, team_id: String, pricing: Pricing) -> Self {
+        let base_url = std::env::var("XAI_MANAGEMENT_API_BASE")
+            .unwrap_or_else(|_| DEFAULT_MANAGEMENT_URL.to_string());
+        Self {
+            management_key,
+            team_id,
+            base_url,
+            client: reqwest::Client::new(),
+            pricing,
+        }
+    }
+}
+
+#[async_trait]
+impl LLMMonitor for XAIMonitor {
+    fn provider(&self) -> &'static str {
+        "xai"
+    }
+
+    async fn get_usage(&self) -> Result<Vec<LLMUsage>, MonitorError> {
+        let response: UsageResponse = self
+            .client
+            .get(format!(
+                "{}/v1/billing/teams/{}/usage",
+                self.base_url, self.team_id
+            ))
+            .bearer_auth(&self.management_key)
+            .query(&[
+                (
+                    "startTime",
+                    billing_period_start(self.provider()).to_rfc3339(),
+                ),
+                ("endTime", Utc::now().to_rfc3339()),
+                ("groupBy", "model".to_string()),
+            ])
+            .send_timed()
+            .await?
+            .check_status()?
+            .json()
+            .await?;
+
+        Ok(response
+            .usage
+            .into_iter()
+            .map(|entry| {
+                let mut usage = LLMUsage {
+                    model: entry.model,
+                    prompt_tokens: entry.prompt_tokens,
+                    completion_tokens: entry.completion_tokens,
+                    request_count: entry.num_requests,
+                    ..LLMUsage::default()
+                };
+                usage.cost_usd = self.pricing.token_cost("xai", &usage);
+                usage
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl ProviderFactory for XAIMonitor {
+    const TYPE: &'static str = "xai";
+    const ENV: &'static [(&'static str, &'static str)] = &[
+        ("management_key", "XAI_MANAGEMENT_KEY"),
+        ("team_id", "XAI_TEAM_ID"),
+    ];
+    const ENABLED_BY: &'static [&'static str] = &["management_key", "team_id"];
+
+    async fn create(
+        settings: &ProviderSettings,
+        pricing: &Pricing,
+    ) -> Result<Box<dyn LLMMonitor>, MonitorError> {
+        Ok(Box::new(Self::new(
+            settings.require("management_key")?,
+            settings.require("team_id")?,
+            pricing.clone(),
+        )))
+    }
+}
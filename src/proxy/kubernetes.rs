@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::kubernetes::KubeClient;
+use crate::MonitorError;
+
+/// How long the workload of an IP is remembered. Short, since pod IPs are reused.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+/// Time the API server has to answer before a call is recorded without its workload.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The workload a calling pod belongs to. Empty when the caller isn't a pod of the cluster.
+#[derive(Debug, Clone, Default)]
+pub struct Workload {
+    pub namespace: String,
+    /// Deployment owning the pod, empty for pods of other controllers.
+    pub deployment: String,
+}
+
+#[derive(Deserialize)]
+struct PodList {
+    items: Vec<Pod>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Pod {
+    metadata: PodMetadata,
+    spec: PodSpec,
+    status: PodStatus,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PodMetadata {
+    namespace: String,
+    labels: HashMap<String, String>,
+    owner_references: Vec<OwnerReference>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct OwnerReference {
+    kind: String,
+    name: String,
+    controller: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct PodSpec {
+    host_network: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct PodStatus {
+    phase: String,
+}
+
+impl Pod {
+    fn workload(&self) -> Workload {
+        // a Deployment owns its pods through a ReplicaSet named after it and the template hash
+        let deployment = self
+            .metadata
+            .owner_references
+            .iter()
+            .find(|owner| owner.controller && owner.kind == "ReplicaSet")
+            .and_then(|owner| {
+                let hash = self.metadata.labels.get("pod-template-hash")?;
+                owner.name.strip_suffix(hash)?.strip_suffix('-')
+            })
+            .unwrap_or_default();
+        Workload {
+            namespace: self.metadata.namespace.clone(),
+            deployment: deployment.to_string(),
+        }
+    }
+}
+
+/// Finds the pod behind a client IP through the Kubernetes API, for an exporter running as
+/// an in-cluster gateway. Needs permission to list pods in every namespace.
+pub struct PodResolver {
+    client: KubeClient,
+    cache: Mutex<HashMap<IpAddr, (Instant, Workload)>>,
+}
+
+impl PodResolver {
+    /// Connects to the API server of the cluster the exporter runs in, with its service
+    /// account.
+    pub fn in_cluster() -> Result<Self, MonitorError> {
+        Ok(Self {
+            client: KubeClient::in_cluster(LOOKUP_TIMEOUT)?,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Workload of the pod with IP `ip`, empty when none is found or the lookup fails.
+    pub async fn resolve(&self, ip: IpAddr) -> Workload {
+        let ip = ip.to_canonical();
+        if let Some((resolved, workload)) = self.cache.lock().unwrap().get(&ip) {
+            if resolved.elapsed() < CACHE_TTL {
+                return workload.clone();
+            }
+        }
+        let workload = match self.lookup(ip).await {
+            Ok(workload) => workload,
+            Err(e) => {
+                warn!(error = %e, %ip, "failed to look up the calling pod");
+                Workload::default()
+            }
+        };
+        debug!(%ip, namespace = %workload.namespace, deployment = %workload.deployment, "resolved calling pod");
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (resolved, _)| resolved.elapsed() < CACHE_TTL);
+        cache.insert(ip, (Instant::now(), workload.clone()));
+        workload
+    }
+
+    async fn lookup(&self, ip: IpAddr) -> Result<Workload, MonitorError> {
+        let pods: PodList = self
+            .client
+            .request(reqwest::Method::GET, "/api/v1/pods")?
+            .query(&[("fieldSelector", format!("status.podIP={}", ip))])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // pods on the host network share the node's IP, so they can't identify a caller
+        Ok(pods
+            .items
+            .iter()
+            .find(|pod| !pod.spec.host_network && pod.status.phase == "Running")
+            .map(Pod::workload)
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,463 @@
+use futures_util::StreamExt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::{info, info_span, warn, Instrument, Span};
+use warp::http::header::{HeaderMap, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+use warp::http::{Method, Response, StatusCode};
+use warp::hyper::body::Bytes;
+use warp::hyper::Body;
+use warp::path::FullPath;
+use warp::Filter;
+
+use crate::shutdown::Shutdown;
+use crate::tracker::{estimate_usage, requested_model, Caller, LLMTracker, StreamUsage};
+use crate::MonitorError;
+
+mod kubernetes;
+
+pub use kubernetes::{PodResolver, Workload};
+
+const DEFAULT_UPSTREAM: &str = "https://api.openai.com";
+const DEFAULT_ANTHROPIC_UPSTREAM: &str = "https://api.anthropic.com";
+/// Anthropic Messages API paths, forwarded to the Anthropic upstream.
+const ANTHROPIC_PATH: &str = "/v1/messages";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
+const DEFAULT_MAX_BODY_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Headers that describe one connection rather than the request, plus `accept-encoding`, so
+/// the upstream answers uncompressed and the usage can be read.
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+    "te",
+    "trailer",
+    "content-length",
+    "accept-encoding",
+];
+
+/// Where the proxy listens and which providers it forwards to.
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub address: SocketAddr,
+    /// Origin OpenAI-compatible requests are forwarded to, path and query unchanged.
+    pub upstream: String,
+    /// `provider` label of the tracked OpenAI-compatible calls.
+    pub provider: String,
+    /// Origin Anthropic Messages API requests (`/v1/messages`) are forwarded to.
+    pub anthropic_upstream: String,
+    pub timeout: Duration,
+    /// Largest request body accepted, since bodies are read whole before being forwarded.
+    pub max_body_bytes: u64,
+    /// Asks OpenAI-compatible upstreams for the usage chunk of streamed calls the client didn't
+    /// request it for.
+    pub stream_usage: bool,
+    /// Labels calls with the namespace and deployment of the calling pod, looked up by its IP
+    /// in the Kubernetes API.
+    pub kubernetes_metadata: bool,
+}
+
+impl ProxyConfig {
+    /// Enabled when `PROXY_LISTEN_ADDRESS` is set; `PROXY_UPSTREAM_URL` defaults to OpenAI,
+    /// `PROXY_PROVIDER` to `openai` and `PROXY_ANTHROPIC_UPSTREAM_URL` to Anthropic.
+    /// `PROXY_MAX_BODY_BYTES` bounds request bodies, 32 MiB by default.
+    /// `PROXY_STREAM_INCLUDE_USAGE=true` enables `stream_usage` and
+    /// `PROXY_KUBERNETES_METADATA=true` enables `kubernetes_metadata`.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let address = match std::env::var("PROXY_LISTEN_ADDRESS") {
+            Ok(address) if !address.is_empty() => address.parse().map_err(|e| {
+                anyhow::anyhow!("invalid PROXY_LISTEN_ADDRESS {:?}: {}", address, e)
+            })?,
+            _ => return Ok(None),
+        };
+        let setting = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let timeout = match setting("PROXY_TIMEOUT_SECS") {
+            Some(secs) => Duration::from_secs(
+                secs.parse()
+                    .map_err(|_| anyhow::anyhow!("invalid PROXY_TIMEOUT_SECS {:?}", secs))?,
+            ),
+            None => DEFAULT_TIMEOUT,
+        };
+        let max_body_bytes = match setting("PROXY_MAX_BODY_BYTES") {
+            Some(bytes) => bytes
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid PROXY_MAX_BODY_BYTES {:?}", bytes))?,
+            None => DEFAULT_MAX_BODY_BYTES,
+        };
+        let origin = |name: &str, default: &str| {
+            setting(name)
+                .as_deref()
+                .unwrap_or(default)
+                .trim_end_matches('/')
+                .to_string()
+        };
+        Ok(Some(Self {
+            address,
+            upstream: origin("PROXY_UPSTREAM_URL", DEFAULT_UPSTREAM),
+            provider: setting("PROXY_PROVIDER").unwrap_or_else(|| "openai".to_string()),
+            anthropic_upstream: origin("PROXY_ANTHROPIC_UPSTREAM_URL", DEFAULT_ANTHROPIC_UPSTREAM),
+            timeout,
+            max_body_bytes,
+            stream_usage: std::env::var("PROXY_STREAM_INCLUDE_USAGE").is_ok_and(|v| v == "true"),
+            kubernetes_metadata: std::env::var("PROXY_KUBERNETES_METADATA")
+                .is_ok_and(|v| v == "true"),
+        }))
+    }
+}
+
+/// Forwards requests to the upstream API and records the usage of every inference call that
+/// passes through. Clients keep sending their own API key (`authorization` or `x-api-key`),
+/// which is passed on untouched.
+struct Proxy {
+    client: reqwest::Client,
+    config: ProxyConfig,
+    tracker: Arc<LLMTracker>,
+    pods: Option<PodResolver>,
+}
+
+fn forwarded_headers(headers: &HeaderMap) -> HeaderMap {
+    headers
+        .iter()
+        .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Body of a request, rejected with 413 when its `content-length` is over `limit`. Requests
+/// without a body, such as most GETs, pass with an empty one, while bodies streamed without a
+/// `content-length` are rejected with 411 since their size isn't known up front.
+fn body(limit: u64) -> impl Filter<Extract = (Bytes,), Error = warp::Rejection> + Clone {
+    let empty = warp::header::headers_cloned().and_then(|headers: HeaderMap| async move {
+        if headers.contains_key(CONTENT_LENGTH) || headers.contains_key(TRANSFER_ENCODING) {
+            Err(warp::reject())
+        } else {
+            Ok(Bytes::new())
+        }
+    });
+    warp::body::content_length_limit(limit)
+        .and(warp::body::bytes())
+        .or(empty)
+        .unify()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({"error": {"message": message, "type": "proxy_error"}});
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        warp::http::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Sets `stream_options.include_usage` on a streamed Chat Completions request, so the last
+/// chunk carries the usage. Other bodies are returned unchanged.
+fn with_stream_usage(body: Bytes) -> Bytes {
+    let Ok(mut request) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return body;
+    };
+    if request.get("stream") != Some(&serde_json::Value::Bool(true)) {
+        return body;
+    }
+    let Some(options) = request
+        .as_object_mut()
+        .map(|request| {
+            request
+                .entry("stream_options")
+                .or_insert_with(|| serde_json::json!({}))
+        })
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return body;
+    };
+    if options.contains_key("include_usage") {
+        return body;
+    }
+    options.insert("include_usage".to_string(), serde_json::Value::Bool(true));
+    serde_json::to_vec(&request).map_or(body, Bytes::from)
+}
+
+/// Records a streamed call once its body is dropped: after the last chunk, on an upstream
+/// error or when the client goes away, with whatever usage had passed through by then.
+/// Streams that carry no usage are estimated from the request and the streamed text.
+struct StreamRecorder {
+    tracker: Arc<LLMTracker>,
+    provider: String,
+    model: String,
+    caller: Caller,
+    request: Bytes,
+    started: Instant,
+    usage: StreamUsage,
+    failed: bool,
+    /// Span of the request, which outlives its handler for as long as the stream.
+    span: Span,
+}
+
+impl Drop for StreamRecorder {
+    fn drop(&mut self) {
+        let _entered = self.span.enter();
+        let elapsed = self.started.elapsed();
+        match self.usage.usage() {
+            Some(usage) => self.tracker.record(
+                &self.provider,
+                &self.model,
+                &self.caller,
+                Some(usage),
+                elapsed,
+            ),
+            None if self.failed => {
+                self.tracker
+                    .record(&self.provider, &self.model, &self.caller, None, elapsed)
+            }
+            None => {
+                let usage =
+                    estimate_usage(&self.model, &self.request, self.usage.completion_tokens());
+                self.tracker.record_estimate(
+                    &self.provider,
+                    &self.model,
+                    &self.caller,
+                    usage,
+                    elapsed,
+                );
+            }
+        }
+    }
+}
+
+impl Proxy {
+    /// Upstream origin and provider label of a request path.
+    fn route(&self, path: &str) -> (&str, &str) {
+        let anthropic = path
+            .strip_prefix(ANTHROPIC_PATH)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        if anthropic {
+            (&self.config.anthropic_upstream, "anthropic")
+        } else {
+            (&self.config.upstream, &self.config.provider)
+        }
+    }
+
+    async fn forward(
+        &self,
+        method: Method,
+        client: Option<IpAddr>,
+        path: FullPath,
+        query: String,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response<Body> {
+        let (upstream, provider) = self.route(path.as_str());
+        let mut url = format!("{}{}", upstream, path.as_str());
+        if !query.is_empty() {
+            url = format!("{}?{}", url, query);
+        }
+        // calls without a model in the body aren't inference calls, e.g. listing models
+        let model = requested_model(&body);
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let mut caller = self.tracker.caller(header, &body);
+        if let (Some(pods), Some(client), Some(_)) = (&self.pods, client, &model) {
+            let workload = pods.resolve(client).await;
+            caller.namespace = workload.namespace;
+            caller.deployment = workload.deployment;
+        }
+        let span = Span::current();
+        span.record("gen_ai.system", provider);
+        if let Some(model) = &model {
+            span.record("gen_ai.request.model", model.as_str());
+        }
+        span.record("llm.team", caller.team.as_str());
+        let request = body.clone();
+        let body = if self.config.stream_usage && provider != "anthropic" {
+            with_stream_usage(body)
+        } else {
+            body
+        };
+        let started = Instant::now();
+        let upstream = self
+            .client
+            .request(method, &url)
+            .headers(forwarded_headers(&headers))
+            .body(body)
+            .send()
+            .await;
+        let upstream = match upstream {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                warn!(error = %e, %url, "proxied request failed");
+                if let Some(model) = &model {
+                    self.tracker
+                        .record(provider, model, &caller, None, started.elapsed());
+                }
+                let status = if e.is_timeout() {
+                    StatusCode::GATEWAY_TIMEOUT
+                } else {
+                    StatusCode::BAD_GATEWAY
+                };
+                return error_response(status, &format!("upstream request failed: {}", e));
+            }
+        };
+
+        let status = upstream.status();
+        span.record("http.response.status_code", status.as_u16());
+        if let Some(model) = &model {
+            self.tracker.record_rate_limits(provider, model, |name| {
+                upstream
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            });
+        }
+        let headers = forwarded_headers(upstream.headers());
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let is_json = content_type.starts_with("application/json");
+        let is_event_stream = content_type.starts_with("text/event-stream");
+        let body = match model {
+            Some(model) if !status.is_success() => {
+                self.tracker
+                    .record(provider, &model, &caller, None, started.elapsed());
+                Body::wrap_stream(upstream.bytes_stream())
+            }
+            Some(model) if is_json => match upstream.bytes().await {
+                Ok(body) => {
+                    self.tracker.record_response(
+                        provider,
+                        &model,
+                        &caller,
+                        &request,
+                        &body,
+                        started.elapsed(),
+                    );
+                    Body::from(body)
+                }
+                Err(e) => {
+                    warn!(error = %e, %url, "failed to read upstream response");
+                    self.tracker
+                        .record(provider, &model, &caller, None, started.elapsed());
+                    return error_response(
+                        StatusCode::BAD_GATEWAY,
+                        &format!("failed to read upstream response: {}", e),
+                    );
+                }
+            },
+            Some(model) if is_event_stream => {
+                let mut recorder = StreamRecorder {
+                    tracker: self.tracker.clone(),
+                    provider: provider.to_string(),
+                    usage: StreamUsage::new(provider, &model),
+                    model,
+                    caller,
+                    request,
+                    started,
+                    failed: false,
+                    span: Span::current(),
+                };
+                Body::wrap_stream(upstream.bytes_stream().map(move |chunk| {
+                    match &chunk {
+                        Ok(chunk) => recorder.usage.feed(chunk),
+                        Err(e) => {
+                            warn!(error = %e, "proxied stream failed");
+                            recorder.failed = true;
+                        }
+                    }
+                    chunk
+                }))
+            }
+            _ => Body::wrap_stream(upstream.bytes_stream()),
+        };
+        let mut response = Response::new(body);
+        *response.status_mut() = status;
+        *response.headers_mut() = headers;
+        response
+    }
+}
+
+/// Span of a proxied request, a child of the caller's trace when the request carries a
+/// `traceparent` header. The request's provider, model and status are recorded on it, and
+/// the cost and tokens by the tracker.
+#[cfg_attr(not(feature = "otlp"), allow(unused_variables))]
+fn request_span(headers: &HeaderMap) -> Span {
+    let span = info_span!(
+        "llm_request",
+        otel.kind = "server",
+        otel.status_code = Empty,
+        gen_ai.system = Empty,
+        gen_ai.request.model = Empty,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        http.response.status_code = Empty,
+        llm.team = Empty,
+        llm.cost_center = Empty,
+        llm.cost_usd = Empty,
+        llm.estimated = Empty,
+    );
+    #[cfg(feature = "otlp")]
+    crate::telemetry::set_parent(&span, |name| {
+        headers.get(name).and_then(|value| value.to_str().ok())
+    });
+    span
+}
+
+/// Serves the proxy until shutdown.
+pub async fn serve(
+    config: ProxyConfig,
+    tracker: Arc<LLMTracker>,
+    shutdown: Shutdown,
+) -> Result<(), MonitorError> {
+    let client = reqwest::Client::builder()
+        .timeout(config.timeout)
+        .build()
+        .map_err(|e| anyhow::anyhow!("failed to create proxy client: {}", e))?;
+    let pods = if config.kubernetes_metadata {
+        Some(PodResolver::in_cluster()?)
+    } else {
+        None
+    };
+    let address = config.address;
+    let max_body_bytes = config.max_body_bytes;
+    info!(
+        %address,
+        upstream = %config.upstream,
+        provider = %config.provider,
+        anthropic_upstream = %config.anthropic_upstream,
+        "serving LLM proxy"
+    );
+    let proxy = Arc::new(Proxy {
+        client,
+        config,
+        tracker,
+        pods,
+    });
+    let routes = warp::method()
+        .and(warp::addr::remote())
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(warp::header::headers_cloned())
+        .and(body(max_body_bytes))
+        .then(
+            move |method, remote: Option<SocketAddr>, path, query, headers, body| {
+                let proxy = proxy.clone();
+                let client = remote.map(|remote| remote.ip());
+                async move {
+                    let span = request_span(&headers);
+                    proxy
+                        .forward(method, client, path, query, headers, body)
+                        .instrument(span)
+                        .await
+                }
+            },
+        );
+    let (_, server) = warp::serve(routes)
+        .try_bind_with_graceful_shutdown(address, shutdown.wait())
+        .map_err(|e| anyhow::anyhow!("failed to bind {}: {}", address, e))?;
+    server.await;
+    Ok(())
+}
@@ -0,0 +1,4 @@
+//! Pushing the gathered registry to systems that cannot scrape the exporter.
+
+pub mod pushgateway;
+pub mod remote_write;
@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use base64::Engine;
+use prometheus::{Encoder, Registry, TextEncoder};
+
+use crate::auth::AuthConfig;
+use crate::providers::env_map;
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+/// Pushes the whole registry to a Prometheus Pushgateway after every poll and once more at
+/// shutdown, replacing the previous push of the same grouping key.
+pub struct Pushgateway {
+    client: reqwest::Client,
+    url: String,
+    auth: Option<AuthConfig>,
+    registry: Registry,
+}
+
+/// Encodes a grouping key value for the URL path. Values that are not plain URL-safe ASCII use
+/// the base64url form the Pushgateway accepts, and an empty value is spelled `@base64/=`.
+fn path_segment(name: &str, value: &str) -> String {
+    let plain = value
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    if value.is_empty() {
+        format!("{}@base64/=", name)
+    } else if plain {
+        format!("{}/{}", name, value)
+    } else {
+        format!(
+            "{}@base64/{}",
+            name,
+            base64::engine::general_purpose::URL_SAFE.encode(value)
+        )
+    }
+}
+
+impl Pushgateway {
+    /// Enabled when `PUSHGATEWAY_URL` is set. `PUSHGATEWAY_JOB` names the job and
+    /// `PUSHGATEWAY_GROUPING` adds grouping labels such as `instance=nightly`.
+    pub fn from_env(registry: Registry) -> Result<Option<Self>, MonitorError> {
+        let base = match std::env::var("PUSHGATEWAY_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let job =
+            std::env::var("PUSHGATEWAY_JOB").unwrap_or_else(|_| "llm_cost_exporter".to_string());
+        let mut url = format!(
+            "{}/metrics/{}",
+            base.trim_end_matches('/'),
+            path_segment("job", &job)
+        );
+        let mut grouping: Vec<_> = env_map("PUSHGATEWAY_GROUPING").into_iter().collect();
+        grouping.sort();
+        for (name, value) in grouping {
+            url.push('/');
+            url.push_str(&path_segment(&name, &value));
+        }
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            url,
+            auth: AuthConfig::from_env("PUSHGATEWAY")?,
+            registry,
+        }))
+    }
+
+    pub async fn push(&self) -> Result<(), MonitorError> {
+        let encoder = TextEncoder::new();
+        let mut body = Vec::new();
+        encoder
+            .encode(&self.registry.gather(), &mut body)
+            .map_err(|e| anyhow::anyhow!("failed to encode metrics: {}", e))?;
+        let mut builder = self
+            .client
+            .put(&self.url)
+            .header("Content-Type", encoder.format_type())
+            .body(body);
+        if let Some(auth) = &self.auth {
+            builder = auth.apply(builder);
+        }
+        builder.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UsageSink for Pushgateway {
+    fn name(&self) -> &'static str {
+        "pushgateway"
+    }
+
+    async fn publish(&self, _usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        self.push().await
+    }
+
+    /// Pushes once more so the values of the last poll land before the exporter exits.
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        self.push().await
+    }
+}
@@ -0,0 +1,191 @@
+use prometheus::proto::{MetricFamily, MetricType};
+use prometheus::Registry;
+use prost::Message;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time;
+
+use crate::auth::AuthConfig;
+use crate::leader::Leadership;
+use crate::providers::env_map;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Periodically sends everything in the registry to a Prometheus remote_write endpoint
+/// (Prometheus, Mimir, Cortex, Thanos receive, VictoriaMetrics).
+pub struct RemoteWriter {
+    client: reqwest::Client,
+    url: String,
+    auth: Option<AuthConfig>,
+    headers: HashMap<String, String>,
+    interval: Duration,
+}
+
+impl RemoteWriter {
+    /// Enabled when `REMOTE_WRITE_URL` is set.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let url = match std::env::var("REMOTE_WRITE_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let interval_secs = std::env::var("REMOTE_WRITE_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            url,
+            auth: AuthConfig::from_env("REMOTE_WRITE")?,
+            headers: env_map("REMOTE_WRITE_HEADERS"),
+            interval: Duration::from_secs(interval_secs),
+        }))
+    }
+
+    /// Writes on every interval until shutdown, then writes once more so the final values land.
+    /// Standby replicas skip the writes, so only the leader's series reach the remote storage.
+    pub async fn run(self, registry: Registry, leadership: Leadership, shutdown: Shutdown) {
+        let mut interval = time::interval(self.interval);
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut stop => break,
+            }
+            if !leadership.is_leader() {
+                continue;
+            }
+            if let Err(e) = self.write(&registry.gather()).await {
+                tracing::warn!(error = %e, "remote write failed");
+            }
+        }
+        if !leadership.is_leader() {
+            return;
+        }
+        if let Err(e) = self.write(&registry.gather()).await {
+            tracing::warn!(error = %e, "final remote write failed");
+        }
+    }
+
+    async fn write(&self, families: &[MetricFamily]) -> Result<(), MonitorError> {
+        let request = WriteRequest {
+            timeseries: to_timeseries(families, chrono::Utc::now().timestamp_millis()),
+        };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .map_err(|e| anyhow::anyhow!("failed to compress remote write request: {}", e))?;
+
+        let mut builder = self
+            .client
+            .post(&self.url)
+            .header("Content-Encoding", "snappy")
+            .header("Content-Type", "application/x-protobuf")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(auth) = &self.auth {
+            builder = auth.apply(builder);
+        }
+        builder.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Flattens metric families into remote write series; histograms and summaries expand into
+/// their `_bucket`/quantile, `_sum` and `_count` series like in the text exposition.
+fn to_timeseries(families: &[MetricFamily], timestamp: i64) -> Vec<TimeSeries> {
+    let mut series = Vec::new();
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let labels: Vec<(&str, String)> = metric
+                .get_label()
+                .iter()
+                .map(|label| (label.get_name(), label.get_value().to_string()))
+                .collect();
+            let mut push = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+                let mut pairs = labels.clone();
+                pairs.extend(extra);
+                pairs.push(("__name__", format!("{}{}", name, suffix)));
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+                series.push(TimeSeries {
+                    labels: pairs
+                        .into_iter()
+                        .map(|(name, value)| Label {
+                            name: name.to_string(),
+                            value,
+                        })
+                        .collect(),
+                    samples: vec![Sample { value, timestamp }],
+                });
+            };
+            match family.get_field_type() {
+                MetricType::COUNTER => push("", None, metric.get_counter().get_value()),
+                MetricType::GAUGE => push("", None, metric.get_gauge().get_value()),
+                MetricType::UNTYPED => push("", None, metric.get_untyped().get_value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    for bucket in histogram.get_bucket() {
+                        push(
+                            "_bucket",
+                            Some(("le", bucket.get_upper_bound().to_string())),
+                            bucket.get_cumulative_count() as f64,
+                        );
+                    }
+                    push(
+                        "_bucket",
+                        Some(("le", "+Inf".to_string())),
+                        histogram.get_sample_count() as f64,
+                    );
+                    push("_sum", None, histogram.get_sample_sum());
+                    push("_count", None, histogram.get_sample_count() as f64);
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        push(
+                            "",
+                            Some(("quantile", quantile.get_quantile().to_string())),
+                            quantile.get_value(),
+                        );
+                    }
+                    push("_sum", None, summary.get_sample_sum());
+                    push("_count", None, summary.get_sample_count() as f64);
+                }
+            }
+        }
+    }
+    series
+}
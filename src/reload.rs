@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{error, info, warn};
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use crate::auth::{self, AuthConfig};
+use crate::budget::BudgetTracker;
+use crate::config::{Cli, Config};
+use crate::exporter::Monitors;
+use crate::metrics::LLMMetrics;
+use crate::pricing::{Pricing, PricingCatalog};
+use crate::providers::ProviderRegistry;
+use crate::shutdown::Shutdown;
+use crate::MonitorError;
+
+/// A reload request, answered with the reload's outcome.
+type ReloadRequest = oneshot::Sender<Result<(), String>>;
+
+/// Asks a running [`ConfigReloader`] to reload the config, e.g. from `POST /-/reload`.
+#[derive(Debug, Clone)]
+pub struct ReloadHandle(mpsc::Sender<ReloadRequest>);
+
+/// Reload requests of the [`ReloadHandle`]s, for [`ConfigReloader::run`].
+pub struct ReloadRequests(mpsc::Receiver<ReloadRequest>);
+
+/// A handle to request reloads and the requests to hand to the reloader.
+pub fn channel() -> (ReloadHandle, ReloadRequests) {
+    let (sender, receiver) = mpsc::channel(1);
+    (ReloadHandle(sender), ReloadRequests(receiver))
+}
+
+impl ReloadHandle {
+    /// Reloads the config and waits for the outcome.
+    pub async fn reload(&self) -> Result<(), String> {
+        let (reply, outcome) = oneshot::channel();
+        self.0
+            .send(reply)
+            .await
+            .map_err(|_| "config reloads are not running".to_string())?;
+        outcome
+            .await
+            .unwrap_or_else(|_| Err("config reload was abandoned".to_string()))
+    }
+}
+
+/// `POST /-/reload`, behind the same authentication as the metrics path. Answers 500 with the
+/// error when the new config is rejected.
+pub fn routes(
+    handle: ReloadHandle,
+    auth: Option<AuthConfig>,
+) -> BoxedFilter<(warp::reply::Response,)> {
+    warp::path!("-" / "reload")
+        .and(warp::post())
+        .and(auth::require(auth))
+        .then(move || {
+            let handle = handle.clone();
+            async move {
+                match handle.reload().await {
+                    Ok(()) => "config reloaded\n".into_response(),
+                    Err(e) => warp::reply::with_status(
+                        format!("config reload failed: {}\n", e),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    )
+                    .into_response(),
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Reloads the config file on SIGHUP or a [`ReloadHandle`] request, applying the providers,
+/// budgets and pricing file without a restart. A new config is validated in full, including
+/// creating its monitors and reading its pricing file, before any of it is applied, so a
+/// rejected config leaves the previous one in use. Server, label, team and cost-center
+/// settings only apply at startup.
+pub struct ConfigReloader {
+    cli: Cli,
+    registry: ProviderRegistry,
+    pricing: Pricing,
+    monitors: Monitors,
+    budgets: BudgetTracker,
+    metrics: Arc<LLMMetrics>,
+}
+
+impl ConfigReloader {
+    /// Reloads with the same flags the exporter was started with; the config file is read
+    /// again, the environment isn't.
+    pub fn new(
+        cli: Cli,
+        registry: ProviderRegistry,
+        pricing: Pricing,
+        monitors: Monitors,
+        budgets: BudgetTracker,
+        metrics: Arc<LLMMetrics>,
+    ) -> Self {
+        Self {
+            cli,
+            registry,
+            pricing,
+            monitors,
+            budgets,
+            metrics,
+        }
+    }
+
+    async fn reload(&self) -> Result<(), MonitorError> {
+        let config = Config::load(self.cli.clone())?;
+        let pricing_file = config
+            .pricing_file
+            .as_deref()
+            .map(PricingCatalog::from_file)
+            .transpose()?;
+        let monitors = self
+            .registry
+            .monitors(config.providers.as_deref(), &self.pricing)
+            .await?;
+        if monitors.is_empty() {
+            warn!("no provider configured after the reload");
+        }
+
+        self.pricing.apply_file(pricing_file);
+        self.monitors.replace(monitors);
+        let providers = self.monitors.providers();
+        self.budgets.retain_providers(&providers);
+        self.budgets.set_budgets(config.budgets);
+        info!(?providers, "config reloaded");
+        Ok(())
+    }
+
+    /// Reloads on every SIGHUP and request until shutdown.
+    pub async fn run(
+        self,
+        mut requests: ReloadRequests,
+        shutdown: Shutdown,
+    ) -> Result<(), MonitorError> {
+        let mut hangup = signal(SignalKind::hangup())
+            .map_err(|e| anyhow::anyhow!("failed to install the SIGHUP handler: {}", e))?;
+        let stop = shutdown.wait();
+        tokio::pin!(stop);
+        loop {
+            let reply = tokio::select! {
+                _ = hangup.recv() => {
+                    info!("received SIGHUP, reloading config");
+                    None
+                }
+                Some(reply) = requests.0.recv() => Some(reply),
+                _ = &mut stop => break,
+            };
+            let outcome = self.reload().await;
+            self.metrics.record_config_reload(outcome.is_ok());
+            if let Err(e) = &outcome {
+                error!(error = %e, "config reload failed, keeping the previous config");
+            }
+            if let Some(reply) = reply {
+                let _ = reply.send(outcome.map_err(|e| e.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
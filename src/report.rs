@@ -0,0 +1,158 @@
+use chrono::{DateTime, Datelike, Months, NaiveDate, TimeDelta, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::history::UsageHistory;
+use crate::providers::{billing_period_start_at, reporting_timezone, start_of_day, LLMUsage};
+use crate::MonitorError;
+
+/// How far before a report's start the history is read for the totals its usage is counted
+/// from, the longest billing period.
+const BASELINE_LOOKBACK: TimeDelta = TimeDelta::days(31);
+
+/// What the rows of a report are broken down by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportGroup {
+    Provider,
+    Model,
+    Team,
+    CostCenter,
+}
+
+/// Usage and cost of one group over a report's date range. Breakdowns a report isn't grouped
+/// by are empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReportRow {
+    pub provider: String,
+    pub model: String,
+    pub team: String,
+    pub cost_center: String,
+    pub cost_usd: f64,
+    /// Every input token: regular, read from and written to the prompt cache, and embedded.
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub requests: u64,
+}
+
+impl ReportRow {
+    pub const HEADER: [&'static str; 8] = [
+        "provider",
+        "model",
+        "team",
+        "cost_center",
+        "cost_usd",
+        "prompt_tokens",
+        "completion_tokens",
+        "requests",
+    ];
+
+    pub fn cells(&self) -> Vec<String> {
+        vec![
+            self.provider.clone(),
+            self.model.clone(),
+            self.team.clone(),
+            self.cost_center.clone(),
+            format!("{:.4}", self.cost_usd),
+            self.prompt_tokens.to_string(),
+            self.completion_tokens.to_string(),
+            self.requests.to_string(),
+        ]
+    }
+
+    /// Adds what `usage` reports beyond `baseline`, an earlier total of the same series.
+    fn add(&mut self, usage: &LLMUsage, baseline: Option<&LLMUsage>) {
+        let prompt_tokens = |usage: &LLMUsage| {
+            usage.prompt_tokens
+                + usage.cached_prompt_tokens
+                + usage.cache_write_tokens
+                + usage.embedding_tokens
+        };
+        let baseline = baseline.cloned().unwrap_or_default();
+        self.cost_usd += usage.cost_usd - baseline.cost_usd;
+        self.prompt_tokens += prompt_tokens(usage).saturating_sub(prompt_tokens(&baseline));
+        self.completion_tokens += usage
+            .completion_tokens
+            .saturating_sub(baseline.completion_tokens);
+        self.requests += usage.request_count.saturating_sub(baseline.request_count);
+    }
+}
+
+/// First and last day of the month before the current one in the reporting timezone, the
+/// default range of a report.
+pub fn last_month() -> (NaiveDate, NaiveDate) {
+    let today = Utc::now().with_timezone(&reporting_timezone()).date_naive();
+    let this_month = today.with_day(1).unwrap_or(today);
+    let start = this_month - Months::new(1);
+    (start, this_month.pred_opt().unwrap_or(start))
+}
+
+/// Usage from `first` to `last` day inclusive, in the reporting timezone, from the recorded
+/// history. Providers report totals for their billing period, so a series' usage is the
+/// change of its total over the range within each period: its last total in the range, less
+/// the last before the range started, if the period did. The history has to cover the start
+/// of the range for series already used then.
+pub async fn usage(
+    history: &dyn UsageHistory,
+    first: NaiveDate,
+    last: NaiveDate,
+    group_by: &[ReportGroup],
+) -> Result<Vec<ReportRow>, MonitorError> {
+    let start = start_of_day(first);
+    let end = start_of_day(last + TimeDelta::days(1));
+    let snapshots = history.query(None, start - BASELINE_LOOKBACK, end).await?;
+
+    // the last total before and within the range of each series and billing period
+    type Series = (
+        String,
+        String,
+        BTreeMap<&'static str, String>,
+        DateTime<Utc>,
+    );
+    let mut totals: BTreeMap<Series, (Option<LLMUsage>, Option<LLMUsage>)> = BTreeMap::new();
+    for snapshot in snapshots {
+        let period = billing_period_start_at(&snapshot.provider, snapshot.polled_at);
+        for usage in snapshot.models {
+            let series = (
+                snapshot.provider.clone(),
+                usage.model.clone(),
+                usage.labels.clone(),
+                period,
+            );
+            let (before, within) = totals.entry(series).or_default();
+            if snapshot.polled_at < start {
+                *before = Some(usage);
+            } else {
+                *within = Some(usage);
+            }
+        }
+    }
+
+    let mut rows: BTreeMap<(String, String, String, String), ReportRow> = BTreeMap::new();
+    for ((provider, model, labels, _), (before, within)) in totals {
+        let Some(within) = within else {
+            continue;
+        };
+        let group = |dimension: ReportGroup, value: &str| match group_by.contains(&dimension) {
+            true => value.to_string(),
+            false => String::new(),
+        };
+        let label = |name| labels.get(name).map_or("", String::as_str);
+        let key = (
+            group(ReportGroup::Provider, &provider),
+            group(ReportGroup::Model, &model),
+            group(ReportGroup::Team, label("team")),
+            group(ReportGroup::CostCenter, label("cost_center")),
+        );
+        rows.entry(key.clone())
+            .or_insert_with(|| ReportRow {
+                provider: key.0,
+                model: key.1,
+                team: key.2,
+                cost_center: key.3,
+                ..ReportRow::default()
+            })
+            .add(&within, before.as_ref());
+    }
+    Ok(rows.into_values().collect())
+}
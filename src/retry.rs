@@ -0,0 +1,201 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+use crate::MonitorError;
+
+/// How often and how patiently a failed provider call is retried before the poll gives up,
+/// so one transient error doesn't leave a gap until the next poll.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per call including the first; 1 disables retries.
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled for each further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Fraction of each wait that is randomized, so instances don't retry in lockstep.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.5,
+        }
+    }
+}
+
+/// A number in `[0, 1)`, random enough to spread retries.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl RetryPolicy {
+    /// Reads `RETRY_MAX_ATTEMPTS`, `RETRY_INITIAL_BACKOFF_MS`, `RETRY_MAX_BACKOFF_MS` and
+    /// `RETRY_JITTER`, keeping the default of any that is unset.
+    pub fn from_env() -> Result<Self, MonitorError> {
+        fn setting<T: std::str::FromStr>(name: &str) -> Result<Option<T>, MonitorError> {
+            match std::env::var(name) {
+                Ok(value) if !value.is_empty() => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| anyhow::anyhow!("invalid {} {:?}", name, value).into()),
+                _ => Ok(None),
+            }
+        }
+        let defaults = Self::default();
+        let policy = Self {
+            max_attempts: setting("RETRY_MAX_ATTEMPTS")?.unwrap_or(defaults.max_attempts),
+            initial_backoff: setting("RETRY_INITIAL_BACKOFF_MS")?
+                .map_or(defaults.initial_backoff, Duration::from_millis),
+            max_backoff: setting("RETRY_MAX_BACKOFF_MS")?
+                .map_or(defaults.max_backoff, Duration::from_millis),
+            jitter: setting("RETRY_JITTER")?.unwrap_or(defaults.jitter),
+        };
+        if policy.max_attempts == 0 {
+            return Err(anyhow::anyhow!("RETRY_MAX_ATTEMPTS must be at least 1").into());
+        }
+        if !(0.0..=1.0).contains(&policy.jitter) {
+            return Err(anyhow::anyhow!("RETRY_JITTER must be between 0 and 1").into());
+        }
+        Ok(policy)
+    }
+
+    /// Wait before retry number `retry`, counting from 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_backoff);
+        backoff.mul_f64(1.0 - self.jitter * random_fraction())
+    }
+
+    /// Runs `call` until it succeeds, fails with an error that isn't transient, or runs out of
+    /// attempts, returning the last result. `on_retry` is told about every retried error.
+    /// A rate limited call waits at least as long as the provider's `Retry-After`, and isn't
+    /// retried when that is longer than the maximum backoff.
+    pub async fn run<T, F, Fut>(
+        &self,
+        mut call: F,
+        on_retry: impl Fn(&MonitorError),
+    ) -> Result<T, MonitorError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, MonitorError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(e)
+                    if e.is_transient()
+                        && attempt < self.max_attempts
+                        && e.retry_after().is_none_or(|wait| wait <= self.max_backoff) =>
+                {
+                    let backoff = self
+                        .backoff(attempt)
+                        .max(e.retry_after().unwrap_or_default());
+                    warn!(error = %e, attempt, ?backoff, "provider call failed, retrying");
+                    on_retry(&e);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Longest a rate limited provider's polls are put off, in poll intervals.
+const MAX_THROTTLE_INTERVALS: u32 = 16;
+
+/// Puts off the polls of providers that rate limit the exporter, so a provider that keeps
+/// answering `429` isn't asked again every interval. Each poll that ends rate limited doubles
+/// the wait, from one poll interval up to 16, or longer when the provider's `Retry-After` says
+/// so; a poll that isn't rate limited resets it.
+#[derive(Default)]
+pub(crate) struct Throttle {
+    /// Rate limited polls in a row and when the provider may be polled again.
+    providers: Mutex<HashMap<&'static str, (u32, Instant)>>,
+}
+
+impl Throttle {
+    /// How long the provider's polls are still put off, if they are.
+    pub(crate) fn remaining(&self, provider: &str) -> Option<Duration> {
+        let providers = self.providers.lock().unwrap();
+        let (_, until) = providers.get(provider)?;
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Records a rate limited poll and returns how long the next is put off.
+    pub(crate) fn back_off(
+        &self,
+        provider: &'static str,
+        retry_after: Option<Duration>,
+        poll_interval: Duration,
+    ) -> Duration {
+        let mut providers = self.providers.lock().unwrap();
+        let (strikes, until) = providers.entry(provider).or_insert((0, Instant::now()));
+        *strikes += 1;
+        let intervals = 2u32
+            .saturating_pow(*strikes - 1)
+            .min(MAX_THROTTLE_INTERVALS);
+        let wait = poll_interval
+            .saturating_mul(intervals)
+            .max(retry_after.unwrap_or_default());
+        *until = Instant::now() + wait;
+        wait
+    }
+
+    pub(crate) fn reset(&self, provider: &str) {
+        self.providers.lock().unwrap().remove(provider);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn throttle_doubles_up_to_the_cap() {
+        let throttle = Throttle::default();
+        let waits: Vec<u64> = (0..7)
+            .map(|_| throttle.back_off("openai", None, INTERVAL).as_secs() / 60)
+            .collect();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 16, 16]);
+        assert!(throttle.remaining("openai").is_some());
+        assert!(throttle.remaining("anthropic").is_none());
+
+        throttle.reset("openai");
+        assert!(throttle.remaining("openai").is_none());
+        assert_eq!(throttle.back_off("openai", None, INTERVAL), INTERVAL);
+    }
+
+    #[test]
+    fn throttle_waits_out_a_longer_retry_after() {
+        let throttle = Throttle::default();
+        let retry_after = Duration::from_secs(300);
+        assert_eq!(
+            throttle.back_off("xai", Some(retry_after), INTERVAL),
+            retry_after
+        );
+        assert_eq!(
+            throttle.back_off("xai", Some(Duration::from_secs(1)), INTERVAL),
+            INTERVAL * 2
+        );
+    }
+}
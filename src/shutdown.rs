@@ -0,0 +1,42 @@
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+
+/// Resolves once SIGTERM or SIGINT is received. Clone it for every task that has to stop.
+#[derive(Debug, Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Installs the signal handlers.
+    pub fn listen() -> Result<Self, std::io::Error> {
+        Self::listen_or_request().map(|(_, shutdown)| shutdown)
+    }
+
+    /// Like [`Shutdown::listen`], but also returns a sender to request the shutdown from within
+    /// by sending `true`, for a task that can't go on to stop the exporter the same way a
+    /// signal does.
+    pub fn listen_or_request() -> Result<(watch::Sender<bool>, Self), std::io::Error> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let (sender, receiver) = watch::channel(false);
+        let signalled = sender.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = terminate.recv() => tracing::info!("received SIGTERM, shutting down"),
+                _ = interrupt.recv() => tracing::info!("received SIGINT, shutting down"),
+            }
+            let _ = signalled.send(true);
+        });
+        Ok((sender, Self(receiver)))
+    }
+
+    /// A shutdown requested by sending `true`, for services that embed the exporter and stop it
+    /// themselves.
+    pub fn channel() -> (watch::Sender<bool>, Self) {
+        let (sender, receiver) = watch::channel(false);
+        (sender, Self(receiver))
+    }
+
+    pub async fn wait(mut self) {
+        let _ = self.0.wait_for(|requested| *requested).await;
+    }
+}
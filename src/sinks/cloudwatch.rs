@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+
+use super::{ProviderUsage, UsageSink};
+use crate::aws::{AwsJsonClient, Dimension, MetricDatum};
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::MonitorError;
+
+/// Publishes usage to CloudWatch with PutMetricData, one data point per metric, provider and
+/// model, dimensioned like the Prometheus labels.
+pub struct CloudWatchSink {
+    cloudwatch: AwsJsonClient,
+    namespace: String,
+}
+
+impl CloudWatchSink {
+    /// Enabled with `CLOUDWATCH_METRICS_ENABLED=true`, using the default AWS configuration.
+    pub async fn from_env() -> Result<Option<Self>, MonitorError> {
+        if !std::env::var("CLOUDWATCH_METRICS_ENABLED").is_ok_and(|v| v == "true") {
+            return Ok(None);
+        }
+        let aws_config = aws_config::load_from_env().await;
+        Ok(Some(Self {
+            cloudwatch: AwsJsonClient::cloudwatch(&aws_config)?,
+            namespace: std::env::var("CLOUDWATCH_METRICS_NAMESPACE")
+                .unwrap_or_else(|_| "LLMCostExporter".to_string()),
+        }))
+    }
+}
+
+fn data(provider: &str, usage: &LLMUsage, timestamp: i64) -> Vec<MetricDatum> {
+    let mut dimensions = vec![
+        Dimension {
+            name: "Provider".to_string(),
+            value: provider.to_string(),
+        },
+        Dimension {
+            name: "Model".to_string(),
+            value: usage.model.clone(),
+        },
+    ];
+    for label in USAGE_LABELS {
+        if let Some(value) = usage.labels.get(label).filter(|value| !value.is_empty()) {
+            dimensions.push(Dimension {
+                name: label.to_string(),
+                value: value.clone(),
+            });
+        }
+    }
+    let datum = |metric_name: &str, value: f64, unit: &'static str| MetricDatum {
+        metric_name: metric_name.to_string(),
+        dimensions: dimensions.clone(),
+        value,
+        unit,
+        timestamp,
+    };
+    vec![
+        datum("CostUSD", usage.cost_usd, "None"),
+        datum("RequestCostUSD", usage.request_cost_usd, "None"),
+        datum("PromptTokens", usage.prompt_tokens as f64, "Count"),
+        datum(
+            "CachedPromptTokens",
+            usage.cached_prompt_tokens as f64,
+            "Count",
+        ),
+        datum("CacheWriteTokens", usage.cache_write_tokens as f64, "Count"),
+        datum("CompletionTokens", usage.completion_tokens as f64, "Count"),
+        datum("EmbeddingTokens", usage.embedding_tokens as f64, "Count"),
+        datum("Requests", usage.request_count as f64, "Count"),
+        datum("ImagesGenerated", usage.images_total() as f64, "Count"),
+        datum(
+            "AudioTranscribedSeconds",
+            usage.transcription_minutes * 60.0,
+            "Seconds",
+        ),
+        datum("SpeechCharacters", usage.speech_characters as f64, "Count"),
+    ]
+}
+
+#[async_trait]
+impl UsageSink for CloudWatchSink {
+    fn name(&self) -> &'static str {
+        "cloudwatch"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let data: Vec<MetricDatum> = usage
+            .iter()
+            .flat_map(|provider_usage| {
+                provider_usage.usage.iter().flat_map(move |model_usage| {
+                    data(provider_usage.provider, model_usage, timestamp)
+                })
+            })
+            .collect();
+        self.cloudwatch
+            .put_metric_data(&self.namespace, &data)
+            .await
+    }
+}
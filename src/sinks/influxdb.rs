@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+
+use super::{ProviderUsage, UsageSink};
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::MonitorError;
+
+/// Writes one `llm_usage` point per provider and model to an InfluxDB v2 bucket.
+pub struct InfluxDbSink {
+    client: reqwest::Client,
+    write_url: String,
+    token: String,
+    measurement: String,
+}
+
+impl InfluxDbSink {
+    /// Enabled when `INFLUXDB_URL` is set; `INFLUXDB_ORG`, `INFLUXDB_BUCKET` and
+    /// `INFLUXDB_TOKEN` are then required.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let url = match std::env::var("INFLUXDB_URL") {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let required = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("{} is required with INFLUXDB_URL", name))
+        };
+        let org = required("INFLUXDB_ORG")?;
+        let bucket = required("INFLUXDB_BUCKET")?;
+        let write_url = reqwest::Url::parse_with_params(
+            &format!("{}/api/v2/write", url.trim_end_matches('/')),
+            &[
+                ("org", org.as_str()),
+                ("bucket", bucket.as_str()),
+                ("precision", "s"),
+            ],
+        )
+        .map_err(|e| anyhow::anyhow!("invalid INFLUXDB_URL {:?}: {}", url, e))?;
+        Ok(Some(Self {
+            client: reqwest::Client::new(),
+            write_url: write_url.to_string(),
+            token: required("INFLUXDB_TOKEN")?,
+            measurement: std::env::var("INFLUXDB_MEASUREMENT")
+                .unwrap_or_else(|_| "llm_usage".to_string()),
+        }))
+    }
+
+    fn line(&self, provider: &str, usage: &LLMUsage, timestamp: i64) -> String {
+        let mut line = escape(&self.measurement, &[',', ' ']);
+        let mut tags = vec![("provider", provider), ("model", usage.model.as_str())];
+        for label in USAGE_LABELS {
+            if let Some(value) = usage.labels.get(label).filter(|value| !value.is_empty()) {
+                tags.push((label, value));
+            }
+        }
+        // line protocol wants tags sorted by key for the fastest writes
+        tags.sort_by_key(|(key, _)| *key);
+        for (key, value) in tags {
+            if value.is_empty() {
+                continue;
+            }
+            line.push(',');
+            line.push_str(&escape(key, &[',', '=', ' ']));
+            line.push('=');
+            line.push_str(&escape(value, &[',', '=', ' ']));
+        }
+        line.push_str(&format!(
+            " cost_usd={},request_cost_usd={}",
+            usage.cost_usd, usage.request_cost_usd
+        ));
+        for (token_type, count) in usage.token_counts() {
+            line.push_str(&format!(",{}_tokens={}i", token_type, count));
+        }
+        line.push_str(&format!(
+            ",requests={}i,images={}i,transcription_minutes={},speech_characters={}i {}",
+            usage.request_count,
+            usage.images_total(),
+            usage.transcription_minutes,
+            usage.speech_characters,
+            timestamp
+        ));
+        line
+    }
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[async_trait]
+impl UsageSink for InfluxDbSink {
+    fn name(&self) -> &'static str {
+        "influxdb"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let body: Vec<String> = usage
+            .iter()
+            .flat_map(|provider_usage| {
+                provider_usage
+                    .usage
+                    .iter()
+                    .map(move |model_usage| (provider_usage.provider, model_usage))
+            })
+            .map(|(provider, model_usage)| self.line(provider, model_usage, timestamp))
+            .collect();
+        if body.is_empty() {
+            return Ok(());
+        }
+        self.client
+            .post(&self.write_url)
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(body.join("\n"))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
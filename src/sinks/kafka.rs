@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{BaseRecord, DeliveryResult, Producer, ProducerContext, ThreadedProducer};
+use rdkafka::ClientContext;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use super::{CallEvent, CallSink};
+use crate::providers::env_map;
+use crate::MonitorError;
+
+const DEFAULT_TOPIC: &str = "llm-usage-events";
+/// How long events still queued are given to reach the brokers when the exporter exits.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Logs the events the brokers didn't take.
+struct DeliveryLogger;
+
+impl ClientContext for DeliveryLogger {}
+
+impl ProducerContext for DeliveryLogger {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if let Err((e, _)) = result {
+            warn!(error = %e, "failed to deliver usage event to Kafka");
+        }
+    }
+}
+
+/// Publishes every tracked call as a JSON event to a Kafka topic, keyed by provider so the
+/// events of a provider keep their order. Events are queued and sent in the background.
+pub struct KafkaSink {
+    producer: Arc<ThreadedProducer<DeliveryLogger>>,
+    topic: String,
+}
+
+impl KafkaSink {
+    /// Enabled when `KAFKA_BROKERS` lists bootstrap servers, publishing to `KAFKA_TOPIC`
+    /// (default `llm-usage-events`). `KAFKA_CONFIG` sets further librdkafka properties as
+    /// `key=value` pairs, e.g. `security.protocol=SASL_SSL,sasl.mechanism=PLAIN`.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        let brokers = match std::env::var("KAFKA_BROKERS") {
+            Ok(brokers) if !brokers.is_empty() => brokers,
+            _ => return Ok(None),
+        };
+        let topic = std::env::var("KAFKA_TOPIC")
+            .ok()
+            .filter(|topic| !topic.is_empty())
+            .unwrap_or_else(|| DEFAULT_TOPIC.to_string());
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &brokers)
+            .set("client.id", "llm-cost-exporter");
+        for (key, value) in env_map("KAFKA_CONFIG") {
+            config.set(key, value);
+        }
+        let producer = config
+            .create_with_context(DeliveryLogger)
+            .map_err(|e| anyhow::anyhow!("failed to create Kafka producer: {}", e))?;
+        Ok(Some(Self {
+            producer: Arc::new(producer),
+            topic,
+        }))
+    }
+}
+
+#[async_trait]
+impl CallSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn publish(&self, event: &CallEvent) -> Result<(), MonitorError> {
+        let payload = serde_json::to_vec(event).map_err(anyhow::Error::from)?;
+        let record = BaseRecord::to(&self.topic)
+            .key(&event.provider)
+            .payload(&payload);
+        self.producer.send(record).map_err(|(e, _)| {
+            anyhow::anyhow!("failed to queue usage event for {}: {}", self.topic, e).into()
+        })
+    }
+
+    /// Waits up to ten seconds for the queued events to reach the brokers, on a blocking
+    /// thread so the runtime's workers keep serving meanwhile.
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        let producer = self.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(FLUSH_TIMEOUT))
+            .await
+            .map_err(anyhow::Error::from)?
+            .map_err(|e| anyhow::anyhow!("failed to flush usage events to Kafka: {}", e).into())
+    }
+}
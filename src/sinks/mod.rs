@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::providers::LLMUsage;
+use crate::MonitorError;
+
+pub mod cloudwatch;
+pub mod influxdb;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod statsd;
+
+/// Usage reported by one monitor in a poll cycle.
+#[derive(Debug, Clone)]
+pub struct ProviderUsage {
+    pub provider: &'static str,
+    pub usage: Vec<LLMUsage>,
+}
+
+/// Destination other than the Prometheus scrape endpoint that receives every poll's usage.
+#[async_trait]
+pub trait UsageSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Called after each poll cycle with the usage of every monitor that polled successfully.
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError>;
+
+    /// Flushes anything buffered before the exporter exits.
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        Ok(())
+    }
+}
+
+/// Builds the sinks enabled in the environment.
+pub async fn from_env() -> Result<Vec<Box<dyn UsageSink>>, MonitorError> {
+    let mut sinks: Vec<Box<dyn UsageSink>> = Vec::new();
+    #[cfg(feature = "otlp")]
+    if let Some(sink) = otlp::OtlpSink::from_env()? {
+        sinks.push(Box::new(sink));
+    }
+    if let Some(sink) = cloudwatch::CloudWatchSink::from_env().await? {
+        sinks.push(Box::new(sink));
+    }
+    if let Some(sink) = influxdb::InfluxDbSink::from_env()? {
+        sinks.push(Box::new(sink));
+    }
+    if let Some(sink) = statsd::StatsdSink::from_env().await? {
+        sinks.push(Box::new(sink));
+    }
+    Ok(sinks)
+}
+
+/// One LLM call tracked in process, through the proxy, the middleware, the layer or the
+/// `track_*_call` wrappers.
+#[derive(Debug, Clone, Serialize)]
+pub struct CallEvent {
+    /// When the call finished.
+    pub timestamp: DateTime<Utc>,
+    pub provider: String,
+    pub model: String,
+    /// `success`, or `error` for a failed call, which has no usage.
+    pub status: &'static str,
+    /// Whether the tokens were estimated from the payloads because the response had no usage.
+    pub estimated: bool,
+    pub prompt_tokens: u64,
+    pub cached_prompt_tokens: u64,
+    pub cache_write_tokens: u64,
+    pub completion_tokens: u64,
+    pub embedding_tokens: u64,
+    pub cost_usd: f64,
+    pub duration_seconds: f64,
+    pub team: String,
+    /// Cost centers the call is charged to and the share of each.
+    pub cost_centers: BTreeMap<String, f64>,
+    pub user: String,
+    pub namespace: String,
+    pub deployment: String,
+    /// Trace ID of the request's `traceparent` header, empty without one.
+    pub trace_id: String,
+}
+
+/// Destination of every tracked call as a raw event, for pipelines that want more than the
+/// aggregated `llm_tracked_*` metrics. Called on the request path, so it must not block.
+#[async_trait]
+pub trait CallSink: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn publish(&self, event: &CallEvent) -> Result<(), MonitorError>;
+
+    /// Delivers the events still queued before the exporter exits.
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        Ok(())
+    }
+}
+
+/// Builds the call sinks enabled in the environment.
+pub fn call_sinks_from_env() -> Result<Vec<Box<dyn CallSink>>, MonitorError> {
+    #[allow(unused_mut)]
+    let mut sinks: Vec<Box<dyn CallSink>> = Vec::new();
+    #[cfg(feature = "kafka")]
+    if let Some(sink) = kafka::KafkaSink::from_env()? {
+        sinks.push(Box::new(sink));
+    }
+    Ok(sinks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn call_event_serializes_to_the_documented_fields() {
+        let event = CallEvent {
+            timestamp: DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            status: "success",
+            estimated: false,
+            prompt_tokens: 1000,
+            cached_prompt_tokens: 200,
+            cache_write_tokens: 0,
+            completion_tokens: 50,
+            embedding_tokens: 0,
+            cost_usd: 0.0035,
+            duration_seconds: 1.5,
+            team: "search".to_string(),
+            cost_centers: BTreeMap::from([("ml".to_string(), 0.6), ("ads".to_string(), 0.4)]),
+            user: "alice".to_string(),
+            namespace: "prod".to_string(),
+            deployment: "ranker".to_string(),
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            json!({
+                "timestamp": "2026-10-16T12:00:00Z",
+                "provider": "openai",
+                "model": "gpt-4o",
+                "status": "success",
+                "estimated": false,
+                "prompt_tokens": 1000,
+                "cached_prompt_tokens": 200,
+                "cache_write_tokens": 0,
+                "completion_tokens": 50,
+                "embedding_tokens": 0,
+                "cost_usd": 0.0035,
+                "duration_seconds": 1.5,
+                "team": "search",
+                "cost_centers": {"ads": 0.4, "ml": 0.6},
+                "user": "alice",
+                "namespace": "prod",
+                "deployment": "ranker",
+                "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736",
+            })
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use async_trait::async_trait;
+use opentelemetry::metrics::{Meter, MeterProvider};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::{MetricExporter, Protocol, WithExportConfig};
+use opentelemetry_sdk::metrics::periodic_reader_with_async_runtime::PeriodicReader;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::{ProviderUsage, UsageSink};
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::MonitorError;
+
+/// Latest usage per provider, read by the instrument callbacks at each export.
+type Snapshot = Arc<RwLock<HashMap<&'static str, Vec<LLMUsage>>>>;
+
+/// Pushes the usage metrics to an OpenTelemetry collector. The endpoint, headers and export
+/// interval come from the standard `OTEL_EXPORTER_OTLP_*` and `OTEL_METRIC_EXPORT_INTERVAL`
+/// variables.
+pub struct OtlpSink {
+    provider: SdkMeterProvider,
+    snapshot: Snapshot,
+}
+
+impl OtlpSink {
+    /// Enabled when an OTLP endpoint is configured.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        if std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err()
+            && std::env::var("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT").is_err()
+        {
+            return Ok(None);
+        }
+        let protocol = std::env::var("OTEL_EXPORTER_OTLP_METRICS_PROTOCOL")
+            .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+            .unwrap_or_else(|_| "grpc".to_string());
+        let exporter = match protocol.as_str() {
+            "grpc" => MetricExporter::builder().with_tonic().build(),
+            "http/protobuf" => MetricExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .build(),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "unsupported OTLP protocol {:?}, expected grpc or http/protobuf",
+                    other
+                )
+                .into())
+            }
+        }
+        .map_err(|e| anyhow::anyhow!("failed to build OTLP exporter: {}", e))?;
+
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter, runtime::Tokio).build())
+            .with_resource(
+                Resource::builder()
+                    .with_service_name("llm-cost-exporter")
+                    .build(),
+            )
+            .build();
+        let snapshot = Snapshot::default();
+        register_instruments(&provider, &snapshot);
+        Ok(Some(Self { provider, snapshot }))
+    }
+}
+
+fn attributes(provider: &str, usage: &LLMUsage) -> Vec<KeyValue> {
+    let mut attributes = vec![
+        KeyValue::new("provider", provider.to_string()),
+        KeyValue::new("model", usage.model.clone()),
+    ];
+    for label in USAGE_LABELS {
+        if let Some(value) = usage.labels.get(label).filter(|value| !value.is_empty()) {
+            attributes.push(KeyValue::new(*label, value.clone()));
+        }
+    }
+    attributes
+}
+
+/// Observes every usage entry in the snapshot with the value picked by `value`.
+fn observe_all(
+    snapshot: &Snapshot,
+    value: impl Fn(&LLMUsage) -> f64,
+    observe: impl Fn(f64, &[KeyValue]),
+) {
+    for (provider, usage) in snapshot.read().unwrap().iter() {
+        for model_usage in usage {
+            observe(value(model_usage), &attributes(provider, model_usage));
+        }
+    }
+}
+
+fn register_gauge(
+    meter: &Meter,
+    snapshot: &Snapshot,
+    name: &'static str,
+    description: &'static str,
+    unit: &'static str,
+    value: fn(&LLMUsage) -> f64,
+) {
+    let snapshot = snapshot.clone();
+    meter
+        .f64_observable_gauge(name)
+        .with_description(description)
+        .with_unit(unit)
+        .with_callback(move |observer| {
+            observe_all(&snapshot, value, |v, attributes| {
+                observer.observe(v, attributes)
+            })
+        })
+        .build();
+}
+
+/// Mirrors the Prometheus usage gauges as observable gauges with the same names and labels.
+fn register_instruments(provider: &SdkMeterProvider, snapshot: &Snapshot) {
+    let meter = provider.meter("llm_cost_exporter");
+    register_gauge(
+        &meter,
+        snapshot,
+        "llm_cost_usd",
+        "Cost of LLM API usage in USD",
+        "USD",
+        |usage| usage.cost_usd,
+    );
+    register_gauge(
+        &meter,
+        snapshot,
+        "llm_request_cost_usd",
+        "Portion of LLM cost billed per request rather than per token, in USD",
+        "USD",
+        |usage| usage.request_cost_usd,
+    );
+    register_gauge(
+        &meter,
+        snapshot,
+        "llm_requests",
+        "Number of LLM API requests",
+        "{request}",
+        |usage| usage.request_count as f64,
+    );
+    register_gauge(
+        &meter,
+        snapshot,
+        "llm_audio_transcribed_minutes",
+        "Minutes of audio transcribed by speech-to-text models",
+        "min",
+        |usage| usage.transcription_minutes,
+    );
+    register_gauge(
+        &meter,
+        snapshot,
+        "llm_speech_characters",
+        "Characters synthesized by text-to-speech models",
+        "{character}",
+        |usage| usage.speech_characters as f64,
+    );
+
+    let images_snapshot = snapshot.clone();
+    meter
+        .f64_observable_gauge("llm_images")
+        .with_description("Images generated by LLM API")
+        .with_unit("{image}")
+        .with_callback(move |observer| {
+            for (provider, usage) in images_snapshot.read().unwrap().iter() {
+                for model_usage in usage {
+                    let attributes = attributes(provider, model_usage);
+                    for images in &model_usage.images_generated {
+                        observer.observe(
+                            images.count as f64,
+                            &[
+                                &attributes[..],
+                                &[
+                                    KeyValue::new("size", images.size.clone()),
+                                    KeyValue::new("quality", images.quality.clone()),
+                                ],
+                            ]
+                            .concat(),
+                        );
+                    }
+                }
+            }
+        })
+        .build();
+
+    let snapshot = snapshot.clone();
+    meter
+        .f64_observable_gauge("llm_tokens")
+        .with_description("Tokens used by LLM API")
+        .with_unit("{token}")
+        .with_callback(move |observer| {
+            for (provider, usage) in snapshot.read().unwrap().iter() {
+                for model_usage in usage {
+                    let attributes = attributes(provider, model_usage);
+                    for (token_type, count) in model_usage.token_counts() {
+                        observer.observe(
+                            count as f64,
+                            &[&attributes[..], &[KeyValue::new("type", token_type)]].concat(),
+                        );
+                    }
+                }
+            }
+        })
+        .build();
+}
+
+#[async_trait]
+impl UsageSink for OtlpSink {
+    fn name(&self) -> &'static str {
+        "otlp"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let mut snapshot = self.snapshot.write().unwrap();
+        for provider_usage in usage {
+            snapshot.insert(provider_usage.provider, provider_usage.usage.clone());
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        let provider = self.provider.clone();
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .map_err(|e| anyhow::anyhow!("OTLP shutdown task failed: {}", e))?
+            .map_err(|e| anyhow::anyhow!("failed to flush OTLP metrics: {}", e))?;
+        Ok(())
+    }
+}
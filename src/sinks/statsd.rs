@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use tokio::net::UdpSocket;
+
+use super::{ProviderUsage, UsageSink};
+use crate::providers::{LLMUsage, USAGE_LABELS};
+use crate::MonitorError;
+
+/// Largest datagram sent, small enough to avoid fragmentation on a standard MTU.
+const MAX_PACKET_SIZE: usize = 1432;
+
+/// Sends usage as DogStatsD gauges over UDP, tagged with provider, model and usage labels.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    tags: Vec<String>,
+}
+
+impl StatsdSink {
+    /// Enabled when `STATSD_ADDRESS` (e.g. `127.0.0.1:8125`) is set.
+    pub async fn from_env() -> Result<Option<Self>, MonitorError> {
+        let address = match std::env::var("STATSD_ADDRESS") {
+            Ok(address) if !address.is_empty() => address,
+            _ => return Ok(None),
+        };
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to open StatsD socket: {}", e))?;
+        socket
+            .connect(&address)
+            .await
+            .map_err(|e| anyhow::anyhow!("invalid STATSD_ADDRESS {:?}: {}", address, e))?;
+        Ok(Some(Self {
+            socket,
+            prefix: std::env::var("STATSD_PREFIX").unwrap_or_else(|_| "llm.".to_string()),
+            tags: std::env::var("STATSD_TAGS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }))
+    }
+
+    fn lines(&self, provider: &str, usage: &LLMUsage) -> Vec<String> {
+        let mut tags = self.tags.clone();
+        tags.push(format!("provider:{}", sanitize(provider)));
+        tags.push(format!("model:{}", sanitize(&usage.model)));
+        for label in USAGE_LABELS {
+            if let Some(value) = usage.labels.get(label).filter(|value| !value.is_empty()) {
+                tags.push(format!("{}:{}", label, sanitize(value)));
+            }
+        }
+        let tags = tags.join(",");
+        let mut metrics = vec![
+            ("cost_usd", usage.cost_usd, None),
+            ("request_cost_usd", usage.request_cost_usd, None),
+            ("requests", usage.request_count as f64, None),
+            ("images", usage.images_total() as f64, None),
+            ("transcription_minutes", usage.transcription_minutes, None),
+            ("speech_characters", usage.speech_characters as f64, None),
+        ];
+        for (token_type, count) in usage.token_counts() {
+            metrics.push(("tokens", count as f64, Some(token_type)));
+        }
+        metrics
+            .into_iter()
+            .map(|(name, value, token_type)| match token_type {
+                Some(token_type) => format!(
+                    "{}{}:{}|g|#{},type:{}",
+                    self.prefix, name, value, tags, token_type
+                ),
+                None => format!("{}{}:{}|g|#{}", self.prefix, name, value, tags),
+            })
+            .collect()
+    }
+
+    async fn send(&self, packet: &str) -> Result<(), MonitorError> {
+        self.socket
+            .send(packet.as_bytes())
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to send StatsD packet: {}", e))?;
+        Ok(())
+    }
+}
+
+/// Strips the characters that delimit DogStatsD fields from a tag value.
+fn sanitize(value: &str) -> String {
+    value.replace(['|', ',', '#', '\n'], "_")
+}
+
+#[async_trait]
+impl UsageSink for StatsdSink {
+    fn name(&self) -> &'static str {
+        "statsd"
+    }
+
+    async fn publish(&self, usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        let mut packet = String::new();
+        for provider_usage in usage {
+            for model_usage in &provider_usage.usage {
+                for line in self.lines(provider_usage.provider, model_usage) {
+                    if !packet.is_empty() && packet.len() + line.len() + 1 > MAX_PACKET_SIZE {
+                        self.send(&packet).await?;
+                        packet.clear();
+                    }
+                    if !packet.is_empty() {
+                        packet.push('\n');
+                    }
+                    packet.push_str(&line);
+                }
+            }
+        }
+        if !packet.is_empty() {
+            self.send(&packet).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::exporter::Monitors;
+use crate::metrics::{CounterState, LLMMetrics};
+use crate::sinks::{ProviderUsage, UsageSink};
+use crate::MonitorError;
+
+/// Saves the usage counters and the checkpoints of monitors to a file after every poll and on
+/// shutdown, so that in counter mode the counters resume from their last values after a
+/// restart instead of dropping to zero, and monitors tailing logs resume where they stopped.
+pub struct StateFile {
+    path: PathBuf,
+    metrics: Arc<LLMMetrics>,
+    monitors: Option<Monitors>,
+}
+
+/// Contents of the state file. Counters are at the top level, as state files saved before
+/// monitors had checkpoints hold nothing else.
+#[derive(Default, Serialize, Deserialize)]
+struct SavedState {
+    #[serde(flatten)]
+    counters: Option<CounterState>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    checkpoints: BTreeMap<String, serde_json::Value>,
+}
+
+impl StateFile {
+    /// Enabled when `METRICS_STATE_PATH` is set.
+    pub fn from_env(metrics: Arc<LLMMetrics>) -> Option<Self> {
+        match std::env::var("METRICS_STATE_PATH") {
+            Ok(path) if !path.is_empty() => Some(Self {
+                path: PathBuf::from(path),
+                metrics,
+                monitors: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Also saves and restores the checkpoints of these monitors.
+    pub fn monitors(mut self, monitors: Monitors) -> Self {
+        self.monitors = Some(monitors);
+        self
+    }
+
+    /// Restores the counters and checkpoints saved by a previous run, if there are any.
+    pub async fn restore(&self) -> Result<(), MonitorError> {
+        let contents = match tokio::fs::read(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(anyhow::anyhow!("failed to read {}: {}", self.path.display(), e).into())
+            }
+        };
+        let state: SavedState = serde_json::from_slice(&contents)
+            .map_err(|e| anyhow::anyhow!("invalid state file {}: {}", self.path.display(), e))?;
+        if let Some(counters) = state.counters {
+            if self.metrics.counter_state().is_some() {
+                self.metrics.restore_counter_state(counters);
+            }
+        }
+        if let Some(monitors) = &self.monitors {
+            monitors.restore(state.checkpoints).await?;
+        }
+        tracing::info!(path = %self.path.display(), "restored state");
+        Ok(())
+    }
+
+    /// Writes the state to a temporary file next to the state file and renames it over the
+    /// state file, so a crash mid-write never leaves a truncated state behind.
+    async fn save(&self) -> Result<(), MonitorError> {
+        let state = SavedState {
+            counters: self.metrics.counter_state(),
+            checkpoints: match &self.monitors {
+                Some(monitors) => monitors.checkpoints().await,
+                None => BTreeMap::new(),
+            },
+        };
+        if state.counters.is_none() && state.checkpoints.is_empty() {
+            return Ok(());
+        }
+        let contents = serde_json::to_vec(&state)
+            .map_err(|e| anyhow::anyhow!("failed to serialize state: {}", e))?;
+        let temporary = self.path.with_extension("tmp");
+        tokio::fs::write(&temporary, contents)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to write {}: {}", temporary.display(), e))?;
+        tokio::fs::rename(&temporary, &self.path)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to replace {}: {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UsageSink for StateFile {
+    fn name(&self) -> &'static str {
+        "state"
+    }
+
+    async fn publish(&self, _usage: &[ProviderUsage]) -> Result<(), MonitorError> {
+        self.save().await
+    }
+
+    async fn shutdown(&self) -> Result<(), MonitorError> {
+        self.save().await
+    }
+}
@@ -0,0 +1,123 @@
+use yaml_rust::Yaml;
+
+use crate::providers::LLMUsage;
+
+/// Shortest API key suffix a team may list, so a few characters can't match unrelated keys.
+const MIN_KEY_SUFFIX: usize = 4;
+
+/// The logical owner of a set of provider credentials and projects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Team {
+    pub name: String,
+    /// API keys, or their last characters as the provider's console shows them
+    /// (`sk-...Xy9z`), matched against the keys of calls tracked in process.
+    pub api_keys: Vec<String>,
+    /// OpenAI project IDs, matched against the `project_id` label.
+    pub project_ids: Vec<String>,
+    /// AWS IAM role ARNs, matched against the `iam_role` label.
+    pub iam_roles: Vec<String>,
+}
+
+/// Maps credentials, projects and roles to teams, which label every cost, token and request
+/// series with `team`, so chargeback is a `sum by (team)`. Usage no team claims keeps an empty
+/// `team` label.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TeamMapping {
+    teams: Vec<Team>,
+}
+
+fn strings(node: &Yaml, field: &str) -> Result<Vec<String>, String> {
+    match node {
+        Yaml::BadValue | Yaml::Null => Ok(Vec::new()),
+        Yaml::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("{} must be a list of strings", field))
+            })
+            .collect(),
+        _ => Err(format!("{} must be a list of strings", field)),
+    }
+}
+
+impl TeamMapping {
+    pub fn new(teams: Vec<Team>) -> Self {
+        Self { teams }
+    }
+
+    /// Reads the `teams` list of the config file.
+    pub fn from_yaml(node: &Yaml) -> Result<Self, String> {
+        let entries = node.as_vec().ok_or("teams must be a list")?;
+        let mut teams: Vec<Team> = Vec::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            let team =
+                Self::team_from_yaml(entry).map_err(|e| format!("teams[{}]: {}", index, e))?;
+            if teams.iter().any(|other| other.name == team.name) {
+                return Err(format!("duplicate team name {:?}", team.name));
+            }
+            teams.push(team);
+        }
+        Ok(Self { teams })
+    }
+
+    fn team_from_yaml(node: &Yaml) -> Result<Team, String> {
+        let name = node["name"].as_str().ok_or("name is required")?.to_string();
+        let api_keys = strings(&node["api_keys"], "api_keys")?
+            .into_iter()
+            .map(|key| match key.rsplit_once("...") {
+                Some((_, suffix)) => suffix.to_string(),
+                None => key,
+            })
+            .collect::<Vec<_>>();
+        if let Some(key) = api_keys.iter().find(|key| key.len() < MIN_KEY_SUFFIX) {
+            return Err(format!(
+                "api key {:?} is too short, list at least its last {} characters",
+                key, MIN_KEY_SUFFIX
+            ));
+        }
+        Ok(Team {
+            name,
+            api_keys,
+            project_ids: strings(&node["project_ids"], "project_ids")?,
+            iam_roles: strings(&node["iam_roles"], "iam_roles")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.teams.is_empty()
+    }
+
+    /// Team owning an API key, the first listing the key or its last characters.
+    pub fn team_of_key(&self, api_key: &str) -> Option<&str> {
+        self.teams
+            .iter()
+            .find(|team| {
+                team.api_keys
+                    .iter()
+                    .any(|key| api_key.ends_with(key.as_str()))
+            })
+            .map(|team| team.name.as_str())
+    }
+
+    /// Team owning polled usage, by its `project_id` or `iam_role` label.
+    pub fn team_of_usage(&self, usage: &LLMUsage) -> Option<&str> {
+        let label = |name| usage.labels.get(name).filter(|value| !value.is_empty());
+        let project_id = label("project_id");
+        let iam_role = label("iam_role");
+        self.teams
+            .iter()
+            .find(|team| {
+                project_id.is_some_and(|id| team.project_ids.contains(id))
+                    || iam_role.is_some_and(|role| team.iam_roles.contains(role))
+            })
+            .map(|team| team.name.as_str())
+    }
+
+    /// Sets the `team` label of polled usage.
+    pub fn assign(&self, usage: &mut LLMUsage) {
+        if let Some(team) = self.team_of_usage(usage) {
+            usage.labels.insert("team", team.to_string());
+        }
+    }
+}
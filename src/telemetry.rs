@@ -0,0 +1,95 @@
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::span_processor_with_async_runtime::BatchSpanProcessor;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use std::collections::HashMap;
+use tracing::{warn, Level, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::MonitorError;
+
+/// Exports the exporter's spans, one per provider poll with a child per API call and one per
+/// proxied LLM request, to an OpenTelemetry collector. The endpoint, headers and sampler come
+/// from the standard `OTEL_EXPORTER_OTLP_*` and `OTEL_TRACES_SAMPLER` variables.
+pub struct Tracing {
+    provider: SdkTracerProvider,
+}
+
+impl Tracing {
+    /// Enabled when `OTEL_TRACES_EXPORTER` is `otlp`. Unlike the OpenTelemetry default, traces
+    /// are off when it is unset, so an endpoint set for the metrics doesn't also turn them on.
+    pub fn from_env() -> Result<Option<Self>, MonitorError> {
+        match std::env::var("OTEL_TRACES_EXPORTER").as_deref() {
+            Err(_) | Ok("") | Ok("none") => return Ok(None),
+            Ok("otlp") => {}
+            Ok(other) => {
+                return Err(anyhow::anyhow!(
+                    "unsupported OTEL_TRACES_EXPORTER {:?}, expected otlp or none",
+                    other
+                )
+                .into())
+            }
+        }
+        let protocol = std::env::var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+            .unwrap_or_else(|_| "grpc".to_string());
+        let exporter = match protocol.as_str() {
+            "grpc" => SpanExporter::builder().with_tonic().build(),
+            "http/protobuf" => SpanExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .build(),
+            other => {
+                return Err(anyhow::anyhow!(
+                    "unsupported OTLP protocol {:?}, expected grpc or http/protobuf",
+                    other
+                )
+                .into())
+            }
+        }
+        .map_err(|e| anyhow::anyhow!("failed to build OTLP span exporter: {}", e))?;
+
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(BatchSpanProcessor::builder(exporter, runtime::Tokio).build())
+            .with_resource(
+                Resource::builder()
+                    .with_service_name("llm-cost-exporter")
+                    .build(),
+            )
+            .build();
+        Ok(Some(Self { provider }))
+    }
+
+    /// The layer turning the exporter's spans into OpenTelemetry spans, whatever the log level.
+    pub fn layer(&self) -> Box<dyn Layer<Registry> + Send + Sync> {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer("llm_cost_exporter"))
+            .with_filter(Targets::new().with_target("llm_cost_exporter", Level::INFO))
+            .boxed()
+    }
+
+    /// Exports the spans still buffered.
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!(error = %e, "failed to shut down the OTLP span exporter");
+        }
+    }
+}
+
+/// Makes `span` a child of the trace of the W3C `traceparent` and `tracestate` request
+/// headers, given the lookup of a header by name.
+pub fn set_parent<'a>(span: &Span, header: impl Fn(&str) -> Option<&'a str>) {
+    let carrier: HashMap<String, String> = ["traceparent", "tracestate"]
+        .into_iter()
+        .filter_map(|name| Some((name.to_string(), header(name)?.to_string())))
+        .collect();
+    if carrier.is_empty() {
+        return;
+    }
+    let _ = span.set_parent(TraceContextPropagator::new().extract(&carrier));
+}
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use futures_util::Stream;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::crypto::{aws_lc_rs, CryptoProvider};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::ServerConfig as RustlsConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::MonitorError;
+
+/// How often the certificate files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// PEM certificate chain and private key served on the metrics endpoint.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_file: String,
+    pub key_file: String,
+}
+
+impl TlsConfig {
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let cert = std::fs::metadata(&self.cert_file).ok()?.modified().ok()?;
+        let key = std::fs::metadata(&self.key_file).ok()?.modified().ok()?;
+        Some((cert, key))
+    }
+
+    fn load(&self, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>, MonitorError> {
+        let open = |path: &str| {
+            File::open(path)
+                .map(BufReader::new)
+                .map_err(|e| anyhow::anyhow!("failed to open {}: {}", path, e))
+        };
+        let certs = rustls_pemfile::certs(&mut open(&self.cert_file)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("invalid certificate {}: {}", self.cert_file, e))?;
+        if certs.is_empty() {
+            return Err(anyhow::anyhow!("no certificate found in {}", self.cert_file).into());
+        }
+        let key = rustls_pemfile::private_key(&mut open(&self.key_file)?)
+            .map_err(|e| anyhow::anyhow!("invalid private key {}: {}", self.key_file, e))?
+            .ok_or_else(|| anyhow::anyhow!("no private key found in {}", self.key_file))?;
+        let signing_key = provider
+            .key_provider
+            .load_private_key(key)
+            .map_err(|e| anyhow::anyhow!("unsupported private key {}: {}", self.key_file, e))?;
+        Ok(Arc::new(CertifiedKey::new(certs, signing_key)))
+    }
+}
+
+/// Serves the most recently loaded certificate, so rotated files are picked up without a restart.
+#[derive(Debug)]
+struct ReloadingCertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Accepts TLS connections on the listener, reloading the certificate when its files change.
+/// Handshakes run on their own tasks so a slow client cannot stall the accept loop.
+pub async fn incoming(
+    listener: TcpListener,
+    config: TlsConfig,
+) -> Result<impl Stream<Item = Result<TlsStream<TcpStream>, std::io::Error>>, MonitorError> {
+    let provider = Arc::new(aws_lc_rs::default_provider());
+    let resolver = Arc::new(ReloadingCertResolver {
+        current: RwLock::new(config.load(&provider)?),
+    });
+    let server_config = RustlsConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| anyhow::anyhow!("failed to configure TLS: {}", e))?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    let acceptor = TlsAcceptor::from(Arc::new(server_config));
+
+    tokio::spawn(async move {
+        let mut loaded = config.modified();
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        loop {
+            interval.tick().await;
+            let modified = config.modified();
+            if modified.is_none() || modified == loaded {
+                continue;
+            }
+            match config.load(&provider) {
+                Ok(key) => {
+                    tracing::info!(cert_file = %config.cert_file, "reloaded TLS certificate");
+                    *resolver.current.write().unwrap() = key;
+                    loaded = modified;
+                }
+                Err(e) => tracing::warn!(error = %e, "keeping previous TLS certificate"),
+            }
+        }
+    });
+
+    let (sender, receiver) = mpsc::channel(64);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!(error = %e, "failed to accept connection");
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = sender.send(Ok(stream)).await;
+                    }
+                    Err(e) => tracing::debug!(error = %e, "TLS handshake failed"),
+                }
+            });
+        }
+    });
+
+    Ok(futures_util::stream::unfold(
+        receiver,
+        |mut receiver| async move { receiver.recv().await.map(|stream| (stream, receiver)) },
+    ))
+}
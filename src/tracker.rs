@@ -1,63 +0,0 @@
-use prometheus::GaugeVec;
-use std::time::Instant;
-
-pub struct LLMTracker {
-    metrics: LLMMetrics,
-}
-
-impl LLMTracker {
-    pub fn new(metrics: LLMMetrics) -> Self {
-        Self { metrics }
-    }
-
-    pub async fn track_openai_call<F, T>(&self, model: &str, call: F) -> Result<T, anyhow::Error>
-    where
-        F: std::future::Future<Output = Result<T, anyhow::Error>>,
-    {
-        let start = Instant::now();
-        let result = call.await;
-
-        if let Ok(response) = &result {
-            // Extract usage from response (pseudo-code)
-            let usage = extract_openai_usage(response);
-
-            // Calculate cost based on model rates
-            let cost = calculate_openai_cost(model, &usage);
-
-            // Update metrics
-            self.metrics
-                .cost
-                .with_label_values(&["openai", model])
-                .inc_by(cost);
-            self.metrics
-                .tokens
-                .with_label_values(&["openai", model, "prompt"])
-                .inc_by(usage.prompt_tokens as f64);
-            self.metrics
-                .tokens
-                .with_label_values(&["openai", model, "completion"])
-                .inc_by(usage.completion_tokens as f64);
-            self.metrics
-                .requests
-                .with_label_values(&["openai", model])
-                .inc(1.0);
-        }
-
-        result
-    }
-
-    // Similar methods for Bedrock and Claude...
-}
-
-fn calculate_openai_cost(model: &str, usage: &LLMUsage) -> f64 {
-    // Current pricing as of 2023-10 (update as needed)
-    match model {
-        "gpt-4" => {
-            (usage.prompt_tokens as f64 * 0.03 + usage.completion_tokens as f64 * 0.06) / 1000.0
-        }
-        "gpt-3.5-turbo" => {
-            (usage.prompt_tokens as f64 * 0.0015 + usage.completion_tokens as f64 * 0.002) / 1000.0
-        }
-        _ => 0.0,
-    }
-}
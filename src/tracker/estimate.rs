@@ -0,0 +1,135 @@
+use serde_json::Value;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use crate::providers::{is_embedding_model, LLMUsage};
+
+/// Fields whose strings are text read or written by the model: message and block `content`
+/// and `text`, Anthropic's `system`, Responses and Embeddings `input`, Completions `prompt`,
+/// Responses `instructions` and tool call `arguments`.
+const TEXT_FIELDS: &[&str] = &[
+    "content",
+    "text",
+    "system",
+    "input",
+    "prompt",
+    "instructions",
+    "arguments",
+];
+
+/// Tokens a chat request spends on the role and separators of each message, plus the
+/// priming of the reply.
+const TOKENS_PER_MESSAGE: u64 = 4;
+const TOKENS_PER_REPLY: u64 = 3;
+
+/// Tokenizer of an OpenAI model, e.g. `o200k_base` for `gpt-4o` and `cl100k_base` for `gpt-4`,
+/// `None` for models tiktoken doesn't know. Router prefixes such as `openai/` are ignored.
+fn tokenizer(model: &str) -> Option<&'static CoreBPE> {
+    let model = model.rsplit('/').next().unwrap_or(model);
+    Some(match get_tokenizer(model)? {
+        Tokenizer::O200kBase => tiktoken_rs::o200k_base_singleton(),
+        Tokenizer::Cl100kBase => tiktoken_rs::cl100k_base_singleton(),
+        Tokenizer::P50kBase => tiktoken_rs::p50k_base_singleton(),
+        Tokenizer::P50kEdit => tiktoken_rs::p50k_edit_singleton(),
+        Tokenizer::R50kBase | Tokenizer::Gpt2 => tiktoken_rs::r50k_base_singleton(),
+    })
+}
+
+/// Token count of text for `model`: exact with the model's tiktoken encoding, approximated for
+/// models whose tokenizer isn't public.
+pub(crate) fn estimate_tokens(model: &str, text: &str) -> u64 {
+    match tokenizer(model) {
+        Some(bpe) => bpe.encode_ordinary(text).len() as u64,
+        None => approximate_tokens(text),
+    }
+}
+
+/// Approximates the token count of text under the BPE tokenizers of current models
+/// (`cl100k_base`, `o200k_base`), splitting it into the same kind of pieces: words of up to
+/// eight letters with their leading space are one token and longer ones about one per six,
+/// digits group in threes, punctuation pairs up, and other scripts take about a token per
+/// character.
+fn approximate_tokens(text: &str) -> u64 {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut run = |matches: fn(&char) -> bool| {
+            let mut length: u64 = 1;
+            while chars.next_if(matches).is_some() {
+                length += 1;
+            }
+            length
+        };
+        tokens += if c.is_ascii_alphabetic() {
+            match run(char::is_ascii_alphabetic) {
+                1..=8 => 1,
+                length => length.div_ceil(6),
+            }
+        } else if c.is_ascii_digit() {
+            run(char::is_ascii_digit).div_ceil(3)
+        } else if c == '\n' {
+            // a run of line breaks is one token
+            run(|c| c.is_ascii_whitespace());
+            1
+        } else if c.is_whitespace() {
+            // a single space joins the word after it
+            u64::from(run(|c| *c == ' ') > 1)
+        } else if c.is_ascii_punctuation() {
+            run(char::is_ascii_punctuation).div_ceil(2)
+        } else {
+            1
+        };
+    }
+    tokens
+}
+
+fn text_tokens(model: &str, value: &Value, is_text: bool) -> u64 {
+    match value {
+        Value::String(text) if is_text => estimate_tokens(model, text),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| text_tokens(model, item, is_text))
+            .sum(),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(name, value)| text_tokens(model, value, TEXT_FIELDS.contains(&name.as_str())))
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Estimated tokens of the text in a request or response body of `model`, or in a streamed
+/// event.
+pub(crate) fn payload_tokens(model: &str, payload: &Value) -> u64 {
+    text_tokens(model, payload, false)
+}
+
+/// Estimated input tokens of a request body for `model`, including the overhead of chat
+/// messages.
+pub(crate) fn request_tokens(model: &str, request: &Value) -> u64 {
+    let mut tokens = payload_tokens(model, request);
+    if let Some(messages) = request.get("messages").and_then(Value::as_array) {
+        tokens += messages.len() as u64 * TOKENS_PER_MESSAGE + TOKENS_PER_REPLY;
+    }
+    tokens
+}
+
+/// Estimates the usage of a call whose response reported none, from the text of its request
+/// and the estimated `completion_tokens` of the response. Cache reads can't be told apart and
+/// count as prompt.
+pub(crate) fn estimate_usage(model: &str, request: &[u8], completion_tokens: u64) -> LLMUsage {
+    let request: Value = serde_json::from_slice(request).unwrap_or_default();
+    let input = request_tokens(model, &request);
+    let mut usage = LLMUsage {
+        model: model.to_string(),
+        request_count: 1,
+        ..Default::default()
+    };
+    if is_embedding_model(model) {
+        usage.embedding_tokens = input;
+    } else {
+        usage.prompt_tokens = input;
+        usage.completion_tokens = completion_tokens;
+    }
+    usage
+}
@@ -0,0 +1,169 @@
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http::{Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::BodyExt;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::warn;
+
+use super::{requested_model, LLMTracker};
+
+/// A `tower` layer that tracks the LLM calls passing through a service, for outbound client
+/// stacks as well as inbound gateway routes. The model is read from the JSON request body and
+/// the usage from JSON responses, which are priced and counted like
+/// [`LLMTracker::track_openai_call`]; error statuses count as failed calls.
+///
+/// Request bodies and successful JSON response bodies are buffered to read them, so both have
+/// to be constructible from [`Bytes`]. The layer keeps the service's error type: a request
+/// body that fails to arrive is answered with `400 Bad Request`, and a response body that
+/// fails with `502 Bad Gateway`. Streamed responses pass through untracked.
+#[derive(Clone)]
+pub struct TrackingLayer {
+    tracker: Arc<LLMTracker>,
+    provider: Arc<str>,
+}
+
+impl TrackingLayer {
+    /// Labels the tracked calls with `provider`; `anthropic` reads Messages API usage, any
+    /// other provider OpenAI-compatible usage.
+    pub fn new(tracker: Arc<LLMTracker>, provider: &str) -> Self {
+        Self {
+            tracker,
+            provider: provider.into(),
+        }
+    }
+}
+
+impl<S> Layer<S> for TrackingLayer {
+    type Service = TrackingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TrackingService {
+            inner,
+            tracker: self.tracker.clone(),
+            provider: self.provider.clone(),
+        }
+    }
+}
+
+/// The service [`TrackingLayer`] wraps around another.
+#[derive(Clone)]
+pub struct TrackingService<S> {
+    inner: S,
+    tracker: Arc<LLMTracker>,
+    provider: Arc<str>,
+}
+
+fn is_json<B>(response: &Response<B>) -> bool {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"))
+}
+
+fn empty_response<B: From<Bytes>>(status: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::from(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TrackingService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Body + From<Bytes> + Send + 'static,
+    ReqBody::Data: Send,
+    ReqBody::Error: Display,
+    ResBody: Body + From<Bytes> + Send + 'static,
+    ResBody::Data: Send,
+    ResBody::Error: Display,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        // the service driven to readiness handles this request, a fresh clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let tracker = self.tracker.clone();
+        let provider = self.provider.clone();
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = match body.collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => {
+                    warn!(error = %e, "failed to read LLM request body");
+                    return Ok(empty_response(StatusCode::BAD_REQUEST));
+                }
+            };
+            let model = requested_model(&body);
+            let header = |name: &str| {
+                parts
+                    .headers
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            };
+            let caller = tracker.caller(header, &body);
+            let request_body = body.clone();
+            let request = Request::from_parts(parts, ReqBody::from(body));
+            // calls without a model in the body aren't inference calls, e.g. listing models
+            let Some(model) = model else {
+                return inner.call(request).await;
+            };
+
+            let started = Instant::now();
+            let response = match inner.call(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    tracker.record(&provider, &model, &caller, None, started.elapsed());
+                    return Err(e);
+                }
+            };
+            tracker.record_rate_limits(&provider, &model, |name| {
+                response
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            });
+            if !response.status().is_success() {
+                tracker.record(&provider, &model, &caller, None, started.elapsed());
+                return Ok(response);
+            }
+            if !is_json(&response) {
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body = match body.collect().await {
+                Ok(body) => body.to_bytes(),
+                Err(e) => {
+                    warn!(error = %e, "failed to read LLM response body");
+                    tracker.record(&provider, &model, &caller, None, started.elapsed());
+                    return Ok(empty_response(StatusCode::BAD_GATEWAY));
+                }
+            };
+            tracker.record_response(
+                &provider,
+                &model,
+                &caller,
+                &request_body,
+                &body,
+                started.elapsed(),
+            );
+            Ok(Response::from_parts(parts, ResBody::from(body)))
+        })
+    }
+}
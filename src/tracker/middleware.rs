@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use task_local_extensions::Extensions;
+
+use super::{requested_model, LLMTracker};
+
+/// Provider label of the hosts of well-known OpenAI-compatible APIs.
+const HOSTS: &[(&str, &str)] = &[
+    ("api.openai.com", "openai"),
+    ("api.anthropic.com", "anthropic"),
+    ("api.together.xyz", "together"),
+    ("api.fireworks.ai", "fireworks"),
+    ("api.deepseek.com", "deepseek"),
+    ("api.x.ai", "xai"),
+    ("openrouter.ai", "openrouter"),
+    ("api.perplexity.ai", "perplexity"),
+];
+
+/// A `reqwest-middleware` layer that tracks every LLM call sent through the client: JSON
+/// responses with a `usage` block are priced and counted like
+/// [`LLMTracker::track_openai_call`], and error statuses count as failed calls.
+///
+/// Successful JSON responses are buffered to read them, so the response handed back no
+/// longer knows its final URL. Streamed responses pass through untracked.
+pub struct TrackingMiddleware {
+    tracker: Arc<LLMTracker>,
+    hosts: HashMap<String, String>,
+}
+
+impl TrackingMiddleware {
+    pub fn new(tracker: Arc<LLMTracker>) -> Self {
+        Self {
+            tracker,
+            hosts: HOSTS
+                .iter()
+                .map(|(host, provider)| (host.to_string(), provider.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Labels calls to `host` with `provider`, e.g. an internal gateway serving OpenAI models.
+    /// Calls to other unknown hosts are labeled with the host name.
+    pub fn host(mut self, host: &str, provider: &str) -> Self {
+        self.hosts.insert(host.to_string(), provider.to_string());
+        self
+    }
+
+    fn provider(&self, request: &Request) -> String {
+        let host = request.url().host_str().unwrap_or_default();
+        self.hosts
+            .get(host)
+            .cloned()
+            .unwrap_or_else(|| host.to_string())
+    }
+}
+
+#[async_trait]
+impl Middleware for TrackingMiddleware {
+    async fn handle(
+        &self,
+        request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let provider = self.provider(&request);
+        // calls without a model in the body aren't inference calls, e.g. listing models
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default();
+        let Some(model) = requested_model(&body) else {
+            return next.run(request, extensions).await;
+        };
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let caller = self.tracker.caller(header, &body);
+
+        let started = Instant::now();
+        let response = match next.run(request, extensions).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.tracker
+                    .record(&provider, &model, &caller, None, started.elapsed());
+                return Err(e);
+            }
+        };
+        self.tracker.record_rate_limits(&provider, &model, |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        });
+        if !response.status().is_success() {
+            self.tracker
+                .record(&provider, &model, &caller, None, started.elapsed());
+            return Ok(response);
+        }
+        let is_json = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"));
+        if !is_json {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let response = response.bytes().await?;
+        self.tracker.record_response(
+            &provider,
+            &model,
+            &caller,
+            &body,
+            &response,
+            started.elapsed(),
+        );
+
+        let mut rebuilt = http02::Response::new(response);
+        *rebuilt.status_mut() = status;
+        *rebuilt.version_mut() = version;
+        *rebuilt.headers_mut() = headers;
+        Ok(Response::from(rebuilt))
+    }
+}
@@ -0,0 +1,605 @@
+use aws_sdk_bedrockruntime::operation::converse::ConverseOutput;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::cost_centers::CostCenterRules;
+use crate::metrics::LLMMetrics;
+use crate::pricing::{OffPeakWindow, Pricing};
+use crate::providers::{is_embedding_model, LLMUsage};
+use crate::sinks::{CallEvent, CallSink};
+use crate::teams::TeamMapping;
+use crate::MonitorError;
+
+mod estimate;
+#[cfg(feature = "tower")]
+pub mod layer;
+#[cfg(feature = "middleware")]
+pub mod middleware;
+mod stream;
+
+pub(crate) use estimate::{estimate_usage, request_tokens};
+
+pub(crate) use stream::StreamUsage;
+
+/// Counts the usage and cost of LLM calls a service makes itself, as they happen, instead of
+/// waiting for the provider's usage API. Wrap each call in the `track_*_call` method of its
+/// provider; the response is returned untouched.
+///
+/// Tracked calls are exported as `llm_tracked_*` counters next to the polled month-to-date
+/// series, so the two never overwrite each other.
+pub struct LLMTracker {
+    metrics: Arc<LLMMetrics>,
+    pricing: Pricing,
+    /// Hours each provider bills at its off-peak rate, by provider.
+    off_peak: HashMap<String, OffPeakWindow>,
+    teams: TeamMapping,
+    cost_centers: CostCenterRules,
+    user_labels: UserLabels,
+    call_sinks: Vec<Box<dyn CallSink>>,
+}
+
+/// Who made a tracked call, resolved from its request. Empty when unknown.
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+    /// Team owning the call's API key.
+    pub team: String,
+    /// End user the call was made for, as exported under [`UserLabels`].
+    pub user: String,
+    /// Namespace of the pod that made a call through the proxy.
+    pub namespace: String,
+    /// Deployment of the pod that made a call through the proxy.
+    pub deployment: String,
+    /// Values of the request headers cost-center rules match on, by lowercase name.
+    pub headers: BTreeMap<String, String>,
+    /// Cost centers the call is charged to and their shares, empty when no rule matched.
+    pub cost_centers: Vec<(String, f64)>,
+    /// Trace ID of the W3C `traceparent` header, exported as the exemplar of the call's
+    /// observations; empty when the request has none.
+    pub trace_id: String,
+}
+
+/// Request fields naming the end user a call was made for: OpenAI's `safety_identifier` and
+/// its predecessor `user`, and Anthropic's `metadata.user_id`.
+const USER_POINTERS: &[&str] = &["/safety_identifier", "/user", "/metadata/user_id"];
+
+/// Headers reporting the quota left in a provider's rate limit, OpenAI's also sent by most
+/// OpenAI-compatible APIs, with the `type` label of each.
+const RATE_LIMIT_HEADERS: &[(&str, &str)] = &[
+    ("x-ratelimit-remaining-requests", "requests"),
+    ("x-ratelimit-remaining-tokens", "tokens"),
+    ("anthropic-ratelimit-requests-remaining", "requests"),
+    ("anthropic-ratelimit-tokens-remaining", "tokens"),
+    ("anthropic-ratelimit-input-tokens-remaining", "input_tokens"),
+    (
+        "anthropic-ratelimit-output-tokens-remaining",
+        "output_tokens",
+    ),
+];
+
+/// Characters of a hashed user label, enough to tell users apart without the full digest.
+const USER_HASH_CHARS: usize = 16;
+
+/// Whether tracked calls are labeled with the end user named in their request. User IDs are
+/// often email addresses, and every user is a series of its own, so this is off by default.
+#[derive(Debug, Clone, Default)]
+pub enum UserLabels {
+    #[default]
+    Off,
+    /// The user ID as sent.
+    Plain,
+    /// A keyed hash of the user ID, which tells users apart without exposing who they are;
+    /// the same key gives the same label across restarts and exporters.
+    Hashed(Vec<u8>),
+}
+
+impl UserLabels {
+    /// Reads `TRACKED_USER_LABEL` (`off`, `plain` or `hashed`) and the hash key from
+    /// `TRACKED_USER_HASH_KEY`.
+    pub fn from_env() -> Result<Self, MonitorError> {
+        match std::env::var("TRACKED_USER_LABEL").as_deref() {
+            Err(_) | Ok("") | Ok("off") => Ok(Self::Off),
+            Ok("plain") => Ok(Self::Plain),
+            Ok("hashed") => {
+                let key = std::env::var("TRACKED_USER_HASH_KEY").unwrap_or_default();
+                if key.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "TRACKED_USER_LABEL=hashed needs TRACKED_USER_HASH_KEY"
+                    )
+                    .into());
+                }
+                Ok(Self::Hashed(key.into_bytes()))
+            }
+            Ok(other) => Err(anyhow::anyhow!(
+                "invalid TRACKED_USER_LABEL {:?}, expected off, plain or hashed",
+                other
+            )
+            .into()),
+        }
+    }
+
+    fn label(&self, user: &str) -> String {
+        match self {
+            Self::Off => String::new(),
+            Self::Plain => user.to_string(),
+            Self::Hashed(key) => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+                mac.update(user.as_bytes());
+                let digest = mac.finalize().into_bytes();
+                digest
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>()[..USER_HASH_CHARS]
+                    .to_string()
+            }
+        }
+    }
+}
+
+/// Trace ID of a W3C `traceparent` header, `00-<trace id>-<parent id>-<flags>`, unless it is
+/// malformed or the invalid all-zero ID.
+fn trace_id(traceparent: &str) -> Option<&str> {
+    let mut fields = traceparent.trim().split('-');
+    let (Some(version), Some(trace_id), Some(parent_id), Some(_flags)) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return None;
+    };
+    let hex = |field: &str, len| {
+        field.len() == len
+            && field
+                .bytes()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    (hex(version, 2)
+        && version != "ff"
+        && hex(trace_id, 32)
+        && hex(parent_id, 16)
+        && trace_id.bytes().any(|byte| byte != b'0'))
+    .then_some(trace_id)
+}
+
+/// API key of a request, from its `authorization` bearer token or `x-api-key` header.
+fn api_key<'a>(authorization: Option<&'a str>, x_api_key: Option<&'a str>) -> Option<&'a str> {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(x_api_key)
+        .map(str::trim)
+}
+
+impl LLMTracker {
+    pub fn new(metrics: Arc<LLMMetrics>, pricing: Pricing) -> Self {
+        Self {
+            metrics,
+            pricing,
+            off_peak: HashMap::from([("deepseek".to_string(), OffPeakWindow::deepseek())]),
+            teams: TeamMapping::default(),
+            cost_centers: CostCenterRules::default(),
+            user_labels: UserLabels::Off,
+            call_sinks: Vec::new(),
+        }
+    }
+
+    /// Prices calls to `provider` that finish inside `window` at the `off_peak_multiplier` of
+    /// the pricing catalog, by default DeepSeek's discount hours.
+    pub fn off_peak_window(mut self, provider: &str, window: OffPeakWindow) -> Self {
+        self.off_peak.insert(provider.to_string(), window);
+        self
+    }
+
+    /// Labels calls made through the proxy, middleware or layer with the team owning their
+    /// API key.
+    pub fn teams(mut self, teams: TeamMapping) -> Self {
+        self.teams = teams;
+        self
+    }
+
+    /// Charges calls to cost centers by their provider, model, team and request headers.
+    pub fn cost_centers(mut self, rules: CostCenterRules) -> Self {
+        self.cost_centers = rules;
+        self
+    }
+
+    /// Labels calls made through the proxy, middleware or layer with the end user named in
+    /// their request body.
+    pub fn user_labels(mut self, user_labels: UserLabels) -> Self {
+        self.user_labels = user_labels;
+        self
+    }
+
+    /// Publishes every tracked call as an event, e.g. to the sinks of
+    /// [`crate::sinks::call_sinks_from_env`].
+    pub fn call_sinks(mut self, sinks: impl IntoIterator<Item = Box<dyn CallSink>>) -> Self {
+        self.call_sinks.extend(sinks);
+        self
+    }
+
+    /// Caller of a request with the JSON `body`, given the lookup of a header by name.
+    pub(crate) fn caller<'a>(
+        &self,
+        header: impl Fn(&str) -> Option<&'a str>,
+        body: &[u8],
+    ) -> Caller {
+        let team = api_key(header("authorization"), header("x-api-key"))
+            .and_then(|key| self.teams.team_of_key(key))
+            .unwrap_or_default()
+            .to_string();
+        let user = match self.user_labels {
+            UserLabels::Off => String::new(),
+            _ => serde_json::from_slice::<Value>(body)
+                .ok()
+                .and_then(|body| {
+                    USER_POINTERS
+                        .iter()
+                        .find_map(|pointer| body.pointer(pointer)?.as_str().map(str::to_string))
+                })
+                .filter(|user| !user.is_empty())
+                .map(|user| self.user_labels.label(&user))
+                .unwrap_or_default(),
+        };
+        let headers = self
+            .cost_centers
+            .headers()
+            .filter_map(|name| Some((name.to_string(), header(name)?.to_string())))
+            .collect();
+        Caller {
+            team,
+            user,
+            headers,
+            trace_id: header("traceparent")
+                .and_then(trace_id)
+                .unwrap_or_default()
+                .to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Tracks an OpenAI call returning a Chat Completions, Responses or Embeddings response,
+    /// either as JSON or as any type that serializes to it.
+    pub async fn track_openai_call<F, T, E>(&self, model: &str, call: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        T: Serialize,
+    {
+        self.track("openai", model, call, |response| {
+            openai_usage(model, &serde_json::to_value(response).ok()?)
+        })
+        .await
+    }
+
+    /// Tracks an Anthropic Messages API call, returning the response as JSON or as any type
+    /// that serializes to it.
+    pub async fn track_anthropic_call<F, T, E>(&self, model: &str, call: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        T: Serialize,
+    {
+        self.track("anthropic", model, call, |response| {
+            anthropic_usage(model, &serde_json::to_value(response).ok()?)
+        })
+        .await
+    }
+
+    /// Tracks a Bedrock Converse call. `model` is the model or inference profile ID the call
+    /// was made with, which the response doesn't repeat.
+    pub async fn track_bedrock_call<F, E>(&self, model: &str, call: F) -> Result<ConverseOutput, E>
+    where
+        F: Future<Output = Result<ConverseOutput, E>>,
+    {
+        self.track("bedrock", model, call, |response| {
+            bedrock_usage(model, response)
+        })
+        .await
+    }
+
+    /// Times the call and records its outcome.
+    async fn track<F, T, E>(
+        &self,
+        provider: &str,
+        model: &str,
+        call: F,
+        extract: impl FnOnce(&T) -> Option<LLMUsage>,
+    ) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let started = Instant::now();
+        let result = call.await;
+        let usage = match &result {
+            Ok(response) => Some(extract(response).unwrap_or_else(|| LLMUsage {
+                model: model.to_string(),
+                request_count: 1,
+                ..Default::default()
+            })),
+            Err(_) => None,
+        };
+        self.record(
+            provider,
+            model,
+            &Caller::default(),
+            usage,
+            started.elapsed(),
+        );
+        result
+    }
+
+    /// Exports the rate limit quota left that a response's headers report, given the lookup
+    /// of a header by name.
+    pub(crate) fn record_rate_limits<'a>(
+        &self,
+        provider: &str,
+        model: &str,
+        header: impl Fn(&str) -> Option<&'a str>,
+    ) {
+        for (name, limit) in RATE_LIMIT_HEADERS {
+            if let Some(remaining) = header(name).and_then(|value| value.trim().parse().ok()) {
+                self.metrics
+                    .set_ratelimit_remaining(provider, model, limit, remaining);
+            }
+        }
+    }
+
+    /// Prices and counts a finished call, `None` for a failed one. A successful response
+    /// without usage still counts as a request, with no tokens or cost.
+    pub(crate) fn record(
+        &self,
+        provider: &str,
+        model: &str,
+        caller: &Caller,
+        usage: Option<LLMUsage>,
+        elapsed: Duration,
+    ) {
+        self.record_call(provider, model, caller, usage, false, elapsed);
+    }
+
+    /// Prices and counts a successful call whose usage was estimated from its payloads.
+    pub(crate) fn record_estimate(
+        &self,
+        provider: &str,
+        model: &str,
+        caller: &Caller,
+        usage: LLMUsage,
+        elapsed: Duration,
+    ) {
+        self.record_call(provider, model, caller, Some(usage), true, elapsed);
+    }
+
+    /// Records a successful JSON response by the usage it reports, or by an estimate from the
+    /// text of the request and response when a gateway on the way stripped it.
+    pub(crate) fn record_response(
+        &self,
+        provider: &str,
+        model: &str,
+        caller: &Caller,
+        request: &[u8],
+        response: &[u8],
+        elapsed: Duration,
+    ) {
+        let Ok(response) = serde_json::from_slice::<Value>(response) else {
+            return;
+        };
+        match provider_usage(provider, model, &response) {
+            Some(usage) => self.record(provider, model, caller, Some(usage), elapsed),
+            None => {
+                let completion_tokens = estimate::payload_tokens(model, &response);
+                let usage = estimate_usage(model, request, completion_tokens);
+                self.record_estimate(provider, model, caller, usage, elapsed);
+            }
+        }
+    }
+
+    fn record_call(
+        &self,
+        provider: &str,
+        model: &str,
+        caller: &Caller,
+        usage: Option<LLMUsage>,
+        estimated: bool,
+        elapsed: Duration,
+    ) {
+        let usage = usage.map(|mut usage| {
+            if let Some(price) = self.pricing.price(provider, &usage.model) {
+                usage.request_cost_usd = price.request_cost(&usage, usage.request_tier.as_deref());
+                usage.cost_usd = price.token_cost(&usage) + usage.request_cost_usd;
+                let off_peak = self
+                    .off_peak
+                    .get(provider)
+                    .is_some_and(|window| window.contains(Utc::now()));
+                if let Some(multiplier) = price.off_peak_multiplier.filter(|_| off_peak) {
+                    usage.cost_usd *= multiplier;
+                    usage.request_cost_usd *= multiplier;
+                }
+            }
+            usage
+        });
+        let mut caller = caller.clone();
+        caller.cost_centers =
+            self.cost_centers
+                .of_call(provider, model, &caller.team, &caller.headers);
+        // attributes of the span of a proxied request, which declares them
+        let span = tracing::Span::current();
+        match &usage {
+            Some(usage) => {
+                span.record("llm.cost_usd", usage.cost_usd);
+                span.record(
+                    "gen_ai.usage.input_tokens",
+                    usage.prompt_tokens
+                        + usage.cached_prompt_tokens
+                        + usage.cache_write_tokens
+                        + usage.embedding_tokens,
+                );
+                span.record("gen_ai.usage.output_tokens", usage.completion_tokens);
+                span.record("llm.estimated", estimated);
+            }
+            None => {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        if !caller.cost_centers.is_empty() {
+            let cost_centers: Vec<&str> = caller
+                .cost_centers
+                .iter()
+                .map(|(cost_center, _)| cost_center.as_str())
+                .collect();
+            span.record("llm.cost_center", cost_centers.join(",").as_str());
+        }
+        self.metrics.record_tracked_call(
+            provider,
+            model,
+            &caller,
+            usage.as_ref(),
+            estimated,
+            elapsed,
+        );
+        if self.call_sinks.is_empty() {
+            return;
+        }
+        let status = if usage.is_some() { "success" } else { "error" };
+        let usage = usage.unwrap_or_default();
+        let event = CallEvent {
+            timestamp: Utc::now(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            status,
+            estimated,
+            prompt_tokens: usage.prompt_tokens,
+            cached_prompt_tokens: usage.cached_prompt_tokens,
+            cache_write_tokens: usage.cache_write_tokens,
+            completion_tokens: usage.completion_tokens,
+            embedding_tokens: usage.embedding_tokens,
+            cost_usd: usage.cost_usd,
+            duration_seconds: elapsed.as_secs_f64(),
+            team: caller.team,
+            cost_centers: caller.cost_centers.into_iter().collect(),
+            user: caller.user,
+            namespace: caller.namespace,
+            deployment: caller.deployment,
+            trace_id: caller.trace_id,
+        };
+        for sink in &self.call_sinks {
+            if let Err(e) = sink.publish(&event) {
+                warn!(sink = sink.name(), error = %e, "failed to publish tracked call");
+            }
+        }
+    }
+
+    /// Shuts the call sinks down, delivering the events they still hold. Call once no more
+    /// calls are tracked, e.g. after the proxy stopped.
+    pub async fn shutdown(&self) {
+        for sink in &self.call_sinks {
+            if let Err(e) = sink.shutdown().await {
+                warn!(sink = sink.name(), error = %e, "failed to shut down call sink");
+            }
+        }
+    }
+}
+
+/// `model` of a JSON request body.
+pub(crate) fn requested_model(body: &[u8]) -> Option<String> {
+    let body: Value = serde_json::from_slice(body).ok()?;
+    body.get("model")?.as_str().map(str::to_string)
+}
+
+/// Usage of a JSON response from the provider's API, Anthropic Messages for `anthropic` and
+/// OpenAI-compatible for everything else.
+fn provider_usage(provider: &str, model: &str, response: &Value) -> Option<LLMUsage> {
+    match provider {
+        "anthropic" => anthropic_usage(model, response),
+        "perplexity" => perplexity_usage(model, response),
+        "deepseek" => deepseek_usage(model, response),
+        _ => openai_usage(model, response),
+    }
+}
+
+/// Token count at a JSON pointer such as `/prompt_tokens_details/cached_tokens`, zero when
+/// absent.
+fn count(usage: &Value, pointer: &str) -> u64 {
+    usage
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .unwrap_or_default()
+}
+
+/// Reads `usage` of a Chat Completions (`prompt_tokens`), Responses (`input_tokens`) or
+/// Embeddings response. Input counts include cache reads, which are split out.
+fn openai_usage(model: &str, response: &Value) -> Option<LLMUsage> {
+    let usage = response.get("usage")?;
+    let input = count(usage, "/prompt_tokens") + count(usage, "/input_tokens");
+    let cached = count(usage, "/prompt_tokens_details/cached_tokens")
+        + count(usage, "/input_tokens_details/cached_tokens");
+    let mut tracked = LLMUsage {
+        model: model.to_string(),
+        completion_tokens: count(usage, "/completion_tokens") + count(usage, "/output_tokens"),
+        request_count: 1,
+        ..Default::default()
+    };
+    if is_embedding_model(model) {
+        tracked.embedding_tokens = input;
+    } else {
+        tracked.prompt_tokens = input.saturating_sub(cached);
+        tracked.cached_prompt_tokens = cached;
+    }
+    Some(tracked)
+}
+
+/// Reads `usage` of a Perplexity Chat Completions response, whose search fee depends on the
+/// `search_context_size` it reports, `low` by default
+/// (<https://docs.perplexity.ai/guides/pricing>).
+fn perplexity_usage(model: &str, response: &Value) -> Option<LLMUsage> {
+    let mut usage = openai_usage(model, response)?;
+    let tier = response
+        .pointer("/usage/search_context_size")
+        .and_then(Value::as_str)
+        .unwrap_or("low");
+    usage.request_tier = Some(tier.to_string());
+    Some(usage)
+}
+
+/// Reads `usage` of a DeepSeek Chat Completions response, which counts prompt cache hits in
+/// `prompt_cache_hit_tokens` (<https://api-docs.deepseek.com/api/create-chat-completion>).
+fn deepseek_usage(model: &str, response: &Value) -> Option<LLMUsage> {
+    let mut usage = openai_usage(model, response)?;
+    let hits = count(response, "/usage/prompt_cache_hit_tokens");
+    if usage.cached_prompt_tokens == 0 && hits > 0 {
+        usage.prompt_tokens = usage.prompt_tokens.saturating_sub(hits);
+        usage.cached_prompt_tokens = hits;
+    }
+    Some(usage)
+}
+
+/// Reads `usage` of a Messages response, whose `input_tokens` already exclude cache reads
+/// and writes.
+fn anthropic_usage(model: &str, response: &Value) -> Option<LLMUsage> {
+    let usage = response.get("usage")?;
+    Some(LLMUsage {
+        model: model.to_string(),
+        prompt_tokens: count(usage, "/input_tokens"),
+        cached_prompt_tokens: count(usage, "/cache_read_input_tokens"),
+        cache_write_tokens: count(usage, "/cache_creation_input_tokens"),
+        completion_tokens: count(usage, "/output_tokens"),
+        request_count: 1,
+        ..Default::default()
+    })
+}
+
+fn bedrock_usage(model: &str, response: &ConverseOutput) -> Option<LLMUsage> {
+    let usage = response.usage()?;
+    let tokens = |count: i32| count.max(0) as u64;
+    Some(LLMUsage {
+        model: model.to_string(),
+        prompt_tokens: tokens(usage.input_tokens()),
+        cached_prompt_tokens: tokens(usage.cache_read_input_tokens().unwrap_or_default()),
+        cache_write_tokens: tokens(usage.cache_write_input_tokens().unwrap_or_default()),
+        completion_tokens: tokens(usage.output_tokens()),
+        request_count: 1,
+        ..Default::default()
+    })
+}
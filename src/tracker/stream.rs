@@ -0,0 +1,78 @@
+use serde_json::{Map, Value};
+
+use super::estimate::payload_tokens;
+use super::provider_usage;
+use crate::providers::LLMUsage;
+
+/// Event payloads that carry usage: the final Chat Completions chunk (with
+/// `stream_options.include_usage`), Anthropic's `message_start` and `message_delta`, and the
+/// Responses API's `response.completed`.
+const USAGE_POINTERS: &[&str] = &["/usage", "/message/usage", "/response/usage"];
+
+/// Collects the usage of a server-sent events response from its chunks as they pass through,
+/// without holding them back. Later events override the fields of earlier ones, so Anthropic's
+/// running `output_tokens` in `message_delta` replace the count from `message_start`.
+pub(crate) struct StreamUsage {
+    provider: String,
+    model: String,
+    pending: Vec<u8>,
+    usage: Map<String, Value>,
+    /// Estimated tokens of the generated text, for streams that carry no usage.
+    completion_tokens: u64,
+}
+
+impl StreamUsage {
+    pub(crate) fn new(provider: &str, model: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            pending: Vec::new(),
+            usage: Map::new(),
+            completion_tokens: 0,
+        }
+    }
+
+    /// Reads the complete lines of a chunk; a line split across chunks waits for the rest.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        self.pending.extend_from_slice(chunk);
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.read_line(&line);
+        }
+    }
+
+    fn read_line(&mut self, line: &[u8]) {
+        // `[DONE]`, comments and event names aren't JSON data and are skipped
+        let Some(data) = line.strip_prefix(b"data:") else {
+            return;
+        };
+        let Ok(event) = serde_json::from_slice::<Value>(data) else {
+            return;
+        };
+        let usage = USAGE_POINTERS
+            .iter()
+            .find_map(|pointer| event.pointer(pointer)?.as_object());
+        for (field, value) in usage.into_iter().flatten() {
+            if !value.is_null() {
+                self.usage.insert(field.clone(), value.clone());
+            }
+        }
+        self.completion_tokens += payload_tokens(&self.model, &event);
+    }
+
+    pub(crate) fn completion_tokens(&self) -> u64 {
+        self.completion_tokens
+    }
+
+    /// Usage seen so far, `None` when the stream carried none.
+    pub(crate) fn usage(&mut self) -> Option<LLMUsage> {
+        // the last event may end without a newline
+        let rest = std::mem::take(&mut self.pending);
+        self.read_line(&rest);
+        if self.usage.is_empty() {
+            return None;
+        }
+        let response = serde_json::json!({ "usage": self.usage });
+        provider_usage(&self.provider, &self.model, &response)
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeSet;
+
+use crate::cardinality::LabelLimits;
+use crate::config::{Cli, Config};
+use crate::expiry::SeriesTtls;
+use crate::exporter::{PollMode, PollTimeouts};
+use crate::metrics::MetricsMode;
+use crate::pricing::{Pricing, PricingCatalog};
+use crate::providers::{ProviderRegistry, Shard};
+use crate::proxy::ProxyConfig;
+use crate::retry::RetryPolicy;
+
+/// Checks what the exporter reads at startup without starting it: the config file, the pricing
+/// file and the providers it prices, the settings and credentials of every provider, the
+/// providers budgets select and the exporter settings in the environment. Returns every
+/// problem found rather than only the first, or the providers that would be polled.
+pub async fn check(cli: Cli) -> Result<Vec<&'static str>, Vec<String>> {
+    let config = Config::load(cli).map_err(|e| vec![e.to_string()])?;
+    let mut problems = Vec::new();
+
+    let settings = [
+        MetricsMode::from_env().err(),
+        LabelLimits::from_env().err(),
+        SeriesTtls::from_env().err(),
+        RetryPolicy::from_env().err(),
+        PollTimeouts::from_env().err(),
+        PollMode::from_env().err(),
+        Shard::from_env().err(),
+    ];
+    problems.extend(settings.into_iter().flatten().map(|e| e.to_string()));
+    let proxy = ProxyConfig::from_env().unwrap_or_else(|e| {
+        problems.push(e.to_string());
+        None
+    });
+
+    let builtin = PricingCatalog::builtin().map_err(|e| vec![e.to_string()])?;
+    let pricing = Pricing::load(None).map_err(|e| vec![e.to_string()])?;
+    let pricing_file = config
+        .pricing_file
+        .as_deref()
+        .map(PricingCatalog::from_file)
+        .transpose()
+        .unwrap_or_else(|e| {
+            problems.push(e.to_string());
+            None
+        });
+    pricing.apply_file(pricing_file.clone());
+
+    // every entry is checked, whichever shard it belongs to
+    let registry = ProviderRegistry::builtin();
+    let mut monitors = Vec::new();
+    match &config.providers {
+        Some(entries) => {
+            for (index, entry) in entries.iter().enumerate() {
+                match registry
+                    .monitors(Some(std::slice::from_ref(entry)), &pricing)
+                    .await
+                {
+                    Ok(created) => monitors.extend(created),
+                    Err(e) => problems.push(format!(
+                        "providers[{}] ({}): {}",
+                        index, entry.provider_type, e
+                    )),
+                }
+            }
+        }
+        None => match registry.monitors(None, &pricing).await {
+            Ok(created) => monitors = created,
+            Err(e) => problems.push(e.to_string()),
+        },
+    }
+    let providers: Vec<&'static str> = monitors.iter().map(|monitor| monitor.provider()).collect();
+    if providers.is_empty() && proxy.is_none() && config.providers.is_none() {
+        problems.push(
+            "no provider configured, set at least one API key or PROXY_LISTEN_ADDRESS".to_string(),
+        );
+    }
+    let mut seen = BTreeSet::new();
+    for provider in &providers {
+        if !seen.insert(*provider) {
+            problems.push(format!(
+                "provider {:?} is configured more than once, give each a name of its own",
+                provider
+            ));
+        }
+    }
+
+    if let (Some(path), Some(file)) = (&config.pricing_file, &pricing_file) {
+        let known: BTreeSet<&str> = builtin
+            .providers()
+            .chain(registry.types())
+            .chain(providers.iter().copied())
+            // named providers, e.g. of the http type, even when they failed to be created
+            .chain(
+                config
+                    .providers
+                    .iter()
+                    .flatten()
+                    .filter_map(|entry| entry.fields.get("name").map(String::as_str)),
+            )
+            .chain(proxy.as_ref().map(|proxy| proxy.provider.as_str()))
+            .collect();
+        for provider in file.providers() {
+            if !known.contains(provider) {
+                problems.push(format!(
+                    "pricing file {} prices provider {:?}, which is neither a provider type nor a configured provider",
+                    path, provider
+                ));
+            }
+        }
+    }
+    for budget in &config.budgets {
+        if let Some(provider) = &budget.provider {
+            if !providers.contains(&provider.as_str()) {
+                problems.push(format!(
+                    "budget {:?} selects provider {:?}, which isn't polled",
+                    budget.name, provider
+                ));
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(providers)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Runs the `validate` subcommand: prints the providers that would be polled, or every problem
+/// and exits with status 1.
+pub async fn run(cli: Cli) {
+    let source = cli
+        .config
+        .clone()
+        .unwrap_or_else(|| "the environment".to_string());
+    match check(cli).await {
+        Ok(providers) => println!(
+            "config of {} is valid, polling: {}",
+            source,
+            providers.join(", ")
+        ),
+        Err(problems) => {
+            for problem in &problems {
+                eprintln!("error: {}", problem);
+            }
+            eprintln!(
+                "config of {} is invalid, {} problem(s) found",
+                source,
+                problems.len()
+            );
+            std::process::exit(1);
+        }
+    }
+}